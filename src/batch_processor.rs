@@ -1,69 +1,379 @@
 use anyhow::{Result, anyhow};
 use chrono::Utc;
+use colored::*;
 
-use indicatif::{ProgressBar, ProgressStyle};
-use std::path::PathBuf;
-use std::{collections::HashMap, time::Duration};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
 
 use crate::{
-    database::{Database, Item},
+    database::{Database, Item, unsorted_name},
+    file_analyzer::similarity_signature,
     models::*,
-    providers::LLMProvider,
+    progress,
+    prompt_template::{PromptTemplate, PromptValues},
+    providers::{LLMProvider, TokenUsage},
+    timings::BatchTiming,
 };
 
+/// What items are grouped by into cabinets (`--group-by`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum GroupBy {
+    /// What the items are about
+    #[default]
+    Content,
+    /// Who owns the files, e.g. on a shared server; shelves still go by content
+    Owner,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /// Approximate token budget for the items of a single request. When unset,
+    /// a default is derived from the model name.
+    pub token_budget: Option<usize>,
+    /// Strongly prefer the existing cabinets and shelves, e.g. when seeded from a template.
+    pub prefer_existing: bool,
+    /// Git repositories are kept whole and described by their README.
+    pub git_aware: bool,
+    /// Only sort into the existing cabinets and shelves; anything the model wants to
+    /// put somewhere new goes to the Unsorted shelf instead.
+    pub existing_only: bool,
+    /// Hint near-duplicate text documents to the model so versions end up together.
+    pub detect_similar: bool,
+    /// Abort after this many batches in a row fail; defaults to
+    /// `DEFAULT_MAX_CONSECUTIVE_FAILURES`.
+    pub max_consecutive_failures: Option<usize>,
+    /// Maximum number of cabinets; defaults to `DEFAULT_MAX_CABINETS`.
+    pub max_cabinets: Option<usize>,
+    /// Maximum number of shelves per cabinet; defaults to `DEFAULT_MAX_SHELVES`.
+    pub max_shelves: Option<usize>,
+    /// Suggested names may carry a corrected extension (`--extension-policy allow-change`).
+    pub allow_extension_change: bool,
+    /// Custom batch analysis prompt; the built-in one when unset.
+    pub prompt_template: Option<PromptTemplate>,
+    /// Fail closed: any anomaly in a response aborts the run instead of being
+    /// skipped or routed to a fallback.
+    pub strict: bool,
+    pub group_by: GroupBy,
+    /// Preferences learned from the user's earlier plan corrections, one per entry.
+    pub preferences: Vec<String>,
+    /// Hint groups of visually near-identical images to the model (`--image-clustering`).
+    pub image_clustering: bool,
+    /// Cabinets items must be placed into, by item path (`--cabinets-from-dirs`). The
+    /// model only picks their shelves.
+    pub fixed_cabinets: HashMap<PathBuf, i64>,
+    /// Split a batch in half and retry the halves when it overflows the model's
+    /// context window, instead of failing it.
+    pub split_on_context_overflow: bool,
+}
+
+/// The cabinets and shelves in the database, including those created while storing a
+/// batch, which the model's references to existing ones are checked against.
+struct KnownStructure {
+    cabinets: HashSet<i64>,
+    /// Cabinet of each shelf
+    shelves: HashMap<i64, i64>,
+}
+
+impl KnownStructure {
+    fn load(database: &Database) -> Result<Self> {
+        Ok(Self {
+            cabinets: database
+                .list_cabinets()?
+                .into_iter()
+                .map(|cabinet| cabinet.id)
+                .collect(),
+            shelves: database
+                .list_shelves(None)?
+                .into_iter()
+                .map(|shelf| (shelf.id, shelf.cabinet_id))
+                .collect(),
+        })
+    }
+}
+
+/// Extensions of macOS bundle directories, which are single logical items.
+pub(crate) const BUNDLE_EXTENSIONS: &[&str] = &["app", "bundle", "framework"];
+
+/// Whether a directory name is that of a macOS bundle, e.g. `Safari.app`.
+pub(crate) fn is_bundle_name(name: &str) -> bool {
+    Path::new(name)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| {
+            BUNDLE_EXTENSIONS
+                .iter()
+                .any(|bundle| ext.eq_ignore_ascii_case(bundle))
+        })
+}
+
+/// Why a directory is treated as a single opaque item and moved whole.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OpaqueReason {
+    GitRepository,
+    Bundle,
+    /// The name matches a known generated or tooling directory
+    KnownPattern(String),
+    /// Nearly all entries are numbered files with the same extension
+    HomogeneousNumbered {
+        extension: String,
+    },
+}
+
+impl fmt::Display for OpaqueReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpaqueReason::GitRepository => write!(f, "git repository"),
+            OpaqueReason::Bundle => write!(f, "application bundle"),
+            OpaqueReason::KnownPattern(name) => write!(f, "known directory name '{}'", name),
+            OpaqueReason::HomogeneousNumbered { extension } => {
+                write!(f, "mostly numbered .{} files", extension)
+            }
+        }
+    }
+}
+
+pub const DEFAULT_MAX_CONSECUTIVE_FAILURES: usize = 3;
+pub const DEFAULT_MAX_CABINETS: usize = 10;
+pub const DEFAULT_MAX_SHELVES: usize = 10;
+
+impl BatchOptions {
+    pub fn failure_limit(&self) -> usize {
+        self.max_consecutive_failures
+            .unwrap_or(DEFAULT_MAX_CONSECUTIVE_FAILURES)
+            .max(1)
+    }
+
+    pub fn cabinet_limit(&self) -> usize {
+        self.max_cabinets.unwrap_or(DEFAULT_MAX_CABINETS).max(1)
+    }
+
+    pub fn shelf_limit(&self) -> usize {
+        self.max_shelves.unwrap_or(DEFAULT_MAX_SHELVES).max(1)
+    }
+}
+
 pub struct BatchProcessor {
     provider: LLMProvider,
-    base_path: PathBuf,
+    db_path: PathBuf,
+    options: BatchOptions,
 }
 
 impl BatchProcessor {
-    const MAX_EXTRACTION_RETRIES: usize = 3;
+    pub(crate) const MAX_EXTRACTION_RETRIES: usize = 3;
+    const MAX_ITEMS_PER_BATCH: usize = 50;
+    /// Typical size of the model's analysis of one item.
+    const RESPONSE_TOKENS_PER_ITEM: usize = 100;
+    /// Signatures differing in at most this many of their 64 bits count as similar.
+    const SIMILARITY_MAX_DISTANCE: u32 = 10;
+    /// Perceptual hashes differing in at most this many bits count as the same picture.
+    const IMAGE_MAX_DISTANCE: u32 = 10;
 
     pub fn new(provider: LLMProvider, base_path: PathBuf) -> Self {
         Self {
             provider,
-            base_path,
+            db_path: Database::db_path(&base_path, None),
+            options: BatchOptions::default(),
         }
     }
 
-    pub async fn process_items_sequentially(&self, items: Vec<ProcessingItem>) -> Result<()> {
-        let database = Database::open_or_create(&self.base_path)?;
+    /// Uses a database stored outside the organized directory.
+    pub fn with_database_path(mut self, db_path: PathBuf) -> Self {
+        self.db_path = db_path;
+        self
+    }
+
+    pub fn with_options(mut self, options: BatchOptions) -> Self {
+        self.options = options;
+        self
+    }
 
-        // Process in batches
-        let batch_size = 10;
-        let total_batches = (items.len() + batch_size - 1) / batch_size;
+    /// Analyzes `items` batch by batch, storing the results as it goes. Returns how
+    /// long each batch took.
+    pub async fn process_items_sequentially(
+        &self,
+        items: Vec<ProcessingItem>,
+    ) -> Result<Vec<BatchTiming>> {
+        let database = Database::open_at(&self.db_path)?;
 
-        println!(
-            "🤖 Processing {} items in {} batches",
-            items.len(),
-            total_batches
+        // Pack items into batches that fit the token budget
+        let token_budget = self
+            .options
+            .token_budget
+            .unwrap_or_else(|| Self::default_token_budget(self.provider.get_model_name()));
+        let total_items = items.len();
+        let similar = if self.options.detect_similar {
+            Self::similarity_clusters(&items)
+        } else {
+            HashMap::new()
+        };
+        let image_clusters = if self.options.image_clustering {
+            Self::image_clusters(&items)
+        } else {
+            HashMap::new()
+        };
+        let batches = Self::pack_batches(items, token_budget);
+        let total_batches = batches.len();
+
+        eprintln!(
+            "🤖 Processing {} items in {} batches (~{} tokens per batch)",
+            total_items, total_batches, token_budget
         );
 
-        let progress_bar = ProgressBar::new(total_batches as u64);
-        progress_bar.enable_steady_tick(Duration::from_millis(200));
-        progress_bar.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
+        let progress_bar = progress::bar(total_batches as u64, "batches");
+
+        let mut items_done = 0;
+        // A failed batch is skipped (its items are retried on the next run), but a
+        // streak of failures means the provider is down and the run is aborted
+        let mut consecutive_failures = 0;
+        let mut failed_items = 0;
+        let mut timings = Vec::with_capacity(total_batches);
+        for (batch_idx, batch) in batches.into_iter().enumerate() {
+            let batch_len = batch.len();
+            let batch_started = Instant::now();
+            let describe = |elapsed: Duration| {
+                format!(
+                    "· batch {}/{}: {} items in flight, waiting on model ({}s) · {}/{} items done",
+                    batch_idx + 1,
+                    total_batches,
+                    batch_len,
+                    elapsed.as_secs(),
+                    items_done,
+                    total_items
                 )
-                .unwrap(),
-        );
+            };
+            progress_bar.set_message(describe(Duration::ZERO));
 
-        for batch in items.chunks(batch_size) {
-            Self::process_single_batch_static(&self.provider, &database, batch.to_vec()).await?;
+            // Keep the message ticking while waiting on the model
+            let batch_future = Self::process_single_batch_static(
+                &self.provider,
+                &database,
+                batch,
+                &similar,
+                &image_clusters,
+                &self.options,
+            );
+            tokio::pin!(batch_future);
+            let mut ticker = tokio::time::interval(Duration::from_secs(1));
+            let result = loop {
+                tokio::select! {
+                    result = &mut batch_future => break result,
+                    _ = ticker.tick() => progress_bar.set_message(describe(batch_started.elapsed())),
+                }
+            };
+            timings.push(BatchTiming {
+                items: batch_len,
+                duration: batch_started.elapsed(),
+                succeeded: result.is_ok(),
+            });
+            match result {
+                Ok(()) => consecutive_failures = 0,
+                Err(e) if self.options.strict => {
+                    progress_bar.abandon();
+                    return Err(e.context(format!(
+                        "Batch {}/{} failed in strict mode",
+                        batch_idx + 1,
+                        total_batches
+                    )));
+                }
+                Err(e) => {
+                    consecutive_failures += 1;
+                    failed_items += batch_len;
+                    if consecutive_failures >= self.options.failure_limit() {
+                        progress_bar.abandon();
+                        return Err(e.context(format!(
+                            "The AI provider appears to be down: {} batches in a row failed",
+                            consecutive_failures
+                        )));
+                    }
+                    // Unlike println, this still shows when the bar is hidden
+                    progress_bar.suspend(|| {
+                        eprintln!(
+                            "{}: batch {}/{} failed, skipping it: {}",
+                            "Warning".yellow().bold(),
+                            batch_idx + 1,
+                            total_batches,
+                            e
+                        )
+                    });
+                }
+            }
+
+            items_done += batch_len;
             progress_bar.inc(1);
         }
 
         progress_bar.finish_with_message("✓ Batch processing complete");
 
-        Ok(())
+        if failed_items > 0 {
+            eprintln!(
+                "{}: {} items could not be analyzed and will be retried on the next run",
+                "Warning".yellow().bold(),
+                failed_items
+            );
+        }
+
+        Ok(timings)
     }
 
     async fn process_single_batch_static(
         provider: &LLMProvider,
         database: &Database,
         items: Vec<ProcessingItem>,
+        similar: &HashMap<PathBuf, Vec<String>>,
+        image_clusters: &HashMap<PathBuf, u32>,
+        options: &BatchOptions,
     ) -> Result<()> {
+        let request = Self::batch_request(database, &items, similar, image_clusters, options)?;
+
+        for i in 0..Self::MAX_EXTRACTION_RETRIES {
+            match Self::extract_and_store_items(provider, &request, &items, database, options).await
+            {
+                Ok(()) => break,
+                // Retrying won't make the prompt fit, but smaller batches might
+                Err(e)
+                    if options.split_on_context_overflow
+                        && items.len() > 1
+                        && LLMProvider::is_context_overflow(&e) =>
+                {
+                    let mut first_half = items;
+                    let second_half = first_half.split_off(first_half.len() / 2);
+                    for half in [first_half, second_half] {
+                        Box::pin(Self::process_single_batch_static(
+                            provider,
+                            database,
+                            half,
+                            similar,
+                            image_clusters,
+                            options,
+                        ))
+                        .await?;
+                    }
+                    return Ok(());
+                }
+                Err(e) => {
+                    if i == Self::MAX_EXTRACTION_RETRIES - 1 {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The request for a batch of `items`, against the cabinets and shelves in the
+    /// database now.
+    fn batch_request(
+        database: &Database,
+        items: &[ProcessingItem],
+        similar: &HashMap<PathBuf, Vec<String>>,
+        image_clusters: &HashMap<PathBuf, u32>,
+        options: &BatchOptions,
+    ) -> Result<BatchAnalysisRequest> {
         // Load existing cabinets and shelves
         let cabinets = database.list_cabinets()?;
         let shelves = database.list_shelves(None)?;
@@ -72,49 +382,23 @@ impl BatchProcessor {
         let item_metadata: Vec<ItemMetadata> = items
             .iter()
             .enumerate()
-            .map(|(idx, item)| match item {
-                ProcessingItem::File(file) => {
-                    let name = file.name.clone();
-
-                    ItemMetadata {
-                        id: idx.to_string(),
-                        name,
-                        item_type: "file".to_string(),
-                        extension: file.extension.clone().unwrap_or_default(),
-                        size_bytes: file.size,
-                        sampled_contents: vec![], // Empty for files
-                        content_preview: file.content_preview.clone().unwrap_or("".into()),
-                    }
-                }
-                ProcessingItem::Directory(dir) => {
-                    let sampled_names: Vec<String> = dir
-                        .sampled_items
-                        .iter()
-                        .map(|item| item.name.clone())
-                        .collect();
-
-                    let is_opaque = Self::is_likely_opaque_directory(&dir.name, &dir.sampled_items);
-
-                    ItemMetadata {
-                        id: idx.to_string(),
-                        name: dir.name.clone(),
-                        item_type: if is_opaque {
-                            "likely_opaque_directory"
-                        } else {
-                            "directory"
-                        }
-                        .to_string(),
-                        extension: "".to_string(), // Empty for directories
-                        size_bytes: 0,             // 0 for directories
-                        sampled_contents: sampled_names,
-                        content_preview: "".to_string(), // Empty for directories
-                    }
+            .map(|(idx, item)| {
+                let mut metadata = Self::item_metadata(idx, item);
+                if let ProcessingItem::File(file) = item {
+                    metadata.similar_to = similar.get(&file.path).cloned().unwrap_or_default();
+                    metadata.image_cluster =
+                        image_clusters.get(&file.path).copied().unwrap_or_default();
                 }
+                metadata.fixed_cabinet = options
+                    .fixed_cabinets
+                    .get(item.path())
+                    .copied()
+                    .unwrap_or_default();
+                metadata
             })
             .collect();
 
-        // Prepare LLM request
-        let request = BatchAnalysisRequest {
+        Ok(BatchAnalysisRequest {
             items: item_metadata,
             existing_cabinets: cabinets
                 .iter()
@@ -133,20 +417,243 @@ impl BatchProcessor {
                     description: s.description.clone(),
                 })
                 .collect(),
-        };
+        })
+    }
 
-        for i in 0..Self::MAX_EXTRACTION_RETRIES {
-            match Self::extract_and_store_items(provider, &request, &items, database).await {
-                Ok(()) => break,
-                Err(e) => {
-                    if i == Self::MAX_EXTRACTION_RETRIES - 1 {
-                        return Err(e);
-                    }
+    pub(crate) fn item_metadata(idx: usize, item: &ProcessingItem) -> ItemMetadata {
+        match item {
+            ProcessingItem::File(file) => ItemMetadata {
+                id: idx.to_string(),
+                name: file.name.clone(),
+                // Without an extension, the detected type is the best hint about the content
+                item_type: if file.extension.is_none() && file.file_type != "Unknown" {
+                    format!("file of type {}", file.file_type)
+                } else {
+                    "file".to_string()
+                },
+                extension: file.extension.clone().unwrap_or_default(),
+                size_bytes: file.size,
+                sampled_contents: vec![], // Empty for files
+                content_preview: file.content_preview.clone().unwrap_or("".into()),
+                detected_title: file.detected_title.clone().unwrap_or_default(),
+                owner: file.owner.clone().unwrap_or_default(),
+                similar_to: vec![],
+                image_cluster: 0,
+                fixed_cabinet: 0,
+            },
+            ProcessingItem::Directory(dir) => {
+                let sampled_names: Vec<String> = dir
+                    .sampled_items
+                    .iter()
+                    .map(|item| item.name.clone())
+                    .collect();
+
+                let item_type = if dir.is_git_repository {
+                    "git_repository"
+                } else if is_bundle_name(&dir.name) {
+                    "application_bundle"
+                } else if Self::is_likely_opaque_directory(dir) {
+                    "likely_opaque_directory"
+                } else {
+                    "directory"
+                };
+
+                ItemMetadata {
+                    id: idx.to_string(),
+                    name: dir.name.clone(),
+                    item_type: item_type.to_string(),
+                    extension: "".to_string(), // Empty for directories
+                    size_bytes: 0,             // 0 for directories
+                    sampled_contents: sampled_names,
+                    // A git repository's README, or some of the files with --deep-dir-preview
+                    content_preview: dir
+                        .readme_preview
+                        .clone()
+                        .or_else(|| dir.content_preview.clone())
+                        .unwrap_or_default(),
+                    detected_title: String::new(),
+                    owner: String::new(),
+                    similar_to: vec![],
+                    image_cluster: 0,
+                    fixed_cabinet: 0,
                 }
             }
         }
+    }
 
-        Ok(())
+    /// Estimates the tokens analyzing `items` will take, before any request is made: the
+    /// prompts of their batches against the structure as it is now, and a typical
+    /// response per item. Retries and split batches aren't foreseen.
+    pub fn estimate_usage(&self, items: &[ProcessingItem]) -> Result<TokenUsage> {
+        let database = Database::open_at(&self.db_path)?;
+        let token_budget = self
+            .options
+            .token_budget
+            .unwrap_or_else(|| Self::default_token_budget(self.provider.get_model_name()));
+        let (similar, image_clusters) = (HashMap::new(), HashMap::new());
+
+        let mut usage = TokenUsage::default();
+        for batch in Self::pack_batches(items.to_vec(), token_budget) {
+            let request =
+                Self::batch_request(&database, &batch, &similar, &image_clusters, &self.options)?;
+            usage.requests += 1;
+            usage.prompt_tokens +=
+                Self::estimate_tokens(&Self::batch_prompt(&request, &self.options));
+            usage.completion_tokens += batch.len() * Self::RESPONSE_TOKENS_PER_ITEM;
+        }
+        Ok(usage)
+    }
+
+    /// Rough token estimate using the ~4 characters per token heuristic.
+    pub fn estimate_tokens(text: &str) -> usize {
+        text.chars().count().div_ceil(4)
+    }
+
+    /// Default per-request token budget for item metadata, based on the model's
+    /// typical context window. Leaves headroom for instructions and the response.
+    pub fn default_token_budget(model_name: &str) -> usize {
+        let model = model_name.to_lowercase();
+        if model.contains("claude")
+            || model.contains("gpt-4o")
+            || model.contains("gpt-4.1")
+            || model.contains("gpt-5")
+        {
+            24_000
+        } else if model.contains("gpt-4") || model.contains("gpt-3.5") {
+            6_000
+        } else {
+            // Local models frequently run with small context windows
+            3_000
+        }
+    }
+
+    /// Groups text files whose previews are near-duplicates, mapping each clustered
+    /// file to the names of the other files in its cluster.
+    pub(crate) fn similarity_clusters(items: &[ProcessingItem]) -> HashMap<PathBuf, Vec<String>> {
+        let signed = items
+            .iter()
+            .filter_map(|item| match item {
+                ProcessingItem::File(file) => {
+                    let signature = similarity_signature(file.content_preview.as_deref()?)?;
+                    Some((file, signature))
+                }
+                ProcessingItem::Directory(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut similar = HashMap::new();
+        for cluster in Self::hash_clusters(&signed, Self::SIMILARITY_MAX_DISTANCE) {
+            for file in &cluster {
+                let others = cluster
+                    .iter()
+                    .filter(|other| other.path != file.path)
+                    .map(|other| Self::file_display_name(other))
+                    .collect();
+                similar.insert(file.path.clone(), others);
+            }
+        }
+        similar
+    }
+
+    /// Groups images whose perceptual hashes are close, mapping each clustered image
+    /// to its cluster's number. Clusters are numbered from 1 in the order the items
+    /// are listed, so the numbers are stable across batches of the same run.
+    pub(crate) fn image_clusters(items: &[ProcessingItem]) -> HashMap<PathBuf, u32> {
+        let hashed = items
+            .iter()
+            .filter_map(|item| match item {
+                ProcessingItem::File(file) => Some((file, file.perceptual_hash?)),
+                ProcessingItem::Directory(_) => None,
+            })
+            .collect::<Vec<_>>();
+
+        let mut clusters = HashMap::new();
+        for (number, cluster) in (1..).zip(Self::hash_clusters(&hashed, Self::IMAGE_MAX_DISTANCE)) {
+            for file in cluster {
+                clusters.insert(file.path.clone(), number);
+            }
+        }
+        clusters
+    }
+
+    /// Union-find over all pairs of hashes within `max_distance` bits of each other.
+    /// Returns the clusters with more than one file, in order of their first file.
+    fn hash_clusters<'a>(
+        hashed: &[(&'a EnrichedFile, u64)],
+        max_distance: u32,
+    ) -> Vec<Vec<&'a EnrichedFile>> {
+        let mut parent = (0..hashed.len()).collect::<Vec<_>>();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for i in 0..hashed.len() {
+            for j in i + 1..hashed.len() {
+                if (hashed[i].1 ^ hashed[j].1).count_ones() <= max_distance {
+                    let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                    parent[a] = b;
+                }
+            }
+        }
+
+        let mut order = Vec::new();
+        let mut clusters: HashMap<usize, Vec<&EnrichedFile>> = HashMap::new();
+        for (i, (file, _)) in hashed.iter().enumerate() {
+            let cluster_root = root(&mut parent, i);
+            let cluster = clusters.entry(cluster_root).or_default();
+            if cluster.is_empty() {
+                order.push(cluster_root);
+            }
+            cluster.push(file);
+        }
+        order
+            .into_iter()
+            .filter_map(|root| clusters.remove(&root))
+            .filter(|cluster| cluster.len() > 1)
+            .collect()
+    }
+
+    fn file_display_name(file: &EnrichedFile) -> String {
+        file.path
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| file.name.clone())
+    }
+
+    /// Greedily packs items into batches whose estimated prompt size stays within
+    /// `token_budget`. An item that exceeds the budget on its own gets its own batch.
+    pub(crate) fn pack_batches(
+        items: Vec<ProcessingItem>,
+        token_budget: usize,
+    ) -> Vec<Vec<ProcessingItem>> {
+        let mut batches = Vec::new();
+        let mut current: Vec<ProcessingItem> = Vec::new();
+        let mut current_tokens = 0;
+
+        for item in items {
+            let metadata = Self::item_metadata(current.len(), &item);
+            let tokens = Self::estimate_tokens(&Self::format_items(&[metadata]));
+
+            if !current.is_empty()
+                && (current_tokens + tokens > token_budget
+                    || current.len() >= Self::MAX_ITEMS_PER_BATCH)
+            {
+                batches.push(std::mem::take(&mut current));
+                current_tokens = 0;
+            }
+
+            current_tokens += tokens;
+            current.push(item);
+        }
+
+        if !current.is_empty() {
+            batches.push(current);
+        }
+
+        batches
     }
 
     async fn extract_and_store_items(
@@ -154,126 +661,163 @@ impl BatchProcessor {
         request: &BatchAnalysisRequest,
         items: &Vec<ProcessingItem>,
         database: &Database,
+        options: &BatchOptions,
     ) -> Result<()> {
-        let response = Self::analyze_batch_with_llm_static(provider, request).await?;
-        Self::store_batch_results_static(database, items, &response).await?;
+        let response = Self::analyze_batch_with_llm_static(provider, request, options).await?;
+        if options.strict {
+            Self::validate_strict(request, &response, options)?;
+        }
+        Self::store_batch_results_static(database, items, &response, options).await?;
         Ok(())
     }
 
     async fn analyze_batch_with_llm_static(
         provider: &LLMProvider,
         request: &BatchAnalysisRequest,
+        options: &BatchOptions,
     ) -> Result<BatchAnalysisResponse> {
-        let prompt = format!(
-            "Analyze these files and directories for organization. \
-            You have up to 10 cabinets (top-level containers) and up to 10 shelves per cabinet.\n\n\
-            Existing Cabinets:\n{}\n\n\
-            Existing Shelves:\n{}\n\n\
-            Items to analyze:\n{}\n\n\
-            For each item, provide:\n\
-            1. A brief description (one sentence)\n\
-            2. A suggested_name (better name if needed, or empty string if current name is fine)\n\
-            3. For directories, determine if they're opaque (homogeneous content, generated files, etc.)\n\
-            4. Assign to an existing or new cabinet and shelf\n\n\
-            For cabinet and shelf assignments:\n\
-            - To use existing: set assignment_type='existing', existing_id to the ID, new_name='' and new_description=''\n\
-            - To create new: set assignment_type='new', existing_id=0, new_name and new_description to actual values\n\n\
-            Guidelines:\n\
-            - Group related items together\n\
-            - Use existing cabinets/shelves when appropriate\n\
-            - Create new ones only when necessary\n\
-            - Keep names short and descriptive\n\
-            - Do not treat non-English items any differently\n",
-            Self::format_cabinets(&request.existing_cabinets),
-            Self::format_shelves(&request.existing_shelves),
-            Self::format_items(&request.items)
-        );
-
+        let prompt = Self::batch_prompt(request, options);
         Self::extract_with_prompt_static(provider, &prompt).await
     }
 
+    /// The prompt sent to the model for `request`.
+    fn batch_prompt(request: &BatchAnalysisRequest, options: &BatchOptions) -> String {
+        let cabinets = Self::format_cabinets(&request.existing_cabinets);
+        let shelves = Self::format_shelves(&request.existing_shelves);
+        let items = Self::format_items(&request.items);
+        let values = PromptValues {
+            max_cabinets: options.cabinet_limit(),
+            max_shelves: options.shelf_limit(),
+            cabinets: &cabinets,
+            shelves: &shelves,
+            items: &items,
+        };
+        let mut prompt = match &options.prompt_template {
+            Some(template) => template.render(&values),
+            None => PromptTemplate::default().render(&values),
+        };
+        prompt.push_str(&Self::extra_guidelines(options));
+        prompt
+    }
+
+    /// Additional prompt guidelines driven by the batch options.
+    fn extra_guidelines(options: &BatchOptions) -> String {
+        let mut guidelines = String::new();
+        if options.prefer_existing {
+            guidelines.push_str(
+                "- The existing cabinets and shelves are the user's preferred structure: \
+                place items into them whenever they reasonably fit, and only create new ones \
+                for items that clearly don't belong anywhere\n",
+            );
+        }
+        if options.existing_only {
+            guidelines.push_str(&format!(
+                "- Do NOT create new cabinets or shelves: always use assignment_type='existing'. \
+                    If nothing fits, use the '{}' shelf if it exists; otherwise pick the \
+                    closest existing shelf\n",
+                unsorted_name()
+            ));
+        }
+        if options.detect_similar {
+            guidelines.push_str(
+                "- Items listed as similar to others are versions of the same document: \
+                put them on the same shelf, e.g. a Versions shelf in the fitting cabinet\n",
+            );
+        }
+        if options.image_clustering {
+            guidelines.push_str(
+                "- Images in the same visual group are near-identical pictures (e.g. edits, \
+                resized copies or burst shots): put them on the same shelf\n",
+            );
+        }
+        if !options.fixed_cabinets.is_empty() {
+            guidelines.push_str(
+                "- Items marked 'belongs in cabinet N' come from that cabinet's directory: \
+                always assign them to existing cabinet N and only choose or create shelves \
+                inside it\n",
+            );
+        }
+        if options.allow_extension_change {
+            guidelines.push_str(
+                "- If a file's extension doesn't match its content (e.g. a .txt file holding JSON), \
+                include the correct extension in its suggested_name\n",
+            );
+        }
+        if options.group_by == GroupBy::Owner {
+            guidelines.push_str(
+                "- Group files by their owner: one cabinet per owner, named after the user, \
+                with shelves by content inside. Items without an owner are grouped by content\n",
+            );
+        }
+        if !options.preferences.is_empty() {
+            guidelines.push_str(
+                "- The user corrected earlier plans for this directory; follow the same \
+                preferences:\n",
+            );
+            for preference in &options.preferences {
+                guidelines.push_str(&format!("  - {}\n", preference));
+            }
+        }
+        if options.git_aware {
+            guidelines.push_str(
+                "- Items of type git_repository are whole repositories: always mark them opaque \
+                and describe them by their name and README, not by individual files\n",
+            );
+        }
+        guidelines
+    }
+
     pub async fn store_batch_results_static(
         database: &Database,
         items: &[ProcessingItem],
         response: &BatchAnalysisResponse,
+        options: &BatchOptions,
     ) -> Result<()> {
+        let mut structure = KnownStructure::load(database)?;
         let mut cabinet_cache: HashMap<String, i64> = HashMap::new();
         let mut shelf_cache: HashMap<(i64, String), i64> = HashMap::new();
 
         for (item, analysis) in items.iter().zip(response.items.iter()) {
-            // Get or create cabinet
-            let cabinet_id = match analysis.cabinet.assignment_type.as_str() {
-                "existing" => {
-                    if analysis.cabinet.existing_id == 0 {
-                        return Err(anyhow!(
-                            "existing_id cannot be 0 for existing cabinet assignment"
-                        ));
-                    }
-                    analysis.cabinet.existing_id
-                }
-                "new" => {
-                    if analysis.cabinet.new_name.is_empty()
-                        || analysis.cabinet.new_description.is_empty()
-                    {
-                        return Err(anyhow!(
-                            "new_name and new_description cannot be empty for new cabinet assignment"
-                        ));
-                    }
-
-                    let name = &analysis.cabinet.new_name;
-                    let description = &analysis.cabinet.new_description;
-
-                    if let Some(&id) = cabinet_cache.get(name) {
-                        id
-                    } else {
-                        let id = database.create_cabinet(name, description)?;
-                        cabinet_cache.insert(name.clone(), id);
-                        id
-                    }
-                }
-                _ => {
-                    return Err(anyhow!(
-                        "Invalid cabinet assignment_type: must be 'existing' or 'new'"
-                    ));
-                }
-            };
-
-            // Get or create shelf
-            let shelf_id = match analysis.shelf.assignment_type.as_str() {
-                "existing" => {
-                    if analysis.shelf.existing_id == 0 {
-                        return Err(anyhow!(
-                            "existing_id cannot be 0 for existing shelf assignment"
-                        ));
-                    }
-                    analysis.shelf.existing_id
-                }
-                "new" => {
-                    if analysis.shelf.new_name.is_empty()
-                        || analysis.shelf.new_description.is_empty()
-                    {
-                        return Err(anyhow!(
-                            "new_name and new_description cannot be empty for new shelf assignment"
-                        ));
-                    }
-
-                    let name = &analysis.shelf.new_name;
-                    let description = &analysis.shelf.new_description;
-
-                    let key = (cabinet_id, name.clone());
-                    if let Some(&id) = shelf_cache.get(&key) {
-                        id
-                    } else {
-                        let id = database.create_shelf(cabinet_id, name, description)?;
-                        shelf_cache.insert(key, id);
-                        id
+            let fixed_cabinet = options.fixed_cabinets.get(item.path()).copied();
+            let wants_new = (fixed_cabinet.is_none() && analysis.cabinet.assignment_type == "new")
+                || analysis.shelf.assignment_type == "new";
+            let shelf_id = if options.existing_only && wants_new {
+                database.get_or_create_unsorted()?
+            } else {
+                let resolved = match fixed_cabinet {
+                    Some(cabinet_id) => Self::pin_to_cabinet(analysis, cabinet_id, &structure)
+                        .and_then(|pinned| {
+                            Self::resolve_shelf(
+                                database,
+                                &pinned,
+                                &mut structure,
+                                &mut cabinet_cache,
+                                &mut shelf_cache,
+                            )
+                        }),
+                    None => Self::resolve_shelf(
+                        database,
+                        analysis,
+                        &mut structure,
+                        &mut cabinet_cache,
+                        &mut shelf_cache,
+                    ),
+                };
+                match resolved {
+                    Ok(shelf_id) => shelf_id,
+                    Err(e) if options.strict => return Err(e),
+                    // An unusable assignment shouldn't cost the whole batch
+                    Err(e) => {
+                        eprintln!(
+                            "{}: {} goes to {}: {}",
+                            "Warning".yellow().bold(),
+                            analysis.id,
+                            unsorted_name(),
+                            e
+                        );
+                        database.get_or_create_unsorted()?
                     }
                 }
-                _ => {
-                    return Err(anyhow!(
-                        "Invalid shelf assignment_type: must be 'existing' or 'new'"
-                    ));
-                }
             };
 
             // Create item record
@@ -290,11 +834,13 @@ impl BatchProcessor {
                 ),
             };
 
-            let is_opaque_dir = match item {
-                ProcessingItem::Directory(dir) => {
-                    Self::is_likely_opaque_directory(&dir.name, &dir.sampled_items)
-                }
-                _ => false,
+            let opaque_reason = match item {
+                ProcessingItem::Directory(dir) => Self::opaque_reason(dir),
+                _ => None,
+            };
+            let content_hash = match item {
+                ProcessingItem::File(file) => file.content_hash.clone(),
+                _ => None,
             };
 
             let suggested_name = if analysis.suggested_name.is_empty() {
@@ -302,6 +848,9 @@ impl BatchProcessor {
             } else {
                 Some(analysis.suggested_name.clone())
             };
+            let rename_confidence = suggested_name
+                .is_some()
+                .then_some(analysis.rename_confidence);
 
             let db_item = Item {
                 id: None,
@@ -311,13 +860,18 @@ impl BatchProcessor {
                 suggested_name,
                 description: analysis.description.clone(),
                 file_type,
-                is_opaque_dir,
+                is_opaque_dir: opaque_reason.is_some(),
+                opaque_reason: opaque_reason.map(|reason| reason.to_string()),
+                rename_confidence,
+                content_hash,
                 processed_at: Utc::now(),
             };
 
-            database.insert_item(&db_item)?;
+            database.upsert_item(&db_item)?;
         }
 
+        // Stored, so an interrupted run no longer needs their scan
+        database.clear_pending_items(items.iter().map(ProcessingItem::path))?;
         Ok(())
     }
 
@@ -333,6 +887,222 @@ impl BatchProcessor {
         provider.extract(prompt).await
     }
 
+    /// Checks a whole response before anything is stored: every item must be
+    /// answered exactly once, in order, with a complete and consistent assignment.
+    pub(crate) fn validate_strict(
+        request: &BatchAnalysisRequest,
+        response: &BatchAnalysisResponse,
+        options: &BatchOptions,
+    ) -> Result<()> {
+        if response.items.len() != request.items.len() {
+            return Err(anyhow!(
+                "Expected {} analyzed items, the model returned {}",
+                request.items.len(),
+                response.items.len()
+            ));
+        }
+
+        for (item, analysis) in request.items.iter().zip(response.items.iter()) {
+            let context =
+                |problem: String| anyhow!("Item {} ({}): {}", item.id, item.name, problem);
+            if analysis.id != item.id {
+                return Err(context(format!(
+                    "the model answered with mismatched id '{}'",
+                    analysis.id
+                )));
+            }
+            if analysis.description.trim().is_empty() {
+                return Err(context("the model could not classify it".to_string()));
+            }
+
+            let cabinet = &analysis.cabinet;
+            let shelf = &analysis.shelf;
+            for (kind, assignment_type, name, description) in [
+                (
+                    "cabinet",
+                    &cabinet.assignment_type,
+                    &cabinet.new_name,
+                    &cabinet.new_description,
+                ),
+                (
+                    "shelf",
+                    &shelf.assignment_type,
+                    &shelf.new_name,
+                    &shelf.new_description,
+                ),
+            ] {
+                match assignment_type.as_str() {
+                    "existing" => {}
+                    "new" if options.existing_only => {
+                        return Err(context(format!(
+                            "assigned a new {} while sorting into existing ones only",
+                            kind
+                        )));
+                    }
+                    "new" if name.trim().is_empty() || description.trim().is_empty() => {
+                        return Err(context(format!(
+                            "new {} is missing its name or description",
+                            kind
+                        )));
+                    }
+                    "new" => {}
+                    other => {
+                        return Err(context(format!(
+                            "unknown {} assignment_type '{}'",
+                            kind, other
+                        )));
+                    }
+                }
+            }
+
+            if cabinet.assignment_type == "existing"
+                && !request
+                    .existing_cabinets
+                    .iter()
+                    .any(|c| c.id == cabinet.existing_id)
+            {
+                return Err(context(format!(
+                    "unknown cabinet id {}",
+                    cabinet.existing_id
+                )));
+            }
+            if shelf.assignment_type == "existing" {
+                let Some(existing) = request
+                    .existing_shelves
+                    .iter()
+                    .find(|s| s.id == shelf.existing_id)
+                else {
+                    return Err(context(format!("unknown shelf id {}", shelf.existing_id)));
+                };
+                if cabinet.assignment_type != "existing"
+                    || existing.cabinet_id != cabinet.existing_id
+                {
+                    return Err(context(format!(
+                        "shelf {} does not belong to the assigned cabinet",
+                        shelf.existing_id
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Rewrites the assignment to use the item's fixed cabinet, whichever cabinet the
+    /// model picked. An existing shelf from another cabinet can't be kept.
+    fn pin_to_cabinet(
+        analysis: &ItemAnalysis,
+        cabinet_id: i64,
+        structure: &KnownStructure,
+    ) -> Result<ItemAnalysis> {
+        if analysis.shelf.assignment_type == "existing"
+            && structure.shelves.get(&analysis.shelf.existing_id) != Some(&cabinet_id)
+        {
+            return Err(anyhow!(
+                "shelf {} is outside the item's cabinet {}",
+                analysis.shelf.existing_id,
+                cabinet_id
+            ));
+        }
+        let mut pinned = analysis.clone();
+        pinned.cabinet = CabinetAssignment {
+            assignment_type: "existing".to_string(),
+            existing_id: cabinet_id,
+            new_name: String::new(),
+            new_description: String::new(),
+        };
+        Ok(pinned)
+    }
+
+    /// Resolves the model's cabinet and shelf assignment to a shelf id, creating new
+    /// ones as needed. The whole assignment is checked first, so an unusable shelf
+    /// doesn't leave an empty new cabinet behind.
+    fn resolve_shelf(
+        database: &Database,
+        analysis: &ItemAnalysis,
+        structure: &mut KnownStructure,
+        cabinet_cache: &mut HashMap<String, i64>,
+        shelf_cache: &mut HashMap<(i64, String), i64>,
+    ) -> Result<i64> {
+        let cabinet = &analysis.cabinet;
+        let shelf = &analysis.shelf;
+        Self::check_assignment(
+            "cabinet",
+            &cabinet.assignment_type,
+            cabinet.existing_id,
+            &cabinet.new_name,
+            &cabinet.new_description,
+        )?;
+        Self::check_assignment(
+            "shelf",
+            &shelf.assignment_type,
+            shelf.existing_id,
+            &shelf.new_name,
+            &shelf.new_description,
+        )?;
+        // Ids the model made up would leave the item on a shelf that doesn't exist
+        if cabinet.assignment_type == "existing"
+            && !structure.cabinets.contains(&cabinet.existing_id)
+        {
+            return Err(anyhow!("unknown cabinet id {}", cabinet.existing_id));
+        }
+        if shelf.assignment_type == "existing" {
+            return if structure.shelves.contains_key(&shelf.existing_id) {
+                Ok(shelf.existing_id)
+            } else {
+                Err(anyhow!("unknown shelf id {}", shelf.existing_id))
+            };
+        }
+
+        // Get or create cabinet
+        let cabinet_id = if cabinet.assignment_type == "existing" {
+            cabinet.existing_id
+        } else if let Some(&id) = cabinet_cache.get(&cabinet.new_name) {
+            id
+        } else {
+            let id = database.create_cabinet(&cabinet.new_name, &cabinet.new_description)?;
+            cabinet_cache.insert(cabinet.new_name.clone(), id);
+            structure.cabinets.insert(id);
+            id
+        };
+
+        // Create the new shelf, once per batch
+        let key = (cabinet_id, shelf.new_name.clone());
+        if let Some(&id) = shelf_cache.get(&key) {
+            return Ok(id);
+        }
+        let id = database.create_shelf(cabinet_id, &shelf.new_name, &shelf.new_description)?;
+        shelf_cache.insert(key, id);
+        structure.shelves.insert(id, cabinet_id);
+        Ok(id)
+    }
+
+    /// Checks that an assignment is either a valid reference to an existing cabinet or
+    /// shelf or a complete new one.
+    fn check_assignment(
+        kind: &str,
+        assignment_type: &str,
+        existing_id: i64,
+        new_name: &str,
+        new_description: &str,
+    ) -> Result<()> {
+        match assignment_type {
+            "existing" if existing_id == 0 => Err(anyhow!(
+                "existing_id cannot be 0 for existing {} assignment",
+                kind
+            )),
+            "new" if new_name.is_empty() || new_description.is_empty() => Err(anyhow!(
+                "new_name and new_description cannot be empty for new {} assignment",
+                kind
+            )),
+            "existing" | "new" => Ok(()),
+            _ => Err(anyhow!(
+                "Invalid {} assignment_type: must be 'existing' or 'new'",
+                kind
+            )),
+        }
+    }
+
     fn format_cabinets(cabinets: &[CabinetInfo]) -> String {
         if cabinets.is_empty() {
             "None yet".to_string()
@@ -362,7 +1132,7 @@ impl BatchProcessor {
         }
     }
 
-    fn format_items(items: &[ItemMetadata]) -> String {
+    pub(crate) fn format_items(items: &[ItemMetadata]) -> String {
         items
             .iter()
             .map(|item| {
@@ -383,6 +1153,21 @@ impl BatchProcessor {
                         .join(", ");
                     desc.push_str(&format!(", contains: [{}...]", sample));
                 }
+                if !item.detected_title.is_empty() {
+                    desc.push_str(&format!(", titled \"{}\"", item.detected_title));
+                }
+                if !item.owner.is_empty() {
+                    desc.push_str(&format!(", owned by {}", item.owner));
+                }
+                if !item.similar_to.is_empty() {
+                    desc.push_str(&format!(", similar to: [{}]", item.similar_to.join(", ")));
+                }
+                if item.image_cluster > 0 {
+                    desc.push_str(&format!(", visual group {}", item.image_cluster));
+                }
+                if item.fixed_cabinet > 0 {
+                    desc.push_str(&format!(", belongs in cabinet {}", item.fixed_cabinet));
+                }
                 if !item.content_preview.is_empty() {
                     desc.push_str(&format!(", {}", item.content_preview));
                 }
@@ -392,10 +1177,22 @@ impl BatchProcessor {
             .join("\n")
     }
 
-    fn is_likely_opaque_directory(
-        name: &str,
-        sampled_items: &[crate::models::SampledItem],
-    ) -> bool {
+    fn is_likely_opaque_directory(dir: &EnrichedDirectory) -> bool {
+        Self::opaque_reason(dir).is_some()
+    }
+
+    pub(crate) fn opaque_reason(dir: &EnrichedDirectory) -> Option<OpaqueReason> {
+        let name = dir.name.as_str();
+        let sampled_items = &dir.sampled_items;
+
+        // A git repository or a bundle is one logical unit and is moved whole
+        if dir.is_git_repository {
+            return Some(OpaqueReason::GitRepository);
+        }
+        if is_bundle_name(name) {
+            return Some(OpaqueReason::Bundle);
+        }
+
         // Known opaque directory patterns
         const OPAQUE_PATTERNS: &[&str] = &[
             "node_modules",
@@ -417,7 +1214,7 @@ impl BatchProcessor {
 
         // Check if name matches known patterns
         if OPAQUE_PATTERNS.iter().any(|&pattern| name == pattern) {
-            return true;
+            return Some(OpaqueReason::KnownPattern(name.to_string()));
         }
 
         // Check for homogeneous numbered content
@@ -443,13 +1240,15 @@ impl BatchProcessor {
                     let same_ext_count = extensions.iter().filter(|&ext| ext == first_ext).count();
 
                     if same_ext_count as f32 / extensions.len() as f32 > 0.8 {
-                        return true;
+                        return Some(OpaqueReason::HomogeneousNumbered {
+                            extension: first_ext.to_string(),
+                        });
                     }
                 }
             }
         }
 
-        false
+        None
     }
 }
 
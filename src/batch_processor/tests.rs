@@ -2,6 +2,7 @@ use super::*;
 use crate::database::Database;
 use crate::providers::LLMProvider;
 use std::fs;
+use std::path::PathBuf;
 use tempfile::TempDir;
 
 #[tokio::test]
@@ -42,6 +43,10 @@ async fn test_complete_organization_workflow() {
             file_type: "text/rust".to_string(),
             size: fs::metadata(base_path.join("src/main.rs")).unwrap().len(),
             content_preview: Some("fn main() { println!(\"Hello, world!\"); }".to_string()),
+            detected_title: None,
+            owner: None,
+            perceptual_hash: None,
+            content_hash: None,
         }),
         ProcessingItem::File(EnrichedFile {
             path: base_path.join("src/lib.rs"),
@@ -50,6 +55,10 @@ async fn test_complete_organization_workflow() {
             file_type: "text/rust".to_string(),
             size: fs::metadata(base_path.join("src/lib.rs")).unwrap().len(),
             content_preview: Some("pub mod utils; pub mod models;".to_string()),
+            detected_title: None,
+            owner: None,
+            perceptual_hash: None,
+            content_hash: None,
         }),
         ProcessingItem::File(EnrichedFile {
             path: base_path.join("README.md"),
@@ -58,6 +67,10 @@ async fn test_complete_organization_workflow() {
             file_type: "text/markdown".to_string(),
             size: fs::metadata(base_path.join("README.md")).unwrap().len(),
             content_preview: Some("# Test Project\n\nThis is a test Rust project".to_string()),
+            detected_title: Some("Test Project".to_string()),
+            owner: None,
+            perceptual_hash: None,
+            content_hash: None,
         }),
     ];
 
@@ -68,6 +81,7 @@ async fn test_complete_organization_workflow() {
                 id: "0".to_string(),
                 description: "Main Rust application entry point".to_string(),
                 suggested_name: "".to_string(),
+                rename_confidence: 0.0,
                 is_opaque_directory: false,
                 cabinet: CabinetAssignment {
                     assignment_type: "new".to_string(),
@@ -86,6 +100,7 @@ async fn test_complete_organization_workflow() {
                 id: "1".to_string(),
                 description: "Library module with shared functionality".to_string(),
                 suggested_name: "".to_string(),
+                rename_confidence: 0.0,
                 is_opaque_directory: false,
                 cabinet: CabinetAssignment {
                     assignment_type: "existing".to_string(),
@@ -104,6 +119,7 @@ async fn test_complete_organization_workflow() {
                 id: "2".to_string(),
                 description: "Project documentation and README".to_string(),
                 suggested_name: "README.md".to_string(),
+                rename_confidence: 0.9,
                 is_opaque_directory: false,
                 cabinet: CabinetAssignment {
                     assignment_type: "new".to_string(),
@@ -222,3 +238,705 @@ async fn test_complete_organization_workflow() {
         "Should still have 2 cabinets after restart"
     );
 }
+
+fn file_with_preview(name: &str, preview: String) -> ProcessingItem {
+    ProcessingItem::File(EnrichedFile {
+        path: PathBuf::from(format!("/tmp/{}", name)),
+        name: name.to_string(),
+        extension: Some("txt".to_string()),
+        file_type: "text/plain".to_string(),
+        size: preview.len() as u64,
+        content_preview: Some(preview),
+        detected_title: None,
+        owner: None,
+        perceptual_hash: None,
+        content_hash: None,
+    })
+}
+
+#[test]
+fn test_long_previews_are_split_into_separate_batches() {
+    let long_preview = "lorem ipsum dolor sit amet ".repeat(200);
+    let items = vec![
+        file_with_preview("a.txt", long_preview.clone()),
+        file_with_preview("b.txt", long_preview.clone()),
+        file_with_preview("c.txt", long_preview),
+    ];
+
+    let batches = BatchProcessor::pack_batches(items, 2_000);
+
+    assert_eq!(batches.len(), 3, "Each long item should get its own batch");
+    assert!(batches.iter().all(|batch| batch.len() == 1));
+}
+
+#[test]
+fn test_small_items_are_packed_together() {
+    let items = (0..20)
+        .map(|i| file_with_preview(&format!("note{}.txt", i), "short".to_string()))
+        .collect::<Vec<_>>();
+
+    let batches = BatchProcessor::pack_batches(items, 2_000);
+
+    assert_eq!(batches.len(), 1, "Small items should share a single batch");
+    assert_eq!(batches[0].len(), 20);
+}
+
+#[test]
+fn test_usage_is_estimated_from_the_batch_prompts() {
+    let temp_dir = TempDir::new().unwrap();
+    let long_preview = "lorem ipsum dolor sit amet ".repeat(200);
+    let items = vec![
+        file_with_preview("a.txt", long_preview.clone()),
+        file_with_preview("b.txt", long_preview),
+    ];
+    let batch_processor = BatchProcessor::new(
+        LLMProvider::new_mock(Vec::new()),
+        temp_dir.path().to_path_buf(),
+    )
+    .with_options(BatchOptions {
+        token_budget: Some(2_000),
+        ..Default::default()
+    });
+
+    let usage = batch_processor.estimate_usage(&items).unwrap();
+
+    assert_eq!(usage.requests, 2);
+    // Each prompt carries its item and the instructions around it
+    let item_tokens = BatchProcessor::estimate_tokens(&BatchProcessor::format_items(&[
+        BatchProcessor::item_metadata(0, &items[0]),
+    ]));
+    assert!(usage.prompt_tokens > 2 * item_tokens);
+    assert_eq!(
+        usage.completion_tokens,
+        2 * BatchProcessor::RESPONSE_TOKENS_PER_ITEM
+    );
+    // Nothing was sent
+    assert_eq!(
+        batch_processor.provider.token_usage(),
+        TokenUsage::default()
+    );
+}
+
+#[test]
+fn test_git_repository_is_opaque() {
+    let repo = EnrichedDirectory {
+        path: PathBuf::from("/tmp/projects/shelfie"),
+        name: "shelfie".to_string(),
+        sampled_items: Vec::new(),
+        is_git_repository: true,
+        readme_preview: Some("# Shelfie".to_string()),
+        content_preview: None,
+    };
+
+    assert!(BatchProcessor::is_likely_opaque_directory(&repo));
+
+    let metadata = BatchProcessor::item_metadata(0, &ProcessingItem::Directory(repo));
+    assert_eq!(metadata.item_type, "git_repository");
+    assert_eq!(metadata.content_preview, "# Shelfie");
+}
+
+#[test]
+fn test_bundle_is_opaque() {
+    let bundle = EnrichedDirectory {
+        path: PathBuf::from("/Applications/Safari.app"),
+        name: "Safari.app".to_string(),
+        sampled_items: Vec::new(),
+        is_git_repository: false,
+        readme_preview: None,
+        content_preview: None,
+    };
+
+    assert!(BatchProcessor::is_likely_opaque_directory(&bundle));
+    assert!(is_bundle_name("Foo.Framework"));
+    assert!(!is_bundle_name("apps"));
+
+    let metadata = BatchProcessor::item_metadata(0, &ProcessingItem::Directory(bundle));
+    assert_eq!(metadata.item_type, "application_bundle");
+}
+
+#[tokio::test]
+async fn test_existing_only_routes_new_assignments_to_unsorted() {
+    let temp_dir = TempDir::new().unwrap();
+    let database = Database::open_or_create(temp_dir.path()).unwrap();
+    let finance = database.create_cabinet("Finance", "Money").unwrap();
+    let taxes = database.create_shelf(finance, "Taxes", "Returns").unwrap();
+
+    let items = vec![
+        file_with_preview("return.pdf", "Tax return 2023".to_string()),
+        file_with_preview("beach.jpg", String::new()),
+    ];
+    let response = BatchAnalysisResponse {
+        items: vec![
+            ItemAnalysis {
+                id: "0".to_string(),
+                description: "Tax return".to_string(),
+                suggested_name: String::new(),
+                rename_confidence: 0.0,
+                is_opaque_directory: false,
+                cabinet: CabinetAssignment {
+                    assignment_type: "existing".to_string(),
+                    existing_id: finance,
+                    new_name: String::new(),
+                    new_description: String::new(),
+                },
+                shelf: ShelfAssignment {
+                    assignment_type: "existing".to_string(),
+                    existing_id: taxes,
+                    new_name: String::new(),
+                    new_description: String::new(),
+                },
+            },
+            ItemAnalysis {
+                id: "1".to_string(),
+                description: "Holiday photo".to_string(),
+                suggested_name: String::new(),
+                rename_confidence: 0.0,
+                is_opaque_directory: false,
+                cabinet: CabinetAssignment {
+                    assignment_type: "new".to_string(),
+                    existing_id: 0,
+                    new_name: "Photos".to_string(),
+                    new_description: "Pictures".to_string(),
+                },
+                shelf: ShelfAssignment {
+                    assignment_type: "new".to_string(),
+                    existing_id: 0,
+                    new_name: "Travel".to_string(),
+                    new_description: "Trips".to_string(),
+                },
+            },
+        ],
+    };
+    let options = BatchOptions {
+        existing_only: true,
+        ..Default::default()
+    };
+
+    BatchProcessor::store_batch_results_static(&database, &items, &response, &options)
+        .await
+        .unwrap();
+
+    assert!(database.get_cabinet_by_name("Photos").unwrap().is_none());
+    let unsorted = database.get_cabinet_by_name("Unsorted").unwrap().unwrap();
+    let unsorted_shelf = database
+        .get_shelf_by_name(unsorted.id, "Unsorted")
+        .unwrap()
+        .unwrap();
+    let photo = database
+        .get_item_by_path("/tmp/beach.jpg")
+        .unwrap()
+        .unwrap();
+    assert_eq!(photo.shelf_id, unsorted_shelf.id);
+    let tax_return = database
+        .get_item_by_path("/tmp/return.pdf")
+        .unwrap()
+        .unwrap();
+    assert_eq!(tax_return.shelf_id, taxes);
+}
+
+#[tokio::test]
+async fn test_unusable_assignment_goes_to_unsorted() {
+    let temp_dir = TempDir::new().unwrap();
+    let database = Database::open_or_create(temp_dir.path()).unwrap();
+
+    let items = vec![file_with_preview("notes.txt", "Scribbles".to_string())];
+    let response = BatchAnalysisResponse {
+        items: vec![ItemAnalysis {
+            id: "0".to_string(),
+            description: "Notes".to_string(),
+            suggested_name: String::new(),
+            rename_confidence: 0.0,
+            is_opaque_directory: false,
+            cabinet: CabinetAssignment {
+                assignment_type: "new".to_string(),
+                existing_id: 0,
+                new_name: String::new(),
+                new_description: String::new(),
+            },
+            shelf: ShelfAssignment {
+                assignment_type: "new".to_string(),
+                existing_id: 0,
+                new_name: "Notes".to_string(),
+                new_description: "Notes".to_string(),
+            },
+        }],
+    };
+
+    BatchProcessor::store_batch_results_static(
+        &database,
+        &items,
+        &response,
+        &BatchOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let notes = database
+        .get_item_by_path("/tmp/notes.txt")
+        .unwrap()
+        .unwrap();
+    assert_eq!(notes.shelf_id, database.get_or_create_unsorted().unwrap());
+
+    // Strict mode still refuses instead of falling back
+    let strict = BatchOptions {
+        strict: true,
+        ..Default::default()
+    };
+    assert!(
+        BatchProcessor::store_batch_results_static(&database, &items, &response, &strict)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_invalid_analyses_dont_cost_the_valid_ones() {
+    let temp_dir = TempDir::new().unwrap();
+    let database = Database::open_or_create(temp_dir.path()).unwrap();
+    let finance = database.create_cabinet("Finance", "Money").unwrap();
+    let taxes = database.create_shelf(finance, "Taxes", "Returns").unwrap();
+
+    let assignment = |cabinet: (&str, i64, &str), shelf: (&str, i64, &str)| {
+        (
+            CabinetAssignment {
+                assignment_type: cabinet.0.to_string(),
+                existing_id: cabinet.1,
+                new_name: cabinet.2.to_string(),
+                new_description: cabinet.2.to_string(),
+            },
+            ShelfAssignment {
+                assignment_type: shelf.0.to_string(),
+                existing_id: shelf.1,
+                new_name: shelf.2.to_string(),
+                new_description: shelf.2.to_string(),
+            },
+        )
+    };
+    let assignments = [
+        assignment(("existing", finance, ""), ("existing", taxes, "")),
+        // A shelf id the model made up
+        assignment(("existing", finance, ""), ("existing", 999, "")),
+        assignment(("new", 0, "Photos"), ("new", 0, "Trips")),
+        // A new cabinet whose shelf is incomplete
+        assignment(("new", 0, "Orphan"), ("new", 0, "")),
+        assignment(("existing", finance, ""), ("new", 0, "Banking")),
+    ];
+    let names = [
+        "return.pdf",
+        "mystery.bin",
+        "beach.jpg",
+        "scrap.txt",
+        "statement.pdf",
+    ];
+    let items = names
+        .iter()
+        .map(|name| file_with_preview(name, String::new()))
+        .collect::<Vec<_>>();
+    let response = BatchAnalysisResponse {
+        items: assignments
+            .into_iter()
+            .enumerate()
+            .map(|(id, (cabinet, shelf))| ItemAnalysis {
+                id: id.to_string(),
+                description: String::new(),
+                suggested_name: String::new(),
+                rename_confidence: 0.0,
+                is_opaque_directory: false,
+                cabinet,
+                shelf,
+            })
+            .collect(),
+    };
+
+    BatchProcessor::store_batch_results_static(
+        &database,
+        &items,
+        &response,
+        &BatchOptions::default(),
+    )
+    .await
+    .unwrap();
+
+    let shelf_of = |name: &str| {
+        let item = database
+            .get_item_by_path(&format!("/tmp/{}", name))
+            .unwrap()
+            .unwrap();
+        item.shelf_id
+    };
+    let photos = database.get_cabinet_by_name("Photos").unwrap().unwrap();
+    let trips = database
+        .get_shelf_by_name(photos.id, "Trips")
+        .unwrap()
+        .unwrap();
+    let banking = database
+        .get_shelf_by_name(finance, "Banking")
+        .unwrap()
+        .unwrap();
+    assert_eq!(shelf_of("return.pdf"), taxes);
+    assert_eq!(shelf_of("beach.jpg"), trips.id);
+    assert_eq!(shelf_of("statement.pdf"), banking.id);
+
+    let unsorted = database.get_or_create_unsorted().unwrap();
+    assert_eq!(shelf_of("mystery.bin"), unsorted);
+    assert_eq!(shelf_of("scrap.txt"), unsorted);
+    assert!(database.get_cabinet_by_name("Orphan").unwrap().is_none());
+}
+
+#[test]
+fn test_near_identical_documents_form_a_cluster() {
+    let draft = "Quarterly report for the marketing team. Revenue grew by twelve percent \
+        compared to last quarter, driven mostly by the new subscription plans. Churn \
+        stayed flat and the team expects similar growth next quarter if the campaign \
+        budget is approved by the board in the upcoming planning meeting.";
+    let items = vec![
+        file_with_preview("report_draft1.txt", draft.to_string()),
+        file_with_preview(
+            "report_draft2.txt",
+            draft.replace("twelve percent", "thirteen percent"),
+        ),
+        file_with_preview(
+            "report_final.txt",
+            draft.replace("upcoming planning meeting", "planning meeting"),
+        ),
+        file_with_preview(
+            "recipe.txt",
+            "Preheat the oven to two hundred degrees, whisk the eggs with sugar until \
+            pale, fold in the flour and bake the sponge for twenty five minutes before \
+            letting it cool on a wire rack overnight."
+                .to_string(),
+        ),
+    ];
+
+    let similar = BatchProcessor::similarity_clusters(&items);
+
+    assert_eq!(similar.len(), 3, "{:?}", similar);
+    let mut others = similar[&PathBuf::from("/tmp/report_draft1.txt")].clone();
+    others.sort();
+    assert_eq!(others, vec!["report_draft2.txt", "report_final.txt"]);
+    assert!(!similar.contains_key(&PathBuf::from("/tmp/recipe.txt")));
+}
+
+/// Writes a PNG of a smooth wave pattern, so downscaling barely changes it.
+fn write_wave_png(path: &std::path::Path, size: u32, shift: u8, frequency: f32) {
+    let image = image::GrayImage::from_fn(size, size, |x, y| {
+        let (x, y) = (x as f32 / size as f32, y as f32 / size as f32);
+        let wave = (x * frequency).sin() * (y * frequency * 0.7).cos();
+        image::Luma([(120.0 + 100.0 * wave) as u8 + shift])
+    });
+    image.save(path).unwrap();
+}
+
+fn image_item(path: PathBuf) -> ProcessingItem {
+    ProcessingItem::File(EnrichedFile {
+        name: path.file_name().unwrap().to_string_lossy().to_string(),
+        extension: Some("png".to_string()),
+        file_type: "image/png".to_string(),
+        size: fs::metadata(&path).unwrap().len(),
+        content_preview: None,
+        detected_title: None,
+        owner: None,
+        perceptual_hash: Some(crate::file_analyzer::perceptual_hash(&path).unwrap()),
+        content_hash: None,
+        path,
+    })
+}
+
+#[test]
+fn test_near_identical_images_share_a_visual_group() {
+    let temp_dir = TempDir::new().unwrap();
+    let original = temp_dir.path().join("beach.png");
+    let edited = temp_dir.path().join("beach_edited.png");
+    let other = temp_dir.path().join("forest.png");
+    write_wave_png(&original, 200, 0, 6.0);
+    // Resized and slightly brightened
+    write_wave_png(&edited, 150, 8, 6.0);
+    write_wave_png(&other, 200, 0, 17.0);
+
+    let items = vec![
+        image_item(original.clone()),
+        image_item(other.clone()),
+        image_item(edited.clone()),
+        file_with_preview("notes.txt", "some notes".to_string()),
+    ];
+    let clusters = BatchProcessor::image_clusters(&items);
+
+    assert_eq!(clusters.len(), 2, "{:?}", clusters);
+    assert_eq!(clusters[&original], 1);
+    assert_eq!(clusters[&edited], 1);
+    assert!(!clusters.contains_key(&other));
+
+    let mut metadata = BatchProcessor::item_metadata(0, &items[0]);
+    metadata.image_cluster = clusters[&original];
+    assert!(BatchProcessor::format_items(&[metadata]).contains("visual group 1"));
+}
+
+#[tokio::test]
+async fn test_consecutive_batch_failures_abort_the_run() {
+    let temp_dir = TempDir::new().unwrap();
+    let items = (0..5)
+        .map(|i| file_with_preview(&format!("note{}.txt", i), "some notes".repeat(20)))
+        .collect::<Vec<_>>();
+
+    let batch_processor = BatchProcessor::new(
+        LLMProvider::new_mock(vec!["not json".to_string()]),
+        temp_dir.path().to_path_buf(),
+    )
+    .with_options(BatchOptions {
+        token_budget: Some(1),
+        max_consecutive_failures: Some(2),
+        ..Default::default()
+    });
+
+    let error = batch_processor
+        .process_items_sequentially(items)
+        .await
+        .unwrap_err();
+
+    assert!(
+        error.to_string().contains("appears to be down"),
+        "{}",
+        error
+    );
+}
+
+#[tokio::test]
+async fn test_batch_overflowing_the_context_window_is_split() {
+    let temp_dir = TempDir::new().unwrap();
+    let items = (0..4)
+        .map(|i| file_with_preview(&format!("note{}.txt", i), "some notes".to_string()))
+        .collect::<Vec<_>>();
+    let analysis = |id: &str| ItemAnalysis {
+        id: id.to_string(),
+        description: "Notes".to_string(),
+        suggested_name: String::new(),
+        rename_confidence: 0.0,
+        is_opaque_directory: false,
+        cabinet: CabinetAssignment {
+            assignment_type: "new".to_string(),
+            existing_id: 0,
+            new_name: "Notes".to_string(),
+            new_description: "Notes".to_string(),
+        },
+        shelf: ShelfAssignment {
+            assignment_type: "new".to_string(),
+            existing_id: 0,
+            new_name: "Misc".to_string(),
+            new_description: "Misc".to_string(),
+        },
+    };
+    let half = serde_json::to_string(&BatchAnalysisResponse {
+        items: vec![analysis("0"), analysis("1")],
+    })
+    .unwrap();
+    // The whole batch doesn't fit, both halves do
+    let provider = LLMProvider::new_mock(vec![
+        "error: This model's maximum context length is 8192 tokens".to_string(),
+        half,
+    ]);
+
+    let batch_processor = BatchProcessor::new(provider.clone(), temp_dir.path().to_path_buf())
+        .with_options(BatchOptions {
+            split_on_context_overflow: true,
+            ..Default::default()
+        });
+    let timings = batch_processor
+        .process_items_sequentially(items)
+        .await
+        .unwrap();
+
+    assert_eq!(timings.len(), 1);
+    assert!(timings[0].succeeded);
+    assert_eq!(provider.token_usage().requests, 2);
+    let database = Database::open_or_create(temp_dir.path()).unwrap();
+    assert_eq!(database.get_processed_paths().unwrap().len(), 4);
+}
+
+#[test]
+fn test_opaque_reason_is_reported() {
+    let dir = |name: &str, sampled_items: Vec<SampledItem>| EnrichedDirectory {
+        path: PathBuf::from("/tmp").join(name),
+        name: name.to_string(),
+        sampled_items,
+        is_git_repository: false,
+        readme_preview: None,
+        content_preview: None,
+    };
+    let screenshots = (1..=6)
+        .map(|i| SampledItem {
+            name: format!("Screenshot_{}.png", i),
+            is_file: true,
+            extension: Some("png".to_string()),
+        })
+        .collect();
+
+    assert_eq!(
+        BatchProcessor::opaque_reason(&dir("node_modules", Vec::new())),
+        Some(OpaqueReason::KnownPattern("node_modules".to_string()))
+    );
+    assert_eq!(
+        BatchProcessor::opaque_reason(&dir("Screenshots", screenshots)),
+        Some(OpaqueReason::HomogeneousNumbered {
+            extension: "png".to_string()
+        })
+    );
+    assert_eq!(
+        BatchProcessor::opaque_reason(&dir("Projects", Vec::new())),
+        None
+    );
+    assert_eq!(
+        OpaqueReason::KnownPattern("node_modules".to_string()).to_string(),
+        "known directory name 'node_modules'"
+    );
+}
+
+#[test]
+fn test_strict_mode_rejects_anomalies() {
+    let request = BatchAnalysisRequest {
+        items: vec![BatchProcessor::item_metadata(
+            0,
+            &file_with_preview("return.pdf", "Tax return 2023".to_string()),
+        )],
+        existing_cabinets: vec![
+            CabinetInfo {
+                id: 1,
+                name: "Finance".to_string(),
+                description: "Money".to_string(),
+            },
+            CabinetInfo {
+                id: 2,
+                name: "Photos".to_string(),
+                description: "Pictures".to_string(),
+            },
+        ],
+        existing_shelves: vec![ShelfInfo {
+            id: 3,
+            cabinet_id: 1,
+            name: "Taxes".to_string(),
+            description: "Returns".to_string(),
+        }],
+    };
+    let analysis = ItemAnalysis {
+        id: "0".to_string(),
+        description: "Tax return".to_string(),
+        suggested_name: String::new(),
+        rename_confidence: 0.0,
+        is_opaque_directory: false,
+        cabinet: CabinetAssignment {
+            assignment_type: "existing".to_string(),
+            existing_id: 1,
+            new_name: String::new(),
+            new_description: String::new(),
+        },
+        shelf: ShelfAssignment {
+            assignment_type: "existing".to_string(),
+            existing_id: 3,
+            new_name: String::new(),
+            new_description: String::new(),
+        },
+    };
+    let options = BatchOptions {
+        strict: true,
+        ..Default::default()
+    };
+    let validate = |change: &dyn Fn(&mut BatchAnalysisResponse)| {
+        let mut response = BatchAnalysisResponse {
+            items: vec![analysis.clone()],
+        };
+        change(&mut response);
+        BatchProcessor::validate_strict(&request, &response, &options).map_err(|e| e.to_string())
+    };
+
+    assert!(validate(&|_| {}).is_ok());
+    assert!(
+        validate(&|r| r.items.clear())
+            .unwrap_err()
+            .contains("Expected 1 analyzed items")
+    );
+    assert!(
+        validate(&|r| r.items[0].id = "7".to_string())
+            .unwrap_err()
+            .contains("mismatched id '7'")
+    );
+    assert!(
+        validate(&|r| r.items[0].shelf.assignment_type = "guess".to_string())
+            .unwrap_err()
+            .contains("unknown shelf assignment_type 'guess'")
+    );
+    assert!(
+        validate(&|r| r.items[0].cabinet.existing_id = 2)
+            .unwrap_err()
+            .contains("does not belong to the assigned cabinet")
+    );
+    assert!(
+        validate(&|r| {
+            r.items[0].shelf.assignment_type = "new".to_string();
+            r.items[0].shelf.new_description = "Returns".to_string();
+        })
+        .unwrap_err()
+        .contains("new shelf is missing its name")
+    );
+}
+
+#[tokio::test]
+async fn test_strict_mode_aborts_on_the_first_failed_batch() {
+    let temp_dir = TempDir::new().unwrap();
+    let items = (0..5)
+        .map(|i| file_with_preview(&format!("note{}.txt", i), "some notes".repeat(20)))
+        .collect::<Vec<_>>();
+
+    let batch_processor = BatchProcessor::new(
+        LLMProvider::new_mock(vec!["not json".to_string()]),
+        temp_dir.path().to_path_buf(),
+    )
+    .with_options(BatchOptions {
+        token_budget: Some(1),
+        strict: true,
+        ..Default::default()
+    });
+
+    let error = batch_processor
+        .process_items_sequentially(items)
+        .await
+        .unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Batch 1/5 failed in strict mode"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_group_by_owner_shows_owners_to_the_model() {
+    let ProcessingItem::File(mut file) = file_with_preview("report.txt", String::new()) else {
+        unreachable!()
+    };
+    file.owner = Some("alice (group staff)".to_string());
+    let metadata = BatchProcessor::item_metadata(0, &ProcessingItem::File(file));
+
+    assert!(BatchProcessor::format_items(&[metadata]).contains("owned by alice (group staff)"));
+
+    let options = BatchOptions {
+        group_by: GroupBy::Owner,
+        ..Default::default()
+    };
+    assert!(BatchProcessor::extra_guidelines(&options).contains("one cabinet per owner"));
+    assert!(!BatchProcessor::extra_guidelines(&BatchOptions::default()).contains("owner"));
+}
+
+#[test]
+fn test_preferences_from_corrections_are_listed() {
+    let options = BatchOptions {
+        preferences: vec![
+            "\"receipts belong with taxes\" (e.g. r.pdf → Finance/Taxes)".to_string(),
+        ],
+        ..Default::default()
+    };
+    let guidelines = BatchProcessor::extra_guidelines(&options);
+    assert!(guidelines.contains("follow the same preferences"));
+    assert!(guidelines.contains("  - \"receipts belong with taxes\" (e.g. r.pdf → Finance/Taxes)"));
+    assert!(!BatchProcessor::extra_guidelines(&BatchOptions::default()).contains("preferences"));
+}
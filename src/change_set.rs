@@ -0,0 +1,106 @@
+use anyhow::{Context, Result, anyhow};
+use colored::*;
+use std::path::PathBuf;
+
+use crate::{
+    consolidator::{MergeKind, ProposedMerge, print_merge},
+    database::Database,
+};
+
+/// A single modification a mutating command would make.
+#[derive(Debug, Clone)]
+pub enum Change {
+    /// Merge a cabinet or shelf into another. Only the database changes.
+    Merge(ProposedMerge),
+    /// Delete a file or a directory with everything in it.
+    Delete { path: PathBuf, files: usize },
+}
+
+/// Everything a mutating command would change, computed before anything is
+/// touched. The same set is printed for `--dry-run` and the confirmation, and
+/// then applied, so the preview is exactly what happens.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeSet {
+    changes: Vec<Change>,
+}
+
+impl ChangeSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, change: Change) {
+        self.changes.push(change);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    /// Files removed by the deletions in the set.
+    pub fn deleted_files(&self) -> usize {
+        self.changes
+            .iter()
+            .map(|change| match change {
+                Change::Delete { files, .. } => *files,
+                Change::Merge(_) => 0,
+            })
+            .sum()
+    }
+
+    pub fn print(&self, title: &str) {
+        println!("\n{}", title.cyan().bold());
+        for change in &self.changes {
+            match change {
+                Change::Merge(merge) => print_merge(merge),
+                Change::Delete { path, files } => println!(
+                    "  {} {} {}",
+                    "Delete".dimmed(),
+                    path.display().to_string().red(),
+                    format!("({} files)", files).dimmed()
+                ),
+            }
+        }
+    }
+
+    /// Applies the changes in order. Merges need the database they were proposed from.
+    pub fn apply(&self, database: Option<&Database>) -> Result<()> {
+        for change in &self.changes {
+            match change {
+                Change::Merge(merge) => {
+                    let database = database
+                        .ok_or_else(|| anyhow!("Merging {} needs a database", merge.source_name))?;
+                    match merge.kind {
+                        MergeKind::Cabinet => {
+                            database.merge_cabinets(merge.source_id, merge.target_id)?
+                        }
+                        MergeKind::Shelf => {
+                            database.merge_shelves(merge.source_id, merge.target_id)?
+                        }
+                    }
+                }
+                Change::Delete { path, .. } => {
+                    if path.is_dir() {
+                        std::fs::remove_dir_all(path)
+                    } else {
+                        std::fs::remove_file(path)
+                    }
+                    .with_context(|| format!("Failed to delete {}", path.display()))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Printed after a `--dry-run` preview.
+pub fn print_dry_run_notice() {
+    println!("\n{}", "Dry run: nothing was changed.".yellow());
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,60 @@
+use super::{Change, ChangeSet};
+use crate::consolidator::{MergeKind, ProposedMerge};
+use crate::database::Database;
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_deletions_apply_only_when_asked() {
+    let temp_dir = TempDir::new().unwrap();
+    let run_dir = temp_dir.path().join("run");
+    fs::create_dir_all(run_dir.join("docs")).unwrap();
+    fs::write(run_dir.join("docs/a.txt"), "a").unwrap();
+    let file = temp_dir.path().join("b.txt");
+    fs::write(&file, "b").unwrap();
+
+    let mut changes = ChangeSet::new();
+    changes.push(Change::Delete {
+        path: run_dir.clone(),
+        files: 1,
+    });
+    changes.push(Change::Delete {
+        path: file.clone(),
+        files: 1,
+    });
+
+    // Previewing leaves everything in place
+    changes.print("Would Delete");
+    assert!(run_dir.exists() && file.exists());
+    assert_eq!(changes.deleted_files(), 2);
+
+    changes.apply(None).unwrap();
+    assert!(!run_dir.exists());
+    assert!(!file.exists());
+}
+
+#[test]
+fn test_merges_need_a_database() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Database::open_or_create(temp_dir.path()).unwrap();
+    let photos = db.create_cabinet("Photos", "Pictures").unwrap();
+    let images = db.create_cabinet("Images", "Pictures").unwrap();
+
+    let mut changes = ChangeSet::new();
+    changes.push(Change::Merge(ProposedMerge {
+        kind: MergeKind::Cabinet,
+        source_id: photos,
+        target_id: images,
+        source_name: "Photos".to_string(),
+        target_name: "Images".to_string(),
+        reason: "Same content".to_string(),
+    }));
+
+    assert!(changes.apply(None).is_err());
+    assert_eq!(db.list_cabinets().unwrap().len(), 2);
+
+    changes.apply(Some(&db)).unwrap();
+    let cabinets = db.list_cabinets().unwrap();
+    assert_eq!(cabinets.len(), 1);
+    assert_eq!(cabinets[0].name, "Images");
+}
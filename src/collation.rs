@@ -0,0 +1,103 @@
+use feruca::{Collator, Locale, Tailoring};
+use std::{cmp::Ordering, path::Path, sync::RwLock};
+
+/// Languages written in the Arabic script, which sort before Latin in their locales.
+const ARABIC_SCRIPT_LANGUAGES: &[&str] = &["ar", "fa", "ps", "sd", "ug", "ur"];
+
+/// `None` until set with `--locale`, in which case the environment's locale is used.
+static COLLATION: RwLock<Option<Collation>> = RwLock::new(None);
+
+/// How names are ordered wherever they are listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Collation {
+    /// Raw byte order, for the `C` and `POSIX` locales
+    Binary,
+    /// The Unicode Collation Algorithm with a CLDR tailoring
+    Unicode(Locale),
+}
+
+impl Collation {
+    /// Maps a locale such as `de_DE.UTF-8` or `fa-IR` to a collation. Unicode
+    /// collation only has a root order and an Arabic-script tailoring, so most
+    /// locales share the root order, which already handles accents and case.
+    pub fn from_locale(locale: &str) -> Self {
+        let locale = locale.trim();
+        let language = locale
+            .split(['_', '-', '.', '@'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+
+        if language == "c" || language == "posix" {
+            Collation::Binary
+        } else if ARABIC_SCRIPT_LANGUAGES.contains(&language.as_str()) {
+            Collation::Unicode(Locale::ArabicScript)
+        } else {
+            Collation::Unicode(Locale::Root)
+        }
+    }
+
+    pub fn from_env() -> Self {
+        sys_locale::get_locale()
+            .map(|locale| Self::from_locale(&locale))
+            .unwrap_or(Collation::Unicode(Locale::Root))
+    }
+
+    /// Sorts `items` by the name `key` returns.
+    pub fn sort_by_name<T>(self, items: &mut [T], key: impl Fn(&T) -> &str) {
+        let mut compare = self.comparator();
+        items.sort_by(|a, b| compare(key(a), key(b)));
+    }
+
+    /// Sorts `items` by the path `key` returns, one component at a time, so that
+    /// separators don't take part in the collation.
+    pub fn sort_by_path<T>(self, items: &mut [T], key: impl Fn(&T) -> &Path) {
+        let mut compare = self.comparator();
+        items.sort_by(|a, b| {
+            let mut a = key(a).components();
+            let mut b = key(b).components();
+            loop {
+                match (a.next(), b.next()) {
+                    (Some(a), Some(b)) => {
+                        let ordering = compare(
+                            &a.as_os_str().to_string_lossy(),
+                            &b.as_os_str().to_string_lossy(),
+                        );
+                        if ordering != Ordering::Equal {
+                            return ordering;
+                        }
+                    }
+                    (a, b) => return a.is_some().cmp(&b.is_some()),
+                }
+            }
+        });
+    }
+
+    /// Punctuation is non-ignorable, as it is significant in file names.
+    fn comparator(self) -> impl FnMut(&str, &str) -> Ordering {
+        let mut collator = match self {
+            Collation::Binary => None,
+            Collation::Unicode(locale) => Some(Collator::new(Tailoring::Cldr(locale), false, true)),
+        };
+        move |a, b| match &mut collator {
+            Some(collator) => collator.collate(a, b),
+            None => a.cmp(b),
+        }
+    }
+}
+
+/// Overrides the environment's locale for the rest of the process (`--locale`).
+pub fn set_locale(locale: &str) {
+    *COLLATION.write().unwrap_or_else(|e| e.into_inner()) = Some(Collation::from_locale(locale));
+}
+
+/// The collation names are sorted with.
+pub fn collation() -> Collation {
+    COLLATION
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .unwrap_or_else(Collation::from_env)
+}
+
+#[cfg(test)]
+mod tests;
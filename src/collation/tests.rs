@@ -0,0 +1,58 @@
+use super::Collation;
+use feruca::Locale;
+use std::path::PathBuf;
+
+#[test]
+fn test_from_locale() {
+    assert_eq!(Collation::from_locale("C"), Collation::Binary);
+    assert_eq!(Collation::from_locale("POSIX"), Collation::Binary);
+    assert_eq!(
+        Collation::from_locale("de_DE.UTF-8"),
+        Collation::Unicode(Locale::Root)
+    );
+    assert_eq!(
+        Collation::from_locale("fa-IR"),
+        Collation::Unicode(Locale::ArabicScript)
+    );
+}
+
+#[test]
+fn test_unicode_collation_sorts_accents_and_case_naturally() {
+    let mut names = vec!["zebra", "Äpfel", "apple", "Banana", "éclair"];
+    Collation::Unicode(Locale::Root).sort_by_name(&mut names, |name| name);
+    assert_eq!(names, vec!["Äpfel", "apple", "Banana", "éclair", "zebra"]);
+
+    let mut names = vec!["zebra", "Äpfel", "apple", "Banana"];
+    Collation::Binary.sort_by_name(&mut names, |name| name);
+    assert_eq!(names, vec!["Banana", "apple", "zebra", "Äpfel"]);
+}
+
+#[test]
+fn test_arabic_script_sorts_before_latin() {
+    let mut names = vec!["report", "تقرير"];
+    Collation::Unicode(Locale::Root).sort_by_name(&mut names, |name| name);
+    assert_eq!(names, vec!["report", "تقرير"]);
+
+    Collation::Unicode(Locale::ArabicScript).sort_by_name(&mut names, |name| name);
+    assert_eq!(names, vec!["تقرير", "report"]);
+}
+
+#[test]
+fn test_sort_by_path_compares_components() {
+    let mut paths = vec![
+        PathBuf::from("notes-old"),
+        PathBuf::from("notes/todo.txt"),
+        PathBuf::from("Notes.md"),
+        PathBuf::from("notes"),
+    ];
+    Collation::Unicode(Locale::Root).sort_by_path(&mut paths, |path| path);
+    assert_eq!(
+        paths,
+        vec![
+            PathBuf::from("notes"),
+            PathBuf::from("notes/todo.txt"),
+            PathBuf::from("notes-old"),
+            PathBuf::from("Notes.md"),
+        ]
+    );
+}
@@ -1,14 +1,49 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use crate::providers::Provider;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Config {
     pub provider: Provider,
     pub model_name: String,
+    /// Output token limit for the configured provider; falls back to built-in defaults when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u64>,
+    /// Directory to keep databases in instead of inside each organized directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db_path: Option<PathBuf>,
+    /// Sampling temperature for the configured provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Nucleus sampling (top_p) for the configured provider
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    /// Maximum number of cabinets in a plan; smaller ones are merged into Overflow beyond it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cabinets: Option<usize>,
+    /// Maximum number of shelves per cabinet; smaller ones are merged into Other beyond it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_shelves_per_cabinet: Option<usize>,
+    /// Custom batch analysis prompt template file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_template: Option<PathBuf>,
+    /// Name of the cabinet for items that can't be classified; Unsorted when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unsorted_cabinet: Option<String>,
+    /// Files and directories scanned at once, which mostly waits on I/O
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scan_concurrency: Option<usize>,
+    /// Content extractions run at once, which is CPU-bound; one per core when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extraction_parallelism: Option<usize>,
+    /// API base URL for the configured provider, e.g. a self-hosted OpenAI-compatible
+    /// gateway; the provider's environment variable or default when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
 }
 
 impl Config {
@@ -26,10 +61,73 @@ impl Config {
         }
 
         let content = fs::read_to_string(&config_path)?;
-        let config: Config = serde_json::from_str(&content)?;
+        let config = Self::parse(&content)
+            .map_err(|e| anyhow!("Invalid configuration in {}: {}", config_path.display(), e))?;
         Ok(Some(config))
     }
 
+    fn parse(content: &str) -> Result<Config> {
+        let value: serde_json::Value = serde_json::from_str(content)?;
+
+        // serde's unknown variant error doesn't say which field it came from
+        if let Some(provider) = value.get("provider")
+            && serde_json::from_value::<Provider>(provider.clone()).is_err()
+        {
+            bail!(
+                "`provider` must be one of OpenAI, Anthropic, Ollama, Groq or Mistral, found {}",
+                provider
+            );
+        }
+
+        let config: Config = serde_json::from_value(value)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Checks the settings serde can't, naming the offending field.
+    pub fn validate(&self) -> Result<()> {
+        if self.model_name.trim().is_empty() {
+            bail!("`model_name` must not be empty");
+        }
+        if self.max_tokens == Some(0) {
+            bail!("`max_tokens` must be at least 1");
+        }
+        if let Some(temperature) = self.temperature
+            && !(0.0..=2.0).contains(&temperature)
+        {
+            bail!("`temperature` must be between 0 and 2, found {}", temperature);
+        }
+        if let Some(top_p) = self.top_p
+            && !(0.0..=1.0).contains(&top_p)
+        {
+            bail!("`top_p` must be between 0 and 1, found {}", top_p);
+        }
+        if self.max_cabinets == Some(0) {
+            bail!("`max_cabinets` must be at least 1");
+        }
+        if self.max_shelves_per_cabinet == Some(0) {
+            bail!("`max_shelves_per_cabinet` must be at least 1");
+        }
+        if self.db_path.as_deref() == Some(Path::new("")) {
+            bail!("`db_path` must not be empty");
+        }
+        if let Some(base_url) = &self.base_url {
+            if !self.provider.supports_base_url() {
+                bail!(
+                    "`base_url` is only supported for OpenAI, Anthropic and Ollama, not {}",
+                    self.provider
+                );
+            }
+            if !(base_url.starts_with("http://") || base_url.starts_with("https://")) {
+                bail!(
+                    "`base_url` must be an http:// or https:// URL, found {:?}",
+                    base_url
+                );
+            }
+        }
+        Ok(())
+    }
+
     pub fn save(&self) -> Result<()> {
         let config_path = Self::get_config_path()?;
         
@@ -47,4 +145,7 @@ impl Config {
     pub fn get_config_file_path() -> Result<PathBuf> {
         Self::get_config_path()
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests;
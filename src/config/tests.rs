@@ -0,0 +1,65 @@
+use super::Config;
+
+fn parse_error(content: &str) -> String {
+    Config::parse(content).unwrap_err().to_string()
+}
+
+#[test]
+fn test_valid_config_parses() {
+    let config = Config::parse(
+        r#"{"provider": "Anthropic", "model_name": "claude-sonnet-4-0", "temperature": 0.5}"#,
+    )
+    .unwrap();
+
+    assert_eq!(config.model_name, "claude-sonnet-4-0");
+    assert_eq!(config.temperature, Some(0.5));
+}
+
+#[test]
+fn test_unknown_provider_names_field() {
+    let error = parse_error(r#"{"provider": "Gemini", "model_name": "gemini-pro"}"#);
+
+    assert!(error.contains("`provider`"), "{}", error);
+    assert!(error.contains("Gemini"), "{}", error);
+}
+
+#[test]
+fn test_out_of_range_setting_names_field() {
+    let error =
+        parse_error(r#"{"provider": "OpenAI", "model_name": "gpt-4o", "temperature": 5.0}"#);
+
+    assert!(error.contains("`temperature`"), "{}", error);
+}
+
+#[test]
+fn test_misspelled_field_is_rejected() {
+    let error = parse_error(r#"{"provider": "OpenAI", "model_name": "gpt-4o", "temprature": 0.5}"#);
+
+    assert!(error.contains("temprature"), "{}", error);
+}
+
+#[test]
+fn test_empty_model_name_is_rejected() {
+    let error = parse_error(r#"{"provider": "Ollama", "model_name": " "}"#);
+
+    assert!(error.contains("`model_name`"), "{}", error);
+}
+
+#[test]
+fn test_base_url_must_be_http_for_a_provider_that_supports_it() {
+    let config = Config::parse(
+        r#"{"provider": "OpenAI", "model_name": "gpt-4o", "base_url": "http://localhost:4000/v1"}"#,
+    )
+    .unwrap();
+    assert_eq!(config.base_url.as_deref(), Some("http://localhost:4000/v1"));
+
+    let error = parse_error(
+        r#"{"provider": "OpenAI", "model_name": "gpt-4o", "base_url": "localhost:4000"}"#,
+    );
+    assert!(error.contains("`base_url`"), "{}", error);
+
+    let error = parse_error(
+        r#"{"provider": "Groq", "model_name": "llama3", "base_url": "https://example.com"}"#,
+    );
+    assert!(error.contains("Groq"), "{}", error);
+}
@@ -0,0 +1,210 @@
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::collections::HashSet;
+
+use crate::{
+    batch_processor::BatchProcessor,
+    change_set::{Change, ChangeSet},
+    database::{Cabinet, Database, Shelf, is_unsorted_name},
+    models::{ConsolidationResponse, ContainerMerge},
+    providers::LLMProvider,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MergeKind {
+    Cabinet,
+    Shelf,
+}
+
+/// A validated merge, with names resolved for display.
+#[derive(Debug, Clone)]
+pub struct ProposedMerge {
+    pub kind: MergeKind,
+    pub source_id: i64,
+    pub target_id: i64,
+    pub source_name: String,
+    pub target_name: String,
+    pub reason: String,
+}
+
+/// Proposes and applies merges of redundant cabinets and shelves. Only the
+/// taxonomy in the database changes; no files are moved.
+pub struct Consolidator {
+    provider: LLMProvider,
+    database: Database,
+}
+
+impl Consolidator {
+    pub fn new(provider: LLMProvider, database: Database) -> Self {
+        Self { provider, database }
+    }
+
+    /// The Unsorted cabinet and its shelves are left out, so they are never merged.
+    pub async fn propose(&self) -> Result<Vec<ProposedMerge>> {
+        let (cabinets, shelves) = mergeable_structure(&self.database)?;
+        if cabinets.len() < 2 && shelves.len() < 2 {
+            return Ok(Vec::new());
+        }
+
+        let prompt = self.prompt(&cabinets, &shelves)?;
+        let mut last_error = None;
+        for _ in 0..BatchProcessor::MAX_EXTRACTION_RETRIES {
+            match self
+                .provider
+                .extract::<ConsolidationResponse>(&prompt)
+                .await
+            {
+                Ok(response) => return Ok(Self::validate(response.merges, &cabinets, &shelves)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow!("Failed to propose a consolidation")))
+    }
+
+    fn prompt(&self, cabinets: &[Cabinet], shelves: &[Shelf]) -> Result<String> {
+        let items = self.database.list_all_items()?;
+        let mut structure = String::new();
+        for cabinet in cabinets {
+            structure.push_str(&format!(
+                "Cabinet (id: {}): {} - {}\n",
+                cabinet.id, cabinet.name, cabinet.description
+            ));
+            for shelf in shelves.iter().filter(|s| s.cabinet_id == cabinet.id) {
+                let item_count = items.iter().filter(|i| i.shelf_id == shelf.id).count();
+                structure.push_str(&format!(
+                    "  Shelf (id: {}): {} - {} ({} items)\n",
+                    shelf.id, shelf.name, shelf.description, item_count
+                ));
+            }
+        }
+
+        Ok(format!(
+            "This is the cabinet and shelf structure of an organized directory, \
+            built up over several runs.\n\n\
+            {}\n\
+            Propose merges of redundant containers, i.e. cabinets or shelves that \
+            cover the same kind of content under different names (for example \
+            \"Images\" and \"Photos\").\n\n\
+            Guidelines:\n\
+            - Only merge containers that are truly redundant, not merely related\n\
+            - Merge the smaller or less descriptive container into the other one\n\
+            - Only merge a cabinet into a cabinet and a shelf into a shelf\n\
+            - Return an empty list if nothing should be merged\n",
+            structure
+        ))
+    }
+
+    /// Drops merges that reference unknown containers, merge a container into
+    /// itself, or conflict with an earlier merge.
+    fn validate(
+        merges: Vec<ContainerMerge>,
+        cabinets: &[Cabinet],
+        shelves: &[Shelf],
+    ) -> Vec<ProposedMerge> {
+        let cabinet_name = |id: i64| cabinets.iter().find(|c| c.id == id).map(|c| c.name.clone());
+        let shelf_name = |id: i64| {
+            let shelf = shelves.iter().find(|s| s.id == id)?;
+            let cabinet = cabinet_name(shelf.cabinet_id)?;
+            Some(format!("{}/{}", cabinet, shelf.name))
+        };
+
+        let mut sources = HashSet::new();
+        let mut targets = HashSet::new();
+        let mut valid = Vec::new();
+        for merge in merges {
+            let (kind, source_name, target_name) = match merge.kind.as_str() {
+                "cabinet" => (
+                    MergeKind::Cabinet,
+                    cabinet_name(merge.source_id),
+                    cabinet_name(merge.target_id),
+                ),
+                "shelf" => (
+                    MergeKind::Shelf,
+                    shelf_name(merge.source_id),
+                    shelf_name(merge.target_id),
+                ),
+                _ => continue,
+            };
+            let (Some(source_name), Some(target_name)) = (source_name, target_name) else {
+                continue;
+            };
+
+            let source = (kind, merge.source_id);
+            let target = (kind, merge.target_id);
+            // A container can't be merged twice, nor receive or give contents
+            // after it was merged away
+            if source == target
+                || sources.contains(&source)
+                || sources.contains(&target)
+                || targets.contains(&source)
+            {
+                continue;
+            }
+            sources.insert(source);
+            targets.insert(target);
+
+            valid.push(ProposedMerge {
+                kind,
+                source_id: merge.source_id,
+                target_id: merge.target_id,
+                source_name,
+                target_name,
+                reason: merge.reason,
+            });
+        }
+
+        valid
+    }
+
+    pub fn apply(&self, changes: &ChangeSet) -> Result<()> {
+        changes.apply(Some(&self.database))
+    }
+}
+
+/// The changes applying `merges` makes. Shelf merges go first, since a cabinet
+/// merge can merge away same-named shelves a shelf merge refers to.
+pub fn merge_changes(merges: &[ProposedMerge]) -> ChangeSet {
+    let mut changes = ChangeSet::new();
+    for kind in [MergeKind::Shelf, MergeKind::Cabinet] {
+        for merge in merges.iter().filter(|m| m.kind == kind) {
+            changes.push(Change::Merge(merge.clone()));
+        }
+    }
+    changes
+}
+
+/// All cabinets and shelves except the Unsorted cabinet and its shelves.
+fn mergeable_structure(database: &Database) -> Result<(Vec<Cabinet>, Vec<Shelf>)> {
+    let (cabinets, unsorted): (Vec<_>, Vec<_>) = database
+        .list_cabinets()?
+        .into_iter()
+        .partition(|cabinet| !is_unsorted_name(&cabinet.name));
+    let shelves = database
+        .list_shelves(None)?
+        .into_iter()
+        .filter(|shelf| {
+            !unsorted
+                .iter()
+                .any(|cabinet| cabinet.id == shelf.cabinet_id)
+        })
+        .collect();
+    Ok((cabinets, shelves))
+}
+
+pub fn print_merge(merge: &ProposedMerge) {
+    let kind = match merge.kind {
+        MergeKind::Cabinet => "Cabinet",
+        MergeKind::Shelf => "Shelf",
+    };
+    println!(
+        "  {} {} → {}",
+        kind.dimmed(),
+        merge.source_name.yellow(),
+        merge.target_name.green()
+    );
+    println!("    {}", merge.reason.dimmed());
+}
+
+#[cfg(test)]
+mod tests;
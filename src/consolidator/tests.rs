@@ -0,0 +1,85 @@
+use super::{Consolidator, MergeKind, merge_changes};
+use crate::database::{Database, Item};
+use crate::models::{ConsolidationResponse, ContainerMerge};
+use crate::providers::LLMProvider;
+use chrono::Utc;
+use tempfile::TempDir;
+
+fn merge(kind: &str, source_id: i64, target_id: i64) -> ContainerMerge {
+    ContainerMerge {
+        kind: kind.to_string(),
+        source_id,
+        target_id,
+        reason: "Same content".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_consolidate_merges_redundant_cabinets() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Database::open_or_create(temp_dir.path()).unwrap();
+    let images = db.create_cabinet("Images", "Pictures").unwrap();
+    let photos = db.create_cabinet("Photos", "Photographs").unwrap();
+    let images_travel = db.create_shelf(images, "Travel", "Trips").unwrap();
+    let photos_travel = db.create_shelf(photos, "Travel", "Trips").unwrap();
+    db.insert_item(&Item {
+        id: None,
+        shelf_id: photos_travel,
+        path: "/beach.jpg".to_string(),
+        original_name: "beach.jpg".to_string(),
+        suggested_name: None,
+        description: "Beach photo".to_string(),
+        file_type: "image".to_string(),
+        is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
+        processed_at: Utc::now(),
+    })
+    .unwrap();
+
+    // Unknown ids, self-merges and mismatched kinds are dropped
+    let response = ConsolidationResponse {
+        merges: vec![
+            merge("cabinet", photos, images),
+            merge("cabinet", 999, images),
+            merge("shelf", images_travel, images_travel),
+            merge("drawer", photos, images),
+        ],
+    };
+    let provider = LLMProvider::new_mock(vec![serde_json::to_string(&response).unwrap()]);
+    let consolidator = Consolidator::new(provider, db);
+
+    let merges = consolidator.propose().await.unwrap();
+    assert_eq!(merges.len(), 1);
+    assert_eq!(merges[0].kind, MergeKind::Cabinet);
+    assert_eq!(merges[0].source_name, "Photos");
+    assert_eq!(merges[0].target_name, "Images");
+
+    consolidator.apply(&merge_changes(&merges)).unwrap();
+
+    let db = Database::open_or_create(temp_dir.path()).unwrap();
+    let cabinets = db.list_cabinets().unwrap();
+    assert_eq!(cabinets.len(), 1);
+    assert_eq!(db.list_shelves(None).unwrap().len(), 1);
+    let item = db.get_item_by_path("/beach.jpg").unwrap().unwrap();
+    assert_eq!(item.shelf_id, images_travel);
+}
+
+#[tokio::test]
+async fn test_consolidate_rejects_chained_merges() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = Database::open_or_create(temp_dir.path()).unwrap();
+    let a = db.create_cabinet("A", "First").unwrap();
+    let b = db.create_cabinet("B", "Second").unwrap();
+    let c = db.create_cabinet("C", "Third").unwrap();
+
+    let response = ConsolidationResponse {
+        merges: vec![merge("cabinet", a, b), merge("cabinet", b, c)],
+    };
+    let provider = LLMProvider::new_mock(vec![serde_json::to_string(&response).unwrap()]);
+
+    let merges = Consolidator::new(provider, db).propose().await.unwrap();
+    assert_eq!(merges.len(), 1);
+    assert_eq!(merges[0].source_id, a);
+}
@@ -2,11 +2,90 @@ use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
 use r2d2::Pool;
 use r2d2_sqlite::SqliteConnectionManager;
-use rusqlite::{Connection, OptionalExtension, Transaction, params};
+use rusqlite::{Connection, OptionalExtension, params};
 use serde::{Deserialize, Serialize};
-use std::path::Path;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
 
-pub(crate) const DB_NAME: &str = ".fs_organizer.db";
+use crate::collation::collation;
+use crate::models::{OrganizationPlan, ProcessingItem};
+
+/// Directory inside the organized directory that holds all of shelfie's state.
+pub(crate) const STATE_DIR_NAME: &str = ".shelfie";
+pub(crate) const DB_NAME: &str = "db.sqlite";
+/// Where the database lived before the state directory; moved on first use.
+pub(crate) const LEGACY_DB_NAME: &str = ".fs_organizer.db";
+/// Files SQLite keeps next to a database, by suffix.
+const DB_SIDE_FILE_SUFFIXES: &[&str] = &["-journal", "-wal", "-shm"];
+
+/// Names shelfie uses for its own state, which must never be used for (or scanned as)
+/// cabinet and shelf directories.
+pub(crate) const RESERVED_NAMES: &[&str] = &[
+    STATE_DIR_NAME,
+    LEGACY_DB_NAME,
+    ".fs_organizer.db-journal",
+    ".fs_organizer.db-wal",
+    ".fs_organizer.db-shm",
+    ".fs_organizer",
+    ".fs-organiser",
+    crate::trash::LEGACY_TRASH_DIR_NAME,
+    crate::manifest::MANIFEST_FILE_NAME,
+    crate::exclude::IGNORE_FILE_NAME,
+    ".",
+    "..",
+];
+
+/// Stable across Rust versions, unlike `DefaultHasher`, so database names don't change.
+pub(crate) fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    })
+}
+
+pub(crate) fn is_reserved_name(name: &str) -> bool {
+    RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Name of the cabinet, and of its shelf, for items that can't be classified confidently.
+pub const DEFAULT_UNSORTED_NAME: &str = "Unsorted";
+const UNSORTED_DESCRIPTION: &str = "Items that couldn't be classified; these need sorting by hand";
+
+static UNSORTED_NAME: RwLock<Option<String>> = RwLock::new(None);
+
+/// Renames the Unsorted cabinet for the rest of the process (`unsorted_cabinet` in the config).
+pub fn set_unsorted_name(name: &str) {
+    let name = name.trim();
+    if !name.is_empty() {
+        *UNSORTED_NAME.write().unwrap_or_else(|e| e.into_inner()) = Some(name.to_string());
+    }
+}
+
+pub fn unsorted_name() -> String {
+    UNSORTED_NAME
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+        .unwrap_or_else(|| DEFAULT_UNSORTED_NAME.to_string())
+}
+
+/// Whether a cabinet or shelf name is the Unsorted one, also after a naming
+/// convention turned it into e.g. `unsorted`.
+pub fn is_unsorted_name(name: &str) -> bool {
+    let normalize = |name: &str| {
+        name.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect::<String>()
+    };
+    normalize(name) == normalize(&unsorted_name())
+}
+
+const LAST_RUN_KEY: &str = "last_completed_run";
+const LAST_PLAN_KEY: &str = "last_executed_plan";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cabinet {
@@ -17,6 +96,15 @@ pub struct Cabinet {
     pub created_at: DateTime<Utc>,
 }
 
+/// A refinement the user made to a plan: their feedback and the items it moved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Correction {
+    pub feedback: String,
+    /// `file → Cabinet/Shelf` for each item the refinement moved.
+    pub moves: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
 mod chrono_serde {
     use chrono::{DateTime, Utc};
     use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -59,18 +147,87 @@ pub struct Item {
     pub description: String,
     pub file_type: String,
     pub is_opaque_dir: bool,
+    /// Why a directory is moved whole, if it is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opaque_reason: Option<String>,
+    /// How confident the model was in `suggested_name`, from 0 to 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rename_confidence: Option<f64>,
+    /// Hash of the file's bytes, when scanned with `--dedupe`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_hash: Option<String>,
     #[serde(with = "chrono_serde")]
     pub processed_at: DateTime<Utc>,
 }
 
+impl Item {
+    /// The suggested name, unless a minimum confidence is required and the model's
+    /// (or a missing) confidence falls short of it.
+    pub fn rename(&self, min_confidence: Option<f64>) -> Option<String> {
+        let confident = min_confidence.is_none_or(|min| {
+            self.rename_confidence
+                .is_some_and(|confidence| confidence >= min)
+        });
+        self.suggested_name.clone().filter(|_| confident)
+    }
+}
+
+/// Version of the `export-db` format, bumped on incompatible changes.
+pub const DUMP_FORMAT_VERSION: u32 = 1;
+
+/// A portable JSON copy of an organization database, for backups and moving an
+/// organization between machines. The content cache is left out, since it is
+/// rebuilt on the next run anyway.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseDump {
+    pub format_version: u32,
+    pub cabinets: Vec<Cabinet>,
+    pub shelves: Vec<Shelf>,
+    pub items: Vec<Item>,
+    pub processing_state: BTreeMap<String, String>,
+}
+
+impl DatabaseDump {
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read database export {}", path.display()))?;
+        let dump: Self = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid database export {}", path.display()))?;
+        if dump.format_version > DUMP_FORMAT_VERSION {
+            anyhow::bail!(
+                "{} was exported by a newer shelfie (format version {}, this one reads up to {})",
+                path.display(),
+                dump.format_version,
+                DUMP_FORMAT_VERSION
+            );
+        }
+        Ok(dump)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write database export {}", path.display()))
+    }
+}
+
 pub struct Database {
     pool: Pool<SqliteConnectionManager>,
 }
 
 impl Database {
     pub fn open_or_create(base_path: &Path) -> Result<Self> {
-        let db_path = base_path.join(DB_NAME);
-        let manager = SqliteConnectionManager::file(&db_path);
+        Self::migrate_legacy_state(base_path)?;
+        Self::open_at(&Self::db_path(base_path, None))
+    }
+
+    pub fn open_at(db_path: &Path) -> Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            std::fs::create_dir_all(parent).with_context(|| {
+                format!("Failed to create database directory {}", parent.display())
+            })?;
+        }
+
+        let manager = SqliteConnectionManager::file(db_path);
         let pool = Pool::new(manager).context("Failed to create connection pool")?;
 
         let db = Self { pool };
@@ -79,7 +236,73 @@ impl Database {
     }
 
     pub fn exists(base_path: &Path) -> bool {
-        base_path.join(DB_NAME).exists()
+        Self::db_path(base_path, None).exists() || base_path.join(LEGACY_DB_NAME).exists()
+    }
+
+    /// The `.shelfie/` directory of `base_path`.
+    pub fn state_dir(base_path: &Path) -> PathBuf {
+        base_path.join(STATE_DIR_NAME)
+    }
+
+    /// Moves the database and trash of older versions, which were loose dotfiles in
+    /// the organized directory, into `.shelfie/`. State already in `.shelfie/` wins,
+    /// so this is a no-op once migrated. Returns whether anything was moved.
+    pub fn migrate_legacy_state(base_path: &Path) -> Result<bool> {
+        let mut moves = Vec::new();
+        let legacy_db = base_path.join(LEGACY_DB_NAME);
+        let db_path = Self::db_path(base_path, None);
+        if legacy_db.is_file() && !db_path.exists() {
+            moves.push((legacy_db.clone(), db_path.clone()));
+            for suffix in DB_SIDE_FILE_SUFFIXES {
+                let side_file = base_path.join(format!("{}{}", LEGACY_DB_NAME, suffix));
+                if side_file.exists() {
+                    let to = Self::state_dir(base_path).join(format!("{}{}", DB_NAME, suffix));
+                    moves.push((side_file, to));
+                }
+            }
+        }
+        let legacy_trash = base_path.join(crate::trash::LEGACY_TRASH_DIR_NAME);
+        let trash = crate::trash::Trash::dir(base_path);
+        if legacy_trash.is_dir() && !trash.exists() {
+            moves.push((legacy_trash, trash));
+        }
+        if moves.is_empty() {
+            return Ok(false);
+        }
+
+        let state_dir = Self::state_dir(base_path);
+        fs::create_dir_all(&state_dir)
+            .with_context(|| format!("Failed to create {}", state_dir.display()))?;
+        for (from, to) in moves {
+            fs::rename(&from, &to).with_context(|| {
+                format!("Failed to move {} to {}", from.display(), to.display())
+            })?;
+        }
+        Ok(true)
+    }
+
+    /// Location of the database for `base_path`. By default it lives in the
+    /// `.shelfie/` directory of the organized directory; with `db_dir` it is stored
+    /// there instead, under a name derived from the canonicalized base path so
+    /// directories don't collide.
+    pub fn db_path(base_path: &Path, db_dir: Option<&Path>) -> PathBuf {
+        let Some(db_dir) = db_dir else {
+            return Self::state_dir(base_path).join(DB_NAME);
+        };
+
+        let canonical = base_path
+            .canonicalize()
+            .unwrap_or_else(|_| base_path.to_path_buf());
+        let dir_name = canonical
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "root".to_string());
+
+        db_dir.join(format!(
+            "{}-{:016x}.db",
+            dir_name,
+            fnv1a_hash(canonical.to_string_lossy().as_bytes())
+        ))
     }
 
     fn get_conn(&self) -> Result<r2d2::PooledConnection<SqliteConnectionManager>> {
@@ -119,6 +342,9 @@ impl Database {
                 file_type TEXT NOT NULL,
                 is_opaque_dir BOOLEAN NOT NULL DEFAULT 0,
                 processed_at TEXT NOT NULL,
+                opaque_reason TEXT,
+                rename_confidence REAL,
+                content_hash TEXT,
                 FOREIGN KEY (shelf_id) REFERENCES shelves(id)
             );
 
@@ -127,11 +353,64 @@ impl Database {
                 value TEXT NOT NULL
             );
 
+            CREATE TABLE IF NOT EXISTS content_cache (
+                path TEXT PRIMARY KEY,
+                size INTEGER NOT NULL,
+                mtime INTEGER NOT NULL,
+                preview TEXT NOT NULL,
+                is_parsable BOOLEAN NOT NULL,
+                title TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS pending_items (
+                path TEXT PRIMARY KEY,
+                mtime INTEGER NOT NULL,
+                settings TEXT NOT NULL,
+                item TEXT NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS corrections (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                feedback TEXT NOT NULL,
+                moves TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            );
+
             CREATE INDEX IF NOT EXISTS idx_items_path ON items(path);
             CREATE INDEX IF NOT EXISTS idx_items_shelf ON items(shelf_id);
             CREATE INDEX IF NOT EXISTS idx_items_processed ON items(processed_at);
             ",
         )?;
+
+        // Columns added since the tables were introduced, which older databases lack
+        Self::add_column_if_missing(&conn, "items", "opaque_reason", "TEXT")?;
+        Self::add_column_if_missing(&conn, "items", "rename_confidence", "REAL")?;
+        Self::add_column_if_missing(&conn, "content_cache", "title", "TEXT")?;
+        Self::add_column_if_missing(&conn, "items", "content_hash", "TEXT")?;
+        conn.execute_batch(
+            "CREATE INDEX IF NOT EXISTS idx_items_content_hash ON items(content_hash);",
+        )?;
+        Ok(())
+    }
+
+    fn add_column_if_missing(
+        conn: &Connection,
+        table: &str,
+        column: &str,
+        definition: &str,
+    ) -> Result<()> {
+        let exists = conn
+            .prepare(&format!("PRAGMA table_info({})", table))?
+            .query_map([], |row| row.get::<_, String>(1))?
+            .collect::<Result<Vec<_>, _>>()?
+            .iter()
+            .any(|name| name == column);
+        if !exists {
+            conn.execute_batch(&format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                table, column, definition
+            ))?;
+        }
         Ok(())
     }
 
@@ -170,10 +449,9 @@ impl Database {
 
     pub fn list_cabinets(&self) -> Result<Vec<Cabinet>> {
         let conn = self.get_conn()?;
-        let mut stmt =
-            conn.prepare("SELECT id, name, description, created_at FROM cabinets ORDER BY name")?;
+        let mut stmt = conn.prepare("SELECT id, name, description, created_at FROM cabinets")?;
 
-        let cabinets = stmt
+        let mut cabinets = stmt
             .query_map([], |row| {
                 Ok(Cabinet {
                     id: row.get(0)?,
@@ -185,6 +463,7 @@ impl Database {
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
+        collation().sort_by_name(&mut cabinets, |c| &c.name);
 
         Ok(cabinets)
     }
@@ -226,17 +505,17 @@ impl Database {
         let query = if let Some(cabinet_id) = cabinet_id {
             format!(
                 "SELECT id, cabinet_id, name, description, created_at FROM shelves
-                    WHERE cabinet_id = {} ORDER BY name",
+                    WHERE cabinet_id = {}",
                 cabinet_id
             )
         } else {
-            "SELECT id, cabinet_id, name, description, created_at FROM shelves ORDER BY cabinet_id, name".to_string()
+            "SELECT id, cabinet_id, name, description, created_at FROM shelves".to_string()
         };
 
         let conn = self.get_conn()?;
         let mut stmt = conn.prepare(&query)?;
 
-        let shelves = stmt
+        let mut shelves = stmt
             .query_map([], |row| {
                 Ok(Shelf {
                     id: row.get(0)?,
@@ -249,6 +528,9 @@ impl Database {
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
+        // Sorting is stable, so this orders by cabinet, then name
+        collation().sort_by_name(&mut shelves, |s| &s.name);
+        shelves.sort_by_key(|s| s.cabinet_id);
 
         Ok(shelves)
     }
@@ -259,8 +541,9 @@ impl Database {
         let processed_at = item.processed_at.to_rfc3339();
         conn.execute(
             "INSERT INTO items (shelf_id, path, original_name, suggested_name, description,
-                              file_type, is_opaque_dir, processed_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                              file_type, is_opaque_dir, processed_at, opaque_reason,
+                              rename_confidence, content_hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
             params![
                 item.shelf_id,
                 item.path,
@@ -269,12 +552,55 @@ impl Database {
                 item.description,
                 item.file_type,
                 item.is_opaque_dir,
-                processed_at
+                processed_at,
+                item.opaque_reason,
+                item.rename_confidence,
+                item.content_hash
             ],
         )?;
         Ok(conn.last_insert_rowid())
     }
 
+    /// Inserts an item, or updates the analysis of the item already stored under the
+    /// same path, so re-running a batch never fails on the unique path.
+    pub fn upsert_item(&self, item: &Item) -> Result<i64> {
+        let processed_at = item.processed_at.to_rfc3339();
+        self.get_conn()?
+            .query_row(
+                "INSERT INTO items (shelf_id, path, original_name, suggested_name, description,
+                                  file_type, is_opaque_dir, processed_at, opaque_reason,
+                                  rename_confidence, content_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+                 ON CONFLICT(path) DO UPDATE SET
+                    shelf_id = excluded.shelf_id,
+                    original_name = excluded.original_name,
+                    suggested_name = excluded.suggested_name,
+                    description = excluded.description,
+                    file_type = excluded.file_type,
+                    is_opaque_dir = excluded.is_opaque_dir,
+                    processed_at = excluded.processed_at,
+                    opaque_reason = excluded.opaque_reason,
+                    rename_confidence = excluded.rename_confidence,
+                    content_hash = excluded.content_hash
+                 RETURNING id",
+                params![
+                    item.shelf_id,
+                    item.path,
+                    item.original_name,
+                    item.suggested_name,
+                    item.description,
+                    item.file_type,
+                    item.is_opaque_dir,
+                    processed_at,
+                    item.opaque_reason,
+                    item.rename_confidence,
+                    item.content_hash
+                ],
+                |row| row.get(0),
+            )
+            .context("Failed to upsert item")
+    }
+
     pub fn update_item_content(
         &self,
         item_id: i64,
@@ -289,91 +615,110 @@ impl Database {
         Ok(())
     }
 
+    pub fn set_content_hash(&self, item_id: i64, content_hash: &str) -> Result<()> {
+        self.get_conn()?.execute(
+            "UPDATE items SET content_hash = ?1 WHERE id = ?2",
+            params![content_hash, item_id],
+        )?;
+        Ok(())
+    }
+
     pub fn get_item_by_path(&self, path: &str) -> Result<Option<Item>> {
         let conn = self.get_conn()?;
         let mut stmt = conn.prepare(
             "SELECT id, shelf_id, path, original_name, suggested_name, description,
-                    file_type, is_opaque_dir, processed_at
+                    file_type, is_opaque_dir, processed_at, opaque_reason, rename_confidence,
+                    content_hash
              FROM items WHERE path = ?1",
         )?;
 
-        stmt.query_row(params![path], |row| {
-            Ok(Item {
-                id: Some(row.get(0)?),
-                shelf_id: row.get(1)?,
-                path: row.get(2)?,
-                original_name: row.get(3)?,
-                suggested_name: row.get(4)?,
-                description: row.get(5)?,
-                file_type: row.get(6)?,
-                is_opaque_dir: row.get(7)?,
-                processed_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(8)?)
-                    .unwrap()
-                    .with_timezone(&Utc),
-            })
-        })
-        .optional()
-        .context("Failed to query item")
+        stmt.query_row(params![path], Self::item_from_row)
+            .optional()
+            .context("Failed to query item")
     }
 
     pub fn list_items_needing_content(&self) -> Result<Vec<Item>> {
         let conn = self.get_conn()?;
         let mut stmt = conn.prepare(
             "SELECT id, shelf_id, path, original_name, suggested_name, description,
-                    file_type, is_opaque_dir, processed_at
+                    file_type, is_opaque_dir, processed_at, opaque_reason, rename_confidence,
+                    content_hash
              FROM items WHERE needs_content_read = 1",
         )?;
 
         let items = stmt
-            .query_map([], |row| {
-                Ok(Item {
-                    id: Some(row.get(0)?),
-                    shelf_id: row.get(1)?,
-                    path: row.get(2)?,
-                    original_name: row.get(3)?,
-                    suggested_name: row.get(4)?,
-                    description: row.get(5)?,
-                    file_type: row.get(6)?,
-                    is_opaque_dir: row.get(7)?,
-                    processed_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(8)?)
-                        .unwrap()
-                        .with_timezone(&Utc),
-                })
-            })?
+            .query_map([], Self::item_from_row)?
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(items)
     }
 
-    pub fn list_all_items(&self) -> Result<Vec<Item>> {
+    /// Groups of items whose files have the same content, each ordered by path.
+    pub fn find_duplicate_hashes(&self) -> Result<Vec<Vec<Item>>> {
         let conn = self.get_conn()?;
         let mut stmt = conn.prepare(
             "SELECT id, shelf_id, path, original_name, suggested_name, description,
-                    file_type, is_opaque_dir, processed_at
-             FROM items ORDER BY shelf_id, original_name",
+                    file_type, is_opaque_dir, processed_at, opaque_reason, rename_confidence,
+                    content_hash
+             FROM items
+             WHERE content_hash IN (SELECT content_hash FROM items
+                                    WHERE content_hash IS NOT NULL
+                                    GROUP BY content_hash HAVING COUNT(*) > 1)
+             ORDER BY content_hash, path",
         )?;
 
         let items = stmt
-            .query_map([], |row| {
-                Ok(Item {
-                    id: Some(row.get(0)?),
-                    shelf_id: row.get(1)?,
-                    path: row.get(2)?,
-                    original_name: row.get(3)?,
-                    suggested_name: row.get(4)?,
-                    description: row.get(5)?,
-                    file_type: row.get(6)?,
-                    is_opaque_dir: row.get(7)?,
-                    processed_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(8)?)
-                        .unwrap()
-                        .with_timezone(&Utc),
-                })
-            })?
+            .query_map([], Self::item_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut groups: Vec<Vec<Item>> = Vec::new();
+        for item in items {
+            match groups.last_mut() {
+                Some(group) if group[0].content_hash == item.content_hash => group.push(item),
+                _ => groups.push(vec![item]),
+            }
+        }
+
+        Ok(groups)
+    }
+
+    pub fn list_all_items(&self) -> Result<Vec<Item>> {
+        let conn = self.get_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT id, shelf_id, path, original_name, suggested_name, description,
+                    file_type, is_opaque_dir, processed_at, opaque_reason, rename_confidence,
+                    content_hash
+             FROM items",
+        )?;
+
+        let mut items = stmt
+            .query_map([], Self::item_from_row)?
             .collect::<Result<Vec<_>, _>>()?;
+        collation().sort_by_name(&mut items, |i| &i.original_name);
+        items.sort_by_key(|i| i.shelf_id);
 
         Ok(items)
     }
 
+    /// Reads an item from the columns selected by the item queries, in their order.
+    fn item_from_row(row: &rusqlite::Row) -> rusqlite::Result<Item> {
+        Ok(Item {
+            id: Some(row.get(0)?),
+            shelf_id: row.get(1)?,
+            path: row.get(2)?,
+            original_name: row.get(3)?,
+            suggested_name: row.get(4)?,
+            description: row.get(5)?,
+            file_type: row.get(6)?,
+            is_opaque_dir: row.get(7)?,
+            opaque_reason: row.get(9)?,
+            rename_confidence: row.get(10)?,
+            content_hash: row.get(11)?,
+            processed_at: DateTime::parse_from_rfc3339(&row.get::<_, String>(8)?)
+                .unwrap()
+                .with_timezone(&Utc),
+        })
+    }
+
     // Processing state operations
     pub fn set_processing_state(&self, key: &str, value: &str) -> Result<()> {
         self.get_conn()?.execute(
@@ -392,6 +737,87 @@ impl Database {
             .context("Failed to query processing state")
     }
 
+    pub fn last_completed_run(&self) -> Result<Option<DateTime<Utc>>> {
+        self.get_processing_state(LAST_RUN_KEY)?
+            .map(|value| {
+                DateTime::parse_from_rfc3339(&value)
+                    .map(|dt| dt.with_timezone(&Utc))
+                    .context("Invalid last run timestamp")
+            })
+            .transpose()
+    }
+
+    pub fn record_completed_run(&self, started_at: DateTime<Utc>) -> Result<()> {
+        self.set_processing_state(LAST_RUN_KEY, &started_at.to_rfc3339())
+    }
+
+    pub fn last_executed_plan(&self) -> Result<Option<OrganizationPlan>> {
+        self.get_processing_state(LAST_PLAN_KEY)?
+            .map(|value| serde_json::from_str(&value).context("Invalid stored plan"))
+            .transpose()
+    }
+
+    pub fn record_executed_plan(&self, plan: &OrganizationPlan) -> Result<()> {
+        self.set_processing_state(LAST_PLAN_KEY, &serde_json::to_string(plan)?)
+    }
+
+    // Correction history operations
+    pub fn record_correction(&self, feedback: &str, moves: &[String]) -> Result<()> {
+        self.get_conn()?.execute(
+            "INSERT INTO corrections (feedback, moves, created_at) VALUES (?1, ?2, ?3)",
+            params![
+                feedback,
+                serde_json::to_string(moves)?,
+                Utc::now().to_rfc3339()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// The most recent corrections, newest first.
+    pub fn recent_corrections(&self, limit: usize) -> Result<Vec<Correction>> {
+        let conn = self.get_conn()?;
+        let mut stmt = conn.prepare(
+            "SELECT feedback, moves, created_at FROM corrections ORDER BY id DESC LIMIT ?1",
+        )?;
+        let rows = stmt
+            .query_map(params![limit as i64], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(|(feedback, moves, created_at)| {
+                Ok(Correction {
+                    feedback,
+                    moves: serde_json::from_str(&moves).context("Invalid stored correction")?,
+                    created_at: DateTime::parse_from_rfc3339(&created_at)
+                        .context("Invalid correction timestamp")?
+                        .with_timezone(&Utc),
+                })
+            })
+            .collect()
+    }
+
+    fn list_processing_state(&self) -> Result<BTreeMap<String, String>> {
+        let conn = self.get_conn()?;
+        let mut stmt = conn.prepare("SELECT key, value FROM processing_state")?;
+        let state = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+        Ok(state)
+    }
+
+    pub fn delete_item_by_path(&self, path: &str) -> Result<()> {
+        self.get_conn()?
+            .execute("DELETE FROM items WHERE path = ?1", params![path])?;
+        Ok(())
+    }
+
     // Helper to get all processed paths for incremental processing
     pub fn get_processed_paths(&self) -> Result<Vec<String>> {
         let conn = self.get_conn()?;
@@ -402,6 +828,39 @@ impl Database {
         Ok(paths)
     }
 
+    /// Removes all cabinets, shelves and items so the directory can be re-analyzed.
+    /// Forgets all items and the structure built for them, except the Unsorted
+    /// cabinet and its shelves, which always stay.
+    pub fn clear_organization(&self) -> Result<()> {
+        let mut conn = self.get_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute("DELETE FROM items", [])?;
+        tx.execute(
+            "DELETE FROM shelves WHERE cabinet_id NOT IN (SELECT id FROM cabinets WHERE name = ?1)",
+            params![unsorted_name()],
+        )?;
+        tx.execute(
+            "DELETE FROM cabinets WHERE name != ?1",
+            params![unsorted_name()],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The shelf of the Unsorted cabinet, where items go that can't be classified,
+    /// creating both if needed.
+    pub fn get_or_create_unsorted(&self) -> Result<i64> {
+        let name = unsorted_name();
+        let cabinet_id = match self.get_cabinet_by_name(&name)? {
+            Some(cabinet) => cabinet.id,
+            None => self.create_cabinet(&name, UNSORTED_DESCRIPTION)?,
+        };
+        match self.get_shelf_by_name(cabinet_id, &name)? {
+            Some(shelf) => Ok(shelf.id),
+            None => self.create_shelf(cabinet_id, &name, UNSORTED_DESCRIPTION),
+        }
+    }
+
     // Update methods for plan refinement
     pub fn update_item_shelf(&self, item_id: i64, new_shelf_id: i64) -> Result<()> {
         self.get_conn()?.execute(
@@ -460,6 +919,272 @@ impl Database {
             .execute("DELETE FROM shelves WHERE id = ?1", params![shelf_id])?;
         Ok(())
     }
+
+    // Content cache operations
+    /// A cached content preview and whether it was parsable, if one exists for the
+    /// path with the same size and modification time.
+    pub fn get_cached_content(
+        &self,
+        path: &str,
+        size: u64,
+        mtime: i64,
+    ) -> Result<Option<(String, bool, Option<String>)>> {
+        self.get_conn()?
+            .query_row(
+                "SELECT preview, is_parsable, title FROM content_cache
+                 WHERE path = ?1 AND size = ?2 AND mtime = ?3",
+                params![path, size as i64, mtime],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()
+            .context("Failed to query content cache")
+    }
+
+    /// Stores a content preview, replacing any entry for an older fingerprint.
+    pub fn cache_content(
+        &self,
+        path: &str,
+        size: u64,
+        mtime: i64,
+        preview: &str,
+        is_parsable: bool,
+        title: Option<&str>,
+    ) -> Result<()> {
+        self.get_conn()?.execute(
+            "INSERT OR REPLACE INTO content_cache (path, size, mtime, preview, is_parsable, title)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![path, size as i64, mtime, preview, is_parsable, title],
+        )?;
+        Ok(())
+    }
+
+    // Scan checkpoints
+    /// Items scanned by a run that didn't get to analyze them, by path, with the
+    /// modification time they were scanned at. Checkpoints taken with other scan
+    /// `settings` are dropped, as they may lack what this scan captures.
+    pub fn load_pending_items(
+        &self,
+        settings: &str,
+    ) -> Result<HashMap<PathBuf, (i64, ProcessingItem)>> {
+        let conn = self.get_conn()?;
+        conn.execute(
+            "DELETE FROM pending_items WHERE settings != ?1",
+            params![settings],
+        )?;
+        let mut stmt = conn.prepare("SELECT mtime, item FROM pending_items")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        // A checkpoint from an older version that no longer parses is scanned again
+        Ok(rows
+            .into_iter()
+            .filter_map(|(mtime, item)| {
+                let item: ProcessingItem = serde_json::from_str(&item).ok()?;
+                Some((item.path().to_path_buf(), (mtime, item)))
+            })
+            .collect())
+    }
+
+    /// Checkpoints a scanned item until its batch is stored.
+    pub fn save_pending_item(
+        &self,
+        item: &ProcessingItem,
+        mtime: i64,
+        settings: &str,
+    ) -> Result<()> {
+        self.get_conn()?.execute(
+            "INSERT OR REPLACE INTO pending_items (path, mtime, settings, item)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                item.path().to_string_lossy(),
+                mtime,
+                settings,
+                serde_json::to_string(item)?
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Drops the checkpoints of `paths`, once they're analyzed or no longer scanned.
+    pub fn clear_pending_items<'a>(&self, paths: impl IntoIterator<Item = &'a Path>) -> Result<()> {
+        let mut conn = self.get_conn()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare("DELETE FROM pending_items WHERE path = ?1")?;
+            for path in paths {
+                stmt.execute(params![path.to_string_lossy()])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // Export and import
+    /// Whether the database holds no organization or processing state at all.
+    pub fn is_empty(&self) -> Result<bool> {
+        let rows: i64 = self.get_conn()?.query_row(
+            "SELECT (SELECT COUNT(*) FROM cabinets) + (SELECT COUNT(*) FROM shelves)
+                  + (SELECT COUNT(*) FROM items) + (SELECT COUNT(*) FROM processing_state)",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(rows == 0)
+    }
+
+    pub fn export(&self) -> Result<DatabaseDump> {
+        Ok(DatabaseDump {
+            format_version: DUMP_FORMAT_VERSION,
+            cabinets: self.list_cabinets()?,
+            shelves: self.list_shelves(None)?,
+            items: self.list_all_items()?,
+            processing_state: self.list_processing_state()?,
+        })
+    }
+
+    /// Replaces the organization and processing state with the contents of `dump`,
+    /// keeping the exported ids so items stay on their shelves.
+    pub fn import(&self, dump: &DatabaseDump) -> Result<()> {
+        let mut conn = self.get_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute_batch(
+            "DELETE FROM items;
+             DELETE FROM shelves;
+             DELETE FROM cabinets;
+             DELETE FROM processing_state;",
+        )?;
+
+        for cabinet in &dump.cabinets {
+            tx.execute(
+                "INSERT INTO cabinets (id, name, description, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![
+                    cabinet.id,
+                    cabinet.name,
+                    cabinet.description,
+                    cabinet.created_at.to_rfc3339()
+                ],
+            )
+            .with_context(|| format!("Failed to import cabinet {}", cabinet.name))?;
+        }
+        for shelf in &dump.shelves {
+            if !dump.cabinets.iter().any(|c| c.id == shelf.cabinet_id) {
+                anyhow::bail!(
+                    "Shelf {} belongs to cabinet {}, which is not in the export",
+                    shelf.name,
+                    shelf.cabinet_id
+                );
+            }
+            tx.execute(
+                "INSERT INTO shelves (id, cabinet_id, name, description, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    shelf.id,
+                    shelf.cabinet_id,
+                    shelf.name,
+                    shelf.description,
+                    shelf.created_at.to_rfc3339()
+                ],
+            )
+            .with_context(|| format!("Failed to import shelf {}", shelf.name))?;
+        }
+        for item in &dump.items {
+            if !dump.shelves.iter().any(|s| s.id == item.shelf_id) {
+                anyhow::bail!(
+                    "Item {} is on shelf {}, which is not in the export",
+                    item.path,
+                    item.shelf_id
+                );
+            }
+            tx.execute(
+                "INSERT INTO items (id, shelf_id, path, original_name, suggested_name, description,
+                                    file_type, is_opaque_dir, processed_at, opaque_reason,
+                                    rename_confidence, content_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+                params![
+                    item.id,
+                    item.shelf_id,
+                    item.path,
+                    item.original_name,
+                    item.suggested_name,
+                    item.description,
+                    item.file_type,
+                    item.is_opaque_dir,
+                    item.processed_at.to_rfc3339(),
+                    item.opaque_reason,
+                    item.rename_confidence,
+                    item.content_hash
+                ],
+            )
+            .with_context(|| format!("Failed to import item {}", item.path))?;
+        }
+        for (key, value) in &dump.processing_state {
+            tx.execute(
+                "INSERT INTO processing_state (key, value) VALUES (?1, ?2)",
+                params![key, value],
+            )?;
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    // Consolidation operations
+    /// Moves every item from `source_shelf_id` onto `target_shelf_id` and
+    /// deletes the emptied source shelf.
+    pub fn merge_shelves(&self, source_shelf_id: i64, target_shelf_id: i64) -> Result<()> {
+        if source_shelf_id == target_shelf_id {
+            anyhow::bail!("Cannot merge a shelf into itself");
+        }
+
+        let mut conn = self.get_conn()?;
+        let tx = conn.transaction()?;
+        tx.execute(
+            "UPDATE items SET shelf_id = ?1 WHERE shelf_id = ?2",
+            params![target_shelf_id, source_shelf_id],
+        )?;
+        tx.execute(
+            "DELETE FROM shelves WHERE id = ?1",
+            params![source_shelf_id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Re-parents a shelf under another cabinet. If the target cabinet already
+    /// has a shelf with the same name, the two shelves are merged instead.
+    pub fn move_shelf(&self, shelf_id: i64, target_cabinet_id: i64) -> Result<()> {
+        let name: String = self.get_conn()?.query_row(
+            "SELECT name FROM shelves WHERE id = ?1",
+            params![shelf_id],
+            |row| row.get(0),
+        )?;
+
+        match self.get_shelf_by_name(target_cabinet_id, &name)? {
+            Some(existing) if existing.id == shelf_id => Ok(()),
+            Some(existing) => self.merge_shelves(shelf_id, existing.id),
+            None => {
+                self.get_conn()?.execute(
+                    "UPDATE shelves SET cabinet_id = ?1 WHERE id = ?2",
+                    params![target_cabinet_id, shelf_id],
+                )?;
+                Ok(())
+            }
+        }
+    }
+
+    /// Moves every shelf of `source_cabinet_id` into `target_cabinet_id`
+    /// (merging same-named shelves) and deletes the emptied source cabinet.
+    pub fn merge_cabinets(&self, source_cabinet_id: i64, target_cabinet_id: i64) -> Result<()> {
+        if source_cabinet_id == target_cabinet_id {
+            anyhow::bail!("Cannot merge a cabinet into itself");
+        }
+
+        for shelf in self.list_shelves(Some(source_cabinet_id))? {
+            self.move_shelf(shelf.id, target_cabinet_id)?;
+        }
+        self.delete_cabinet(source_cabinet_id)
+    }
 }
 
 #[cfg(test)]
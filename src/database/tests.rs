@@ -1,5 +1,7 @@
-use super::{Database, Item};
+use super::{Database, DatabaseDump, Item, is_unsorted_name};
+use crate::models::{EnrichedDirectory, ProcessingItem};
 use chrono::Utc;
+use std::path::{Path, PathBuf};
 use tempfile::TempDir;
 
 fn setup_test_db() -> (TempDir, Database) {
@@ -135,6 +137,9 @@ fn test_insert_and_get_item() {
         description: "A test file".to_string(),
         file_type: "text/plain".to_string(),
         is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
         processed_at: Utc::now(),
     };
 
@@ -168,6 +173,9 @@ fn test_item_unique_path_constraint() {
         description: "First".to_string(),
         file_type: "text".to_string(),
         is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
         processed_at: Utc::now(),
     };
 
@@ -182,6 +190,9 @@ fn test_item_unique_path_constraint() {
         description: "Second".to_string(),
         file_type: "text".to_string(),
         is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
         processed_at: Utc::now(),
     };
 
@@ -189,6 +200,47 @@ fn test_item_unique_path_constraint() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_upsert_item_updates_existing_path() {
+    let (_dir, db) = setup_test_db();
+
+    let cabinet_id = db.create_cabinet("Cabinet", "Test").unwrap();
+    let first_shelf = db.create_shelf(cabinet_id, "First", "Test").unwrap();
+    let second_shelf = db.create_shelf(cabinet_id, "Second", "Test").unwrap();
+
+    let item = Item {
+        id: None,
+        shelf_id: first_shelf,
+        path: "/retried/path".to_string(),
+        original_name: "file.txt".to_string(),
+        suggested_name: None,
+        description: "First analysis".to_string(),
+        file_type: "text".to_string(),
+        is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
+        processed_at: Utc::now(),
+    };
+    let first_id = db.upsert_item(&item).unwrap();
+
+    let second_id = db
+        .upsert_item(&Item {
+            shelf_id: second_shelf,
+            suggested_name: Some("notes.txt".to_string()),
+            description: "Second analysis".to_string(),
+            ..item
+        })
+        .unwrap();
+
+    assert_eq!(first_id, second_id);
+    let stored = db.get_item_by_path("/retried/path").unwrap().unwrap();
+    assert_eq!(stored.shelf_id, second_shelf);
+    assert_eq!(stored.description, "Second analysis");
+    assert_eq!(stored.suggested_name.as_deref(), Some("notes.txt"));
+    assert_eq!(db.list_all_items().unwrap().len(), 1);
+}
+
 #[test]
 fn test_list_items_by_shelf() {
     let (_dir, db) = setup_test_db();
@@ -207,6 +259,9 @@ fn test_list_items_by_shelf() {
             description: format!("File {}", i),
             file_type: "text".to_string(),
             is_opaque_dir: false,
+            opaque_reason: None,
+            rename_confidence: None,
+            content_hash: None,
             processed_at: Utc::now(),
         };
         db.insert_item(&item).unwrap();
@@ -222,6 +277,9 @@ fn test_list_items_by_shelf() {
             description: format!("File {}", i),
             file_type: "text".to_string(),
             is_opaque_dir: false,
+            opaque_reason: None,
+            rename_confidence: None,
+            content_hash: None,
             processed_at: Utc::now(),
         };
         db.insert_item(&item).unwrap();
@@ -247,6 +305,9 @@ fn test_update_item_content() {
         description: "Original description".to_string(),
         file_type: "text".to_string(),
         is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
         processed_at: Utc::now(),
     };
 
@@ -279,6 +340,9 @@ fn test_get_processed_paths() {
             description: "Test".to_string(),
             file_type: "text".to_string(),
             is_opaque_dir: false,
+            opaque_reason: None,
+            rename_confidence: None,
+            content_hash: None,
             processed_at: Utc::now(),
         };
         db.insert_item(&item).unwrap();
@@ -308,6 +372,9 @@ fn test_opaque_directory_flag() {
         description: "Node dependencies".to_string(),
         file_type: "directory".to_string(),
         is_opaque_dir: true,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
         processed_at: Utc::now(),
     };
 
@@ -339,9 +406,471 @@ fn test_foreign_key_constraints() {
         description: "Test".to_string(),
         file_type: "text".to_string(),
         is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
         processed_at: Utc::now(),
     };
 
     let result = db.insert_item(&item);
     assert!(result.is_err());
 }
+
+#[test]
+fn test_clear_organization() {
+    let (_dir, db) = setup_test_db();
+
+    let cabinet_id = db.create_cabinet("Cabinet", "Test").unwrap();
+    let shelf_id = db.create_shelf(cabinet_id, "Shelf", "Test").unwrap();
+    let item = Item {
+        id: None,
+        shelf_id,
+        path: "/file.txt".to_string(),
+        original_name: "file.txt".to_string(),
+        suggested_name: None,
+        description: "Test".to_string(),
+        file_type: "text".to_string(),
+        is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
+        processed_at: Utc::now(),
+    };
+    db.insert_item(&item).unwrap();
+
+    db.clear_organization().unwrap();
+
+    assert!(db.get_processed_paths().unwrap().is_empty());
+    assert!(db.list_shelves(None).unwrap().is_empty());
+    assert!(db.list_cabinets().unwrap().is_empty());
+}
+
+#[test]
+fn test_clear_organization_keeps_unsorted() {
+    let (_dir, db) = setup_test_db();
+
+    let unsorted_shelf = db.get_or_create_unsorted().unwrap();
+    assert_eq!(db.get_or_create_unsorted().unwrap(), unsorted_shelf);
+    db.create_cabinet("Cabinet", "Test").unwrap();
+
+    db.clear_organization().unwrap();
+
+    let cabinets = db.list_cabinets().unwrap();
+    assert_eq!(cabinets.len(), 1);
+    assert!(is_unsorted_name(&cabinets[0].name));
+    assert_eq!(db.get_or_create_unsorted().unwrap(), unsorted_shelf);
+}
+
+#[test]
+fn test_is_unsorted_name_survives_naming_conventions() {
+    assert!(is_unsorted_name("Unsorted"));
+    assert!(is_unsorted_name("unsorted"));
+    assert!(is_unsorted_name("UNSORTED"));
+    assert!(!is_unsorted_name("Unsorted Photos"));
+}
+
+#[test]
+fn test_record_completed_run() {
+    let (_dir, db) = setup_test_db();
+
+    assert!(db.last_completed_run().unwrap().is_none());
+
+    let started_at = Utc::now();
+    db.record_completed_run(started_at).unwrap();
+
+    let recorded = db.last_completed_run().unwrap().unwrap();
+    assert_eq!(recorded.timestamp(), started_at.timestamp());
+}
+
+#[test]
+fn test_delete_item_by_path() {
+    let (_dir, db) = setup_test_db();
+
+    let cabinet_id = db.create_cabinet("Cabinet", "Test").unwrap();
+    let shelf_id = db.create_shelf(cabinet_id, "Shelf", "Test").unwrap();
+    let item = Item {
+        id: None,
+        shelf_id,
+        path: "/stale.txt".to_string(),
+        original_name: "stale.txt".to_string(),
+        suggested_name: None,
+        description: "Test".to_string(),
+        file_type: "text".to_string(),
+        is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
+        processed_at: Utc::now(),
+    };
+    db.insert_item(&item).unwrap();
+
+    db.delete_item_by_path("/stale.txt").unwrap();
+
+    assert!(db.get_item_by_path("/stale.txt").unwrap().is_none());
+}
+
+#[test]
+fn test_db_path_outside_base_path() {
+    let scanned = TempDir::new().unwrap();
+    let db_dir = TempDir::new().unwrap();
+
+    let db_path = Database::db_path(scanned.path(), Some(db_dir.path()));
+    assert!(db_path.starts_with(db_dir.path()));
+    assert_eq!(
+        db_path,
+        Database::db_path(scanned.path(), Some(db_dir.path())),
+        "The same directory should always map to the same database"
+    );
+
+    let other = TempDir::new().unwrap();
+    assert_ne!(
+        db_path,
+        Database::db_path(other.path(), Some(db_dir.path()))
+    );
+
+    let db = Database::open_at(&db_path).unwrap();
+    db.create_cabinet("Finance", "Money").unwrap();
+    assert!(db_path.exists());
+    assert!(!Database::exists(scanned.path()));
+}
+
+#[test]
+fn test_record_executed_plan() {
+    use crate::models::{FileMovement, OrganizationPlan};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = Database::open_or_create(temp_dir.path()).unwrap();
+    assert!(db.last_executed_plan().unwrap().is_none());
+
+    let plan = OrganizationPlan {
+        cabinets: Vec::new(),
+        movements: vec![FileMovement {
+            from: "/docs/return.pdf".into(),
+            to_cabinet: "Finance".to_string(),
+            to_shelf: "Taxes".to_string(),
+            new_name: None,
+            reasoning: "Tax return".to_string(),
+            opaque_reason: None,
+        }],
+    };
+    db.record_executed_plan(&plan).unwrap();
+
+    let stored = db.last_executed_plan().unwrap().unwrap();
+    assert_eq!(stored.movements.len(), 1);
+    assert_eq!(stored.movements[0].to_shelf, "Taxes");
+}
+
+#[test]
+fn test_merge_cabinets_combines_same_named_shelves() {
+    let (_dir, db) = setup_test_db();
+
+    let images = db.create_cabinet("Images", "Pictures").unwrap();
+    let photos = db.create_cabinet("Photos", "Also pictures").unwrap();
+    let images_travel = db.create_shelf(images, "Travel", "Trips").unwrap();
+    let photos_travel = db.create_shelf(photos, "Travel", "Trips").unwrap();
+    db.create_shelf(photos, "Family", "Relatives").unwrap();
+
+    for (shelf_id, path) in [(images_travel, "/a.jpg"), (photos_travel, "/b.jpg")] {
+        db.insert_item(&Item {
+            id: None,
+            shelf_id,
+            path: path.to_string(),
+            original_name: path.trim_start_matches('/').to_string(),
+            suggested_name: None,
+            description: "Photo".to_string(),
+            file_type: "image".to_string(),
+            is_opaque_dir: false,
+            opaque_reason: None,
+            rename_confidence: None,
+            content_hash: None,
+            processed_at: Utc::now(),
+        })
+        .unwrap();
+    }
+
+    db.merge_cabinets(photos, images).unwrap();
+
+    assert!(db.get_cabinet_by_name("Photos").unwrap().is_none());
+    let shelves = db.list_shelves(Some(images)).unwrap();
+    let names: Vec<_> = shelves.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["Family", "Travel"]);
+    assert!(
+        db.list_all_items()
+            .unwrap()
+            .iter()
+            .all(|item| item.shelf_id == images_travel)
+    );
+}
+
+#[test]
+fn test_merge_shelf_into_itself_fails() {
+    let (_dir, db) = setup_test_db();
+
+    let cabinet = db.create_cabinet("Docs", "Documents").unwrap();
+    let shelf = db.create_shelf(cabinet, "Invoices", "Bills").unwrap();
+
+    assert!(db.merge_shelves(shelf, shelf).is_err());
+    assert!(db.merge_cabinets(cabinet, cabinet).is_err());
+}
+
+#[test]
+fn test_older_database_gains_opaque_reason_column() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("old.db");
+    rusqlite::Connection::open(&db_path)
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE items (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                shelf_id INTEGER NOT NULL,
+                path TEXT NOT NULL UNIQUE,
+                original_name TEXT NOT NULL,
+                suggested_name TEXT,
+                description TEXT NOT NULL,
+                file_type TEXT NOT NULL,
+                is_opaque_dir BOOLEAN NOT NULL DEFAULT 0,
+                processed_at TEXT NOT NULL
+            );",
+        )
+        .unwrap();
+
+    let db = Database::open_at(&db_path).unwrap();
+    let cabinet_id = db.create_cabinet("Code", "Projects").unwrap();
+    let shelf_id = db
+        .create_shelf(cabinet_id, "Dependencies", "Vendored")
+        .unwrap();
+    db.insert_item(&Item {
+        id: None,
+        shelf_id,
+        path: "/node_modules".to_string(),
+        original_name: "node_modules".to_string(),
+        suggested_name: None,
+        description: "Dependencies".to_string(),
+        file_type: "directory".to_string(),
+        is_opaque_dir: true,
+        opaque_reason: Some("known directory name 'node_modules'".to_string()),
+        rename_confidence: None,
+        content_hash: None,
+        processed_at: Utc::now(),
+    })
+    .unwrap();
+
+    let item = db.get_item_by_path("/node_modules").unwrap().unwrap();
+    assert_eq!(
+        item.opaque_reason.as_deref(),
+        Some("known directory name 'node_modules'")
+    );
+}
+
+#[test]
+fn test_export_and_import_round_trip() {
+    let (dir, db) = setup_test_db();
+    let finance = db.create_cabinet("Finance", "Money").unwrap();
+    db.create_cabinet("Empty", "Nothing yet").unwrap();
+    let taxes = db.create_shelf(finance, "Taxes", "Returns").unwrap();
+    db.insert_item(&Item {
+        id: None,
+        shelf_id: taxes,
+        path: "/return.pdf".to_string(),
+        original_name: "return.pdf".to_string(),
+        suggested_name: Some("tax-return-2023.pdf".to_string()),
+        description: "Tax return".to_string(),
+        file_type: "pdf".to_string(),
+        is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
+        processed_at: Utc::now(),
+    })
+    .unwrap();
+    db.record_completed_run(Utc::now()).unwrap();
+
+    let export_path = dir.path().join("export.json");
+    db.export().unwrap().save(&export_path).unwrap();
+    let dump = DatabaseDump::load(&export_path).unwrap();
+
+    let (_other_dir, imported) = setup_test_db();
+    assert!(imported.is_empty().unwrap());
+    imported.import(&dump).unwrap();
+
+    assert!(!imported.is_empty().unwrap());
+    assert_eq!(imported.list_cabinets().unwrap().len(), 2);
+    let item = imported.get_item_by_path("/return.pdf").unwrap().unwrap();
+    assert_eq!(item.shelf_id, taxes);
+    assert_eq!(item.suggested_name.as_deref(), Some("tax-return-2023.pdf"));
+    assert!(imported.last_completed_run().unwrap().is_some());
+    assert_eq!(
+        serde_json::to_value(imported.export().unwrap()).unwrap(),
+        serde_json::to_value(&dump).unwrap()
+    );
+}
+
+#[test]
+fn test_import_rejects_items_on_missing_shelves() {
+    let (_dir, db) = setup_test_db();
+    let cabinet_id = db.create_cabinet("Cabinet", "Test").unwrap();
+    let shelf_id = db.create_shelf(cabinet_id, "Shelf", "Test").unwrap();
+    db.insert_item(&Item {
+        id: None,
+        shelf_id,
+        path: "/file.txt".to_string(),
+        original_name: "file.txt".to_string(),
+        suggested_name: None,
+        description: "Test".to_string(),
+        file_type: "text".to_string(),
+        is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
+        processed_at: Utc::now(),
+    })
+    .unwrap();
+    let mut dump = db.export().unwrap();
+    dump.shelves.clear();
+
+    let (_other_dir, imported) = setup_test_db();
+    let error = imported.import(&dump).unwrap_err();
+
+    assert!(error.to_string().contains("not in the export"), "{}", error);
+    assert!(imported.is_empty().unwrap());
+}
+
+#[test]
+fn test_rename_respects_minimum_confidence() {
+    let (_dir, db) = setup_test_db();
+    let cabinet = db.create_cabinet("Finance", "Money").unwrap();
+    let shelf = db.create_shelf(cabinet, "Taxes", "Returns").unwrap();
+    db.insert_item(&Item {
+        id: None,
+        shelf_id: shelf,
+        path: "/scan001.pdf".to_string(),
+        original_name: "scan001.pdf".to_string(),
+        suggested_name: Some("tax-return-2023.pdf".to_string()),
+        description: "Tax return".to_string(),
+        file_type: "pdf".to_string(),
+        is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: Some(0.6),
+        content_hash: None,
+        processed_at: Utc::now(),
+    })
+    .unwrap();
+
+    let item = db.get_item_by_path("/scan001.pdf").unwrap().unwrap();
+    assert_eq!(item.rename_confidence, Some(0.6));
+    assert_eq!(item.rename(None).as_deref(), Some("tax-return-2023.pdf"));
+    assert_eq!(
+        item.rename(Some(0.5)).as_deref(),
+        Some("tax-return-2023.pdf")
+    );
+    assert_eq!(item.rename(Some(0.8)), None);
+
+    let unrated = Item {
+        rename_confidence: None,
+        ..item
+    };
+    assert_eq!(unrated.rename(None).as_deref(), Some("tax-return-2023.pdf"));
+    assert_eq!(unrated.rename(Some(0.1)), None);
+}
+
+#[test]
+fn test_corrections_newest_first_and_survive_reorganize() {
+    let (_dir, db) = setup_test_db();
+    db.record_correction(
+        "keep receipts with taxes",
+        &["receipt.pdf → Finance/Taxes".to_string()],
+    )
+    .unwrap();
+    db.record_correction("photos go by year", &[]).unwrap();
+    db.clear_organization().unwrap();
+
+    let corrections = db.recent_corrections(10).unwrap();
+    assert_eq!(corrections.len(), 2);
+    assert_eq!(corrections[0].feedback, "photos go by year");
+    assert!(corrections[0].moves.is_empty());
+    assert_eq!(corrections[1].moves, vec!["receipt.pdf → Finance/Taxes"]);
+
+    assert_eq!(db.recent_corrections(1).unwrap().len(), 1);
+}
+
+#[test]
+fn test_legacy_state_moves_into_state_dir() {
+    let temp_dir = TempDir::new().unwrap();
+    let base = temp_dir.path();
+    {
+        let legacy = Database::open_at(&base.join(".fs_organizer.db")).unwrap();
+        legacy.create_cabinet("Finance", "Money").unwrap();
+    }
+    std::fs::create_dir_all(base.join(".shelfie-trash/20240101-000000")).unwrap();
+    std::fs::write(base.join(".shelfie-trash/20240101-000000/a.txt"), "a").unwrap();
+    assert!(Database::exists(base));
+
+    let db = Database::open_or_create(base).unwrap();
+    assert!(db.get_cabinet_by_name("Finance").unwrap().is_some());
+    assert!(base.join(".shelfie/db.sqlite").exists());
+    assert!(base.join(".shelfie/trash/20240101-000000/a.txt").exists());
+    assert!(!base.join(".fs_organizer.db").exists());
+    assert!(!base.join(".shelfie-trash").exists());
+
+    // Nothing left to migrate
+    assert!(!Database::migrate_legacy_state(base).unwrap());
+}
+
+#[test]
+fn test_pending_items_are_only_resumed_with_the_same_settings() {
+    let (_temp_dir, db) = setup_test_db();
+    let item = ProcessingItem::Directory(EnrichedDirectory {
+        path: PathBuf::from("/photos/2024"),
+        name: "2024".to_string(),
+        sampled_items: Vec::new(),
+        is_git_repository: false,
+        readme_preview: None,
+        content_preview: None,
+    });
+    db.save_pending_item(&item, 42, "content").unwrap();
+
+    let pending = db.load_pending_items("content").unwrap();
+    assert_eq!(pending[Path::new("/photos/2024")].0, 42);
+
+    // Other settings drop the checkpoint for good
+    assert!(db.load_pending_items("content owner").unwrap().is_empty());
+    assert!(db.load_pending_items("content").unwrap().is_empty());
+
+    db.save_pending_item(&item, 42, "content").unwrap();
+    db.clear_pending_items([Path::new("/photos/2024")]).unwrap();
+    assert!(db.load_pending_items("content").unwrap().is_empty());
+}
+
+#[test]
+fn test_items_with_the_same_content_hash_are_grouped() {
+    let (_dir, db) = setup_test_db();
+    let cabinet = db.create_cabinet("Downloads", "Downloaded files").unwrap();
+    let shelf = db.create_shelf(cabinet, "Invoices", "Bills").unwrap();
+    let item = |path: &str, hash: Option<&str>| Item {
+        id: None,
+        shelf_id: shelf,
+        path: path.to_string(),
+        original_name: path.trim_start_matches('/').to_string(),
+        suggested_name: None,
+        description: "Invoice".to_string(),
+        file_type: "pdf".to_string(),
+        is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: hash.map(str::to_string),
+        processed_at: Utc::now(),
+    };
+    db.insert_item(&item("/invoice.pdf", Some("aaa"))).unwrap();
+    db.insert_item(&item("/invoice (1).pdf", Some("aaa")))
+        .unwrap();
+    db.insert_item(&item("/receipt.pdf", Some("bbb"))).unwrap();
+    db.insert_item(&item("/notes.txt", None)).unwrap();
+    db.insert_item(&item("/todo.txt", None)).unwrap();
+
+    let groups = db.find_duplicate_hashes().unwrap();
+
+    assert_eq!(groups.len(), 1);
+    let paths: Vec<_> = groups[0].iter().map(|item| item.path.as_str()).collect();
+    assert_eq!(paths, ["/invoice (1).pdf", "/invoice.pdf"]);
+    assert_eq!(groups[0][0].content_hash.as_deref(), Some("aaa"));
+}
@@ -0,0 +1,240 @@
+use anyhow::{Result, anyhow};
+use colored::*;
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::{sync::Semaphore, task::JoinSet};
+use walkdir::WalkDir;
+
+use crate::{
+    batch_processor::{BatchProcessor, is_bundle_name},
+    collation::collation,
+    database::is_reserved_name,
+    models::{DescribeResponse, ItemMetadata, ProcessingItem},
+    organizer::FileOrganizer,
+    progress,
+    providers::LLMProvider,
+    utils::relative_display,
+};
+
+/// One row of a `shelfie describe` inventory.
+#[derive(Debug, Clone, Serialize)]
+pub struct ItemSummary {
+    /// Path relative to the described directory
+    pub path: String,
+    pub file_type: String,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggested_name: Option<String>,
+}
+
+/// Describes the contents of a directory without organizing it. Unlike
+/// `FileOrganizer`, nothing is written to the directory, not even a database.
+pub struct Describer {
+    provider: LLMProvider,
+    base_path: PathBuf,
+}
+
+impl Describer {
+    pub fn new(provider: LLMProvider, base_path: PathBuf) -> Self {
+        Self {
+            provider,
+            base_path,
+        }
+    }
+
+    pub async fn describe(&self, max_depth: usize) -> Result<Vec<ItemSummary>> {
+        let items = self.scan(max_depth).await?;
+        if items.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let token_budget = BatchProcessor::default_token_budget(self.provider.get_model_name());
+        let batches = BatchProcessor::pack_batches(items, token_budget);
+
+        let progress_bar = progress::bar(batches.len() as u64, "batches");
+
+        let mut summaries = Vec::new();
+        for batch in batches {
+            summaries.extend(self.describe_batch(&batch).await?);
+            progress_bar.inc(1);
+        }
+        progress_bar.finish_and_clear();
+
+        Ok(summaries)
+    }
+
+    async fn scan(&self, max_depth: usize) -> Result<Vec<ProcessingItem>> {
+        const MAX_CONCURRENCY: usize = 10;
+
+        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENCY));
+        let mut join_set = JoinSet::new();
+
+        for entry in WalkDir::new(&self.base_path)
+            .max_depth(max_depth)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            let path = entry.path().to_path_buf();
+            let relative = path.strip_prefix(&self.base_path).unwrap_or(&path);
+            if relative.as_os_str().is_empty()
+                || Self::is_skipped(relative)
+                || FileOrganizer::is_special_file(&path)
+            {
+                continue;
+            }
+
+            let semaphore = Arc::clone(&semaphore);
+            join_set.spawn(async move {
+                let _permit = semaphore.acquire().await?;
+                if path.is_dir() {
+                    FileOrganizer::process_directory_static(&path, false, false, None).await
+                } else {
+                    FileOrganizer::process_file_static(&path, None, None).await
+                }
+            });
+        }
+
+        let mut items = Vec::new();
+        while let Some(result) = join_set.join_next().await {
+            if let Ok(Ok(item)) = result {
+                items.push(item);
+            }
+        }
+        collation().sort_by_path(&mut items, Self::item_path);
+
+        Ok(items)
+    }
+
+    /// Hidden and reserved entries are skipped, including anything inside them, and
+    /// so is the inside of bundles, which are described as a whole.
+    fn is_skipped(relative_path: &Path) -> bool {
+        let inside_bundle = relative_path.parent().is_some_and(|parent| {
+            parent
+                .components()
+                .any(|component| is_bundle_name(&component.as_os_str().to_string_lossy()))
+        });
+
+        inside_bundle
+            || relative_path.components().any(|component| {
+                let name = component.as_os_str().to_string_lossy();
+                name.starts_with('.') || is_reserved_name(&name)
+            })
+    }
+
+    fn item_path(item: &ProcessingItem) -> &Path {
+        match item {
+            ProcessingItem::File(file) => &file.path,
+            ProcessingItem::Directory(dir) => &dir.path,
+        }
+    }
+
+    async fn describe_batch(&self, items: &[ProcessingItem]) -> Result<Vec<ItemSummary>> {
+        let metadata = items
+            .iter()
+            .enumerate()
+            .map(|(idx, item)| BatchProcessor::item_metadata(idx, item))
+            .collect::<Vec<_>>();
+        let prompt = Self::prompt(&metadata);
+
+        let mut last_error = None;
+        for _ in 0..BatchProcessor::MAX_EXTRACTION_RETRIES {
+            match self.provider.extract::<DescribeResponse>(&prompt).await {
+                Ok(response) => return Ok(self.summaries(items, &metadata, response)),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow!("Failed to describe batch")))
+    }
+
+    fn prompt(metadata: &[ItemMetadata]) -> String {
+        format!(
+            "Describe each of these files and directories.\n\n\
+            Items:\n{}\n\n\
+            For each item, provide:\n\
+            1. A brief description (one sentence) of what it contains or represents\n\
+            2. A suggested_name (better name if needed, or empty string if current name is fine)\n\n\
+            Guidelines:\n\
+            - Base descriptions on the content preview when there is one\n\
+            - Do not treat non-English items any differently\n",
+            BatchProcessor::format_items(metadata)
+        )
+    }
+
+    fn summaries(
+        &self,
+        items: &[ProcessingItem],
+        metadata: &[ItemMetadata],
+        response: DescribeResponse,
+    ) -> Vec<ItemSummary> {
+        items
+            .iter()
+            .zip(metadata)
+            .enumerate()
+            .map(|(idx, (item, meta))| {
+                // Match by id, falling back to position
+                let description = response
+                    .items
+                    .iter()
+                    .find(|d| d.id == meta.id)
+                    .or_else(|| response.items.get(idx));
+                let file_type = match item {
+                    ProcessingItem::File(file) => file.file_type.clone(),
+                    ProcessingItem::Directory(_) => "directory".to_string(),
+                };
+
+                ItemSummary {
+                    path: relative_display(Self::item_path(item), &self.base_path),
+                    file_type,
+                    description: description
+                        .map(|d| d.description.clone())
+                        .unwrap_or_default(),
+                    suggested_name: description
+                        .map(|d| d.suggested_name.clone())
+                        .filter(|name| !name.is_empty()),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Prints the inventory as an aligned table.
+pub fn print_summary_table(summaries: &[ItemSummary]) {
+    let path_width = summaries
+        .iter()
+        .map(|s| s.path.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(4, 50);
+    let type_width = summaries
+        .iter()
+        .map(|s| s.file_type.chars().count())
+        .max()
+        .unwrap_or(0)
+        .clamp(4, 30);
+
+    println!(
+        "{:<path_width$}  {:<type_width$}  {}",
+        "Path".bold(),
+        "Type".bold(),
+        "Description".bold()
+    );
+    for summary in summaries {
+        let mut description = summary.description.clone();
+        if let Some(name) = &summary.suggested_name {
+            description.push_str(&format!(" (suggested name: {})", name));
+        }
+        println!(
+            "{:<path_width$}  {:<type_width$}  {}",
+            summary.path.yellow(),
+            summary.file_type.dimmed(),
+            description
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,50 @@
+use super::Describer;
+use crate::models::{DescribeResponse, ItemDescription};
+use crate::providers::LLMProvider;
+use std::fs;
+use tempfile::TempDir;
+
+#[tokio::test]
+async fn test_describe_lists_items_without_touching_the_directory() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("notes")).unwrap();
+    fs::write(temp_dir.path().join("notes/todo.txt"), "buy milk").unwrap();
+    fs::write(temp_dir.path().join("report.md"), "# Q3 report").unwrap();
+    fs::write(temp_dir.path().join(".hidden"), "secret").unwrap();
+
+    // Items are sorted by path: notes, notes/todo.txt, report.md
+    let response = DescribeResponse {
+        items: ["Notes folder", "Shopping list", "Quarterly report"]
+            .iter()
+            .enumerate()
+            .map(|(idx, description)| ItemDescription {
+                id: idx.to_string(),
+                description: description.to_string(),
+                suggested_name: if idx == 2 {
+                    "q3_report".to_string()
+                } else {
+                    String::new()
+                },
+            })
+            .collect(),
+    };
+    let provider = LLMProvider::new_mock(vec![serde_json::to_string(&response).unwrap()]);
+
+    let summaries = Describer::new(provider, temp_dir.path().to_path_buf())
+        .describe(2)
+        .await
+        .unwrap();
+
+    let paths = summaries
+        .iter()
+        .map(|s| s.path.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec!["notes", "notes/todo.txt", "report.md"]);
+    assert_eq!(summaries[0].file_type, "directory");
+    assert_eq!(summaries[1].description, "Shopping list");
+    assert_eq!(summaries[1].suggested_name, None);
+    assert_eq!(summaries[2].suggested_name.as_deref(), Some("q3_report"));
+
+    assert!(!crate::database::Database::exists(temp_dir.path()));
+    assert!(temp_dir.path().join("report.md").exists());
+}
@@ -0,0 +1,40 @@
+use anyhow::{Context, Result};
+use colored::*;
+use std::path::{Path, PathBuf};
+
+/// File looked for in the current and the target directory.
+pub const ENV_FILE_NAME: &str = ".env";
+
+/// Loads environment variables from `explicit` (`--env-file`), or else from a `.env`
+/// in each of `directories`. Variables already set in the environment always win,
+/// and so do files loaded earlier. Returns the files that were loaded.
+///
+/// An explicit file must exist and parse; a broken implicit one is only warned about.
+pub fn load(explicit: Option<&Path>, directories: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    if let Some(path) = explicit {
+        dotenvy::from_path(path)
+            .with_context(|| format!("Failed to load environment file {}", path.display()))?;
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut loaded: Vec<PathBuf> = Vec::new();
+    for directory in directories {
+        let path = directory.join(ENV_FILE_NAME);
+        if !path.is_file() || loaded.contains(&path) {
+            continue;
+        }
+        match dotenvy::from_path(&path) {
+            Ok(()) => loaded.push(path),
+            Err(e) => eprintln!(
+                "{}: ignoring {}: {}",
+                "Warning".yellow().bold(),
+                path.display(),
+                e
+            ),
+        }
+    }
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,54 @@
+use super::{ENV_FILE_NAME, load};
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_env_file_does_not_override_environment() {
+    let first = TempDir::new().unwrap();
+    let second = TempDir::new().unwrap();
+    fs::write(
+        first.path().join(ENV_FILE_NAME),
+        "SHELFIE_TEST_ENV_FROM_FILE=first\nSHELFIE_TEST_ENV_ALREADY_SET=file\n",
+    )
+    .unwrap();
+    fs::write(
+        second.path().join(ENV_FILE_NAME),
+        "SHELFIE_TEST_ENV_FROM_FILE=second\nSHELFIE_TEST_ENV_SECOND_ONLY=second\n",
+    )
+    .unwrap();
+    // SAFETY: only this test uses these variables
+    unsafe { std::env::set_var("SHELFIE_TEST_ENV_ALREADY_SET", "process") };
+
+    let loaded = load(
+        None,
+        &[
+            first.path().to_path_buf(),
+            second.path().to_path_buf(),
+            first.path().to_path_buf(),
+        ],
+    )
+    .unwrap();
+
+    assert_eq!(loaded.len(), 2);
+    assert_eq!(
+        std::env::var("SHELFIE_TEST_ENV_FROM_FILE").unwrap(),
+        "first"
+    );
+    assert_eq!(
+        std::env::var("SHELFIE_TEST_ENV_SECOND_ONLY").unwrap(),
+        "second"
+    );
+    assert_eq!(
+        std::env::var("SHELFIE_TEST_ENV_ALREADY_SET").unwrap(),
+        "process"
+    );
+}
+
+#[test]
+fn test_missing_explicit_env_file_fails() {
+    let dir = TempDir::new().unwrap();
+
+    assert!(load(Some(&dir.path().join("missing.env")), &[]).is_err());
+    // Directories without a .env are simply skipped
+    assert!(load(None, &[dir.path().to_path_buf()]).unwrap().is_empty());
+}
@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use glob::{MatchOptions, Pattern};
+use std::path::Path;
+
+/// File in the organized directory listing entries to skip, like a `.gitignore`.
+pub const IGNORE_FILE_NAME: &str = ".shelfieignore";
+
+/// A `--exclude` glob or `.shelfieignore` line, matched like a `.gitignore` line:
+/// without a `/` it matches an entry's name at any depth, e.g. `*.lock`; otherwise its
+/// path relative to the organized directory, e.g. `docs/drafts`. A trailing `/` only
+/// matches directories.
+#[derive(Debug, Clone)]
+pub struct ExcludePattern {
+    pattern: Pattern,
+    directories_only: bool,
+    anchored: bool,
+}
+
+impl ExcludePattern {
+    /// Parses a glob as given on the command line.
+    pub fn parse(glob: &str) -> Result<Self, String> {
+        let directories_only = glob.ends_with('/');
+        let glob = glob.trim_end_matches('/').trim_start_matches("./");
+        if glob.is_empty() {
+            return Err("the pattern is empty".to_string());
+        }
+        let anchored = glob.contains('/');
+        let pattern = Pattern::new(glob.trim_start_matches('/')).map_err(|e| e.to_string())?;
+        Ok(Self {
+            pattern,
+            directories_only,
+            anchored,
+        })
+    }
+
+    /// Whether the entry at `relative`, a path inside the organized directory, is
+    /// excluded by this pattern.
+    pub fn matches(&self, relative: &Path, is_dir: bool) -> bool {
+        if self.directories_only && !is_dir {
+            return false;
+        }
+        let options = MatchOptions {
+            require_literal_separator: true,
+            ..MatchOptions::new()
+        };
+        if self.anchored {
+            self.pattern.matches_path_with(relative, options)
+        } else {
+            relative
+                .file_name()
+                .is_some_and(|name| self.pattern.matches_with(&name.to_string_lossy(), options))
+        }
+    }
+}
+
+/// The entries skipped while scanning: the rules of `.shelfieignore` followed by the
+/// `--exclude` globs. As in a `.gitignore`, the last rule matching an entry decides,
+/// and a rule starting with `!` keeps what an earlier one skipped. An entry inside a
+/// skipped directory can't be kept, as the directory isn't scanned.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    /// Each pattern, and whether it keeps the entries it matches
+    rules: Vec<(ExcludePattern, bool)>,
+}
+
+impl IgnoreRules {
+    /// Parses the lines of an ignore file. Blank lines and `#` comments are skipped.
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (glob, keep) = match line.strip_prefix('!') {
+                Some(glob) => (glob, true),
+                None => (line, false),
+            };
+            let pattern = ExcludePattern::parse(glob)
+                .map_err(|e| anyhow::anyhow!("'{}' on line {}: {}", line, number + 1, e))?;
+            rules.push((pattern, keep));
+        }
+        Ok(Self { rules })
+    }
+
+    /// The rules of the `.shelfieignore` in `base_path`, if there is one.
+    pub fn load(base_path: &Path) -> Result<Self> {
+        let path = base_path.join(IGNORE_FILE_NAME);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&contents).with_context(|| format!("Invalid pattern in {}", path.display()))
+    }
+
+    /// Adds `patterns` after the rules so far, so they take precedence.
+    pub fn with_excludes(mut self, patterns: &[ExcludePattern]) -> Self {
+        self.rules
+            .extend(patterns.iter().map(|pattern| (pattern.clone(), false)));
+        self
+    }
+
+    /// Whether the entry at `relative`, a path inside the organized directory, is skipped.
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|(pattern, _)| pattern.matches(relative, is_dir))
+            .is_some_and(|(_, keep)| !keep)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,74 @@
+use super::*;
+
+fn excluded(glob: &str, relative: &str, is_dir: bool) -> bool {
+    ExcludePattern::parse(glob)
+        .unwrap()
+        .matches(Path::new(relative), is_dir)
+}
+
+#[test]
+fn test_names_match_at_any_depth() {
+    assert!(excluded("*.lock", "Cargo.lock", false));
+    assert!(excluded("*.lock", "app/yarn.lock", false));
+    assert!(!excluded("*.lock", "lockfiles", true));
+    assert!(excluded("node_modules", "web/node_modules", true));
+}
+
+#[test]
+fn test_paths_match_from_the_organized_directory() {
+    assert!(excluded("docs/drafts", "docs/drafts", true));
+    assert!(excluded("./docs/*.tmp", "docs/notes.tmp", false));
+    assert!(!excluded("docs/*.tmp", "docs/old/notes.tmp", false));
+    assert!(!excluded("docs/drafts", "archive/docs/drafts", true));
+}
+
+#[test]
+fn test_trailing_slash_only_matches_directories() {
+    assert!(excluded("target/", "target", true));
+    assert!(!excluded("target/", "target", false));
+}
+
+#[test]
+fn test_invalid_patterns_are_rejected() {
+    assert!(ExcludePattern::parse("[abc").is_err());
+    assert!(ExcludePattern::parse("/").is_err());
+}
+
+#[test]
+fn test_ignore_file_skips_comments_and_blank_lines() {
+    let rules = IgnoreRules::parse("# build output\n\n   \ntarget/\n  *.log  \n").unwrap();
+
+    assert!(rules.is_ignored(Path::new("target"), true));
+    assert!(rules.is_ignored(Path::new("logs/app.log"), false));
+    assert!(!rules.is_ignored(Path::new("# build output"), false));
+    assert!(!rules.is_ignored(Path::new("notes.txt"), false));
+}
+
+#[test]
+fn test_negation_keeps_what_an_earlier_rule_skipped() {
+    let rules = IgnoreRules::parse("*.txt\n!keep.txt\n").unwrap();
+
+    assert!(rules.is_ignored(Path::new("notes.txt"), false));
+    assert!(!rules.is_ignored(Path::new("keep.txt"), false));
+    assert!(!rules.is_ignored(Path::new("docs/keep.txt"), false));
+
+    // The last matching rule decides, including excludes from the command line
+    let rules = rules.with_excludes(&[ExcludePattern::parse("keep.txt").unwrap()]);
+    assert!(rules.is_ignored(Path::new("keep.txt"), false));
+}
+
+#[test]
+fn test_trailing_slash_rules_only_skip_directories() {
+    let rules = IgnoreRules::parse("build/\n").unwrap();
+
+    assert!(rules.is_ignored(Path::new("build"), true));
+    assert!(rules.is_ignored(Path::new("app/build"), true));
+    assert!(!rules.is_ignored(Path::new("build"), false));
+}
+
+#[test]
+fn test_invalid_ignore_line_names_its_line() {
+    let error = IgnoreRules::parse("*.log\n[abc\n").unwrap_err();
+
+    assert!(error.to_string().contains("line 2"), "{}", error);
+}
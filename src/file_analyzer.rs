@@ -1,11 +1,179 @@
 use std::{
     path::{Path, PathBuf},
-    time::Duration,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    time::{Duration, UNIX_EPOCH},
 };
 
-use anyhow::Result;
-use extractous::Extractor;
-use tokio::{io::AsyncReadExt, time::timeout};
+use anyhow::{Context, Result};
+
+use crate::database::{Database, fnv1a_hash};
+use crate::models::EnrichedFile;
+use crate::preview;
+use crate::utils::file_owner;
+use tokio::{io::AsyncReadExt, sync::Semaphore, time::timeout};
+
+/// Maximum number of characters kept in a content preview.
+pub(crate) const PREVIEW_MAX_CHARS: usize = 1000;
+/// Previews with a larger share of control or replacement characters are treated as binary.
+const MAX_NOISE_RATIO: f64 = 0.3;
+/// Words per shingle for similarity signatures.
+const SHINGLE_WORDS: usize = 3;
+/// Larger images aren't decoded for a perceptual hash.
+const MAX_HASHED_IMAGE_BYTES: u64 = 25 * 1024 * 1024;
+/// Largest width or height of an image decoded for a perceptual hash.
+const MAX_HASHED_IMAGE_DIMENSION: u32 = 12_000;
+/// Longest first line of a text file still taken as its title.
+const TITLE_MAX_CHARS: usize = 80;
+
+static CONTENT_EXTRACTION_DISABLED: AtomicBool = AtomicBool::new(false);
+static OWNER_CAPTURE: AtomicBool = AtomicBool::new(false);
+static IMAGE_HASHING: AtomicBool = AtomicBool::new(false);
+static CONTENT_HASHING: AtomicBool = AtomicBool::new(false);
+/// Extractions allowed to run at once; 0 until set, meaning one per core.
+static EXTRACTION_PARALLELISM: AtomicUsize = AtomicUsize::new(0);
+static EXTRACTION_PERMITS: OnceLock<Semaphore> = OnceLock::new();
+
+/// Skips content extraction for the rest of the process (`--no-content`), so files
+/// are classified by name, type and size only.
+pub fn set_content_extraction_disabled(disabled: bool) {
+    CONTENT_EXTRACTION_DISABLED.store(disabled, Ordering::Relaxed);
+}
+
+fn content_extraction_enabled() -> bool {
+    !CONTENT_EXTRACTION_DISABLED.load(Ordering::Relaxed)
+}
+
+/// The process-wide settings that shape what analyzing a file captures, e.g.
+/// `content owner`, so items analyzed under other settings can be told apart.
+pub fn capture_settings() -> String {
+    [
+        (content_extraction_enabled(), "content"),
+        (OWNER_CAPTURE.load(Ordering::Relaxed), "owner"),
+        (IMAGE_HASHING.load(Ordering::Relaxed), "image-hash"),
+        (CONTENT_HASHING.load(Ordering::Relaxed), "content-hash"),
+    ]
+    .into_iter()
+    .filter_map(|(enabled, name)| enabled.then_some(name))
+    .collect::<Vec<_>>()
+    .join(" ")
+}
+
+/// Records who owns each file for the rest of the process (`--group-by owner`).
+/// Off by default, since the lookups aren't free on systems with many users.
+pub fn set_owner_capture(enabled: bool) {
+    OWNER_CAPTURE.store(enabled, Ordering::Relaxed);
+}
+
+/// Computes perceptual hashes of images for the rest of the process
+/// (`--image-clustering`). Off by default, since it decodes every image.
+pub fn set_image_hashing(enabled: bool) {
+    IMAGE_HASHING.store(enabled, Ordering::Relaxed);
+}
+
+/// Hashes the bytes of every file for the rest of the process (`--dedupe`). Off by
+/// default, since it reads every file in full.
+pub fn set_content_hashing(enabled: bool) {
+    CONTENT_HASHING.store(enabled, Ordering::Relaxed);
+}
+
+/// Caps how many content extractions run at once (`--parallel-extraction`). Has to be
+/// called before the first file is analyzed.
+///
+/// Extraction is CPU-bound and runs on tokio's blocking pool, separately from the scan
+/// tasks that read directories and file headers, which mostly wait on I/O. So this
+/// can be raised on a machine with many cores while scan concurrency stays modest.
+pub fn set_extraction_parallelism(parallelism: usize) {
+    EXTRACTION_PARALLELISM.store(parallelism.max(1), Ordering::Relaxed);
+}
+
+pub fn extraction_parallelism() -> usize {
+    match EXTRACTION_PARALLELISM.load(Ordering::Relaxed) {
+        0 => std::thread::available_parallelism().map_or(4, |n| n.get()),
+        parallelism => parallelism,
+    }
+}
+
+fn extraction_permits() -> &'static Semaphore {
+    EXTRACTION_PERMITS.get_or_init(|| Semaphore::new(extraction_parallelism()))
+}
+
+/// Content previews cached in the database, keyed by the file's path, size and
+/// modification time, so unchanged files aren't extracted again.
+pub struct ContentCache {
+    database: Arc<Database>,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl ContentCache {
+    pub fn new(database: Arc<Database>) -> Self {
+        Self {
+            database,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Returns the cached preview and metadata title when the fingerprint matches, or
+    /// extracts and caches fresh ones. Extraction failures are not cached, so they are
+    /// retried.
+    async fn preview(
+        &self,
+        path: &Path,
+        metadata: &std::fs::Metadata,
+        detected_type: &str,
+    ) -> Result<(FileContent, Option<String>)> {
+        let path_str = path.to_string_lossy();
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |elapsed| elapsed.as_millis() as i64);
+
+        if let Ok(Some((preview, is_parsable, title))) =
+            self.database
+                .get_cached_content(&path_str, metadata.len(), mtime)
+        {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            let content = if is_parsable {
+                FileContent::Preview(preview)
+            } else {
+                FileContent::Unparsable(preview)
+            };
+            return Ok((content, title));
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let (content, title) = AnalyzedFile::extract_preview_from_file(path, detected_type).await?;
+        let (preview, is_parsable) = match &content {
+            FileContent::Preview(preview) => (preview, true),
+            FileContent::Unparsable(reason) => (reason, false),
+        };
+        // A cache that can't be written only costs speed
+        self.database
+            .cache_content(
+                &path_str,
+                metadata.len(),
+                mtime,
+                preview,
+                is_parsable,
+                title.as_deref(),
+            )
+            .ok();
+        Ok((content, title))
+    }
+}
 
 #[derive(Debug, Clone)]
 pub enum FileContent {
@@ -21,10 +189,43 @@ pub struct AnalyzedFile {
     pub content: FileContent,
     pub detected_type: String, // mime type like "image/jpeg"
     pub size: u64,
+    /// The document's own title: from its metadata, first Markdown heading or
+    /// title-like first line
+    pub detected_title: Option<String>,
+    /// Owner and group, only captured when grouping by owner
+    pub owner: Option<String>,
+    /// Perceptual hash of an image, only computed with `--image-clustering`
+    pub perceptual_hash: Option<u64>,
+    /// BLAKE3 hash of the file's bytes, only computed with `--dedupe`
+    pub content_hash: Option<String>,
 }
 
 impl AnalyzedFile {
     pub async fn new(path: PathBuf) -> Result<Self> {
+        Self::analyze(path, content_extraction_enabled(), None, None).await
+    }
+
+    /// Like `new`, but reuses a cached content preview if the file is unchanged.
+    pub async fn new_cached(path: PathBuf, cache: &ContentCache) -> Result<Self> {
+        Self::analyze(path, content_extraction_enabled(), None, Some(cache)).await
+    }
+
+    /// Like `new`, but files over `max_size` bytes are classified by their type alone,
+    /// without extracting their content (`--max-file-size`).
+    pub async fn new_limited(
+        path: PathBuf,
+        max_size: Option<u64>,
+        cache: Option<&ContentCache>,
+    ) -> Result<Self> {
+        Self::analyze(path, content_extraction_enabled(), max_size, cache).await
+    }
+
+    async fn analyze(
+        path: PathBuf,
+        extract_content: bool,
+        max_size: Option<u64>,
+        cache: Option<&ContentCache>,
+    ) -> Result<Self> {
         let metadata = tokio::fs::metadata(&path).await?;
         let name = path
             .file_stem()
@@ -36,19 +237,55 @@ impl AnalyzedFile {
             .and_then(|s| s.to_str())
             .map(|s| s.to_string());
 
-        // Read first few bytes to detect file type
-        let mut buffer = vec![0; 512];
-        let bytes_read = match tokio::fs::File::open(&path).await {
-            Ok(mut file) => file.read_exact(&mut buffer).await.unwrap_or(0),
-            Err(_) => 0,
-        };
-        buffer.truncate(bytes_read);
+        // Read first few bytes to detect file type (files may be shorter than that)
+        let mut buffer = Vec::with_capacity(512);
+        if let Ok(file) = tokio::fs::File::open(&path).await {
+            file.take(512).read_to_end(&mut buffer).await.ok();
+        }
 
         let detected_type = Self::detect_file_type(&buffer, extension.as_deref());
-        let content = match Self::extract_preview_from_file(&path).await {
-            Ok(content) => content,
-            Err(err) => FileContent::Unparsable(format!("Failed to read file content: {}", err)),
+        let (content, metadata_title) = if !extract_content {
+            (
+                FileContent::Unparsable("Content extraction disabled".to_string()),
+                None,
+            )
+        } else if let Some(max_size) = max_size
+            && metadata.len() > max_size
+        {
+            (
+                FileContent::Unparsable(format!("Larger than {} bytes, not read", max_size)),
+                None,
+            )
+        } else {
+            let preview = match cache {
+                Some(cache) => cache.preview(&path, &metadata, &detected_type).await,
+                None => Self::extract_preview_from_file(&path, &detected_type).await,
+            };
+            match preview {
+                Ok(extracted) => extracted,
+                Err(err) => (
+                    FileContent::Unparsable(format!("Failed to read file content: {}", err)),
+                    None,
+                ),
+            }
         };
+        let perceptual_hash = if IMAGE_HASHING.load(Ordering::Relaxed)
+            && detected_type.starts_with("image/")
+            && metadata.len() <= MAX_HASHED_IMAGE_BYTES
+        {
+            Self::image_hash(&path).await
+        } else {
+            None
+        };
+        let content_hash = if CONTENT_HASHING.load(Ordering::Relaxed) {
+            Self::content_hash(&path).await
+        } else {
+            None
+        };
+        let detected_title = metadata_title.or_else(|| match &content {
+            FileContent::Preview(preview) => Self::detect_title(preview, extension.as_deref()),
+            FileContent::Unparsable(_) => None,
+        });
 
         Ok(Self {
             path,
@@ -57,7 +294,46 @@ impl AnalyzedFile {
             content,
             detected_type,
             size: metadata.len(),
+            detected_title,
+            owner: OWNER_CAPTURE
+                .load(Ordering::Relaxed)
+                .then(|| file_owner(&metadata))
+                .flatten(),
+            perceptual_hash,
+            content_hash,
+        })
+    }
+
+    /// Hashes the file on the blocking pool, like content extraction. Files that can't
+    /// be read get no hash, so they're never taken for duplicates.
+    pub(crate) async fn content_hash(path: &Path) -> Option<String> {
+        let permit = extraction_permits().acquire().await.ok()?;
+        let path = path.to_path_buf();
+        tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let mut hasher = blake3::Hasher::new();
+            hasher.update_reader(std::fs::File::open(&path)?)?;
+            Ok::<_, std::io::Error>(hasher.finalize().to_hex().to_string())
         })
+        .await
+        .ok()?
+        .ok()
+    }
+
+    /// Decodes the image on the blocking pool, like content extraction, and hashes
+    /// it. Images that can't be decoded within the size limits get no hash.
+    async fn image_hash(path: &Path) -> Option<u64> {
+        let permit = extraction_permits().acquire().await.ok()?;
+        let path = path.to_path_buf();
+        let hashing = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            perceptual_hash(&path)
+        });
+        timeout(Duration::from_secs(5), hashing)
+            .await
+            .ok()?
+            .ok()?
+            .ok()
     }
 
     fn detect_file_type(buffer: &[u8], extension: Option<&str>) -> String {
@@ -73,32 +349,222 @@ impl AnalyzedFile {
             }
 
             mime_type.to_string()
+        } else if let Some(script_type) = Self::detect_shebang(buffer) {
+            script_type.to_string()
         } else {
             "Unknown".to_string()
         }
     }
 
-    async fn extract_preview_from_file(path: &Path) -> Result<FileContent> {
-        let extractor = Extractor::new().set_extract_string_max_length(1000);
+    /// Maps a `#!` interpreter line (`#!/bin/bash`, `#!/usr/bin/env python3`) to a
+    /// script mime type, so extensionless scripts aren't classified by name alone.
+    fn detect_shebang(buffer: &[u8]) -> Option<&'static str> {
+        let line = buffer.strip_prefix(b"#!")?.split(|b| *b == b'\n').next()?;
+        let line = std::str::from_utf8(line).ok()?;
+        let mut parts = line.split_whitespace();
+        let mut interpreter = parts.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            interpreter = parts.find(|part| !part.starts_with('-'))?;
+        }
+
+        let script_type =
+            match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+                "sh" | "bash" | "zsh" | "dash" | "ksh" | "fish" => "text/x-shellscript",
+                "python" => "text/x-python",
+                "perl" => "text/x-perl",
+                "ruby" => "text/x-ruby",
+                "node" | "deno" | "bun" => "text/javascript",
+                "php" => "application/x-php",
+                "lua" => "text/x-lua",
+                _ => "text/x-script",
+            };
+        Some(script_type)
+    }
 
-        let mut file = tokio::fs::File::open(path).await?;
-        let mut buffer = vec![0; file.metadata().await?.len().min(1024) as usize];
-        file.read_exact(&mut buffer).await?;
-        let extraction_future =
-            tokio::task::spawn_blocking(move || extractor.extract_bytes_to_string(&buffer));
+    /// Extracts the content preview with the strategy registered for `detected_type`
+    /// (see `preview::PreviewRegistry`) and, for documents that carry one, the title
+    /// from their metadata.
+    async fn extract_preview_from_file(
+        path: &Path,
+        detected_type: &str,
+    ) -> Result<(FileContent, Option<String>)> {
+        let (strategy, fallback) = preview::strategies_for(detected_type);
+        let path = path.to_path_buf();
+        // The permit goes with the blocking task, so an extraction that outlives its
+        // timeout still counts until it's done
+        let permit = extraction_permits().acquire().await?;
+        let extraction_future = tokio::task::spawn_blocking(move || {
+            let _permit = permit;
+            let preview = strategy
+                .preview(&path)
+                .with_context(|| format!("{} preview failed", strategy.name()))?;
+            match preview {
+                Some(preview) => Ok(preview),
+                None => fallback
+                    .preview(&path)?
+                    .ok_or_else(|| anyhow::anyhow!("No preview available")),
+            }
+        });
         match timeout(Duration::from_secs(5), extraction_future).await {
-            Ok(extraction_result) => match extraction_result? {
-                Ok((preview, _)) => Ok(FileContent::Preview(preview)),
-                Err(e) => Err(anyhow::Error::new(e)),
-            },
+            Ok(extraction_result) => {
+                let preview = extraction_result??;
+                Ok((
+                    Self::clean_preview(&preview.text, PREVIEW_MAX_CHARS),
+                    preview.title,
+                ))
+            }
             Err(_) => Err(anyhow::Error::msg("Timeout")),
         }
     }
 
+    /// Finds a title in the start of a text document: the first `# ` heading of
+    /// Markdown, or the first line of plain text if it is short and not a sentence.
+    pub(crate) fn detect_title(preview: &str, extension: Option<&str>) -> Option<String> {
+        let extension = extension.map(str::to_lowercase);
+        let mut lines = preview
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty());
+        let title = match extension.as_deref() {
+            Some("md" | "markdown") => lines.find_map(|line| line.strip_prefix("# "))?.trim(),
+            Some("txt" | "text") => {
+                let line = lines.next()?;
+                let looks_like_title = line.chars().count() <= TITLE_MAX_CHARS
+                    && line.chars().any(char::is_alphabetic)
+                    && !line.ends_with(['.', ',', ';', ':']);
+                looks_like_title.then_some(line)?
+            }
+            _ => return None,
+        };
+        (!title.is_empty()).then(|| title.to_string())
+    }
+
+    /// Strips control characters and collapses whitespace so the preview doesn't waste
+    /// prompt tokens, truncating to `max_chars`. Previews that are mostly noise are
+    /// reported as unparsable.
+    fn clean_preview(raw: &str, max_chars: usize) -> FileContent {
+        let mut cleaned = String::with_capacity(raw.len().min(max_chars * 4));
+        let mut noise = 0usize;
+        let mut visible = 0usize;
+        let mut pending_newlines = 0usize;
+        let mut pending_space = false;
+
+        for c in raw.chars() {
+            if c == '\n' {
+                pending_newlines += 1;
+            } else if c.is_whitespace() {
+                pending_space = true;
+            } else if c.is_control() || c == char::REPLACEMENT_CHARACTER {
+                noise += 1;
+            } else {
+                visible += 1;
+                if !cleaned.is_empty() {
+                    match pending_newlines {
+                        0 if pending_space => cleaned.push(' '),
+                        0 => {}
+                        1 => cleaned.push('\n'),
+                        _ => cleaned.push_str("\n\n"),
+                    }
+                }
+                pending_newlines = 0;
+                pending_space = false;
+                cleaned.push(c);
+            }
+        }
+
+        if noise > 0 && noise as f64 / (noise + visible) as f64 > MAX_NOISE_RATIO {
+            return FileContent::Unparsable("Content is mostly binary".to_string());
+        }
+
+        if let Some((end, _)) = cleaned.char_indices().nth(max_chars) {
+            cleaned.truncate(end);
+        }
+
+        FileContent::Preview(cleaned)
+    }
+
     pub fn get_type_description(&self) -> String {
         return self.detected_type.to_string();
     }
 }
 
+/// SimHash of the word shingles of `text`: near-identical texts get signatures that
+/// differ in only a few bits. `None` for texts too short to compare meaningfully.
+pub fn similarity_signature(text: &str) -> Option<u64> {
+    let words = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>();
+    if words.len() < SHINGLE_WORDS * 2 {
+        return None;
+    }
+
+    let mut weights = [0i32; 64];
+    for shingle in words.windows(SHINGLE_WORDS) {
+        let hash = fnv1a_hash(shingle.join(" ").as_bytes());
+        for (bit, weight) in weights.iter_mut().enumerate() {
+            *weight += if hash >> bit & 1 == 1 { 1 } else { -1 };
+        }
+    }
+
+    Some(
+        weights
+            .iter()
+            .enumerate()
+            .filter(|(_, weight)| **weight > 0)
+            .fold(0, |signature, (bit, _)| signature | 1 << bit),
+    )
+}
+
+/// Difference hash of an image: it is shrunk to 9×8 grey pixels and each bit tells
+/// whether a pixel is brighter than its right neighbour. Resized, recompressed or
+/// slightly edited copies get hashes that differ in only a few bits.
+pub fn perceptual_hash(path: &Path) -> Result<u64> {
+    let mut limits = image::Limits::default();
+    limits.max_image_width = Some(MAX_HASHED_IMAGE_DIMENSION);
+    limits.max_image_height = Some(MAX_HASHED_IMAGE_DIMENSION);
+    let mut reader = image::ImageReader::open(path)?.with_guessed_format()?;
+    reader.limits(limits);
+    let grey = image::imageops::resize(
+        &reader.decode()?.to_luma8(),
+        9,
+        8,
+        image::imageops::FilterType::Triangle,
+    );
+
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if grey.get_pixel(x, y)[0] > grey.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    Ok(hash)
+}
+
+impl From<AnalyzedFile> for EnrichedFile {
+    fn from(analyzed: AnalyzedFile) -> Self {
+        let file_type = analyzed.get_type_description();
+        Self {
+            path: analyzed.path,
+            name: analyzed.name,
+            extension: analyzed.extension,
+            file_type,
+            size: analyzed.size,
+            detected_title: analyzed.detected_title,
+            owner: analyzed.owner,
+            perceptual_hash: analyzed.perceptual_hash,
+            content_hash: analyzed.content_hash,
+            content_preview: match analyzed.content {
+                FileContent::Preview(content) => Some(content),
+                FileContent::Unparsable(_) => None,
+            },
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests;
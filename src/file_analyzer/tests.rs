@@ -1,6 +1,9 @@
-use super::{AnalyzedFile, FileContent};
+use super::{AnalyzedFile, ContentCache, FileContent};
+use crate::database::Database;
+use crate::models::EnrichedFile;
 use std::fs;
 use std::path::PathBuf;
+use std::sync::Arc;
 use tempfile::TempDir;
 
 async fn create_test_file(dir: &TempDir, name: &str, content: &[u8]) -> PathBuf {
@@ -37,6 +40,10 @@ async fn test_analyze_image_file() {
     assert_eq!(analyzed.extension, Some("png".to_string()));
     assert_eq!(analyzed.size, 75037);
     assert_eq!(analyzed.detected_type, "image/png");
+    match analyzed.content {
+        FileContent::Preview(content) => assert_eq!(content, "Image, 500×500 pixels"),
+        FileContent::Unparsable(reason) => panic!("Image should have a preview: {}", reason),
+    }
 }
 
 #[tokio::test]
@@ -152,3 +159,245 @@ async fn test_analyze_large_filename() {
     );
     assert_eq!(analyzed.extension, Some("gz".to_string()));
 }
+
+#[tokio::test]
+async fn test_enriched_file_from_analyzed() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = create_test_file(&temp_dir, "notes.txt", b"Meeting notes").await;
+
+    let analyzed = AnalyzedFile::new(path.clone()).await.unwrap();
+    let detected_type = analyzed.detected_type.clone();
+    let enriched = EnrichedFile::from(analyzed);
+
+    assert_eq!(enriched.path, path);
+    assert_eq!(enriched.name, "notes");
+    assert_eq!(enriched.extension, Some("txt".to_string()));
+    assert_eq!(enriched.file_type, detected_type);
+    assert_eq!(enriched.size, 13);
+    assert!(enriched.content_preview.unwrap().contains("Meeting notes"));
+}
+
+#[tokio::test]
+async fn test_mixed_binary_file_gets_clean_preview() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut content = b"Invoice 2024\x00\x01\x02   for   services\r\n\n\n\nTotal: 42".to_vec();
+    content.extend_from_slice(&[0x07, 0x1B]);
+    let path = create_test_file(&temp_dir, "mixed.txt", &content).await;
+
+    let analyzed = AnalyzedFile::new(path).await.unwrap();
+
+    match analyzed.content {
+        FileContent::Preview(preview) => {
+            assert!(!preview.chars().any(|c| c.is_control() && c != '\n'));
+            assert!(preview.contains("Invoice 2024 for services"));
+            assert!(!preview.contains("\n\n\n"));
+        }
+        FileContent::Unparsable(reason) => panic!("Mostly-text file was unparsable: {}", reason),
+    }
+}
+
+#[test]
+fn test_preview_is_truncated_on_char_boundary() {
+    let raw = "é".repeat(50);
+
+    match AnalyzedFile::clean_preview(&raw, 10) {
+        FileContent::Preview(preview) => assert_eq!(preview.chars().count(), 10),
+        FileContent::Unparsable(_) => panic!("Text should be parsable"),
+    }
+}
+
+#[test]
+fn test_mostly_binary_preview_is_unparsable() {
+    let raw = format!("ab{}", "\u{FFFD}\u{0}".repeat(20));
+
+    assert!(matches!(
+        AnalyzedFile::clean_preview(&raw, 1000),
+        FileContent::Unparsable(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_analyze_without_content_extraction() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = create_test_file(&temp_dir, "notes.txt", b"Hello, world!").await;
+
+    let analyzed = AnalyzedFile::analyze(path, false, None, None)
+        .await
+        .unwrap();
+
+    assert_eq!(analyzed.name, "notes");
+    assert_eq!(analyzed.size, 13);
+    assert!(matches!(analyzed.content, FileContent::Unparsable(_)));
+    assert_eq!(EnrichedFile::from(analyzed).content_preview, None);
+}
+
+#[tokio::test]
+async fn test_extensionless_shell_script_is_detected() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = create_test_file(
+        &temp_dir,
+        "deploy",
+        b"#!/bin/bash\nset -e\nrsync -a . server:/srv\n",
+    )
+    .await;
+
+    let analyzed = AnalyzedFile::new(path).await.unwrap();
+
+    assert_eq!(analyzed.extension, None);
+    assert_eq!(analyzed.detected_type, "text/x-shellscript");
+}
+
+#[test]
+fn test_shebang_interpreters() {
+    assert_eq!(
+        AnalyzedFile::detect_shebang(b"#!/usr/bin/env python3\nprint(1)"),
+        Some("text/x-python")
+    );
+    assert_eq!(
+        AnalyzedFile::detect_shebang(b"#!/usr/bin/env -S node --no-warnings\n"),
+        Some("text/javascript")
+    );
+    assert_eq!(
+        AnalyzedFile::detect_shebang(b"#!/opt/custom/interp\n"),
+        Some("text/x-script")
+    );
+    assert_eq!(AnalyzedFile::detect_shebang(b"plain text"), None);
+}
+
+#[tokio::test]
+async fn test_content_cache_reuses_preview_until_file_changes() {
+    let dir = TempDir::new().unwrap();
+    let database = Arc::new(Database::open_or_create(dir.path()).unwrap());
+    let cache = ContentCache::new(Arc::clone(&database));
+    let path = create_test_file(&dir, "notes.txt", b"first version").await;
+
+    AnalyzedFile::new_cached(path.clone(), &cache)
+        .await
+        .unwrap();
+    assert_eq!((cache.hits(), cache.misses()), (0, 1));
+
+    // Poison the cached entry so a hit is distinguishable from a re-extraction
+    let metadata = fs::metadata(&path).unwrap();
+    let mtime = metadata
+        .modified()
+        .unwrap()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64;
+    database
+        .cache_content(
+            &path.to_string_lossy(),
+            metadata.len(),
+            mtime,
+            "cached",
+            true,
+            None,
+        )
+        .unwrap();
+
+    let analyzed = AnalyzedFile::new_cached(path.clone(), &cache)
+        .await
+        .unwrap();
+    assert!(matches!(analyzed.content, FileContent::Preview(ref p) if p == "cached"));
+    assert_eq!(cache.hits(), 1);
+
+    fs::write(&path, "second, longer version").unwrap();
+    let analyzed = AnalyzedFile::new_cached(path, &cache).await.unwrap();
+    assert!(!matches!(analyzed.content, FileContent::Preview(ref p) if p == "cached"));
+    assert_eq!((cache.hits(), cache.misses()), (1, 2));
+}
+
+#[test]
+fn test_markdown_title_is_first_heading() {
+    let preview = "Some intro text\n## Setup\n# Quarterly Report 2024\n\nBody";
+
+    assert_eq!(
+        AnalyzedFile::detect_title(preview, Some("md")).as_deref(),
+        Some("Quarterly Report 2024")
+    );
+    assert_eq!(
+        AnalyzedFile::detect_title("## Only a subheading", Some("md")),
+        None
+    );
+    assert_eq!(
+        AnalyzedFile::detect_title("#hashtag", Some("markdown")),
+        None
+    );
+}
+
+#[test]
+fn test_plain_text_title_must_look_like_one() {
+    assert_eq!(
+        AnalyzedFile::detect_title("\n  Lease Agreement  \nThis lease is made", Some("txt"))
+            .as_deref(),
+        Some("Lease Agreement")
+    );
+    // Sentences, labels and long lines are content, not titles
+    assert_eq!(
+        AnalyzedFile::detect_title("I went to the shop today.", Some("txt")),
+        None
+    );
+    assert_eq!(
+        AnalyzedFile::detect_title("Notes:\n- milk", Some("txt")),
+        None
+    );
+    assert_eq!(
+        AnalyzedFile::detect_title(&"word ".repeat(30), Some("txt")),
+        None
+    );
+    assert_eq!(AnalyzedFile::detect_title("12345", Some("txt")), None);
+    // Other formats rely on their metadata
+    assert_eq!(
+        AnalyzedFile::detect_title("Lease Agreement", Some("csv")),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_detected_title_reaches_enriched_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = create_test_file(
+        &temp_dir,
+        "draft.md",
+        b"# Trip to Lisbon\n\nDay one: arrived late.",
+    )
+    .await;
+
+    let analyzed = AnalyzedFile::new(path).await.unwrap();
+
+    assert_eq!(analyzed.detected_title.as_deref(), Some("Trip to Lisbon"));
+    assert_eq!(
+        EnrichedFile::from(analyzed).detected_title.as_deref(),
+        Some("Trip to Lisbon")
+    );
+}
+
+#[tokio::test]
+async fn test_files_over_the_size_limit_are_not_read() {
+    let analyzed = AnalyzedFile::new_limited("test_dir/12.png".into(), Some(1000), None)
+        .await
+        .unwrap();
+    let enriched = EnrichedFile::from(analyzed);
+
+    assert_eq!(enriched.content_preview, None);
+    assert_eq!(enriched.size, 75037);
+    assert_eq!(enriched.file_type, "image/png");
+
+    // Files within the limit are read as usual
+    let analyzed = AnalyzedFile::new_limited("test_dir/12.png".into(), Some(75037), None)
+        .await
+        .unwrap();
+    assert!(EnrichedFile::from(analyzed).content_preview.is_some());
+}
+
+#[tokio::test]
+async fn test_identical_files_have_the_same_content_hash() {
+    let temp_dir = TempDir::new().unwrap();
+    let original = create_test_file(&temp_dir, "invoice.pdf", b"%PDF-1.4 invoice").await;
+    let copy = create_test_file(&temp_dir, "invoice (1).pdf", b"%PDF-1.4 invoice").await;
+    let other = create_test_file(&temp_dir, "receipt.pdf", b"%PDF-1.4 receipt").await;
+
+    let hash = AnalyzedFile::content_hash(&original).await.unwrap();
+    assert_eq!(AnalyzedFile::content_hash(&copy).await.unwrap(), hash);
+    assert_ne!(AnalyzedFile::content_hash(&other).await.unwrap(), hash);
+}
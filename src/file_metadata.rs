@@ -0,0 +1,367 @@
+use anyhow::{Context, Result};
+use img_parts::{
+    Bytes,
+    jpeg::{Jpeg, JpegSegment, markers},
+    png::{Png, PngChunk},
+};
+use lopdf::{Dictionary, Document, Object, decode_text_string, dictionary, text_string};
+use std::io::Write;
+use std::path::Path;
+
+use crate::utils::preserve_times;
+
+/// Identifies an XMP packet in a JPEG APP1 segment.
+const XMP_NAMESPACE: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// PNG's registered keyword for a description of the image.
+const PNG_DESCRIPTION_KEYWORD: &[u8] = b"Description";
+/// Element holding the description in an XMP packet written by shelfie.
+const XMP_DESCRIPTION_START: &str = "<rdf:li xml:lang=\"x-default\">";
+/// Largest payload of a JPEG segment.
+const MAX_JPEG_SEGMENT: usize = 65_533;
+
+/// Formats a description can be written into. Others, like Office documents, which
+/// would have to be repacked, are left alone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DescribableFormat {
+    /// The document information's `/Subject`
+    Pdf,
+    /// An international text chunk with the `Description` keyword
+    Png,
+    /// `dc:description` in an XMP packet
+    Jpeg,
+}
+
+impl DescribableFormat {
+    /// The format of the file at `path`, by its content rather than its name.
+    pub fn of(path: &Path) -> Option<Self> {
+        match infer::get_from_path(path).ok()??.mime_type() {
+            "application/pdf" => Some(Self::Pdf),
+            "image/png" => Some(Self::Png),
+            "image/jpeg" => Some(Self::Jpeg),
+            _ => None,
+        }
+    }
+}
+
+/// Writes `description` into the file's own metadata, so it shows in other
+/// applications, e.g. as a PDF's subject. Returns whether it was written: files of
+/// other formats, and those that can't be changed safely, such as encrypted or signed
+/// PDFs, are left untouched. PDFs only get an update appended.
+///
+/// The file is replaced through a temporary copy, and keeps its modification time.
+pub fn write_description(path: &Path, description: &str) -> Result<bool> {
+    let Some(format) = DescribableFormat::of(path) else {
+        return Ok(false);
+    };
+    let original = std::fs::read(path)?;
+    let updated = match format {
+        DescribableFormat::Pdf => pdf_with_description(&original, description)?,
+        DescribableFormat::Png => png_with_description(original.clone(), description)?,
+        DescribableFormat::Jpeg => jpeg_with_description(original.clone(), description)?,
+    };
+    let Some(updated) = updated else {
+        return Ok(false);
+    };
+
+    let metadata = std::fs::metadata(path)?;
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.shelfie-describe", name));
+    std::fs::write(&temporary, updated)
+        .and_then(|_| std::fs::set_permissions(&temporary, metadata.permissions()))
+        .and_then(|_| preserve_times(&metadata, &temporary))
+        .and_then(|_| std::fs::rename(&temporary, path))
+        .inspect_err(|_| {
+            let _ = std::fs::remove_file(&temporary);
+        })
+        .with_context(|| format!("Failed to write the description into {}", path.display()))?;
+    Ok(true)
+}
+
+/// The description in the file's own metadata, as written by `write_description`.
+pub fn read_description(path: &Path) -> Result<Option<String>> {
+    let Some(format) = DescribableFormat::of(path) else {
+        return Ok(None);
+    };
+    let contents = std::fs::read(path)?;
+    Ok(match format {
+        DescribableFormat::Pdf => {
+            let document = Document::load_mem(&contents)?;
+            pdf_info(&document)
+                .and_then(|info| info.get(b"Subject").ok())
+                .and_then(|subject| decode_text_string(subject).ok())
+        }
+        DescribableFormat::Png => Png::from_bytes(contents.into())?
+            .chunks_by_type(*b"iTXt")
+            .find_map(|chunk| itxt_text(chunk.contents())),
+        DescribableFormat::Jpeg => Jpeg::from_bytes(contents.into())?
+            .segments_by_marker(markers::APP1)
+            .find_map(|segment| segment.contents().strip_prefix(XMP_NAMESPACE))
+            .and_then(xmp_description),
+    })
+}
+
+/// A PDF's title from its document information, wherever in the file that is.
+pub fn read_pdf_title(path: &Path) -> Result<Option<String>> {
+    let document = Document::load(path)?;
+    Ok(pdf_info(&document)
+        .and_then(|info| info.get(b"Title").ok())
+        .and_then(|title| decode_text_string(title).ok())
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty()))
+}
+
+fn pdf_info(document: &Document) -> Option<&Dictionary> {
+    let info = document.trailer.get(b"Info").ok()?;
+    match info {
+        Object::Reference(id) => document.get_dictionary(*id).ok(),
+        Object::Dictionary(info) => Some(info),
+        _ => None,
+    }
+}
+
+/// Appends an incremental update holding a new document information dictionary, so
+/// the original bytes stay as they are. Its cross-reference section is a table or a
+/// stream, like the one before it. Signed and encrypted PDFs are left alone.
+fn pdf_with_description(original: &[u8], description: &str) -> Result<Option<Vec<u8>>> {
+    if original
+        .windows(b"/ByteRange".len())
+        .any(|w| w == b"/ByteRange")
+    {
+        return Ok(None);
+    }
+    let document = Document::load_mem(original)?;
+    if document.is_encrypted() {
+        return Ok(None);
+    }
+    let Some(previous_xref) = last_xref_offset(original) else {
+        return Ok(None);
+    };
+    let xref_table = match original.get(previous_xref..) {
+        Some(xref) if xref.starts_with(b"xref") => true,
+        Some(xref) if xref.first().is_some_and(u8::is_ascii_digit) => false,
+        _ => return Ok(None),
+    };
+
+    let mut info = pdf_info(&document).cloned().unwrap_or_default();
+    info.set("Subject", text_string(description));
+    let info_id = document
+        .trailer
+        .get(b"Size")
+        .and_then(Object::as_i64)
+        .map_or(document.max_id + 1, |size| size as u32)
+        .max(document.max_id + 1);
+    // A cross-reference stream is an object of its own, after the Info
+    let xref_id = info_id + 1;
+    let mut trailer = dictionary! {
+        "Size" => xref_id + u32::from(!xref_table),
+        "Root" => document.trailer.get(b"Root")?.clone(),
+        "Info" => (info_id, 0),
+        "Prev" => previous_xref as i64,
+    };
+    if let Ok(id) = document.trailer.get(b"ID") {
+        trailer.set("ID", id.clone());
+    }
+
+    let mut updated = original.to_vec();
+    if !updated.ends_with(b"\n") {
+        updated.push(b'\n');
+    }
+    let info_offset = updated.len();
+    writeln!(updated, "{} 0 obj", info_id)?;
+    write_pdf_object(&mut updated, &Object::Dictionary(info))?;
+    write!(updated, "\nendobj\n")?;
+    let xref_offset = updated.len();
+    if xref_table {
+        // Cross-reference entries are exactly 20 bytes long
+        write!(
+            updated,
+            "xref\n{} 1\n{:010} 00000 n\r\ntrailer\n",
+            info_id, info_offset
+        )?;
+        write_pdf_object(&mut updated, &Object::Dictionary(trailer))?;
+    } else {
+        // Type 1 entries: an offset in 8 bytes and a generation in 2
+        let entries = [info_offset, xref_offset]
+            .iter()
+            .flat_map(|&offset| [&[1][..], &(offset as u64).to_be_bytes(), &[0, 0]].concat())
+            .collect::<Vec<u8>>();
+        trailer.set("Type", "XRef");
+        trailer.set("W", vec![1.into(), 8.into(), 2.into()]);
+        trailer.set("Index", vec![info_id.into(), 2.into()]);
+        trailer.set("Length", entries.len() as i64);
+        writeln!(updated, "{} 0 obj", xref_id)?;
+        write_pdf_object(&mut updated, &Object::Dictionary(trailer))?;
+        write!(updated, "\nstream\n")?;
+        updated.extend(entries);
+        write!(updated, "\nendstream\nendobj")?;
+    }
+    write!(updated, "\nstartxref\n{}\n%%EOF\n", xref_offset)?;
+
+    // Never replace a document with one that doesn't load again
+    Document::load_mem(&updated).context("The PDF didn't survive adding a description")?;
+    Ok(Some(updated))
+}
+
+/// Where the last cross-reference section starts, from the file's final `startxref`.
+fn last_xref_offset(pdf: &[u8]) -> Option<usize> {
+    let keyword = b"startxref";
+    let start = pdf.windows(keyword.len()).rposition(|w| w == keyword)? + keyword.len();
+    let digits = pdf[start..]
+        .iter()
+        .skip_while(|b| b.is_ascii_whitespace())
+        .take_while(|b| b.is_ascii_digit())
+        .map(|&b| b as char)
+        .collect::<String>();
+    digits.parse().ok()
+}
+
+/// Serializes the objects a document information dictionary holds. Strings are
+/// written in hex, so they never need escaping.
+fn write_pdf_object(out: &mut Vec<u8>, object: &Object) -> std::io::Result<()> {
+    match object {
+        Object::Null | Object::Stream(_) => write!(out, "null"),
+        Object::Boolean(value) => write!(out, "{}", value),
+        Object::Integer(value) => write!(out, "{}", value),
+        Object::Real(value) => write!(out, "{}", value),
+        Object::Name(name) => write_pdf_name(out, name),
+        Object::String(bytes, _) => {
+            write!(out, "<")?;
+            for byte in bytes {
+                write!(out, "{:02X}", byte)?;
+            }
+            write!(out, ">")
+        }
+        Object::Array(items) => {
+            write!(out, "[")?;
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    write!(out, " ")?;
+                }
+                write_pdf_object(out, item)?;
+            }
+            write!(out, "]")
+        }
+        Object::Dictionary(dictionary) => {
+            write!(out, "<<")?;
+            for (key, value) in dictionary.iter() {
+                write_pdf_name(out, key)?;
+                write!(out, " ")?;
+                write_pdf_object(out, value)?;
+            }
+            write!(out, ">>")
+        }
+        Object::Reference((id, generation)) => write!(out, "{} {} R", id, generation),
+    }
+}
+
+fn write_pdf_name(out: &mut Vec<u8>, name: &[u8]) -> std::io::Result<()> {
+    write!(out, "/")?;
+    for &byte in name {
+        if byte.is_ascii_alphanumeric() || b"-_.".contains(&byte) {
+            out.push(byte);
+        } else {
+            write!(out, "#{:02X}", byte)?;
+        }
+    }
+    Ok(())
+}
+
+fn png_with_description(original: Vec<u8>, description: &str) -> Result<Option<Vec<u8>>> {
+    let mut png = Png::from_bytes(original.into())?;
+    if png.chunks().last().map(PngChunk::kind) != Some(*b"IEND") {
+        return Ok(None);
+    }
+    // Replaces earlier descriptions, in any of PNG's text chunks
+    let keyword = [PNG_DESCRIPTION_KEYWORD, b"\0"].concat();
+    png.chunks_mut().retain(|chunk| {
+        !matches!(&chunk.kind(), b"iTXt" | b"tEXt" | b"zTXt")
+            || !chunk.contents().starts_with(&keyword)
+    });
+
+    // Keyword, no compression, and no language or translated keyword
+    let contents = [
+        PNG_DESCRIPTION_KEYWORD,
+        b"\0\0\0\0\0",
+        description.as_bytes(),
+    ]
+    .concat();
+    let end = png.chunks().len() - 1;
+    png.chunks_mut()
+        .insert(end, PngChunk::new(*b"iTXt", contents.into()));
+    Ok(Some(png.encoder().bytes().to_vec()))
+}
+
+/// The text of an uncompressed `Description` iTXt chunk.
+fn itxt_text(contents: &Bytes) -> Option<String> {
+    let rest = contents.strip_prefix(PNG_DESCRIPTION_KEYWORD)?;
+    let rest = rest.strip_prefix(b"\0\0\0")?;
+    // Skip the language tag and translated keyword
+    let mut parts = rest.splitn(3, |b| *b == 0);
+    let (_, _, text) = (parts.next()?, parts.next()?, parts.next()?);
+    String::from_utf8(text.to_vec()).ok()
+}
+
+/// An existing XMP packet would have to be merged into, which isn't safe without
+/// understanding all of it, so those images are skipped.
+fn jpeg_with_description(original: Vec<u8>, description: &str) -> Result<Option<Vec<u8>>> {
+    let mut jpeg = Jpeg::from_bytes(original.into())?;
+    let has_xmp = jpeg
+        .segments_by_marker(markers::APP1)
+        .any(|segment| segment.contents().starts_with(XMP_NAMESPACE));
+    let contents = [XMP_NAMESPACE, xmp_packet(description).as_bytes()].concat();
+    if has_xmp || contents.len() > MAX_JPEG_SEGMENT {
+        return Ok(None);
+    }
+
+    // After the JFIF and Exif segments, which readers expect first
+    let position = jpeg
+        .segments()
+        .iter()
+        .position(|segment| !(markers::APP0..=markers::APP15).contains(&segment.marker()))
+        .unwrap_or(jpeg.segments().len());
+    jpeg.segments_mut().insert(
+        position,
+        JpegSegment::new_with_contents(markers::APP1, contents.into()),
+    );
+    Ok(Some(jpeg.encoder().bytes().to_vec()))
+}
+
+fn xmp_packet(description: &str) -> String {
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\
+        <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\
+        <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\
+        <rdf:Description rdf:about=\"\" xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\
+        <dc:description><rdf:Alt>{}{}</rdf:li></rdf:Alt></dc:description>\
+        </rdf:Description></rdf:RDF></x:xmpmeta><?xpacket end=\"w\"?>",
+        XMP_DESCRIPTION_START,
+        xml_escape(description)
+    )
+}
+
+/// The default-language description of a packet written by `xmp_packet`.
+fn xmp_description(packet: &[u8]) -> Option<String> {
+    let packet = std::str::from_utf8(packet).ok()?;
+    let start = packet.find(XMP_DESCRIPTION_START)? + XMP_DESCRIPTION_START.len();
+    let end = start + packet[start..].find("</rdf:li>")?;
+    Some(xml_unescape(&packet[start..end]))
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&quot;", "\"")
+        .replace("&gt;", ">")
+        .replace("&lt;", "<")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests;
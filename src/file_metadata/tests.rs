@@ -0,0 +1,177 @@
+use super::*;
+use lopdf::dictionary;
+use std::fs;
+use tempfile::TempDir;
+
+fn sample_pdf(path: &Path, extra: Option<Dictionary>) {
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let page_id = document.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "MediaBox" => vec![0.into(), 0.into(), 595.into(), 842.into()],
+    });
+    document.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }),
+    );
+    let catalog_id = document.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    document.trailer.set("Root", catalog_id);
+    if let Some(extra) = extra {
+        document.add_object(extra);
+    }
+    document.save(path).unwrap();
+}
+
+#[test]
+fn test_pdf_description_is_written_and_read_back() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("invoice.pdf");
+    sample_pdf(&path, None);
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+
+    let description = "Rechnung für März – ACME (2024)";
+    assert!(write_description(&path, description).unwrap());
+
+    assert_eq!(
+        read_description(&path).unwrap().as_deref(),
+        Some(description)
+    );
+    assert_eq!(fs::metadata(&path).unwrap().modified().unwrap(), modified);
+    assert_eq!(Document::load(&path).unwrap().get_pages().len(), 1);
+    // Only the document itself is left
+    assert_eq!(fs::read_dir(temp_dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn test_pdf_description_is_appended_as_an_update() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("report.pdf");
+    sample_pdf(&path, None);
+    let mut document = Document::load(&path).unwrap();
+    let info_id = document.add_object(dictionary! {
+        "Title" => text_string("Annual Report"),
+        "Subject" => text_string("Old subject"),
+    });
+    document.trailer.set("Info", info_id);
+    document.save(&path).unwrap();
+    let original = fs::read(&path).unwrap();
+
+    assert!(write_description(&path, "Figures for 2024").unwrap());
+
+    let updated = fs::read(&path).unwrap();
+    assert!(updated.starts_with(&original));
+    assert_eq!(
+        read_description(&path).unwrap().as_deref(),
+        Some("Figures for 2024")
+    );
+    let document = Document::load(&path).unwrap();
+    let title = pdf_info(&document).unwrap().get(b"Title").unwrap();
+    assert_eq!(decode_text_string(title).unwrap(), "Annual Report");
+
+    // A second description is another update on top
+    assert!(write_description(&path, "Figures for 2025").unwrap());
+    assert!(fs::read(&path).unwrap().starts_with(&updated));
+    assert_eq!(
+        read_description(&path).unwrap().as_deref(),
+        Some("Figures for 2025")
+    );
+}
+
+#[test]
+fn test_pdf_with_cross_reference_table_gets_a_table_update() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("classic.pdf");
+    sample_pdf(&path, None);
+    let mut document = Document::load(&path).unwrap();
+    document.reference_table.cross_reference_type = lopdf::xref::XrefType::CrossReferenceTable;
+    document.save(&path).unwrap();
+    let original = fs::read(&path).unwrap();
+    assert!(original.windows(5).any(|w| w == b"\nxref"));
+
+    assert!(write_description(&path, "A classic document").unwrap());
+
+    let updated = fs::read(&path).unwrap();
+    assert!(updated.starts_with(&original));
+    assert!(updated[original.len()..].windows(5).any(|w| w == b"xref\n"));
+    assert_eq!(
+        read_description(&path).unwrap().as_deref(),
+        Some("A classic document")
+    );
+    assert_eq!(Document::load(&path).unwrap().get_pages().len(), 1);
+}
+
+#[test]
+fn test_signed_pdf_is_left_untouched() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("contract.pdf");
+    sample_pdf(
+        &path,
+        Some(dictionary! {
+            "Type" => "Sig",
+            "ByteRange" => vec![0.into(), 10.into(), 20.into(), 30.into()],
+        }),
+    );
+    let original = fs::read(&path).unwrap();
+
+    assert!(!write_description(&path, "A contract").unwrap());
+    assert_eq!(fs::read(&path).unwrap(), original);
+}
+
+#[test]
+fn test_png_description_replaces_the_previous_one() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("chart.png");
+    image::RgbImage::new(2, 2).save(&path).unwrap();
+
+    assert!(write_description(&path, "A first guess").unwrap());
+    assert!(write_description(&path, "A bar chart of <sales>").unwrap());
+
+    assert_eq!(
+        read_description(&path).unwrap().as_deref(),
+        Some("A bar chart of <sales>")
+    );
+    let png = Png::from_bytes(fs::read(&path).unwrap().into()).unwrap();
+    assert_eq!(png.chunks_by_type(*b"iTXt").count(), 1);
+    assert_eq!(image::open(&path).unwrap().width(), 2);
+}
+
+#[test]
+fn test_jpeg_description_is_written_once() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("beach.jpg");
+    image::RgbImage::new(2, 2).save(&path).unwrap();
+
+    assert!(write_description(&path, "Sunset at the beach & pier").unwrap());
+    assert_eq!(
+        read_description(&path).unwrap().as_deref(),
+        Some("Sunset at the beach & pier")
+    );
+    assert_eq!(image::open(&path).unwrap().width(), 2);
+
+    // An existing XMP packet isn't merged into
+    assert!(!write_description(&path, "Something else").unwrap());
+    assert_eq!(
+        read_description(&path).unwrap().as_deref(),
+        Some("Sunset at the beach & pier")
+    );
+}
+
+#[test]
+fn test_other_formats_are_left_untouched() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("notes.pdf");
+    // Named like a PDF, but the content is what counts
+    fs::write(&path, "just some notes").unwrap();
+
+    assert!(!write_description(&path, "Notes").unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "just some notes");
+    assert_eq!(read_description(&path).unwrap(), None);
+}
@@ -1,9 +1,33 @@
 pub mod batch_processor;
+pub mod change_set;
+pub mod collation;
 pub mod config;
+pub mod consolidator;
 pub mod database;
+pub mod describer;
+pub mod env_file;
+pub mod exclude;
 pub mod file_analyzer;
+pub mod file_metadata;
+pub mod manifest;
 pub mod models;
+pub mod naming;
+pub mod notify;
+pub mod open;
 pub mod organizer;
+pub mod plan_diff;
 pub mod plan_refiner;
+pub mod preview;
+pub mod pricing;
+pub mod progress;
+pub mod prompt_template;
+pub mod prompts;
 pub mod providers;
+pub mod report;
+pub mod skeleton;
+pub mod template;
+pub mod timings;
+pub mod trash;
 pub mod utils;
+pub mod watch;
+pub mod whatif;
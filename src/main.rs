@@ -1,19 +1,75 @@
 mod batch_processor;
+mod change_set;
+mod collation;
 mod config;
+mod consolidator;
 mod database;
+mod describer;
+mod env_file;
+mod exclude;
 mod file_analyzer;
+mod file_metadata;
+mod manifest;
 mod models;
+mod naming;
+mod notify;
+mod open;
 mod organizer;
+mod plan_diff;
 mod plan_refiner;
+mod preview;
+mod pricing;
+mod progress;
+mod prompt_template;
+mod prompts;
 mod providers;
+mod report;
+mod skeleton;
+mod template;
+mod timings;
+mod trash;
 mod utils;
+mod watch;
+mod whatif;
 
 use anyhow::Result;
 use clap::{Arg, Command};
 use colored::*;
-use std::path::PathBuf;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use crate::{config::Config, organizer::FileOrganizer, providers::LLMProvider, utils::print_tree};
+use crate::{
+    batch_processor::{BatchOptions, GroupBy},
+    config::Config,
+    consolidator::{Consolidator, merge_changes},
+    database::{Database, DatabaseDump},
+    describer::{Describer, print_summary_table},
+    exclude::ExcludePattern,
+    file_analyzer::{AnalyzedFile, FileContent},
+    naming::{ExtensionPolicy, NamingConvention},
+    notify::RunOutcome,
+    open::{choose_item, current_path, find_items, open_path},
+    organizer::{
+        ConflictPolicy, DEFAULT_DIR_PREVIEW_FILES, DEFAULT_SCAN_CONCURRENCY, DuplicateAction,
+        FileOrganizer, FilesystemCase, FlatLayout, MAX_DIR_PREVIEW_FILES, OrganizeOptions,
+    },
+    plan_refiner::{DEFAULT_REFINE_MAX_TURNS, DEFAULT_REFINE_TIMEOUT_SECS},
+    prompt_template::PromptTemplate,
+    providers::{LLMProvider, resolve_base_url},
+    report::{ReportFormat, write_report},
+    skeleton::DEFAULT_SKELETON_SAMPLE_SIZE,
+    template::OrganizationTemplate,
+    timings::TimingsFormat,
+    trash::Trash,
+    utils::{
+        MAX_SAFE_TOP_LEVEL_ENTRIES, dangerous_target, parse_size, print_tree, relative_display,
+        resolve_directory,
+    },
+    watch::{DEFAULT_SETTLE_SECS, watch},
+    whatif::{parse_model_spec, plan_with, print_comparison},
+};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -31,11 +87,158 @@ async fn main() -> Result<()> {
                         .required(true)
                         .index(1),
                 )
+                .args(organize_args())
+                .arg(dry_run_arg())
+                .args(report_args())
+                .args(notify_args())
+                .arg(dangerous_target_arg()),
+        )
+        .subcommand(
+            Command::new("watch")
+                .about("Keep organizing new files as they arrive in a directory, e.g. Downloads")
+                .arg(
+                    Arg::new("directory")
+                        .help("Directory to watch")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("settle")
+                        .long("settle")
+                        .value_name("SECONDS")
+                        .help(format!(
+                            "How long a new file must stay unchanged before it's organized (default {})",
+                            DEFAULT_SETTLE_SECS
+                        ))
+                        .value_parser(clap::builder::RangedU64ValueParser::<u64>::new().range(1..)),
+                )
+                .arg(
+                    Arg::new("allow-new-cabinets")
+                        .long("allow-new-cabinets")
+                        .help("Let new files start new cabinets and shelves instead of only sorting into existing ones")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .args(organize_args())
+                .arg(dangerous_target_arg()),
+        )
+        .subcommand(
+            Command::new("plan")
+                .about(
+                    "Analyze a directory and output the organization plan without moving anything",
+                )
+                .arg(
+                    Arg::new("directory")
+                        .help("Target directory to plan")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("save-plan")
+                        .long("save-plan")
+                        .help("Write the plan as JSON to this file instead of stdout")
+                        .value_parser(clap::value_parser!(PathBuf)),
+                )
+                .args(organize_args())
+                .args(report_args()),
+        )
+        .subcommand(
+            Command::new("whatif")
+                .about("Compare how two models would organize a directory, without moving anything")
+                .arg(
+                    Arg::new("directory")
+                        .help("Target directory to analyze")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("models")
+                        .long("models")
+                        .value_name("MODEL,MODEL")
+                        .help("The two models to compare, each as MODEL or PROVIDER:MODEL, e.g. gpt-4o,ollama:llama3")
+                        .required(true)
+                        .value_delimiter(',')
+                        .num_args(1..),
+                )
+                .args(organize_args()),
+        )
+        .subcommand(
+            Command::new("execute")
+                .about("Execute the plan in an organized directory's database without re-analyzing")
+                .arg(
+                    Arg::new("directory")
+                        .help("Previously analyzed directory")
+                        .required(true)
+                        .index(1),
+                )
+                .args(organize_args())
+                .arg(dry_run_arg())
+                .args(report_args()),
+        )
+        .subcommand(
+            Command::new("export-template")
+                .about("Export the cabinet and shelf structure of an organized directory")
+                .arg(
+                    Arg::new("directory")
+                        .help("Previously organized directory")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("output")
+                        .help("Path of the template file to write")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(db_path_arg()),
+        )
+        .subcommand(
+            Command::new("export-db")
+                .about("Export the database of an organized directory as portable JSON")
+                .arg(
+                    Arg::new("directory")
+                        .help("Previously organized directory")
+                        .required(true)
+                        .index(1),
+                )
                 .arg(
-                    Arg::new("show-tree")
-                        .long("show-tree")
-                        .help("Show current directory tree")
+                    Arg::new("output")
+                        .help("Path of the JSON file to write")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(db_path_arg()),
+        )
+        .subcommand(
+            Command::new("import-db")
+                .about("Recreate the database of a directory from a JSON export")
+                .arg(
+                    Arg::new("directory")
+                        .help("Directory the export belongs to")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("input")
+                        .help("JSON file written by export-db")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(db_path_arg())
+                .arg(
+                    Arg::new("force")
+                        .long("force")
+                        .help("Replace the directory's database even if it isn't empty")
                         .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("describe")
+                .about("Describe the files in a directory without reorganizing them")
+                .arg(
+                    Arg::new("directory")
+                        .help("Directory to describe")
+                        .required(true)
+                        .index(1),
                 )
                 .arg(
                     Arg::new("depth")
@@ -46,13 +249,85 @@ async fn main() -> Result<()> {
                         .default_value("1"),
                 )
                 .arg(
-                    Arg::new("auto-confirm")
-                        .long("auto-confirm")
-                        .short('y')
-                        .help("Automatically confirm the organization plan without prompting")
+                    Arg::new("json")
+                        .long("json")
+                        .help("Print the descriptions as JSON instead of a table")
                         .action(clap::ArgAction::SetTrue),
+                )
+                .arg(no_content_arg())
+                .arg(parallel_extraction_arg()),
+        )
+        .subcommand(
+            Command::new("inspect")
+                .about("Show what shelfie extracts from a single file, without a model or database")
+                .arg(
+                    Arg::new("file")
+                        .help("File to inspect")
+                        .required(true)
+                        .value_parser(clap::value_parser!(PathBuf))
+                        .index(1),
                 ),
         )
+        .subcommand(
+            Command::new("consolidate")
+                .about("Merge redundant cabinets and shelves of an organized directory")
+                .arg(
+                    Arg::new("directory")
+                        .help("Previously organized directory")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(db_path_arg())
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .help("Apply the proposed merges without asking")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(dry_run_arg()),
+        )
+        .subcommand(
+            Command::new("open")
+                .about("Open an organized item, found by its id or name")
+                .arg(
+                    Arg::new("directory")
+                        .help("Previously organized directory")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("item")
+                        .help("Id of the item, or (part of) its original or new name")
+                        .required(true)
+                        .index(2),
+                )
+                .arg(
+                    Arg::new("reveal")
+                        .long("reveal")
+                        .help("Show the item in the file manager instead of opening it")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(db_path_arg()),
+        )
+        .subcommand(
+            Command::new("empty-trash")
+                .about("Permanently delete the originals kept by --trash")
+                .arg(
+                    Arg::new("directory")
+                        .help("Organized directory whose trash to empty")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::new("yes")
+                        .long("yes")
+                        .short('y')
+                        .help("Don't ask for confirmation")
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(dry_run_arg()),
+        )
         .subcommand(
             Command::new("config")
                 .about("Configuration management")
@@ -68,35 +343,180 @@ async fn main() -> Result<()> {
                 .help("Target directory to organize (default mode)")
                 .index(1),
         )
+        .args(organize_args())
+        .arg(dry_run_arg())
+        .args(report_args())
+        .args(notify_args())
+        .arg(dangerous_target_arg())
         .arg(
-            Arg::new("show-tree")
-                .long("show-tree")
-                .help("Show current directory tree")
-                .action(clap::ArgAction::SetTrue),
+            Arg::new("locale")
+                .long("locale")
+                .help("Locale to sort names by, e.g. de_DE or C for byte order (default: from the environment)")
+                .global(true),
         )
         .arg(
-            Arg::new("depth")
-                .long("depth")
-                .short('d')
-                .help("Maximum depth to scan (1 = top-level only)")
-                .value_parser(clap::value_parser!(usize))
-                .default_value("1"),
+            Arg::new("quiet-progress")
+                .long("quiet-progress")
+                .help("Don't show progress bars; without a terminal or with TERM=dumb they're printed as plain lines")
+                .action(clap::ArgAction::SetTrue)
+                .global(true),
         )
         .arg(
-            Arg::new("auto-confirm")
-                .long("auto-confirm")
-                .short('y')
-                .help("Automatically confirm the organization plan without prompting")
-                .action(clap::ArgAction::SetTrue),
+            Arg::new("env-file")
+                .long("env-file")
+                .value_name("PATH")
+                .help("Load API keys and provider settings from this file instead of .env in the current or target directory")
+                .value_parser(clap::value_parser!(PathBuf))
+                .global(true),
         )
         .get_matches();
 
+    let env_file = matches.get_one::<PathBuf>("env-file").or_else(|| {
+        matches
+            .subcommand()
+            .and_then(|(_, sub_matches)| sub_matches.get_one::<PathBuf>("env-file"))
+    });
+    let target_dir = matches
+        .subcommand()
+        .and_then(|(_, sub_matches)| {
+            sub_matches
+                .try_get_one::<String>("directory")
+                .ok()
+                .flatten()
+        })
+        .or_else(|| matches.get_one::<String>("directory"));
+    let target_dir = target_dir.and_then(|dir| resolve_directory(&PathBuf::from(dir)).ok());
+    let env_dirs: Vec<PathBuf> = std::env::current_dir()
+        .ok()
+        .into_iter()
+        .chain(target_dir.clone())
+        .collect();
+    if let Err(e) = env_file::load(env_file.map(PathBuf::as_path), &env_dirs) {
+        eprintln!("{}: {:#}", "Error".red().bold(), e);
+        std::process::exit(1);
+    }
+
+    // Older versions kept their state in loose dotfiles next to the organized files
+    if let Some(target_dir) = &target_dir {
+        match Database::migrate_legacy_state(target_dir) {
+            Ok(true) => eprintln!(
+                "📦 Moved shelfie's state into {}",
+                Database::state_dir(target_dir).display()
+            ),
+            Ok(false) => {}
+            Err(e) => {
+                eprintln!("{}: {:#}", "Error".red().bold(), e);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    let locale = matches.get_one::<String>("locale").or_else(|| {
+        matches
+            .subcommand()
+            .and_then(|(_, sub_matches)| sub_matches.get_one::<String>("locale"))
+    });
+    if let Some(locale) = locale {
+        collation::set_locale(locale);
+    }
+    // An invalid config is reported by the commands that need it
+    if let Some(name) = Config::load()
+        .ok()
+        .flatten()
+        .and_then(|config| config.unsorted_cabinet)
+    {
+        database::set_unsorted_name(&name);
+    }
+    progress::set_quiet(
+        matches.get_flag("quiet-progress")
+            || matches
+                .subcommand()
+                .is_some_and(|(_, sub_matches)| sub_matches.get_flag("quiet-progress")),
+    );
+
     match matches.subcommand() {
         Some(("organize", sub_matches)) => {
             let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
-            let depth = *sub_matches.get_one::<usize>("depth").unwrap();
-            let auto_confirm = sub_matches.get_flag("auto-confirm");
-            run_organize_command(target_dir, sub_matches, depth, auto_confirm).await?;
+            run_organize_command(target_dir, sub_matches).await?;
+        }
+        Some(("watch", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            run_watch_command(target_dir, sub_matches).await?;
+        }
+        Some(("plan", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            run_plan_command(target_dir, sub_matches).await?;
+        }
+        Some(("whatif", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            run_whatif_command(target_dir, sub_matches).await?;
+        }
+        Some(("execute", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            run_execute_command(target_dir, sub_matches).await?;
+        }
+        Some(("export-template", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            let output = PathBuf::from(sub_matches.get_one::<String>("output").unwrap());
+            let db_dir = database_dir(sub_matches.get_one::<PathBuf>("db-path").cloned())?;
+            run_export_template_command(target_dir, output, db_dir.as_deref())?;
+        }
+        Some(("export-db", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            let output = PathBuf::from(sub_matches.get_one::<String>("output").unwrap());
+            let db_dir = database_dir(sub_matches.get_one::<PathBuf>("db-path").cloned())?;
+            run_export_db_command(target_dir, output, db_dir.as_deref())?;
+        }
+        Some(("import-db", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            let input = PathBuf::from(sub_matches.get_one::<String>("input").unwrap());
+            let db_dir = database_dir(sub_matches.get_one::<PathBuf>("db-path").cloned())?;
+            run_import_db_command(
+                target_dir,
+                input,
+                db_dir.as_deref(),
+                sub_matches.get_flag("force"),
+            )?;
+        }
+        Some(("describe", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            let max_depth = *sub_matches.get_one::<usize>("depth").unwrap();
+            configure_extraction(sub_matches)?;
+            run_describe_command(target_dir, max_depth, sub_matches.get_flag("json")).await?;
+        }
+        Some(("inspect", sub_matches)) => {
+            let file = sub_matches.get_one::<PathBuf>("file").unwrap().clone();
+            run_inspect_command(file).await?;
+        }
+        Some(("consolidate", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            let db_dir = database_dir(sub_matches.get_one::<PathBuf>("db-path").cloned())?;
+            run_consolidate_command(
+                target_dir,
+                db_dir.as_deref(),
+                sub_matches.get_flag("yes"),
+                sub_matches.get_flag("dry-run"),
+            )
+            .await?;
+        }
+        Some(("open", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            let query = sub_matches.get_one::<String>("item").unwrap();
+            let db_dir = database_dir(sub_matches.get_one::<PathBuf>("db-path").cloned())?;
+            run_open_command(
+                target_dir,
+                query,
+                db_dir.as_deref(),
+                sub_matches.get_flag("reveal"),
+            )?;
+        }
+        Some(("empty-trash", sub_matches)) => {
+            let target_dir = PathBuf::from(sub_matches.get_one::<String>("directory").unwrap());
+            run_empty_trash_command(
+                target_dir,
+                sub_matches.get_flag("yes"),
+                sub_matches.get_flag("dry-run"),
+            )?;
         }
         Some(("config", sub_matches)) => {
             run_config_command(sub_matches).await?;
@@ -105,9 +525,7 @@ async fn main() -> Result<()> {
             // Default mode - organize if directory is provided
             if let Some(directory) = matches.get_one::<String>("directory") {
                 let target_dir = PathBuf::from(directory);
-                let depth = *matches.get_one::<usize>("depth").unwrap();
-                let auto_confirm = matches.get_flag("auto-confirm");
-                run_organize_command(target_dir, &matches, depth, auto_confirm).await?;
+                run_organize_command(target_dir, &matches).await?;
             } else {
                 println!("{}", "📚 Shelfie - AI File Organizer".cyan().bold());
                 println!("Use 'shelfie --help' for usage information");
@@ -120,30 +538,561 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-async fn run_organize_command(
-    target_dir: PathBuf,
-    matches: &clap::ArgMatches,
-    depth: usize,
-    auto_confirm: bool,
-) -> Result<()> {
-    if !target_dir.exists() {
+/// Options shared by the `organize` subcommand and the default mode.
+fn organize_args() -> Vec<Arg> {
+    vec![
+        Arg::new("show-tree")
+            .long("show-tree")
+            .help("Show current directory tree")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("depth")
+            .long("depth")
+            .short('d')
+            .help("Maximum depth to scan (1 = top-level only)")
+            .value_parser(clap::value_parser!(usize))
+            .default_value("1"),
+        Arg::new("auto-confirm")
+            .long("auto-confirm")
+            .short('y')
+            .visible_alias("yes")
+            .help(
+                "Automatically confirm the organization plan without prompting \
+                (also enabled by SHELFIE_AUTO_CONFIRM=1)",
+            )
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("reorganize")
+            .long("reorganize")
+            .help("Discard the previous analysis and re-analyze all items")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("resume")
+            .long("resume")
+            .visible_alias("resume-from-plan")
+            .help("Skip scanning and analysis, and resume from the plan in the existing database")
+            .conflicts_with("reorganize")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("since-last-run")
+            .long("since-last-run")
+            .help("Only process files created or modified since the last completed run")
+            .conflicts_with("reorganize")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("template")
+            .long("template")
+            .help("Seed the cabinet/shelf structure from a template exported with export-template")
+            .value_parser(clap::value_parser!(PathBuf)),
+        Arg::new("prompt-template")
+            .long("prompt-template")
+            .help("Use a custom batch analysis prompt with {cabinets}, {shelves} and {items} placeholders")
+            .value_parser(clap::value_parser!(PathBuf)),
+        Arg::new("naming-convention")
+            .long("naming-convention")
+            .help("Normalize suggested file names to a naming convention")
+            .value_parser(clap::value_parser!(NamingConvention))
+            .default_value("none"),
+        Arg::new("extension-policy")
+            .long("extension-policy")
+            .help("What happens to the extension when a file is renamed to its suggested name")
+            .value_parser(clap::value_parser!(ExtensionPolicy))
+            .default_value("preserve"),
+        Arg::new("normalize-folder-names")
+            .long("normalize-folder-names")
+            .help("Also apply the naming convention to cabinet and shelf directories")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("flat")
+            .long("flat")
+            .help("Use a single directory level instead of cabinet/shelf (the database keeps both levels)")
+            .value_parser(clap::value_parser!(FlatLayout))
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("combined"),
+        Arg::new("cabinets-from-dirs")
+            .long("cabinets-from-dirs")
+            .help("Keep each top-level directory as a cabinet and only organize shelves inside them")
+            .conflicts_with_all(["flat", "normalize-folder-names"])
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("max-tokens")
+            .long("max-tokens")
+            .help("Maximum output tokens per LLM response (overrides the config)")
+            .value_parser(clap::value_parser!(u64).range(1..)),
+        Arg::new("temperature")
+            .long("temperature")
+            .help("Sampling temperature for LLM requests (default 0.2)")
+            .value_parser(parse_temperature),
+        Arg::new("top-p")
+            .long("top-p")
+            .help("Nucleus sampling (top_p) for LLM requests")
+            .value_parser(parse_top_p),
+        db_path_arg(),
+        Arg::new("git-aware")
+            .long("git-aware")
+            .help("Keep git repositories intact and describe them by their README")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("show-full-plan")
+            .long("show-full-plan")
+            .help("Show the whole plan instead of only the changes since the last run")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("sort-into-existing-only")
+            .long("sort-into-existing-only")
+            .visible_alias("no-new-cabinets")
+            .help("Only use existing cabinets and shelves; items that don't fit go to Unsorted")
+            .conflicts_with("reorganize")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("strict")
+            .long("strict")
+            .help("Abort on any anomaly in the model's answers instead of falling back to defaults")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("token-budget")
+            .long("token-budget")
+            .help("Approximate token budget per analysis request (default depends on the model)")
+            .value_parser(clap::value_parser!(usize)),
+        Arg::new("model-fallback-on-context-overflow")
+            .long("model-fallback-on-context-overflow")
+            .help("Split a batch in half and retry when it doesn't fit the model's context window")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("max-consecutive-failures")
+            .long("max-consecutive-failures")
+            .help("Abort when this many analysis batches fail in a row (default 3)")
+            .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        no_content_arg(),
+        parallel_extraction_arg(),
+        Arg::new("group-by")
+            .long("group-by")
+            .value_name("WHAT")
+            .help("Group items into cabinets by content, or by file owner on shared systems")
+            .value_parser(clap::value_parser!(GroupBy))
+            .default_value("content"),
+        Arg::new("scan-concurrency")
+            .long("scan-concurrency")
+            .value_name("N")
+            .help("Files and directories to scan at once, which mostly waits on disk I/O (default: 10)")
+            .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        Arg::new("no-content-cache")
+            .long("no-content-cache")
+            .help("Re-extract file contents instead of reusing previews cached from earlier runs")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("detect-similar")
+            .long("detect-similar")
+            .help("Detect near-duplicate text documents so versions are grouped together")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("image-clustering")
+            .long("image-clustering")
+            .alias("group-images-by-visual-similarity")
+            .help("Group visually near-identical images by perceptual hash (decodes every image)")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("confirm-structure")
+            .long("confirm-structure")
+            .help("Approve the new cabinet and shelf directories before the file moves are shown")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("include-hidden")
+            .long("include-hidden")
+            .help("Also organize dotfiles and dot-directories (.git and shelfie's own files are still skipped)")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("exclude")
+            .long("exclude")
+            .value_name("GLOB")
+            .help("Skip entries matching this glob, e.g. '*.lock' or 'target/' (a trailing / only matches directories; repeatable, on top of .shelfieignore)")
+            .action(clap::ArgAction::Append)
+            .value_parser(ExcludePattern::parse),
+        Arg::new("plan-skeleton")
+            .long("plan-skeleton")
+            .help("Have the model propose the top-level cabinets from a sample of all items before analyzing them in batches")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("skeleton-sample-size")
+            .long("skeleton-sample-size")
+            .value_name("N")
+            .help("Number of items sampled for --plan-skeleton (default 40)")
+            .requires("plan-skeleton")
+            .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        Arg::new("deep-dir-preview")
+            .long("deep-dir-preview")
+            .help("Read a few files in each directory to classify it by content, not just file names (slower)")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("max-preview-files-per-dir")
+            .long("max-preview-files-per-dir")
+            .value_name("N")
+            .help("Files read per directory for --deep-dir-preview (default 3, at most 10)")
+            .requires("deep-dir-preview")
+            .value_parser(
+                clap::builder::RangedU64ValueParser::<usize>::new()
+                    .range(1..=MAX_DIR_PREVIEW_FILES as u64),
+            ),
+        Arg::new("max-file-size")
+            .long("max-file-size")
+            .value_name("SIZE")
+            .help("Classify files larger than this by name and type only, without reading them, e.g. 50MB or 2GiB")
+            .value_parser(parse_size),
+        Arg::new("timings")
+            .long("timings")
+            .value_name("FORMAT")
+            .help("Report how long scanning, analysis, planning and execution took (--timings=json for JSON)")
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("table")
+            .value_parser(clap::value_parser!(TimingsFormat)),
+        Arg::new("limit-cabinets")
+            .long("limit-cabinets")
+            .value_name("N")
+            .help("Merge the cabinets with the fewest items into Overflow until at most N remain, without asking the model")
+            .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        Arg::new("limit-shelves")
+            .long("limit-shelves")
+            .value_name("M")
+            .help("Merge the shelves with the fewest items into Other until each cabinet has at most M, without asking the model")
+            .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        Arg::new("no-rename")
+            .long("no-rename")
+            .help("Keep every file's original name in its new location")
+            .conflicts_with("min-confidence-for-rename")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("min-confidence-for-rename")
+            .long("min-confidence-for-rename")
+            .value_name("CONFIDENCE")
+            .help("Keep an item's original name unless the model is at least this confident (0-1) in its new name")
+            .value_parser(parse_confidence),
+        Arg::new("on-conflict")
+            .long("on-conflict")
+            .value_name("POLICY")
+            .help("What to do when a destination already exists")
+            .value_parser(clap::value_parser!(ConflictPolicy))
+            .default_value("rename"),
+        Arg::new("dedupe")
+            .long("dedupe")
+            .value_name("ACTION")
+            .help("Hash file contents and move only the first of identical files, reporting the rest (--dedupe=trash or --dedupe=hardlink to handle them)")
+            .num_args(0..=1)
+            .require_equals(true)
+            .default_missing_value("report")
+            .value_parser(clap::value_parser!(DuplicateAction)),
+        Arg::new("filesystem-case")
+            .long("filesystem-case")
+            .value_name("MODE")
+            .help("Whether the filesystem ignores case, so case-only renames and directories like Docs and docs need care")
+            .value_parser(clap::value_parser!(FilesystemCase))
+            .default_value("auto"),
+        Arg::new("write-manifests")
+            .long("write-manifests")
+            .help("Write a .shelf-manifest.json describing its items into every shelf directory")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("write-descriptions")
+            .long("write-descriptions")
+            .help("Write each file's description into its own metadata (PDF subject, PNG and JPEG description) so other apps show it")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("use-history")
+            .long("use-history")
+            .help("Remember how you refine plans and follow the same preferences in later runs")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("refine-max-turns")
+            .long("refine-max-turns")
+            .value_name("N")
+            .help(format!(
+                "Most responses the model may take to apply one piece of refinement feedback (default: {})",
+                DEFAULT_REFINE_MAX_TURNS
+            ))
+            .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..)),
+        Arg::new("refine-timeout")
+            .long("refine-timeout")
+            .value_name("SECONDS")
+            .help(format!(
+                "Undo a refinement that takes longer than this (default: {})",
+                DEFAULT_REFINE_TIMEOUT_SECS
+            ))
+            .value_parser(clap::builder::RangedU64ValueParser::<u64>::new().range(1..)),
+        Arg::new("trash")
+            .long("trash")
+            .help("Keep originals in .shelfie/trash/ instead of deleting them after copying")
+            .action(clap::ArgAction::SetTrue),
+    ]
+}
+
+fn parse_temperature(value: &str) -> Result<f64, String> {
+    parse_in_range(value, 0.0, 2.0)
+}
+
+fn parse_top_p(value: &str) -> Result<f64, String> {
+    parse_in_range(value, 0.0, 1.0)
+}
+
+fn parse_confidence(value: &str) -> Result<f64, String> {
+    parse_in_range(value, 0.0, 1.0)
+}
+
+fn parse_in_range(value: &str, min: f64, max: f64) -> Result<f64, String> {
+    let parsed = value
+        .parse::<f64>()
+        .map_err(|_| format!("'{}' is not a number", value))?;
+    if (min..=max).contains(&parsed) {
+        Ok(parsed)
+    } else {
+        Err(format!("must be between {} and {}", min, max))
+    }
+}
+
+fn db_path_arg() -> Arg {
+    Arg::new("db-path")
+        .long("db-path")
+        .help("Directory to keep the database in instead of the organized directory")
+        .value_parser(clap::value_parser!(PathBuf))
+}
+
+/// Preview of a mutating command: print its changes without applying them.
+fn dry_run_arg() -> Arg {
+    Arg::new("dry-run")
+        .long("dry-run")
+        .help("Show what would change without changing anything")
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// Override for the safety check in `run_organize_command`.
+fn dangerous_target_arg() -> Arg {
+    Arg::new("i-know-what-im-doing")
+        .long("i-know-what-im-doing")
+        .help("Allow organizing a filesystem root, the home directory or a huge directory")
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// Plan export, for the commands that produce a plan.
+fn report_args() -> Vec<Arg> {
+    vec![
+        Arg::new("report")
+            .long("report")
+            .value_name("FILE")
+            .help("Write the approved plan to this file (format from its extension, or --report-format)")
+            .value_parser(clap::value_parser!(PathBuf)),
+        Arg::new("report-format")
+            .long("report-format")
+            .value_name("FORMAT")
+            .help("Format of the --report file")
+            .requires("report")
+            .value_parser(clap::value_parser!(ReportFormat)),
+    ]
+}
+
+/// Completion notifications, for the commands that organize.
+fn notify_args() -> Vec<Arg> {
+    vec![
+        Arg::new("notify")
+            .long("notify")
+            .help("Show a desktop notification when the run finishes or fails")
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("on-complete")
+            .long("on-complete")
+            .value_name("COMMAND")
+            .help(
+                "Shell command to run when the run finishes or fails; gets the status, \
+                directory and summary as arguments and SHELFIE_* variables",
+            ),
+    ]
+}
+
+fn no_content_arg() -> Arg {
+    Arg::new("no-content")
+        .long("no-content")
+        .help("Skip reading file contents and classify by name, type and size only")
+        .action(clap::ArgAction::SetTrue)
+}
+
+fn parallel_extraction_arg() -> Arg {
+    Arg::new("parallel-extraction")
+        .long("parallel-extraction")
+        .value_name("N")
+        .help("Content extractions to run at once, which is CPU-bound (default: one per core)")
+        .value_parser(clap::builder::RangedU64ValueParser::<usize>::new().range(1..))
+}
+
+/// Applies `--no-content`, `--parallel-extraction` (falling back to the config) and,
+/// where they're accepted, `--group-by owner`, `--image-clustering` and `--dedupe`.
+fn configure_extraction(matches: &clap::ArgMatches) -> Result<()> {
+    if matches.get_flag("no-content") {
+        file_analyzer::set_content_extraction_disabled(true);
+    }
+    if let Ok(Some(GroupBy::Owner)) = matches.try_get_one::<GroupBy>("group-by") {
+        file_analyzer::set_owner_capture(true);
+    }
+    if let Ok(Some(true)) = matches.try_get_one::<bool>("image-clustering") {
+        file_analyzer::set_image_hashing(true);
+    }
+    if let Ok(Some(_)) = matches.try_get_one::<DuplicateAction>("dedupe") {
+        file_analyzer::set_content_hashing(true);
+    }
+    let parallelism = match matches.get_one::<usize>("parallel-extraction") {
+        Some(parallelism) => Some(*parallelism),
+        None => Config::load()?.and_then(|config| config.extraction_parallelism),
+    };
+    if let Some(parallelism) = parallelism {
+        file_analyzer::set_extraction_parallelism(parallelism);
+    }
+    Ok(())
+}
+
+/// The database directory from the command line, falling back to the config default.
+fn database_dir(from_args: Option<PathBuf>) -> Result<Option<PathBuf>> {
+    match from_args {
+        Some(dir) => Ok(Some(dir)),
+        None => Ok(Config::load()?.and_then(|config| config.db_path)),
+    }
+}
+
+fn organize_options(matches: &clap::ArgMatches) -> Result<OrganizeOptions> {
+    let config = Config::load()?;
+    let prompt_template = matches
+        .get_one::<PathBuf>("prompt-template")
+        .cloned()
+        .or_else(|| {
+            config
+                .as_ref()
+                .and_then(|config| config.prompt_template.clone())
+        })
+        .map(|path| PromptTemplate::load(&path))
+        .transpose()?;
+    Ok(OrganizeOptions {
+        max_depth: *matches.get_one::<usize>("depth").unwrap(),
+        auto_confirm: matches.get_flag("auto-confirm"),
+        reorganize: matches.get_flag("reorganize"),
+        resume: matches.get_flag("resume"),
+        since_last_run: matches.get_flag("since-last-run"),
+        template: matches.get_one::<PathBuf>("template").cloned(),
+        naming_convention: *matches
+            .get_one::<NamingConvention>("naming-convention")
+            .unwrap(),
+        extension_policy: *matches
+            .get_one::<ExtensionPolicy>("extension-policy")
+            .unwrap(),
+        normalize_folder_names: matches.get_flag("normalize-folder-names"),
+        flat: matches.get_one::<FlatLayout>("flat").copied(),
+        max_tokens: matches.get_one::<u64>("max-tokens").copied(),
+        temperature: matches.get_one::<f64>("temperature").copied(),
+        top_p: matches.get_one::<f64>("top-p").copied(),
+        db_dir: matches.get_one::<PathBuf>("db-path").cloned(),
+        git_aware: matches.get_flag("git-aware"),
+        show_full_plan: matches.get_flag("show-full-plan"),
+        trash: matches.get_flag("trash"),
+        confirm_structure: matches.get_flag("confirm-structure"),
+        include_hidden: matches.get_flag("include-hidden"),
+        exclude: matches
+            .get_many::<ExcludePattern>("exclude")
+            .unwrap_or_default()
+            .cloned()
+            .collect(),
+        content_cache: !matches.get_flag("no-content-cache"),
+        plan_skeleton: matches.get_flag("plan-skeleton").then(|| {
+            matches
+                .get_one::<usize>("skeleton-sample-size")
+                .copied()
+                .unwrap_or(DEFAULT_SKELETON_SAMPLE_SIZE)
+        }),
+        deep_dir_preview: matches.get_flag("deep-dir-preview").then(|| {
+            matches
+                .get_one::<usize>("max-preview-files-per-dir")
+                .copied()
+                .unwrap_or(DEFAULT_DIR_PREVIEW_FILES)
+        }),
+        max_file_size: matches.get_one::<u64>("max-file-size").copied(),
+        timings: matches.get_one::<TimingsFormat>("timings").copied(),
+        scan_concurrency: matches
+            .get_one::<usize>("scan-concurrency")
+            .copied()
+            .or_else(|| config.as_ref().and_then(|config| config.scan_concurrency))
+            .unwrap_or(DEFAULT_SCAN_CONCURRENCY),
+        limit_cabinets: matches.get_one::<usize>("limit-cabinets").copied(),
+        limit_shelves: matches.get_one::<usize>("limit-shelves").copied(),
+        no_rename: matches.get_flag("no-rename"),
+        min_rename_confidence: matches.get_one::<f64>("min-confidence-for-rename").copied(),
+        on_conflict: *matches.get_one::<ConflictPolicy>("on-conflict").unwrap(),
+        dedupe: matches.get_one::<DuplicateAction>("dedupe").copied(),
+        filesystem_case: *matches
+            .get_one::<FilesystemCase>("filesystem-case")
+            .unwrap(),
+        write_manifests: matches.get_flag("write-manifests"),
+        write_descriptions: matches.get_flag("write-descriptions"),
+        use_history: matches.get_flag("use-history"),
+        refine_max_turns: matches
+            .get_one::<usize>("refine-max-turns")
+            .copied()
+            .unwrap_or(DEFAULT_REFINE_MAX_TURNS),
+        refine_timeout: Duration::from_secs(
+            matches
+                .get_one::<u64>("refine-timeout")
+                .copied()
+                .unwrap_or(DEFAULT_REFINE_TIMEOUT_SECS),
+        ),
+        cabinets_from_dirs: matches.get_flag("cabinets-from-dirs"),
+        // Only the commands that execute a plan accept a dry run
+        dry_run: matches!(matches.try_get_one::<bool>("dry-run"), Ok(Some(true))),
+        // whatif doesn't accept a report
+        report: matches
+            .try_get_one::<PathBuf>("report")
+            .ok()
+            .flatten()
+            .map(|path| {
+                let format = matches
+                    .get_one::<ReportFormat>("report-format")
+                    .copied()
+                    .unwrap_or_else(|| ReportFormat::for_path(path));
+                (path.clone(), format)
+            }),
+        batch: BatchOptions {
+            token_budget: matches.get_one::<usize>("token-budget").copied(),
+            split_on_context_overflow: matches.get_flag("model-fallback-on-context-overflow"),
+            existing_only: matches.get_flag("sort-into-existing-only"),
+            detect_similar: matches.get_flag("detect-similar"),
+            image_clustering: matches.get_flag("image-clustering"),
+            max_consecutive_failures: matches
+                .get_one::<usize>("max-consecutive-failures")
+                .copied(),
+            max_cabinets: config.as_ref().and_then(|config| config.max_cabinets),
+            max_shelves: config
+                .as_ref()
+                .and_then(|config| config.max_shelves_per_cabinet),
+            prompt_template,
+            strict: matches.get_flag("strict"),
+            group_by: *matches.get_one::<GroupBy>("group-by").unwrap(),
+            ..Default::default()
+        },
+    })
+}
+
+/// Resolves the directory to organize, exiting with an error when it doesn't exist,
+/// isn't a directory, or is dangerous to organize without --i-know-what-im-doing.
+fn organizable_directory(target_dir: &Path, matches: &clap::ArgMatches) -> PathBuf {
+    let Ok(resolved) = resolve_directory(target_dir) else {
         eprintln!(
             "{}: Directory does not exist: {}",
             "Error".red().bold(),
             target_dir.display()
         );
         std::process::exit(1);
-    }
+    };
 
-    if !target_dir.is_dir() {
+    if !resolved.is_dir() {
         eprintln!(
             "{}: Path is not a directory: {}",
             "Error".red().bold(),
-            target_dir.display()
+            resolved.display()
+        );
+        std::process::exit(1);
+    }
+
+    if let Some(reason) = dangerous_target(
+        &resolved,
+        dirs::home_dir().as_deref(),
+        MAX_SAFE_TOP_LEVEL_ENTRIES,
+    ) && !matches.get_flag("i-know-what-im-doing")
+    {
+        eprintln!(
+            "{}: Refusing to organize {} because {}",
+            "Error".red().bold(),
+            resolved.display(),
+            reason
         );
+        eprintln!("Pass --i-know-what-im-doing if you really mean to organize it");
         std::process::exit(1);
     }
 
+    resolved
+}
+
+async fn run_organize_command(target_dir: PathBuf, matches: &clap::ArgMatches) -> Result<()> {
+    let target_dir = organizable_directory(&target_dir, matches);
+
     println!("{}", "📚 Shelfie - AI File Organizer".cyan().bold());
     println!(
         "Target directory: {}\n",
@@ -156,7 +1105,33 @@ async fn run_organize_command(
         println!();
     }
 
-    match run_organizer(target_dir, depth, auto_confirm).await {
+    let options = organize_options(matches)?;
+    if options.auto_confirm {
+        prompts::set_non_interactive(true);
+    }
+    configure_extraction(matches)?;
+
+    let result = run_organizer(target_dir.clone(), &options).await;
+
+    // Notifications report the outcome but never change it
+    let outcome = RunOutcome {
+        success: result.is_ok(),
+        directory: &target_dir,
+        summary: match &result {
+            Ok(_) => "File organization completed".to_string(),
+            Err(e) => e.to_string(),
+        },
+    };
+    if matches.get_flag("notify") {
+        notify::notify_desktop(&outcome);
+    }
+    if let Some(command) = matches.get_one::<String>("on-complete") {
+        notify::run_hook(command, &outcome);
+    }
+
+    match result {
+        // The dry run notice already said nothing was organized
+        Ok(_) if options.dry_run => {}
         Ok(_) => {
             println!(
                 "\n{}",
@@ -190,6 +1165,473 @@ async fn run_organize_command(
     Ok(())
 }
 
+async fn run_watch_command(target_dir: PathBuf, matches: &clap::ArgMatches) -> Result<()> {
+    let target_dir = organizable_directory(&target_dir, matches);
+
+    // Nobody is there to confirm each run
+    prompts::set_non_interactive(true);
+    configure_extraction(matches)?;
+    let mut options = organize_options(matches)?;
+    options.auto_confirm = true;
+    options.batch.existing_only |= !matches.get_flag("allow-new-cabinets");
+    let settle = Duration::from_secs(
+        matches
+            .get_one::<u64>("settle")
+            .copied()
+            .unwrap_or(DEFAULT_SETTLE_SECS),
+    );
+
+    let provider = LLMProvider::new()
+        .await?
+        .with_max_tokens(options.max_tokens)
+        .with_sampling(options.temperature, options.top_p);
+    println!(
+        "{} Using {} with model {}",
+        "✓".green().bold(),
+        format!("{:?}", provider.get_provider()).cyan(),
+        provider.get_model_name().yellow()
+    );
+    let db_dir = database_dir(options.db_dir.clone())?;
+    let organizer = FileOrganizer::new(provider, target_dir.clone(), db_dir.as_deref())?;
+    watch(&organizer, &target_dir, &options, settle).await
+}
+
+async fn run_plan_command(target_dir: PathBuf, matches: &clap::ArgMatches) -> Result<()> {
+    let target_dir = resolve_directory(&target_dir)?;
+    if !target_dir.is_dir() {
+        eprintln!(
+            "{}: {} is not a directory",
+            "Error".red().bold(),
+            target_dir.display()
+        );
+        std::process::exit(1);
+    }
+
+    // Planning never prompts, so it can run from scripts
+    prompts::set_non_interactive(true);
+    configure_extraction(matches)?;
+
+    let options = organize_options(matches)?;
+    let provider = LLMProvider::new()
+        .await?
+        .with_max_tokens(options.max_tokens)
+        .with_sampling(options.temperature, options.top_p);
+    eprintln!(
+        "{} Planning {} with {} model {}",
+        "✓".green().bold(),
+        target_dir.display().to_string().yellow(),
+        format!("{:?}", provider.get_provider()).cyan(),
+        provider.get_model_name().yellow()
+    );
+
+    let db_dir = database_dir(options.db_dir.clone())?;
+    let organizer = FileOrganizer::new(provider, target_dir, db_dir.as_deref())?;
+    let plan = organizer.plan(&options).await?;
+    if let Some(format) = options.timings {
+        organizer.timings().print(format);
+    }
+    if let Some((path, format)) = &options.report {
+        write_report(&plan, *format, path)?;
+        eprintln!(
+            "✅ Wrote the plan report to {}",
+            path.display().to_string().yellow()
+        );
+    }
+    let plan = serde_json::to_string_pretty(&plan)?;
+
+    match matches.get_one::<PathBuf>("save-plan") {
+        Some(path) => {
+            std::fs::write(path, plan)?;
+            eprintln!(
+                "✅ Saved the organization plan to {}",
+                path.display().to_string().yellow()
+            );
+        }
+        None => println!("{}", plan),
+    }
+
+    Ok(())
+}
+
+/// Plans the directory once per model, each against its own scratch database,
+/// and compares the results.
+async fn run_whatif_command(target_dir: PathBuf, matches: &clap::ArgMatches) -> Result<()> {
+    let target_dir = resolve_directory(&target_dir)?;
+    if !target_dir.is_dir() {
+        eprintln!(
+            "{}: {} is not a directory",
+            "Error".red().bold(),
+            target_dir.display()
+        );
+        std::process::exit(1);
+    }
+
+    let specs = matches
+        .get_many::<String>("models")
+        .unwrap()
+        .map(|spec| spec.trim())
+        .collect::<Vec<_>>();
+    if specs.len() != 2 {
+        anyhow::bail!("--models takes exactly two models to compare, e.g. gpt-4o,ollama:llama3");
+    }
+
+    prompts::set_non_interactive(true);
+    configure_extraction(matches)?;
+
+    let options = organize_options(matches)?;
+    let configured = LLMProvider::new()
+        .await?
+        .with_max_tokens(options.max_tokens)
+        .with_sampling(options.temperature, options.top_p);
+
+    let mut runs = Vec::new();
+    for spec in specs {
+        let (provider, model) =
+            parse_model_spec(spec).ok_or_else(|| anyhow::anyhow!("Invalid model '{}'", spec))?;
+        let provider = configured
+            .with_model(
+                provider.unwrap_or_else(|| configured.get_provider().clone()),
+                model,
+            )
+            .await?;
+        eprintln!(
+            "\n{} Planning {} with {} model {}",
+            "✓".green().bold(),
+            target_dir.display().to_string().yellow(),
+            format!("{:?}", provider.get_provider()).cyan(),
+            provider.get_model_name().yellow()
+        );
+        runs.push(plan_with(spec, provider, &target_dir, &options).await?);
+    }
+
+    print_comparison(&runs[0], &runs[1]);
+    Ok(())
+}
+
+/// Executes the plan held in the database, with no scan and no model calls, for
+/// applying manual edits to the database.
+async fn run_execute_command(target_dir: PathBuf, matches: &clap::ArgMatches) -> Result<()> {
+    let target_dir = resolve_directory(&target_dir)?;
+    let options = organize_options(matches)?;
+    if options.auto_confirm {
+        prompts::set_non_interactive(true);
+    }
+
+    let db_dir = database_dir(options.db_dir.clone())?;
+    let organizer = FileOrganizer::open_existing(target_dir, db_dir.as_deref())?;
+    organizer.execute_from_database(&options).await
+}
+
+fn run_export_template_command(
+    target_dir: PathBuf,
+    output: PathBuf,
+    db_dir: Option<&Path>,
+) -> Result<()> {
+    let target_dir = resolve_directory(&target_dir)?;
+    let db_path = Database::db_path(&target_dir, db_dir);
+    if !db_path.exists() {
+        eprintln!(
+            "{}: No organization database found in {}",
+            "Error".red().bold(),
+            target_dir.display()
+        );
+        std::process::exit(1);
+    }
+
+    let database = Database::open_at(&db_path)?;
+    let template = OrganizationTemplate::from_database(&database)?;
+    template.save(&output)?;
+
+    println!(
+        "✅ Exported {} cabinets to {}",
+        template.cabinets.len(),
+        output.display().to_string().yellow()
+    );
+
+    Ok(())
+}
+
+fn run_export_db_command(
+    target_dir: PathBuf,
+    output: PathBuf,
+    db_dir: Option<&Path>,
+) -> Result<()> {
+    let target_dir = resolve_directory(&target_dir)?;
+    let db_path = Database::db_path(&target_dir, db_dir);
+    if !db_path.exists() {
+        eprintln!(
+            "{}: No organization database found in {}",
+            "Error".red().bold(),
+            target_dir.display()
+        );
+        std::process::exit(1);
+    }
+
+    let dump = Database::open_at(&db_path)?.export()?;
+    dump.save(&output)?;
+
+    println!(
+        "✅ Exported {} cabinets, {} shelves and {} items to {}",
+        dump.cabinets.len(),
+        dump.shelves.len(),
+        dump.items.len(),
+        output.display().to_string().yellow()
+    );
+
+    Ok(())
+}
+
+fn run_import_db_command(
+    target_dir: PathBuf,
+    input: PathBuf,
+    db_dir: Option<&Path>,
+    force: bool,
+) -> Result<()> {
+    let target_dir = resolve_directory(&target_dir)?;
+    let dump = DatabaseDump::load(&input)?;
+
+    let database = Database::open_at(&Database::db_path(&target_dir, db_dir))?;
+    if !force && !database.is_empty()? {
+        eprintln!(
+            "{}: {} already has an organization database; use --force to replace it",
+            "Error".red().bold(),
+            target_dir.display()
+        );
+        std::process::exit(1);
+    }
+    database.import(&dump)?;
+
+    println!(
+        "✅ Imported {} cabinets, {} shelves and {} items into {}",
+        dump.cabinets.len(),
+        dump.shelves.len(),
+        dump.items.len(),
+        target_dir.display().to_string().yellow()
+    );
+
+    Ok(())
+}
+
+async fn run_describe_command(target_dir: PathBuf, max_depth: usize, json: bool) -> Result<()> {
+    let target_dir = resolve_directory(&target_dir)?;
+    if !target_dir.is_dir() {
+        eprintln!(
+            "{}: {} is not a directory",
+            "Error".red().bold(),
+            target_dir.display()
+        );
+        std::process::exit(1);
+    }
+
+    // Status goes to stderr so that --json output can be piped
+    let provider = LLMProvider::new().await?;
+    eprintln!(
+        "{} Describing {} with {} model {}",
+        "✓".green().bold(),
+        target_dir.display().to_string().yellow(),
+        format!("{:?}", provider.get_provider()).cyan(),
+        provider.get_model_name().yellow()
+    );
+
+    let summaries = Describer::new(provider, target_dir)
+        .describe(max_depth)
+        .await?;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summaries)?);
+    } else if summaries.is_empty() {
+        println!("{}", "No files to describe".yellow());
+    } else {
+        print_summary_table(&summaries);
+    }
+
+    Ok(())
+}
+
+/// Prints everything the analyzer extracts from `file`, i.e. what the model is told
+/// about it. Owner and perceptual hash are included, which runs otherwise only
+/// capture when they're used.
+async fn run_inspect_command(file: PathBuf) -> Result<()> {
+    if !file.is_file() {
+        eprintln!("{}: {} is not a file", "Error".red().bold(), file.display());
+        std::process::exit(1);
+    }
+
+    file_analyzer::set_owner_capture(true);
+    file_analyzer::set_image_hashing(true);
+    let analyzed = AnalyzedFile::new(file).await?;
+
+    let field = |label: &str, value: Option<String>| {
+        println!(
+            "  {:<16} {}",
+            format!("{}:", label).cyan(),
+            value.unwrap_or_else(|| "-".dimmed().to_string())
+        );
+    };
+    println!("🔍 {}", analyzed.path.display().to_string().yellow().bold());
+    field("Name", Some(analyzed.name.clone()));
+    field("Extension", analyzed.extension.clone());
+    field("Detected type", Some(analyzed.detected_type.clone()));
+    field("Size", Some(format!("{} bytes", analyzed.size)));
+    field("Title", analyzed.detected_title.clone());
+    field("Owner", analyzed.owner.clone());
+    field(
+        "Perceptual hash",
+        analyzed
+            .perceptual_hash
+            .map(|hash| format!("{:016x}", hash)),
+    );
+    field(
+        "Description",
+        file_metadata::read_description(&analyzed.path)
+            .ok()
+            .flatten(),
+    );
+
+    match &analyzed.content {
+        FileContent::Preview(preview) => {
+            println!(
+                "\n{} ({} characters)",
+                "Content preview".cyan().bold(),
+                preview.chars().count()
+            );
+            println!("{}", preview);
+        }
+        FileContent::Unparsable(reason) => {
+            println!("\n{} {}", "No content preview:".cyan().bold(), reason);
+        }
+    }
+
+    Ok(())
+}
+
+/// Restructures the taxonomy only; files already moved stay where they are.
+async fn run_consolidate_command(
+    target_dir: PathBuf,
+    db_dir: Option<&Path>,
+    yes: bool,
+    dry_run: bool,
+) -> Result<()> {
+    let target_dir = resolve_directory(&target_dir)?;
+    let db_path = Database::db_path(&target_dir, db_dir);
+    if !db_path.exists() {
+        eprintln!(
+            "{}: No organization database found in {}",
+            "Error".red().bold(),
+            target_dir.display()
+        );
+        std::process::exit(1);
+    }
+
+    let provider = LLMProvider::new().await?;
+    println!(
+        "{} Consolidating {} with {} model {}",
+        "✓".green().bold(),
+        target_dir.display().to_string().yellow(),
+        format!("{:?}", provider.get_provider()).cyan(),
+        provider.get_model_name().yellow()
+    );
+
+    let consolidator = Consolidator::new(provider, Database::open_at(&db_path)?);
+    let merges = consolidator.propose().await?;
+    if merges.is_empty() {
+        println!("{}", "No redundant cabinets or shelves found.".green());
+        return Ok(());
+    }
+
+    let changes = merge_changes(&merges);
+    changes.print("🗂️  Proposed Consolidation");
+    if dry_run {
+        change_set::print_dry_run_notice();
+        return Ok(());
+    }
+
+    let confirmed = yes || prompts::confirm("\nApply these merges?", false, false)?;
+    if confirmed {
+        consolidator.apply(&changes)?;
+        println!("✅ Applied {} merges", changes.len());
+    } else {
+        println!("{}", "Consolidation cancelled.".yellow());
+    }
+
+    Ok(())
+}
+
+fn run_open_command(
+    target_dir: PathBuf,
+    query: &str,
+    db_dir: Option<&Path>,
+    reveal: bool,
+) -> Result<()> {
+    let target_dir = resolve_directory(&target_dir)?;
+    let db_path = Database::db_path(&target_dir, db_dir);
+    if !db_path.exists() {
+        eprintln!(
+            "{}: No organization database found in {}",
+            "Error".red().bold(),
+            target_dir.display()
+        );
+        std::process::exit(1);
+    }
+
+    let database = Database::open_at(&db_path)?;
+    let last_plan = database.last_executed_plan()?;
+    let matches = find_items(database.list_all_items()?, query, last_plan.as_ref());
+    let item = choose_item(&database, matches, query)?;
+    let Some(path) = current_path(&target_dir, &item, last_plan.as_ref()) else {
+        eprintln!(
+            "{}: {} is no longer where shelfie put it",
+            "Error".red().bold(),
+            item.suggested_name.as_ref().unwrap_or(&item.original_name)
+        );
+        std::process::exit(1);
+    };
+
+    println!(
+        "{} {}",
+        if reveal {
+            "📂 Revealing"
+        } else {
+            "📂 Opening"
+        },
+        relative_display(&path, &target_dir).yellow()
+    );
+    open_path(&path, reveal)
+}
+
+fn run_empty_trash_command(target_dir: PathBuf, yes: bool, dry_run: bool) -> Result<()> {
+    let target_dir = resolve_directory(&target_dir)?;
+    let trash_dir = Trash::dir(&target_dir);
+    let changes = Trash::pending_deletion(&target_dir)?;
+    if changes.is_empty() {
+        println!("{}", "Trash is already empty.".yellow());
+        return Ok(());
+    }
+
+    if dry_run {
+        changes.print("🗑️  Would Delete");
+        change_set::print_dry_run_notice();
+        return Ok(());
+    }
+
+    // Never purge without an explicit answer
+    let confirmed = yes
+        || prompts::confirm(
+            &format!("Permanently delete everything in {}?", trash_dir.display()),
+            false,
+            false,
+        )?;
+
+    if confirmed {
+        let removed = Trash::empty(&target_dir, &changes)?;
+        println!("✅ Deleted {} files from the trash", removed);
+    } else {
+        println!("{}", "Trash kept.".yellow());
+    }
+
+    Ok(())
+}
+
 async fn run_config_command(matches: &clap::ArgMatches) -> Result<()> {
     match matches.subcommand() {
         Some(("edit", _)) => {
@@ -221,9 +1663,41 @@ async fn config_edit() -> Result<()> {
     // Force a new provider selection
     let provider = LLMProvider::new_interactive().await?;
 
+    // Keep the token limit only if it was set for the same provider
+    let existing = Config::load()?;
+    let max_tokens = existing.as_ref().and_then(|existing| {
+        (existing.provider.to_string() == provider.get_provider().to_string())
+            .then_some(existing.max_tokens)
+            .flatten()
+    });
+
     let config = Config {
         provider: provider.get_provider().clone(),
         model_name: provider.get_model_name().to_string(),
+        max_tokens,
+        db_path: existing.as_ref().and_then(|config| config.db_path.clone()),
+        temperature: existing.as_ref().and_then(|config| config.temperature),
+        top_p: existing.as_ref().and_then(|config| config.top_p),
+        max_cabinets: existing.as_ref().and_then(|config| config.max_cabinets),
+        max_shelves_per_cabinet: existing
+            .as_ref()
+            .and_then(|config| config.max_shelves_per_cabinet),
+        prompt_template: existing
+            .as_ref()
+            .and_then(|config| config.prompt_template.clone()),
+        unsorted_cabinet: existing
+            .as_ref()
+            .and_then(|config| config.unsorted_cabinet.clone()),
+        scan_concurrency: existing.as_ref().and_then(|config| config.scan_concurrency),
+        extraction_parallelism: existing
+            .as_ref()
+            .and_then(|config| config.extraction_parallelism),
+        // Like the token limit, the base URL belongs to the provider it was set for
+        base_url: existing.as_ref().and_then(|existing| {
+            (existing.provider.to_string() == provider.get_provider().to_string())
+                .then(|| existing.base_url.clone())
+                .flatten()
+        }),
     };
 
     config.save()?;
@@ -242,6 +1716,55 @@ async fn config_show() -> Result<()> {
         Some(config) => {
             println!("Provider: {}", format!("{:?}", config.provider).green());
             println!("Model: {}", config.model_name.green());
+            if let Some(base_url) =
+                resolve_base_url(&config.provider, config.base_url.as_deref(), |key| {
+                    std::env::var(key).ok()
+                })
+            {
+                println!("Base URL: {}", base_url.green());
+            }
+            if let Some(max_tokens) = config.max_tokens {
+                println!("Max tokens: {}", max_tokens.to_string().green());
+            }
+            if let Some(temperature) = config.temperature {
+                println!("Temperature: {}", temperature.to_string().green());
+            }
+            if let Some(top_p) = config.top_p {
+                println!("Top p: {}", top_p.to_string().green());
+            }
+            if let Some(max_cabinets) = config.max_cabinets {
+                println!("Max cabinets: {}", max_cabinets.to_string().green());
+            }
+            if let Some(max_shelves) = config.max_shelves_per_cabinet {
+                println!(
+                    "Max shelves per cabinet: {}",
+                    max_shelves.to_string().green()
+                );
+            }
+            if let Some(prompt_template) = &config.prompt_template {
+                println!(
+                    "Prompt template: {}",
+                    prompt_template.display().to_string().green()
+                );
+            }
+            if let Some(scan_concurrency) = config.scan_concurrency {
+                println!("Scan concurrency: {}", scan_concurrency.to_string().green());
+            }
+            if let Some(parallelism) = config.extraction_parallelism {
+                println!(
+                    "Extraction parallelism: {}",
+                    parallelism.to_string().green()
+                );
+            }
+            if let Some(unsorted) = &config.unsorted_cabinet {
+                println!("Unsorted cabinet: {}", unsorted.green());
+            }
+            if let Some(db_path) = &config.db_path {
+                println!(
+                    "Database directory: {}",
+                    db_path.display().to_string().green()
+                );
+            }
 
             let config_path = Config::get_config_file_path()?;
             println!(
@@ -261,8 +1784,6 @@ async fn config_show() -> Result<()> {
 }
 
 async fn config_reset() -> Result<()> {
-    use dialoguer::{Confirm, theme::ColorfulTheme};
-
     let config_path = Config::get_config_file_path()?;
 
     if !config_path.exists() {
@@ -270,10 +1791,12 @@ async fn config_reset() -> Result<()> {
         return Ok(());
     }
 
-    let confirmed = Confirm::with_theme(&ColorfulTheme::default())
-        .with_prompt("Are you sure you want to reset the configuration?")
-        .default(false)
-        .interact()?;
+    // Never reset without an explicit answer
+    let confirmed = prompts::confirm(
+        "Are you sure you want to reset the configuration?",
+        false,
+        false,
+    )?;
 
     if confirmed {
         std::fs::remove_file(&config_path)?;
@@ -288,9 +1811,12 @@ async fn config_reset() -> Result<()> {
     Ok(())
 }
 
-async fn run_organizer(target_dir: PathBuf, depth: usize, auto_confirm: bool) -> Result<()> {
+async fn run_organizer(target_dir: PathBuf, options: &OrganizeOptions) -> Result<()> {
     println!("{}", "📚 Setting up AI provider...".cyan().bold());
-    let provider = LLMProvider::new().await?;
+    let provider = LLMProvider::new()
+        .await?
+        .with_max_tokens(options.max_tokens)
+        .with_sampling(options.temperature, options.top_p);
 
     println!(
         "{} Using {} with model {}",
@@ -299,8 +1825,12 @@ async fn run_organizer(target_dir: PathBuf, depth: usize, auto_confirm: bool) ->
         provider.get_model_name().yellow()
     );
 
-    let organizer = FileOrganizer::new(provider, target_dir.clone())?;
-    organizer.analyze_and_organize(depth, auto_confirm).await?;
+    let db_dir = database_dir(options.db_dir.clone())?;
+    let organizer = FileOrganizer::new(provider, target_dir.clone(), db_dir.as_deref())?;
+    let result = organizer.analyze_and_organize(options).await;
+    if let Some(format) = options.timings {
+        organizer.timings().print(format);
+    }
 
-    Ok(())
+    result
 }
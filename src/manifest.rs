@@ -0,0 +1,55 @@
+use anyhow::{Context, Result};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// File `--write-manifests` puts into every shelf directory.
+pub const MANIFEST_FILE_NAME: &str = ".shelf-manifest.json";
+
+/// Describes a shelf directory and its items, so an organized directory stays
+/// understandable without shelfie's database.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ShelfManifest {
+    pub cabinet: String,
+    pub cabinet_description: String,
+    /// Empty for flat layouts, where the directory is the cabinet
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub shelf: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub shelf_description: String,
+    pub generated_at: String,
+    pub items: Vec<ManifestItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestItem {
+    /// Name in the shelf directory
+    pub name: String,
+    pub original_name: String,
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_type: Option<String>,
+    /// Why the item is a directory moved whole, if it is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opaque_reason: Option<String>,
+}
+
+impl ShelfManifest {
+    pub fn new(cabinet: (&str, &str), shelf: (&str, &str), items: Vec<ManifestItem>) -> Self {
+        Self {
+            cabinet: cabinet.0.to_string(),
+            cabinet_description: cabinet.1.to_string(),
+            shelf: shelf.0.to_string(),
+            shelf_description: shelf.1.to_string(),
+            generated_at: Utc::now().to_rfc3339(),
+            items,
+        }
+    }
+
+    /// Writes the manifest into `shelf_dir`, replacing one from an earlier run.
+    pub fn write(&self, shelf_dir: &Path) -> Result<()> {
+        let path = shelf_dir.join(MANIFEST_FILE_NAME);
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write manifest {}", path.display()))
+    }
+}
@@ -1,14 +1,23 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ProcessingItem {
     File(EnrichedFile),
     Directory(EnrichedDirectory),
 }
 
-#[derive(Debug, Clone)]
+impl ProcessingItem {
+    pub fn path(&self) -> &Path {
+        match self {
+            ProcessingItem::File(file) => &file.path,
+            ProcessingItem::Directory(dir) => &dir.path,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnrichedFile {
     pub path: PathBuf,
     pub name: String,
@@ -16,16 +25,30 @@ pub struct EnrichedFile {
     pub file_type: String,
     pub size: u64,
     pub content_preview: Option<String>,
+    /// Title or first heading found in the document
+    pub detected_title: Option<String>,
+    /// Owner and group, e.g. `alice (group staff)`, when grouping by owner
+    pub owner: Option<String>,
+    /// Perceptual hash of an image, with `--image-clustering`
+    pub perceptual_hash: Option<u64>,
+    /// Hash of the file's bytes, with `--dedupe`
+    pub content_hash: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EnrichedDirectory {
     pub path: PathBuf,
     pub name: String,
     pub sampled_items: Vec<SampledItem>,
+    /// Set in git-aware mode when the directory is the root of a git repository
+    pub is_git_repository: bool,
+    /// Start of the repository's README, used to characterize git repositories
+    pub readme_preview: Option<String>,
+    /// Previews of a few of its files, with `--deep-dir-preview`
+    pub content_preview: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SampledItem {
     pub name: String,
     pub is_file: bool,
@@ -51,6 +74,11 @@ pub struct ItemMetadata {
     pub size_bytes: u64,               // Use 0 for directories or unknown
     pub sampled_contents: Vec<String>, // Use empty vec for files
     pub content_preview: String,       // Use empty string if no preview
+    pub detected_title: String,        // Document title or first heading; empty string if none
+    pub owner: String,                 // Owner and group when grouping by owner; empty string otherwise
+    pub similar_to: Vec<String>,       // Names of near-duplicates; empty unless detecting similar items
+    pub image_cluster: u32,            // Shared by visually near-identical images; 0 if none
+    pub fixed_cabinet: i64,            // Cabinet the item must go into; 0 if the model may choose
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
@@ -92,6 +120,10 @@ pub struct ItemAnalysis {
         description = "Better name for the item if current name needs improvement, or empty string if current name is fine"
     )]
     pub suggested_name: String,
+    #[schemars(
+        description = "Confidence from 0 to 1 that suggested_name is an improvement, or 0 if suggested_name is empty"
+    )]
+    pub rename_confidence: f64,
     #[schemars(
         description = "For directories only: true if directory contains homogeneous/generated content that should be treated as a single unit"
     )]
@@ -142,33 +174,111 @@ pub struct ShelfAssignment {
     pub new_description: String,
 }
 
+// Describe-only extraction structures
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct DescribeResponse {
+    #[schemars(
+        description = "Description of each item in the batch, in the same order as the input items"
+    )]
+    pub items: Vec<ItemDescription>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ItemDescription {
+    #[schemars(description = "Must match the id from the corresponding input item")]
+    pub id: String,
+    #[schemars(
+        description = "Brief one-sentence description of what this item contains or represents"
+    )]
+    pub description: String,
+    #[schemars(
+        description = "Better name for the item if current name needs improvement, or empty string if current name is fine"
+    )]
+    pub suggested_name: String,
+}
+
+// Consolidation extraction structures
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ConsolidationResponse {
+    #[schemars(
+        description = "Merges of redundant containers, or an empty list if the structure has no redundancy"
+    )]
+    pub merges: Vec<ContainerMerge>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ContainerMerge {
+    #[schemars(description = "Either 'cabinet' or 'shelf'")]
+    pub kind: String,
+    #[schemars(
+        description = "ID of the cabinet or shelf to merge away; it is deleted after the merge"
+    )]
+    pub source_id: i64,
+    #[schemars(
+        description = "ID of the cabinet or shelf that receives the contents; must be of the same kind as the source"
+    )]
+    pub target_id: i64,
+    #[schemars(description = "Brief explanation of why the two containers are redundant")]
+    pub reason: String,
+}
+
+// Skeleton extraction structures
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct SkeletonResponse {
+    #[schemars(description = "Top-level cabinets that together fit all kinds of items in the sample")]
+    pub cabinets: Vec<SkeletonCabinet>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct SkeletonCabinet {
+    #[schemars(description = "Short, descriptive cabinet name")]
+    pub name: String,
+    #[schemars(description = "One sentence on what belongs in the cabinet")]
+    pub description: String,
+}
+
 // Organization preview structures
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrganizationPlan {
     pub cabinets: Vec<CabinetPlan>,
     pub movements: Vec<FileMovement>,
 }
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CabinetPlan {
+    /// Database id, stable across runs and refinements; negative for an Overflow
+    /// cabinet the plan adds that isn't in the database yet
+    pub id: i64,
     pub name: String,
     pub description: String,
     pub shelves: Vec<ShelfPlan>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ShelfPlan {
+    /// Database id, stable across runs and refinements; negative for an Other shelf
+    /// the plan adds that isn't in the database yet
+    pub id: i64,
     pub name: String,
     pub description: String,
     pub item_count: usize,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileMovement {
     pub from: PathBuf,
     pub to_cabinet: String,
     pub to_shelf: String,
     pub new_name: Option<String>,
     pub reasoning: String,
+    /// Why the item is a directory moved whole, if it is
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub opaque_reason: Option<String>,
 }
@@ -0,0 +1,182 @@
+use clap::ValueEnum;
+
+use crate::database::is_reserved_name;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum NamingConvention {
+    /// snake_case
+    Snake,
+    /// kebab-case
+    Kebab,
+    /// Title Case
+    Title,
+    /// Keep names exactly as suggested
+    #[default]
+    None,
+}
+
+/// What happens to the extension when an item is renamed to its suggested name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ExtensionPolicy {
+    /// Always keep the source extension
+    #[default]
+    Preserve,
+    /// Use the extension of the suggested name if it has one
+    AllowChange,
+    /// Drop the extension
+    Strip,
+}
+
+/// Applies `policy` to a suggested name for an item with `source_extension`.
+/// Only alphabetic-looking suffixes count as extensions, so names like
+/// `Release 1.2` and `Notes.v2` keep their version. A suggested name that already
+/// ends with the source extension, in any case, doesn't get it twice.
+pub fn apply_extension_policy(
+    new_name: &str,
+    source_extension: Option<&str>,
+    policy: ExtensionPolicy,
+) -> String {
+    // Trailing dots would double up with the extension, and Windows drops them anyway
+    let new_name = match new_name.trim_end_matches('.') {
+        "" => new_name,
+        trimmed => trimmed,
+    };
+    let (stem, extension) = match split_extension(new_name) {
+        (stem, Some(ext)) if ext.chars().any(|c| c.is_ascii_alphabetic()) && !is_version(ext) => {
+            (stem, Some(ext))
+        }
+        _ => (new_name, None),
+    };
+
+    match (policy, source_extension) {
+        (ExtensionPolicy::Strip, _) => stem.to_string(),
+        (ExtensionPolicy::AllowChange, _) if extension.is_some() => new_name.to_string(),
+        (_, Some(source)) => format!("{}.{}", stem, source),
+        (_, None) => stem.to_string(),
+    }
+}
+
+/// Normalizes `name` to the given convention, keeping a trailing file extension intact.
+pub fn apply_naming_convention(name: &str, convention: NamingConvention) -> String {
+    if convention == NamingConvention::None {
+        return name.to_string();
+    }
+
+    let (stem, extension) = split_extension(name);
+    let words = split_words(stem);
+
+    if words.is_empty() {
+        return name.to_string();
+    }
+
+    let converted = match convention {
+        NamingConvention::Snake => words
+            .iter()
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("_"),
+        NamingConvention::Kebab => words
+            .iter()
+            .map(|w| w.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("-"),
+        NamingConvention::Title => words
+            .iter()
+            .map(|w| title_case_word(w))
+            .collect::<Vec<_>>()
+            .join(" "),
+        NamingConvention::None => unreachable!(),
+    };
+
+    match extension {
+        Some(ext) => format!("{}.{}", converted, ext),
+        None => converted,
+    }
+}
+
+/// Makes sure a cabinet or shelf name can't collide with shelfie's own state files.
+pub fn safe_folder_name(name: &str) -> String {
+    if !is_reserved_name(name) {
+        return name.to_string();
+    }
+
+    let trimmed = name.trim().trim_start_matches('.');
+    if trimmed.is_empty() {
+        "folder".to_string()
+    } else {
+        format!("{}_folder", trimmed)
+    }
+}
+
+/// A version suffix such as `v2`, which looks like an extension but isn't one.
+fn is_version(suffix: &str) -> bool {
+    suffix
+        .strip_prefix(['v', 'V'])
+        .is_some_and(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Splits off a short alphanumeric extension such as `pdf` or `tar.gz`'s `gz`.
+fn split_extension(name: &str) -> (&str, Option<&str>) {
+    match name.rsplit_once('.') {
+        Some((stem, ext))
+            if !stem.is_empty()
+                && !ext.is_empty()
+                && ext.len() <= 5
+                && ext.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            (stem, Some(ext))
+        }
+        _ => (name, None),
+    }
+}
+
+/// Breaks a name into words on separators and camelCase boundaries, keeping
+/// acronyms such as `HTTP` together.
+fn split_words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+
+    for chunk in name.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<char> = chunk.chars().collect();
+        let mut current = String::new();
+
+        for (i, &c) in chars.iter().enumerate() {
+            if !current.is_empty() && c.is_uppercase() {
+                let prev = chars[i - 1];
+                let next_is_lower = chars.get(i + 1).is_some_and(|n| n.is_lowercase());
+                // "fooBar" -> foo|Bar, "HTTPServer" -> HTTP|Server
+                if prev.is_lowercase()
+                    || prev.is_numeric()
+                    || (prev.is_uppercase() && next_is_lower)
+                {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            current.push(c);
+        }
+
+        if !current.is_empty() {
+            words.push(current);
+        }
+    }
+
+    words
+}
+
+fn title_case_word(word: &str) -> String {
+    // Leave acronyms and numbers alone
+    if word.chars().all(|c| !c.is_lowercase()) {
+        return word.to_string();
+    }
+
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,127 @@
+use super::{
+    ExtensionPolicy, NamingConvention, apply_extension_policy, apply_naming_convention,
+    safe_folder_name,
+};
+
+#[test]
+fn test_snake_case() {
+    let convert = |name| apply_naming_convention(name, NamingConvention::Snake);
+
+    assert_eq!(convert("Quarterly Sales Report"), "quarterly_sales_report");
+    assert_eq!(convert("quarterly-sales-report"), "quarterly_sales_report");
+    assert_eq!(convert("quarterlySalesReport"), "quarterly_sales_report");
+    assert_eq!(convert("HTTPServer config"), "http_server_config");
+}
+
+#[test]
+fn test_kebab_case() {
+    let convert = |name| apply_naming_convention(name, NamingConvention::Kebab);
+
+    assert_eq!(convert("Quarterly Sales Report"), "quarterly-sales-report");
+    assert_eq!(convert("quarterly_sales_report"), "quarterly-sales-report");
+    assert_eq!(convert("Tax Return 2023"), "tax-return-2023");
+}
+
+#[test]
+fn test_title_case() {
+    let convert = |name| apply_naming_convention(name, NamingConvention::Title);
+
+    assert_eq!(convert("quarterly_sales_report"), "Quarterly Sales Report");
+    assert_eq!(convert("quarterly-sales-report"), "Quarterly Sales Report");
+    assert_eq!(convert("NASA mission_notes"), "NASA Mission Notes");
+}
+
+#[test]
+fn test_none_keeps_name() {
+    assert_eq!(
+        apply_naming_convention("Some weird_Name", NamingConvention::None),
+        "Some weird_Name"
+    );
+}
+
+#[test]
+fn test_extension_is_preserved() {
+    assert_eq!(
+        apply_naming_convention("Annual Report.PDF", NamingConvention::Snake),
+        "annual_report.PDF"
+    );
+    assert_eq!(
+        apply_naming_convention("backup_archive.tar.gz", NamingConvention::Kebab),
+        "backup-archive-tar.gz"
+    );
+}
+
+#[test]
+fn test_already_converted_names_are_unchanged() {
+    assert_eq!(
+        apply_naming_convention("already_snake_case", NamingConvention::Snake),
+        "already_snake_case"
+    );
+    assert_eq!(
+        apply_naming_convention("already-kebab-case", NamingConvention::Kebab),
+        "already-kebab-case"
+    );
+    assert_eq!(
+        apply_naming_convention("Already Title Case", NamingConvention::Title),
+        "Already Title Case"
+    );
+}
+
+#[test]
+fn test_reserved_names_are_made_safe() {
+    assert_eq!(
+        safe_folder_name(".fs_organizer.db"),
+        "fs_organizer.db_folder"
+    );
+    assert_eq!(
+        safe_folder_name(".FS_ORGANIZER.DB-journal"),
+        "FS_ORGANIZER.DB-journal_folder"
+    );
+    assert_eq!(safe_folder_name("."), "folder");
+    assert_eq!(safe_folder_name("Documents"), "Documents");
+}
+
+#[test]
+fn test_extension_policy_ignores_version_numbers() {
+    assert_eq!(
+        apply_extension_policy("Release 1.2", Some("zip"), ExtensionPolicy::AllowChange),
+        "Release 1.2.zip"
+    );
+    assert_eq!(
+        apply_extension_policy("Release 1.2", Some("zip"), ExtensionPolicy::Strip),
+        "Release 1.2"
+    );
+    assert_eq!(
+        apply_extension_policy("build.mk", None, ExtensionPolicy::Preserve),
+        "build"
+    );
+}
+
+#[test]
+fn test_suggested_extension_is_not_doubled() {
+    let preserve = |name| apply_extension_policy(name, Some("pdf"), ExtensionPolicy::Preserve);
+    assert_eq!(preserve("report.pdf"), "report.pdf");
+    assert_eq!(preserve("report.PDF"), "report.pdf");
+    assert_eq!(preserve("report"), "report.pdf");
+    assert_eq!(preserve("report."), "report.pdf");
+    assert_eq!(
+        apply_extension_policy("report.pdf", Some("pdf"), ExtensionPolicy::AllowChange),
+        "report.pdf"
+    );
+}
+
+#[test]
+fn test_dots_in_suggested_names_are_kept() {
+    let preserve = |name| apply_extension_policy(name, Some("txt"), ExtensionPolicy::Preserve);
+    assert_eq!(preserve("Meeting Notes.v2"), "Meeting Notes.v2.txt");
+    assert_eq!(preserve("St. Mary's Invoice"), "St. Mary's Invoice.txt");
+    assert_eq!(preserve("Release 1.2"), "Release 1.2.txt");
+}
+
+#[test]
+fn test_source_without_extension_gets_none() {
+    let preserve = |name| apply_extension_policy(name, None, ExtensionPolicy::Preserve);
+    assert_eq!(preserve("Build Rules"), "Build Rules");
+    assert_eq!(preserve("Build Rules.v2"), "Build Rules.v2");
+    assert_eq!(preserve("Build Rules."), "Build Rules");
+}
@@ -0,0 +1,85 @@
+use colored::*;
+use notify_rust::Notification;
+use std::path::Path;
+use std::process::Command;
+
+/// How a run ended, passed to the desktop notification and the completion hook.
+#[derive(Debug, Clone)]
+pub struct RunOutcome<'a> {
+    pub success: bool,
+    pub directory: &'a Path,
+    pub summary: String,
+}
+
+impl RunOutcome<'_> {
+    fn status(&self) -> &'static str {
+        if self.success { "success" } else { "failure" }
+    }
+}
+
+/// Shows a desktop notification through the platform's notification service
+/// (D-Bus on Linux and BSD, Notification Center on macOS, toasts on Windows).
+/// Failures are reported but never fail the run.
+pub fn notify_desktop(outcome: &RunOutcome) {
+    let title = if outcome.success {
+        "Shelfie finished"
+    } else {
+        "Shelfie failed"
+    };
+    let body = format!("{}: {}", outcome.directory.display(), outcome.summary);
+
+    if let Err(e) = Notification::new()
+        .appname("shelfie")
+        .summary(title)
+        .body(&body)
+        .show()
+    {
+        eprintln!(
+            "{}: could not show a desktop notification: {}",
+            "Warning".yellow().bold(),
+            e
+        );
+    }
+}
+
+/// Runs the user's `--on-complete` command through the shell. The outcome is passed
+/// both as arguments (`$1` status, `$2` directory, `$3` summary) and as
+/// `SHELFIE_STATUS`, `SHELFIE_DIRECTORY` and `SHELFIE_SUMMARY`. A failing hook is
+/// reported but never fails the run.
+pub fn run_hook(command: &str, outcome: &RunOutcome) {
+    let mut shell = if cfg!(windows) {
+        let mut shell = Command::new("cmd");
+        shell.arg("/C").arg(command);
+        shell
+    } else {
+        let mut shell = Command::new("sh");
+        shell.arg("-c").arg(command).arg("shelfie");
+        shell
+    };
+
+    let result = shell
+        .arg(outcome.status())
+        .arg(outcome.directory)
+        .arg(&outcome.summary)
+        .env("SHELFIE_STATUS", outcome.status())
+        .env("SHELFIE_DIRECTORY", outcome.directory)
+        .env("SHELFIE_SUMMARY", &outcome.summary)
+        .status();
+
+    match result {
+        Ok(status) if status.success() => {}
+        Ok(status) => eprintln!(
+            "{}: completion hook exited with {}",
+            "Warning".yellow().bold(),
+            status
+        ),
+        Err(e) => eprintln!(
+            "{}: could not run completion hook: {}",
+            "Warning".yellow().bold(),
+            e
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,41 @@
+use super::{RunOutcome, run_hook};
+use std::fs;
+use tempfile::TempDir;
+
+#[cfg(unix)]
+#[test]
+fn test_hook_receives_outcome() {
+    let temp_dir = TempDir::new().unwrap();
+    let output = temp_dir.path().join("hook.txt");
+    let outcome = RunOutcome {
+        success: false,
+        directory: temp_dir.path(),
+        summary: "No API key".to_string(),
+    };
+
+    run_hook(
+        &format!(
+            "echo \"$1|$3|$SHELFIE_STATUS|$SHELFIE_SUMMARY\" > {}",
+            output.display()
+        ),
+        &outcome,
+    );
+
+    assert_eq!(
+        fs::read_to_string(output).unwrap().trim(),
+        "failure|No API key|failure|No API key"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_failing_hook_does_not_panic() {
+    let temp_dir = TempDir::new().unwrap();
+    let outcome = RunOutcome {
+        success: true,
+        directory: temp_dir.path(),
+        summary: String::new(),
+    };
+
+    run_hook("exit 3", &outcome);
+}
@@ -0,0 +1,168 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    database::{Database, Item},
+    models::{FileMovement, OrganizationPlan},
+    naming::{ExtensionPolicy, apply_extension_policy},
+    organizer::FileOrganizer,
+    prompts,
+};
+
+/// Items matching `query`: the item with that id, or else those with a name equal to
+/// `query`, ignoring case. Only when no name is equal, names containing it match.
+pub fn find_items(
+    items: Vec<Item>,
+    query: &str,
+    last_plan: Option<&OrganizationPlan>,
+) -> Vec<Item> {
+    let query = query.trim();
+    if let Ok(id) = query.parse::<i64>()
+        && let Some(item) = items.iter().find(|item| item.id == Some(id))
+    {
+        return vec![item.clone()];
+    }
+
+    let query = query.to_lowercase();
+    let names = items
+        .iter()
+        .map(|item| item_names(item, last_plan))
+        .collect::<Vec<_>>();
+    let matching = |matches: &dyn Fn(&str) -> bool| {
+        items
+            .iter()
+            .zip(&names)
+            .filter(|(_, names)| names.iter().any(|name| matches(name)))
+            .map(|(item, _)| item.clone())
+            .collect::<Vec<_>>()
+    };
+
+    let exact = matching(&|name| name == query);
+    if exact.is_empty() {
+        matching(&|name| name.contains(&query))
+    } else {
+        exact
+    }
+}
+
+/// The lowercased names an item goes by: its original and suggested names, and the
+/// name it was given by the last executed plan.
+fn item_names(item: &Item, last_plan: Option<&OrganizationPlan>) -> Vec<String> {
+    let source_extension = Path::new(&item.original_name)
+        .extension()
+        .and_then(|ext| ext.to_str());
+    let planned_name = movement_for(item, last_plan)
+        .map(|movement| FileOrganizer::target_name(movement, ExtensionPolicy::default()));
+
+    std::iter::once(item.original_name.clone())
+        .chain(
+            item.suggested_name.iter().map(|name| {
+                apply_extension_policy(name, source_extension, ExtensionPolicy::default())
+            }),
+        )
+        .chain(item.suggested_name.clone())
+        .chain(planned_name)
+        .map(|name| name.to_lowercase())
+        .collect()
+}
+
+fn movement_for<'a>(
+    item: &Item,
+    last_plan: Option<&'a OrganizationPlan>,
+) -> Option<&'a FileMovement> {
+    let from = Path::new(&item.path);
+    last_plan?
+        .movements
+        .iter()
+        .find(|movement| movement.from == from)
+}
+
+/// Picks the item to open among `matches`, asking which one when there are several.
+pub fn choose_item(database: &Database, mut matches: Vec<Item>, query: &str) -> Result<Item> {
+    match matches.len() {
+        0 => anyhow::bail!("No item matches '{}'", query),
+        1 => return Ok(matches.remove(0)),
+        _ => {}
+    }
+
+    let cabinets = database
+        .list_cabinets()?
+        .into_iter()
+        .map(|cabinet| (cabinet.id, cabinet.name))
+        .collect::<HashMap<_, _>>();
+    let shelves = database
+        .list_shelves(None)?
+        .into_iter()
+        .map(|shelf| (shelf.id, shelf))
+        .collect::<HashMap<_, _>>();
+    let labels = matches
+        .iter()
+        .map(|item| {
+            let location = shelves
+                .get(&item.shelf_id)
+                .map_or_else(String::new, |shelf| {
+                    let cabinet = cabinets.get(&shelf.cabinet_id).map_or("?", String::as_str);
+                    format!("{}/{}", cabinet, shelf.name)
+                });
+            format!(
+                "#{} {} ({})",
+                item.id.unwrap_or_default(),
+                item.suggested_name.as_ref().unwrap_or(&item.original_name),
+                location
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let selection = prompts::select(&format!("Several items match '{}'", query), &labels)?;
+    Ok(matches.remove(selection))
+}
+
+/// Where `item` is on disk now: at its destination in the last executed plan, or
+/// still where it was found if it wasn't moved. `None` if it's in neither place, e.g.
+/// because it was moved by hand since.
+pub fn current_path(
+    base_path: &Path,
+    item: &Item,
+    last_plan: Option<&OrganizationPlan>,
+) -> Option<PathBuf> {
+    // The plan doesn't record the extension policy it was executed with
+    let destinations = movement_for(item, last_plan)
+        .into_iter()
+        .flat_map(|movement| {
+            ExtensionPolicy::value_variants()
+                .iter()
+                .filter_map(|policy| {
+                    let name = FileOrganizer::target_name(movement, *policy);
+                    let mut parts =
+                        FileOrganizer::destination_parts(&movement.to_cabinet, &movement.to_shelf);
+                    parts.push(&name);
+                    parts
+                        .iter()
+                        .try_fold(base_path.to_path_buf(), |path, part| {
+                            FileOrganizer::join_contained(&path, part)
+                        })
+                })
+        });
+
+    destinations
+        .chain(std::iter::once(PathBuf::from(&item.path)))
+        .find(|path| path.exists())
+}
+
+/// Opens `path` with its default application or, with `reveal`, shows it in the file
+/// manager.
+pub fn open_path(path: &Path, reveal: bool) -> Result<()> {
+    let result = if reveal {
+        opener::reveal(path)
+    } else {
+        opener::open(path)
+    };
+    result.with_context(|| format!("Failed to open {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,109 @@
+use super::*;
+use crate::models::{CabinetPlan, ShelfPlan};
+use std::fs;
+use tempfile::TempDir;
+
+fn item(id: i64, path: &Path, suggested_name: Option<&str>) -> Item {
+    Item {
+        id: Some(id),
+        shelf_id: 1,
+        path: path.to_string_lossy().to_string(),
+        original_name: path.file_name().unwrap().to_string_lossy().to_string(),
+        suggested_name: suggested_name.map(str::to_string),
+        description: "A file".to_string(),
+        file_type: "text/plain".to_string(),
+        is_opaque_dir: false,
+        opaque_reason: None,
+        rename_confidence: None,
+        content_hash: None,
+        processed_at: chrono::Utc::now(),
+    }
+}
+
+fn plan_moving(item: &Item, new_name: Option<&str>) -> OrganizationPlan {
+    OrganizationPlan {
+        cabinets: vec![CabinetPlan {
+            id: 1,
+            name: "Finance".to_string(),
+            description: "Money".to_string(),
+            shelves: vec![ShelfPlan {
+                id: 1,
+                name: "Invoices".to_string(),
+                description: "Bills".to_string(),
+                item_count: 1,
+            }],
+        }],
+        movements: vec![FileMovement {
+            from: PathBuf::from(&item.path),
+            to_cabinet: "Finance".to_string(),
+            to_shelf: "Invoices".to_string(),
+            new_name: new_name.map(str::to_string),
+            reasoning: "An invoice".to_string(),
+            opaque_reason: None,
+        }],
+    }
+}
+
+#[test]
+fn test_items_are_found_by_id_or_name() {
+    let items = vec![
+        item(
+            1,
+            Path::new("/base/scan_001.pdf"),
+            Some("ACME Invoice 2024"),
+        ),
+        item(2, Path::new("/base/invoice.txt"), None),
+        item(3, Path::new("/base/invoice-old.txt"), None),
+    ];
+    let ids = |query: &str| {
+        find_items(items.clone(), query, None)
+            .iter()
+            .map(|item| item.id.unwrap())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(ids("2"), [2]);
+    // The suggested name counts with and without the original extension
+    assert_eq!(ids("acme invoice 2024.PDF"), [1]);
+    assert_eq!(ids("SCAN_001.pdf"), [1]);
+    // An exact name wins over names that merely contain it
+    assert_eq!(ids("invoice.txt"), [2]);
+    assert_eq!(ids("invoice"), [1, 2, 3]);
+    assert!(ids("receipt").is_empty());
+}
+
+#[test]
+fn test_current_path_follows_the_last_executed_plan() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path();
+    let item = item(1, &base_path.join("scan.pdf"), Some("Invoice"));
+
+    // Not organized yet: still where it was found
+    fs::write(base_path.join("scan.pdf"), "invoice").unwrap();
+    assert_eq!(
+        current_path(base_path, &item, None),
+        Some(base_path.join("scan.pdf"))
+    );
+
+    // Moved and renamed by the last run, whatever its extension policy was
+    let plan = plan_moving(&item, Some("Invoice"));
+    let shelf_dir = base_path.join("Finance/Invoices");
+    fs::create_dir_all(&shelf_dir).unwrap();
+    fs::rename(base_path.join("scan.pdf"), shelf_dir.join("Invoice")).unwrap();
+    assert_eq!(
+        current_path(base_path, &item, Some(&plan)),
+        Some(shelf_dir.join("Invoice"))
+    );
+
+    fs::remove_file(shelf_dir.join("Invoice")).unwrap();
+    assert_eq!(current_path(base_path, &item, Some(&plan)), None);
+}
+
+#[test]
+fn test_no_match_is_an_error() {
+    let temp_dir = TempDir::new().unwrap();
+    let database = Database::open_or_create(temp_dir.path()).unwrap();
+
+    let error = choose_item(&database, Vec::new(), "receipt").unwrap_err();
+    assert_eq!(error.to_string(), "No item matches 'receipt'");
+}
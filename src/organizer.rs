@@ -1,135 +1,1108 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use colored::*;
-use dialoguer::{Confirm, theme::ColorfulTheme};
 
-use indicatif::{ProgressBar, ProgressStyle};
-use std::{path::PathBuf, sync::Arc, time::Duration};
-use tokio::{sync::Semaphore, task::JoinSet};
+use indicatif::ProgressBar;
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, UNIX_EPOCH},
+};
+use tokio::{sync::Semaphore, task::JoinSet, time::timeout};
 
 use walkdir::WalkDir;
 
 use crate::{
-    batch_processor::BatchProcessor,
-    database::{DB_NAME, Database},
-    file_analyzer::{AnalyzedFile, FileContent},
+    batch_processor::{BatchOptions, BatchProcessor, is_bundle_name},
+    change_set::print_dry_run_notice,
+    collation::collation,
+    database::{Database, is_reserved_name, is_unsorted_name},
+    exclude::{ExcludePattern, IgnoreRules},
+    file_analyzer::{AnalyzedFile, ContentCache, FileContent, capture_settings},
+    file_metadata::write_description,
+    manifest::{ManifestItem, ShelfManifest},
     models::{
         CabinetPlan, EnrichedDirectory, EnrichedFile, FileMovement, OrganizationPlan,
         ProcessingItem, SampledItem, ShelfPlan,
     },
-    plan_refiner::PlanRefiner,
-    providers::LLMProvider,
+    naming::{
+        ExtensionPolicy, NamingConvention, apply_extension_policy, apply_naming_convention,
+        safe_folder_name,
+    },
+    plan_diff::{PlanDiff, diff_plans},
+    plan_refiner::{DEFAULT_REFINE_MAX_TURNS, DEFAULT_REFINE_TIMEOUT_SECS, PlanRefiner},
+    pricing::{FALLBACK_PRICE, model_price},
+    progress, prompts,
+    providers::{LLMProvider, TokenUsage},
+    report::{ReportFormat, write_report},
+    skeleton::{propose_skeleton, sample_items},
+    template::OrganizationTemplate,
+    timings::{RunTimings, TimingsFormat},
+    trash::Trash,
+    utils::{
+        copy_recursive, differ_only_in_case, is_case_insensitive, relative_display,
+        retry_transient, same_device,
+    },
 };
 
+/// Cabinet that absorbs the smallest cabinets when a plan exceeds the cabinet limit.
+const OVERFLOW_CABINET: &str = "Overflow";
+/// Files and directories scanned at once unless configured otherwise.
+pub const DEFAULT_SCAN_CONCURRENCY: usize = 10;
+/// Files previewed per directory with `--deep-dir-preview` unless configured otherwise.
+pub const DEFAULT_DIR_PREVIEW_FILES: usize = 3;
+/// Most files previewed per directory, however it's configured.
+pub const MAX_DIR_PREVIEW_FILES: usize = 10;
+/// Characters kept from each file previewed for a directory.
+const DIR_PREVIEW_CHARS_PER_FILE: usize = 300;
+/// Files previewed for a directory that take longer are left out.
+const DIR_PREVIEW_FILE_TIMEOUT: Duration = Duration::from_secs(3);
+/// Shelf that absorbs the smallest shelves when a cabinet exceeds the shelf limit.
+const OTHER_SHELF: &str = "Other";
+/// Past corrections summarized into the prompt with `--use-history`.
+const HISTORY_LIMIT: usize = 10;
+/// Example moves shown per correction.
+const HISTORY_EXAMPLE_MOVES: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct OrganizeOptions {
+    pub max_depth: usize,
+    pub auto_confirm: bool,
+    pub reorganize: bool,
+    pub resume: bool,
+    pub since_last_run: bool,
+    pub template: Option<PathBuf>,
+    pub naming_convention: NamingConvention,
+    pub extension_policy: ExtensionPolicy,
+    pub normalize_folder_names: bool,
+    pub flat: Option<FlatLayout>,
+    pub max_tokens: Option<u64>,
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    /// Directory to store the database in instead of the organized directory.
+    pub db_dir: Option<PathBuf>,
+    /// Treat git repositories as single opaque items.
+    pub git_aware: bool,
+    /// Print the whole plan instead of the changes since the last executed one.
+    pub show_full_plan: bool,
+    /// Keep originals in `.shelfie/trash/` instead of deleting them after a copy.
+    pub trash: bool,
+    /// Approve the new directory structure before the movements are shown.
+    pub confirm_structure: bool,
+    /// Process dotfiles and dot-directories too; `.git` and shelfie's state are still skipped.
+    pub include_hidden: bool,
+    /// Entries matching any of these are skipped, directories with everything in them,
+    /// in addition to those in `.shelfieignore`.
+    pub exclude: Vec<ExcludePattern>,
+    /// Reuse content previews cached for files that haven't changed.
+    pub content_cache: bool,
+    /// Seed top-level cabinets proposed from a sample of this many items before the
+    /// batches are analyzed (`--plan-skeleton`).
+    pub plan_skeleton: Option<usize>,
+    /// Characterize directories by the content of up to this many of their files, not
+    /// just their names (`--deep-dir-preview`).
+    pub deep_dir_preview: Option<usize>,
+    /// Files larger than this many bytes are classified by name and type without
+    /// reading their content (`--max-file-size`).
+    pub max_file_size: Option<u64>,
+    /// Report how long each phase took at the end of the run.
+    pub timings: Option<TimingsFormat>,
+    /// Files and directories scanned at once. Scanning mostly waits on I/O; content
+    /// extraction is capped separately (`file_analyzer::set_extraction_parallelism`).
+    pub scan_concurrency: usize,
+    /// Merge the smallest cabinets into Overflow until at most this many remain,
+    /// without asking the model. Tightens `batch.max_cabinets`.
+    pub limit_cabinets: Option<usize>,
+    /// Merge the smallest shelves of a cabinet into Other until at most this many remain.
+    pub limit_shelves: Option<usize>,
+    /// Keep every item's original name, ignoring suggested renames.
+    pub no_rename: bool,
+    /// Keep original names where the model's confidence in the suggested one is lower.
+    pub min_rename_confidence: Option<f64>,
+    /// What to do when a destination already exists.
+    pub on_conflict: ConflictPolicy,
+    /// Move only the first of the files with the same content, handling the rest
+    /// like this (`--dedupe`).
+    pub dedupe: Option<DuplicateAction>,
+    /// Whether names differing only in case are the same on the organized filesystem.
+    pub filesystem_case: FilesystemCase,
+    /// Write a `.shelf-manifest.json` describing its items into every shelf directory.
+    pub write_manifests: bool,
+    /// Write each item's description into its own metadata, for the formats that
+    /// can take it safely.
+    pub write_descriptions: bool,
+    /// Record accepted refinements and feed them into later runs as preferences.
+    pub use_history: bool,
+    /// Most responses the refinement agent may take per piece of feedback.
+    pub refine_max_turns: usize,
+    /// How long the refinement agent may work on a piece of feedback before its
+    /// changes are undone.
+    pub refine_timeout: Duration,
+    /// Print what executing the plan would do instead of doing it. The analysis is
+    /// still saved to the database.
+    pub dry_run: bool,
+    /// Write the approved plan to this file in this format before executing it.
+    pub report: Option<(PathBuf, ReportFormat)>,
+    /// Each top-level directory is a cabinet its contents stay in; the model only
+    /// picks shelves for them.
+    pub cabinets_from_dirs: bool,
+    pub batch: BatchOptions,
+}
+
+/// How cabinet and shelf are collapsed into a single directory level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum FlatLayout {
+    /// `Cabinet - Shelf`
+    Combined,
+    /// Just the shelf name
+    Shelf,
+}
+
+/// What to do when a movement's destination already exists.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ConflictPolicy {
+    /// Leave the source where it is and report it
+    Skip,
+    /// Keep both, numbering the moved item, e.g. `report (2).pdf`
+    #[default]
+    Rename,
+    /// Replace the destination (kept in the trash with --trash)
+    Overwrite,
+    /// Ask for each conflict; skipped when prompts are disabled
+    Ask,
+}
+
+/// What to do with a file whose content is the same as one moved before it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DuplicateAction {
+    /// Leave it where it is and report it
+    Report,
+    /// Move it into the trash
+    Trash,
+    /// Replace it with a hard link to the moved file
+    Hardlink,
+}
+
+/// Whether names differing only in case, like `Docs` and `docs`, are the same on the
+/// organized filesystem.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum FilesystemCase {
+    /// Find out by probing the organized directory
+    #[default]
+    Auto,
+    /// They are different files
+    Sensitive,
+    /// They are the same file, as by default on macOS and Windows
+    Insensitive,
+}
+
+impl FilesystemCase {
+    fn is_insensitive(self, dir: &Path) -> bool {
+        match self {
+            Self::Auto => is_case_insensitive(dir),
+            Self::Sensitive => false,
+            Self::Insensitive => true,
+        }
+    }
+}
+
+/// What happened to the movements of an executed plan.
+#[derive(Debug, Default)]
+struct ExecutionReport {
+    moved: usize,
+    /// Sources that no longer existed at execution time
+    missing: Vec<String>,
+    /// Operations whose destination would have left the base path
+    refused: Vec<String>,
+    /// Sources left in place because their destination already existed
+    conflicts: Vec<String>,
+    /// Sources not moved because their content is the same as a moved one's
+    duplicates: Vec<String>,
+    /// Moves done by copying because the destination is on another device
+    crossed_devices: usize,
+    /// Originals and replaced destinations kept in the trash
+    trashed: usize,
+}
+
+impl ExecutionReport {
+    fn print_summary(&self, total_movements: usize, base_path: &Path) {
+        if !self.refused.is_empty() {
+            eprintln!(
+                "{}: refused {} operations whose destination would leave {}:",
+                "Warning".yellow().bold(),
+                self.refused.len(),
+                base_path.display()
+            );
+            for item in &self.refused {
+                eprintln!("  - {}", item);
+            }
+        }
+
+        let skipped = total_movements - self.moved;
+        println!(
+            "Processed {}, moved {}, skipped {} ({} missing source, {} conflicts, {} duplicates, {} refused)",
+            total_movements,
+            self.moved,
+            skipped,
+            self.missing.len(),
+            self.conflicts.len(),
+            self.duplicates.len(),
+            skipped - self.missing.len() - self.conflicts.len() - self.duplicates.len()
+        );
+        if !self.conflicts.is_empty() {
+            eprintln!(
+                "{}: {} items were left in place because their destination already exists:",
+                "Warning".yellow().bold(),
+                self.conflicts.len()
+            );
+            for item in &self.conflicts {
+                eprintln!("  - {}", item);
+            }
+        }
+        if !self.duplicates.is_empty() {
+            println!(
+                "♊ {} items have the same content as a moved one:",
+                self.duplicates.len()
+            );
+            for item in &self.duplicates {
+                println!("  - {}", item);
+            }
+        }
+        if self.trashed > 0 {
+            println!(
+                "🗑️  Kept {} originals in {} - run 'shelfie empty-trash {}' to remove them",
+                self.trashed,
+                relative_display(&Trash::dir(base_path), base_path),
+                base_path.display()
+            );
+        }
+        if self.crossed_devices > 0 {
+            println!(
+                "🔀 {} items were on another device and were copied, then removed from the source",
+                self.crossed_devices
+            );
+        }
+        if !self.missing.is_empty() {
+            eprintln!(
+                "{}: {} items no longer existed when their move came up:",
+                "Warning".yellow().bold(),
+                self.missing.len()
+            );
+            for item in &self.missing {
+                eprintln!("  - {}", item);
+            }
+        }
+    }
+}
+
+/// Result of scanning the target directory for items to analyze.
+struct ScanResult {
+    items: Vec<ProcessingItem>,
+    already_processed: usize,
+    organized_dirs: usize,
+    /// Hidden entries and shelfie's own state
+    hidden: usize,
+    /// Entries matching `.shelfieignore` or `--exclude`, counting a directory once
+    excluded: usize,
+    /// FIFOs, sockets and devices, which can't be read like files
+    special: usize,
+    /// Content cache hits and extractions, when the cache was used
+    content_cache: Option<(usize, usize)>,
+    /// Items taken from the checkpoints of an interrupted run instead of scanned again
+    resumed: usize,
+}
+
+impl OrganizeOptions {
+    /// On-disk name for a cabinet or shelf directory.
+    pub fn folder_name(&self, name: &str) -> String {
+        if self.normalize_folder_names {
+            safe_folder_name(&apply_naming_convention(name, self.naming_convention))
+        } else {
+            safe_folder_name(name)
+        }
+    }
+
+    /// Most cabinets the plan may have: the batch limit, or `limit_cabinets` if lower.
+    fn cabinet_limit(&self) -> usize {
+        self.limit_cabinets
+            .map_or(self.batch.cabinet_limit(), |limit| {
+                limit.min(self.batch.cabinet_limit())
+            })
+    }
+
+    /// Most shelves a cabinet may have: the batch limit, or `limit_shelves` if lower.
+    fn shelf_limit(&self) -> usize {
+        self.limit_shelves
+            .map_or(self.batch.shelf_limit(), |limit| {
+                limit.min(self.batch.shelf_limit())
+            })
+    }
+
+    /// On-disk name of the top-level directory a shelf's items end up in when
+    /// `--flat` is set. Cabinet and shelf are expected to be folder names already.
+    fn flat_folder_name(layout: FlatLayout, cabinet: &str, shelf: &str) -> String {
+        match layout {
+            FlatLayout::Combined => safe_folder_name(&format!("{} - {}", cabinet, shelf)),
+            FlatLayout::Shelf => shelf.to_string(),
+        }
+    }
+}
+
+impl Default for OrganizeOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: 1,
+            auto_confirm: false,
+            reorganize: false,
+            resume: false,
+            since_last_run: false,
+            template: None,
+            naming_convention: NamingConvention::None,
+            extension_policy: ExtensionPolicy::Preserve,
+            normalize_folder_names: false,
+            flat: None,
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            db_dir: None,
+            git_aware: false,
+            show_full_plan: false,
+            trash: false,
+            confirm_structure: false,
+            include_hidden: false,
+            exclude: Vec::new(),
+            content_cache: true,
+            plan_skeleton: None,
+            deep_dir_preview: None,
+            max_file_size: None,
+            timings: None,
+            scan_concurrency: DEFAULT_SCAN_CONCURRENCY,
+            limit_cabinets: None,
+            limit_shelves: None,
+            no_rename: false,
+            min_rename_confidence: None,
+            on_conflict: ConflictPolicy::default(),
+            dedupe: None,
+            filesystem_case: FilesystemCase::default(),
+            write_manifests: false,
+            write_descriptions: false,
+            use_history: false,
+            refine_max_turns: DEFAULT_REFINE_MAX_TURNS,
+            refine_timeout: Duration::from_secs(DEFAULT_REFINE_TIMEOUT_SECS),
+            dry_run: false,
+            report: None,
+            cabinets_from_dirs: false,
+            batch: BatchOptions::default(),
+        }
+    }
+}
+
 pub struct FileOrganizer {
-    provider: LLMProvider,
+    /// `None` when only executing a plan from the database, which needs no model
+    provider: Option<LLMProvider>,
     base_path: PathBuf,
+    db_path: PathBuf,
     database: Arc<Database>,
+    timings: Mutex<RunTimings>,
 }
 
 impl FileOrganizer {
-    pub fn new(provider: LLMProvider, base_path: PathBuf) -> Result<Self> {
-        let database = Arc::new(Database::open_or_create(&base_path)?);
+    /// Opens the organizer for `base_path`, keeping its database in `db_dir` when
+    /// given instead of inside the organized directory.
+    pub fn new(provider: LLMProvider, base_path: PathBuf, db_dir: Option<&Path>) -> Result<Self> {
+        let base_path = Self::canonical_base(&base_path)?;
+        let db_path = Database::db_path(&base_path, db_dir);
+        let database = Arc::new(Database::open_at(&db_path)?);
         Ok(Self {
-            provider,
+            provider: Some(provider),
             base_path,
+            db_path,
             database,
+            timings: Mutex::default(),
         })
     }
 
-    pub async fn analyze_and_organize(&self, max_depth: usize, auto_confirm: bool) -> Result<()> {
-        // Check if database exists for resuming
-        if Database::exists(&self.base_path) {
-            println!(
+    /// Opens the database of a previously analyzed directory without an AI provider,
+    /// for executing the plan it holds.
+    pub fn open_existing(base_path: PathBuf, db_dir: Option<&Path>) -> Result<Self> {
+        let base_path = Self::canonical_base(&base_path)?;
+        let db_path = Database::db_path(&base_path, db_dir);
+        if !db_path.exists() {
+            anyhow::bail!("No organization database found for {}", base_path.display());
+        }
+        let database = Arc::new(Database::open_at(&db_path)?);
+        Ok(Self {
+            provider: None,
+            base_path,
+            db_path,
+            database,
+            timings: Mutex::default(),
+        })
+    }
+
+    /// Resolves symlinks and `..` once, so the database location, the paths the scan
+    /// compares against stored ones and the containment checks all agree.
+    fn canonical_base(base_path: &Path) -> Result<PathBuf> {
+        base_path
+            .canonicalize()
+            .with_context(|| format!("Directory does not exist: {}", base_path.display()))
+    }
+
+    /// Whether the database has any cabinets to sort items into.
+    pub fn has_structure(&self) -> Result<bool> {
+        Ok(!self.database.list_cabinets()?.is_empty())
+    }
+
+    /// Phase timings of the run so far.
+    pub fn timings(&self) -> RunTimings {
+        self.timings.lock().map(|t| t.clone()).unwrap_or_default()
+    }
+
+    fn record_phase(&self, phase: &str, started: Instant, items: usize) {
+        if let Ok(mut timings) = self.timings.lock() {
+            timings.record_phase(phase, started, items);
+        }
+    }
+
+    fn provider(&self) -> Result<&LLMProvider> {
+        self.provider
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("No AI provider configured"))
+    }
+
+    pub async fn analyze_and_organize(&self, options: &OrganizeOptions) -> Result<()> {
+        if options.resume {
+            return self.execute_from_database(options).await;
+        }
+
+        let run_started = Utc::now();
+        if !self.analyze(options).await? {
+            return Ok(());
+        }
+
+        if self.review_and_execute(options).await? {
+            self.database.record_completed_run(run_started)?;
+        }
+
+        Ok(())
+    }
+
+    /// Scans, analyzes and returns the organization plan without prompting or
+    /// moving anything. Progress is reported on stderr so the plan can be piped.
+    pub async fn plan(&self, options: &OrganizeOptions) -> Result<OrganizationPlan> {
+        if options.resume {
+            self.ensure_previous_analysis()?;
+        } else {
+            self.analyze(options).await?;
+        }
+
+        let planning_started = Instant::now();
+        let plan = Self::transform_plan(
+            self.create_organization_plan(options.min_rename_confidence)?,
+            options,
+        )?;
+        self.record_phase("planning", planning_started, plan.movements.len());
+        Ok(plan)
+    }
+
+    /// Steps 1 and 2: scans for new items and analyzes them into the database.
+    /// Returns `false` when there was nothing new to analyze.
+    async fn analyze(&self, options: &OrganizeOptions) -> Result<bool> {
+        if options.reorganize {
+            eprintln!("♻️  Discarding previous analysis - all items will be re-analyzed");
+            self.database.clear_organization()?;
+        } else if !self.database.get_processed_paths()?.is_empty() {
+            eprintln!(
                 "📁 Found existing organization database - processing new/modified items only"
             );
         }
 
+        if let Some(template_path) = &options.template {
+            let template = OrganizationTemplate::load(template_path)?;
+            let (cabinets, shelves) = template.seed(&self.database)?;
+            eprintln!(
+                "📐 Seeded {} cabinets and {} shelves from template {}",
+                cabinets,
+                shelves,
+                template_path.display()
+            );
+        }
+        let dir_cabinets = if options.cabinets_from_dirs {
+            self.seed_dir_cabinets(options)?
+        } else {
+            HashMap::new()
+        };
+
         // Step 1: Scan directory and collect items
-        println!("\n{}", "Step 1: Scanning directory...".green().bold());
-        let items = self.collect_items(max_depth).await?;
+        eprintln!("\n{}", "Step 1: Scanning directory...".green().bold());
+        let scan_started = Instant::now();
+        let scan = self.collect_items(options).await?;
+        let items = scan.items;
+        self.record_phase("scan", scan_started, items.len());
 
         if items.is_empty() {
-            println!("✓ All items already processed or no new items found");
-            return Ok(());
+            if scan.already_processed == 0 && scan.organized_dirs == 0 {
+                eprintln!("✓ No files or directories found to organize");
+            } else {
+                eprintln!(
+                    "✓ Nothing new since the last run ({} items already processed, {} organized directories)",
+                    scan.already_processed, scan.organized_dirs
+                );
+                eprintln!(
+                    "{}",
+                    "💡 Tip: run again with --reorganize to re-analyze everything from scratch"
+                        .yellow()
+                );
+            }
+            return Ok(false);
         }
 
-        println!("✓ Found {} items to process", items.len());
+        eprintln!("✓ Found {} items to process", items.len());
+        if scan.already_processed + scan.organized_dirs + scan.hidden > 0 {
+            eprintln!(
+                "  Skipped {} already processed, {} organized directories, {} hidden or internal",
+                scan.already_processed, scan.organized_dirs, scan.hidden
+            );
+        }
+        if scan.special > 0 {
+            eprintln!(
+                "  Skipped {} special files (FIFOs, sockets or devices)",
+                scan.special
+            );
+        }
+        if scan.excluded > 0 {
+            eprintln!(
+                "  Skipped {} excluded by .shelfieignore or --exclude",
+                scan.excluded
+            );
+        }
+        if scan.resumed > 0 {
+            eprintln!(
+                "  Resumed {} items scanned by an interrupted run",
+                scan.resumed
+            );
+        }
+        if let Some((hits, extracted)) = scan.content_cache
+            && hits + extracted > 0
+        {
+            eprintln!(
+                "  Content cache: {} previews reused, {} extracted",
+                hits, extracted
+            );
+        }
 
         // Step 2: Process with AI in batches
-        println!("\n{}", "Step 2: Analyzing with AI...".green().bold());
-        let batch_processor = BatchProcessor::new(self.provider.clone(), self.base_path.clone());
+        eprintln!("\n{}", "Step 2: Analyzing with AI...".green().bold());
+        if options.batch.existing_only && self.database.list_cabinets()?.is_empty() {
+            anyhow::bail!(
+                "--sort-into-existing-only needs an existing structure to sort into; \
+                run once without it or seed one with --template"
+            );
+        }
+
+        let mut batch_options = options.batch.clone();
+        batch_options.prefer_existing |= options.template.is_some();
+        batch_options.git_aware = options.git_aware;
+        batch_options.allow_extension_change =
+            options.extension_policy == ExtensionPolicy::AllowChange;
+        if options.use_history {
+            batch_options.preferences = self.correction_preferences()?;
+        }
+        batch_options.fixed_cabinets = self.fixed_cabinets(&items, &dir_cabinets);
+        let batch_processor = |batch_options| -> Result<BatchProcessor> {
+            Ok(
+                BatchProcessor::new(self.provider()?.clone(), self.base_path.clone())
+                    .with_database_path(self.db_path.clone())
+                    .with_options(batch_options),
+            )
+        };
+
+        let estimate = batch_processor(batch_options.clone())?.estimate_usage(&items)?;
+        if !self.confirm_cost(&estimate)? {
+            eprintln!("{}", "Organization cancelled.".yellow());
+            return Ok(false);
+        }
+
+        let analysis_started = Instant::now();
+        let item_count = items.len();
+        let skeleton = match options.plan_skeleton {
+            Some(sample_size) => self.seed_skeleton(&items, sample_size, options).await?,
+            None => Vec::new(),
+        };
+        batch_options.prefer_existing |= !skeleton.is_empty();
+        let batch_processor = batch_processor(batch_options)?;
+
+        let batch_timings = batch_processor.process_items_sequentially(items).await?;
+        self.prune_skeleton(&skeleton)?;
+        self.record_phase("analysis", analysis_started, item_count);
+        if let Ok(mut timings) = self.timings.lock() {
+            timings.batches.extend(batch_timings);
+        }
+
+        Ok(true)
+    }
 
-        batch_processor.process_items_sequentially(items).await?;
+    /// Shows what analyzing is estimated to cost and, for a paid model, asks whether to
+    /// go ahead. Without prompts the estimate is only shown.
+    fn confirm_cost(&self, estimate: &TokenUsage) -> Result<bool> {
+        let provider = self.provider()?;
+        let model = provider.get_model_name();
+        let price = model_price(provider.get_provider(), model);
+        eprintln!(
+            "💰 Estimated usage: ~{} tokens in {} requests ({} prompt, {} response)",
+            estimate.total(),
+            estimate.requests,
+            estimate.prompt_tokens,
+            estimate.completion_tokens
+        );
+        match price {
+            Some(price) if price.is_free() => return Ok(true),
+            Some(price) => eprintln!(
+                "   Roughly ${:.2} at {}'s list price",
+                price.cost(estimate),
+                model
+            ),
+            None => eprintln!(
+                "   Roughly ${:.2}, assuming ${:.2} per million prompt and ${:.2} per million \
+                response tokens, as {} isn't in the price table",
+                FALLBACK_PRICE.cost(estimate),
+                FALLBACK_PRICE.input,
+                FALLBACK_PRICE.output,
+                model
+            ),
+        }
+        if prompts::is_non_interactive() {
+            return Ok(true);
+        }
+        prompts::confirm("Analyze the items with this model?", true, true)
+    }
+
+    /// Creates a cabinet named after every top-level directory (`--cabinets-from-dirs`).
+    /// Returns each directory's cabinet id.
+    fn seed_dir_cabinets(&self, options: &OrganizeOptions) -> Result<HashMap<PathBuf, i64>> {
+        let database_dir = self.database_dir_in_scan_root();
+        let ignore = IgnoreRules::load(&self.base_path)?.with_excludes(&options.exclude);
+        let mut cabinets = HashMap::new();
+        let mut created = 0;
+        for entry in std::fs::read_dir(&self.base_path)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !entry.file_type()?.is_dir()
+                || Self::is_hidden_entry(&name, options.include_hidden)
+                || ignore.is_ignored(Path::new(&name), true)
+                || is_bundle_name(&name)
+                || database_dir.as_ref() == Some(&entry.path())
+            {
+                continue;
+            }
+
+            let cabinet_id = match self.database.get_cabinet_by_name(&name)? {
+                Some(existing) => existing.id,
+                None => {
+                    created += 1;
+                    self.database
+                        .create_cabinet(&name, &format!("Everything in the {} directory", name))?
+                }
+            };
+            cabinets.insert(entry.path(), cabinet_id);
+        }
+        eprintln!(
+            "📐 Using {} top-level directories as cabinets ({} new)",
+            cabinets.len(),
+            created
+        );
+        Ok(cabinets)
+    }
+
+    /// The cabinet of the top-level directory each item is in, for items inside one.
+    fn fixed_cabinets(
+        &self,
+        items: &[ProcessingItem],
+        dir_cabinets: &HashMap<PathBuf, i64>,
+    ) -> HashMap<PathBuf, i64> {
+        items
+            .iter()
+            .filter_map(|item| {
+                let mut components = item.path().strip_prefix(&self.base_path).ok()?.components();
+                let top_level = self.base_path.join(components.next()?);
+                // The directory itself isn't in its own cabinet
+                components.next()?;
+                let cabinet_id = dir_cabinets.get(&top_level)?;
+                Some((item.path().to_path_buf(), *cabinet_id))
+            })
+            .collect()
+    }
+
+    /// One line per recent correction: the user's feedback and a few of the moves it
+    /// led to, newest first.
+    fn correction_preferences(&self) -> Result<Vec<String>> {
+        Ok(self
+            .database
+            .recent_corrections(HISTORY_LIMIT)?
+            .into_iter()
+            .map(|correction| {
+                if correction.moves.is_empty() {
+                    format!("\"{}\"", correction.feedback)
+                } else {
+                    let examples: Vec<&str> = correction
+                        .moves
+                        .iter()
+                        .take(HISTORY_EXAMPLE_MOVES)
+                        .map(String::as_str)
+                        .collect();
+                    format!("\"{}\" (e.g. {})", correction.feedback, examples.join(", "))
+                }
+            })
+            .collect())
+    }
+
+    /// Seeds the cabinets the model proposes from a sample of `items`, so the first
+    /// batches have a structure to anchor on. Skipped when the database already has
+    /// one. Returns the names of the cabinets created.
+    async fn seed_skeleton(
+        &self,
+        items: &[ProcessingItem],
+        sample_size: usize,
+        options: &OrganizeOptions,
+    ) -> Result<Vec<String>> {
+        if !self.database.list_cabinets()?.is_empty() {
+            eprintln!(
+                "📐 Skipping the cabinet skeleton - the existing cabinets anchor the analysis"
+            );
+            return Ok(Vec::new());
+        }
+
+        let sample = sample_items(items, sample_size);
+        let skeleton = match propose_skeleton(
+            self.provider()?,
+            &sample,
+            items.len(),
+            options.batch.cabinet_limit(),
+        )
+        .await
+        {
+            Ok(skeleton) => skeleton,
+            Err(e) if !options.batch.strict => {
+                eprintln!(
+                    "{}: failed to propose a cabinet skeleton, analyzing without one: {}",
+                    "Warning".yellow().bold(),
+                    e
+                );
+                return Ok(Vec::new());
+            }
+            Err(e) => return Err(e.context("Failed to propose a cabinet skeleton")),
+        };
+
+        skeleton.seed(&self.database)?;
+        let names = skeleton
+            .cabinets
+            .into_iter()
+            .map(|cabinet| cabinet.name)
+            .collect::<Vec<_>>();
+        eprintln!(
+            "📐 Seeded {} cabinets proposed from {} sample items: {}",
+            names.len(),
+            sample.len(),
+            names.join(", ")
+        );
+        Ok(names)
+    }
+
+    /// Removes skeleton cabinets nothing was sorted into, so they don't end up as
+    /// empty directories.
+    fn prune_skeleton(&self, names: &[String]) -> Result<()> {
+        for name in names {
+            if let Some(cabinet) = self.database.get_cabinet_by_name(name)?
+                && self.database.list_shelves(Some(cabinet.id))?.is_empty()
+            {
+                self.database.delete_cabinet(cabinet.id)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Skips scanning and analysis, rebuilding the plan from the database as it is now,
+    /// e.g. after a previous run or manual edits, and executing it.
+    pub async fn execute_from_database(&self, options: &OrganizeOptions) -> Result<()> {
+        self.ensure_previous_analysis()?;
+
+        eprintln!("📁 Resuming from existing organization database - skipping scan and analysis");
+
+        self.review_and_execute(options).await?;
+        Ok(())
+    }
+
+    fn ensure_previous_analysis(&self) -> Result<()> {
+        if self.database.get_processed_paths()?.is_empty() {
+            anyhow::bail!(
+                "No previous analysis found in {} - run 'shelfie organize' without --resume first",
+                self.base_path.display()
+            );
+        }
+        Ok(())
+    }
+
+    /// The limits only fold the plan. Once it has been executed, the database follows
+    /// it: Overflow and Other are created, shelves of merged cabinets move into
+    /// Overflow, items move onto Other, and what was emptied is removed.
+    fn persist_limits(&self, plan: &OrganizationPlan) -> Result<()> {
+        let shelves = self.database.list_shelves(None)?;
+        let mut emptied_cabinets = HashSet::new();
+        let mut targets = HashMap::new();
+        for cabinet in &plan.cabinets {
+            let cabinet_id = if cabinet.id < 0 {
+                self.database
+                    .create_cabinet(&cabinet.name, &cabinet.description)?
+            } else {
+                cabinet.id
+            };
+            for shelf_plan in &cabinet.shelves {
+                let shelf_id = if shelf_plan.id < 0 {
+                    self.database.create_shelf(
+                        cabinet_id,
+                        &shelf_plan.name,
+                        &shelf_plan.description,
+                    )?
+                } else {
+                    if let Some(shelf) = shelves.iter().find(|s| s.id == shelf_plan.id)
+                        && shelf.cabinet_id != cabinet_id
+                    {
+                        self.database.update_shelf(
+                            shelf.id,
+                            &shelf_plan.name,
+                            &shelf_plan.description,
+                        )?;
+                        self.database.move_shelf(shelf.id, cabinet_id)?;
+                        emptied_cabinets.insert(shelf.cabinet_id);
+                    }
+                    // Moving merges it into a shelf of the same name if there is one
+                    self.database
+                        .get_shelf_by_name(cabinet_id, &shelf_plan.name)?
+                        .map_or(shelf_plan.id, |shelf| shelf.id)
+                };
+                targets.insert((cabinet.name.as_str(), shelf_plan.name.as_str()), shelf_id);
+            }
+        }
+
+        let mut emptied_shelves = HashSet::new();
+        for movement in &plan.movements {
+            let Some(item) = self
+                .database
+                .get_item_by_path(&movement.from.to_string_lossy())?
+            else {
+                continue;
+            };
+            if let (Some(item_id), Some(&shelf_id)) = (
+                item.id,
+                targets.get(&(movement.to_cabinet.as_str(), movement.to_shelf.as_str())),
+            ) && item.shelf_id != shelf_id
+            {
+                self.database.update_item_shelf(item_id, shelf_id)?;
+                emptied_shelves.insert(item.shelf_id);
+            }
+        }
+
+        let items = self.database.list_all_items()?;
+        for shelf_id in emptied_shelves {
+            if !items.iter().any(|item| item.shelf_id == shelf_id) {
+                self.database.delete_shelf(shelf_id)?;
+            }
+        }
+        for cabinet_id in emptied_cabinets {
+            if self.database.list_shelves(Some(cabinet_id))?.is_empty() {
+                self.database.delete_cabinet(cabinet_id)?;
+            }
+        }
+        Ok(())
+    }
 
+    /// Builds the plan from the database, lets the user confirm or refine it, and executes it.
+    /// Returns whether the plan was executed.
+    async fn review_and_execute(&self, options: &OrganizeOptions) -> Result<bool> {
+        let planning_started = Instant::now();
         // Step 3: Generate organization plan
         println!(
             "\n{}",
             "Step 3: Creating organization plan...".green().bold()
         );
-        let plan = self.create_organization_plan()?;
+        let mut limited = Self::limit_plan(
+            self.create_organization_plan(options.min_rename_confidence)?,
+            options,
+        )?;
+        let plan = Self::lay_out_plan(limited.clone(), options);
+        self.record_phase("planning", planning_started, plan.movements.len());
+
+        let interactive = !options.auto_confirm && !prompts::is_non_interactive();
+        if options.confirm_structure && interactive && !self.confirm_structure(&plan)? {
+            println!("{}", "Organization cancelled.".yellow());
+            return Ok(false);
+        }
 
-        println!("\n{}", "Proposed Organization Plan:".cyan().bold());
-        self.print_plan(&plan)?;
+        match self.database.last_executed_plan()? {
+            Some(previous) if !options.show_full_plan => {
+                println!(
+                    "\n{}",
+                    "Proposed Organization Plan (changes since the last run):"
+                        .cyan()
+                        .bold()
+                );
+                self.print_plan_diff(&diff_plans(&previous, &plan));
+            }
+            _ => {
+                println!("\n{}", "Proposed Organization Plan:".cyan().bold());
+                self.print_plan(&plan)?;
+            }
+        }
 
-        let final_plan = if auto_confirm {
+        let final_plan = if !interactive {
             println!("{}", "Auto-confirming organization plan...".yellow());
             plan
         } else {
             // First ask if they want to proceed with the current plan
-            let initial_confirm = Confirm::with_theme(&ColorfulTheme::default())
-                .with_prompt(
-                    "Do you want to proceed with this organization? (if not, I can modify it)",
-                )
-                .interact()?;
+            let initial_confirm = prompts::confirm(
+                if self.provider.is_some() {
+                    "Do you want to proceed with this organization? (if not, I can modify it)"
+                } else {
+                    "Do you want to proceed with this organization?"
+                },
+                false,
+                true,
+            )?;
 
             if initial_confirm {
                 plan
+            } else if self.provider.is_none() {
+                println!("{}", "Organization cancelled.".yellow());
+                return Ok(false);
             } else {
                 // Enter refinement mode
                 println!("\n{}", "Entering plan refinement mode...".cyan().bold());
                 let refiner = PlanRefiner::new(
-                    self.provider.clone(),
+                    self.provider()?.clone(),
                     Arc::clone(&self.database),
                     self.base_path.clone(),
-                );
+                )
+                .with_min_rename_confidence(options.min_rename_confidence)
+                .with_history(options.use_history)
+                .with_limits(options.refine_max_turns, options.refine_timeout);
 
                 match refiner.refine_plan_with_feedback(&plan).await? {
                     Some(refined_plan) => {
                         println!("\n{}", "Plan refinement completed!".green().bold());
-                        refined_plan
+                        limited = Self::limit_plan(refined_plan, options)?;
+                        Self::lay_out_plan(limited.clone(), options)
                     }
                     None => {
                         println!("{}", "Organization cancelled.".yellow());
-                        return Ok(());
+                        return Ok(false);
                     }
                 }
             }
         };
 
+        if let Some((path, format)) = &options.report {
+            write_report(&final_plan, *format, path)?;
+            println!("📝 Wrote the plan report to {}", path.display());
+        }
+
+        if options.dry_run {
+            println!(
+                "\n{}",
+                "Step 4: Previewing reorganization...".green().bold()
+            );
+            self.execute_plan(&final_plan, options).await?;
+            print_dry_run_notice();
+            return Ok(false);
+        }
+
         println!("\n{}", "Step 4: Executing reorganization...".green().bold());
-        self.execute_plan(&final_plan).await?;
+        let execution_started = Instant::now();
+        let report = self.execute_plan(&final_plan, options).await?;
+        self.record_phase("execution", execution_started, report.moved);
+        report.print_summary(final_plan.movements.len(), &self.base_path);
+        self.persist_limits(&limited)?;
+        self.database.record_executed_plan(&final_plan)?;
         println!("{}", "✓ Organization complete!".green().bold());
 
-        Ok(())
+        Ok(true)
     }
 
-    async fn collect_items(&self, max_depth: usize) -> Result<Vec<ProcessingItem>> {
+    async fn collect_items(&self, options: &OrganizeOptions) -> Result<ScanResult> {
         let processed_paths = self.database.get_processed_paths().unwrap_or_default();
+        let cabinets = self.database.list_cabinets().unwrap_or_default();
+        let cabinet_dirs = match options.flat {
+            None => cabinets
+                .iter()
+                .map(|c| self.base_path.join(options.folder_name(&c.name)))
+                .collect::<Vec<_>>(),
+            Some(_) => Vec::new(),
+        };
+        // Contents of the cabinet directories are scanned too, except for shelves that
+        // are already organized: they hold what earlier runs moved there
+        let shelf_dirs = self
+            .database
+            .list_shelves(None)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|shelf| {
+                let cabinet = cabinets.iter().find(|c| c.id == shelf.cabinet_id)?;
+                let (cabinet, shelf) = (
+                    options.folder_name(&cabinet.name),
+                    options.folder_name(&shelf.name),
+                );
+                Some(match options.flat {
+                    None => self.base_path.join(cabinet).join(shelf),
+                    Some(layout) => self
+                        .base_path
+                        .join(OrganizeOptions::flat_folder_name(layout, &cabinet, &shelf)),
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut max_depth = options.max_depth;
+        if options.cabinets_from_dirs {
+            max_depth += 1;
+        }
+        let changed_since = if options.since_last_run {
+            let last_run = self.database.last_completed_run()?;
+            if last_run.is_none() {
+                eprintln!("No completed run recorded yet - processing all new items");
+            }
+            last_run
+        } else {
+            None
+        };
+        let database_dir = self.database_dir_in_scan_root();
+        let ignore = IgnoreRules::load(&self.base_path)?.with_excludes(&options.exclude);
+        let mut already_processed = 0;
+        let mut organized_dirs = 0;
+        let mut hidden = 0;
+        let mut excluded = 0;
+        let mut special = 0;
         let mut join_set = JoinSet::new();
-        const MAX_CONCURRENCY: usize = 10;
-
-        let semaphore = Arc::new(Semaphore::new(MAX_CONCURRENCY));
-
-        let progress_bar = ProgressBar::new_spinner();
-        progress_bar.set_style(
-            ProgressStyle::default_spinner()
-                .template("{spinner:.green} {msg}")
-                .unwrap(),
-        );
-        progress_bar.set_message("Scanning files...");
-        progress_bar.enable_steady_tick(Duration::from_millis(200));
-
-        for entry in WalkDir::new(&self.base_path).max_depth(max_depth) {
+        let semaphore = Arc::new(Semaphore::new(options.scan_concurrency.max(1)));
+        let content_cache = options
+            .content_cache
+            .then(|| Arc::new(ContentCache::new(Arc::clone(&self.database))));
+        // Items are checkpointed as they're scanned, so an interrupted run resumes
+        // where it stopped rather than scanning everything again
+        let settings: Arc<str> = Self::scan_settings(options).into();
+        let mut pending = self
+            .database
+            .load_pending_items(&settings)
+            .unwrap_or_default();
+        let mut resumed = Vec::new();
+
+        let progress_bar = progress::spinner("Scanning files...");
+
+        let include_hidden = options.include_hidden;
+        let deep_dir_preview = options.deep_dir_preview;
+        let max_file_size = options.max_file_size;
+        let mut walker = WalkDir::new(&self.base_path)
+            .max_depth(max_depth)
+            .into_iter();
+        while let Some(entry) = walker.next() {
             let entry = match entry {
                 Ok(e) => e,
                 Err(_) => continue,
@@ -137,18 +1110,59 @@ impl FileOrganizer {
 
             let path = entry.path().to_path_buf();
 
-            // Skip if already processed
-            let path_str = path.to_string_lossy().to_string();
-            if processed_paths.contains(&path_str) {
+            // Skip hidden files and shelfie's own state, including anything inside them
+            if entry.depth() > 0
+                && Self::is_hidden_entry(&entry.file_name().to_string_lossy(), include_hidden)
+            {
+                hidden += 1;
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
+                }
                 continue;
             }
 
-            // Skip hidden files and the database file
-            if let Some(name) = path.file_name() {
-                let name_str = name.to_string_lossy();
-                if name_str.starts_with('.') || name_str == DB_NAME {
-                    continue;
+            if entry.depth() > 0
+                && let Ok(relative) = path.strip_prefix(&self.base_path)
+                && ignore.is_ignored(relative, path.is_dir())
+            {
+                excluded += 1;
+                if entry.file_type().is_dir() {
+                    walker.skip_current_dir();
                 }
+                continue;
+            }
+
+            let is_shelf_dir = path.is_dir() && shelf_dirs.contains(&path);
+            if is_shelf_dir {
+                walker.skip_current_dir();
+            }
+            let is_cabinet_dir = is_shelf_dir || (path.is_dir() && cabinet_dirs.contains(&path));
+            // Repositories and bundles are analyzed as a whole, so don't descend into them,
+            // unless they're cabinets
+            let is_git_repository = options.git_aware
+                && !is_cabinet_dir
+                && path != self.base_path
+                && Self::is_git_repository(&path);
+            let is_bundle = path != self.base_path
+                && !is_cabinet_dir
+                && entry.file_type().is_dir()
+                && is_bundle_name(&entry.file_name().to_string_lossy());
+            if is_git_repository || is_bundle {
+                walker.skip_current_dir();
+            }
+
+            // Skip if already processed, unless we're looking for changes since the last run
+            let path_str = path.to_string_lossy().to_string();
+            let previously_processed = processed_paths.contains(&path_str);
+            if previously_processed && changed_since.is_none() {
+                already_processed += 1;
+                continue;
+            }
+
+            // Skip cabinet and shelf directories created by a previous run
+            if is_cabinet_dir {
+                organized_dirs += 1;
+                continue;
             }
 
             // Skip the base path itself
@@ -156,26 +1170,82 @@ impl FileOrganizer {
                 continue;
             }
 
+            // Reading a FIFO or device would block the scan or never end
+            if Self::is_special_file(&path) {
+                special += 1;
+                continue;
+            }
+
+            // Skip the database and, when stored elsewhere inside the scan root, its directory
+            if path == self.db_path
+                || database_dir
+                    .as_ref()
+                    .is_some_and(|dir| path.starts_with(dir))
+            {
+                hidden += 1;
+                continue;
+            }
+
+            if let Some(since) = changed_since {
+                if !Self::modified_since(&path, since) {
+                    already_processed += 1;
+                    continue;
+                }
+                // Modified in place - drop the stale analysis so it gets re-analyzed
+                if previously_processed {
+                    self.database.delete_item_by_path(&path_str)?;
+                }
+            }
+
+            let mtime = Self::modified_millis(&path);
+            if let Some((checkpointed_at, item)) = pending.remove(&path)
+                && mtime == Some(checkpointed_at)
+            {
+                resumed.push(item);
+                continue;
+            }
+
             let semaphore = Arc::clone(&semaphore);
+            let database = Arc::clone(&self.database);
+            let settings = Arc::clone(&settings);
+            // A checkpoint that can't be written only costs a rescan
+            let checkpoint = move |item: &ProcessingItem| {
+                if let Some(mtime) = mtime {
+                    let _ = database.save_pending_item(item, mtime, &settings);
+                }
+            };
             if path.is_file() {
+                let content_cache = content_cache.clone();
                 join_set.spawn(async move {
                     let _permit = semaphore.acquire().await?;
-                    Self::process_file_static(&path).await
+                    Self::process_file_static(&path, max_file_size, content_cache.as_deref())
+                        .await
+                        .inspect(checkpoint)
                 });
             } else if path.is_dir() {
                 join_set.spawn(async move {
                     let _permit = semaphore.acquire().await?;
-                    Self::process_directory_static(&path).await
+                    Self::process_directory_static(
+                        &path,
+                        is_git_repository,
+                        include_hidden,
+                        deep_dir_preview,
+                    )
+                    .await
+                    .inspect(checkpoint)
                 });
             }
         }
 
-        progress_bar.set_style(ProgressStyle::default_bar().template(
-            "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})",
-        )?);
-        progress_bar.set_length(join_set.len() as u64);
+        progress::switch_to_bar(&progress_bar, join_set.len() as u64, "entries");
+
+        // Checkpoints of entries that changed or are no longer scanned are stale
+        let _ = self
+            .database
+            .clear_pending_items(pending.keys().map(PathBuf::as_path));
 
-        let mut items = Vec::new();
+        let resumed_count = resumed.len();
+        let mut items = resumed;
 
         while let Some(result) = join_set.join_next().await {
             items.push(result??);
@@ -183,31 +1253,121 @@ impl FileOrganizer {
         }
 
         progress_bar.finish_with_message("✓ Scan complete");
-        Ok(items)
+        Ok(ScanResult {
+            items,
+            already_processed,
+            organized_dirs,
+            hidden,
+            excluded,
+            special,
+            content_cache: content_cache.map(|cache| (cache.hits(), cache.misses())),
+            resumed: resumed_count,
+        })
     }
 
-    async fn process_file_static(path: &std::path::Path) -> Result<ProcessingItem> {
-        let analyzed = AnalyzedFile::new(path.to_path_buf())
-            .await
-            .context("Failed to analyze file")?;
+    /// What shapes the items a scan produces, so checkpoints taken with other
+    /// settings aren't reused.
+    fn scan_settings(options: &OrganizeOptions) -> String {
+        format!(
+            "{} hidden={} git={} preview={:?} max-size={:?}",
+            capture_settings(),
+            options.include_hidden,
+            options.git_aware,
+            options.deep_dir_preview,
+            options.max_file_size
+        )
+    }
 
-        let enriched = EnrichedFile {
-            path: path.to_path_buf(),
-            name: analyzed.name.clone(),
-            extension: analyzed.extension.clone(),
-            file_type: analyzed.get_type_description(),
-            size: analyzed.size,
-            content_preview: if let FileContent::Preview(content) = analyzed.content {
-                Some(content)
-            } else {
-                None
-            },
+    /// Modification time in milliseconds since the epoch, if it can be read.
+    fn modified_millis(path: &Path) -> Option<i64> {
+        let elapsed = std::fs::metadata(path)
+            .ok()?
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?;
+        Some(elapsed.as_millis() as i64)
+    }
+
+    /// The directory holding a custom-located database, if it lives inside the scan root.
+    fn database_dir_in_scan_root(&self) -> Option<PathBuf> {
+        let db_dir = self.db_path.parent()?.canonicalize().ok()?;
+        (db_dir != self.base_path && db_dir.starts_with(&self.base_path)).then_some(db_dir)
+    }
+
+    /// Whether the path was created or modified after `since`. Paths whose timestamps
+    /// can't be read are treated as modified.
+    fn modified_since(path: &Path, since: DateTime<Utc>) -> bool {
+        let Ok(metadata) = std::fs::metadata(path) else {
+            return true;
         };
 
-        Ok(ProcessingItem::File(enriched))
+        [metadata.modified(), metadata.created()]
+            .into_iter()
+            .filter_map(|time| time.ok())
+            .map(DateTime::<Utc>::from)
+            .max()
+            .is_none_or(|latest| latest > since)
+    }
+
+    /// Files over `max_size` bytes are analyzed without reading their content.
+    pub(crate) async fn process_file_static(
+        path: &Path,
+        max_size: Option<u64>,
+        content_cache: Option<&ContentCache>,
+    ) -> Result<ProcessingItem> {
+        let analyzed = AnalyzedFile::new_limited(path.to_path_buf(), max_size, content_cache)
+            .await
+            .context("Failed to analyze file")?;
+
+        Ok(ProcessingItem::File(EnrichedFile::from(analyzed)))
+    }
+
+    fn is_git_repository(path: &Path) -> bool {
+        path.join(".git").exists()
+    }
+
+    /// Reads the start of a repository's top-level README, if it has one.
+    async fn read_readme_preview(path: &Path) -> Option<String> {
+        const README_PREVIEW_CHARS: usize = 500;
+        const README_NAMES: &[&str] = &["README.md", "README", "README.txt", "readme.md"];
+
+        for readme in README_NAMES {
+            if let Ok(content) = tokio::fs::read_to_string(path.join(readme)).await {
+                let preview = content
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+                    .chars()
+                    .take(README_PREVIEW_CHARS)
+                    .collect::<String>();
+                return Some(preview);
+            }
+        }
+
+        None
+    }
+
+    /// Whether the path (following symlinks) is neither a regular file nor a
+    /// directory, e.g. a FIFO, socket or device.
+    pub(crate) fn is_special_file(path: &Path) -> bool {
+        std::fs::metadata(path).is_ok_and(|metadata| !metadata.is_file() && !metadata.is_dir())
+    }
+
+    /// Whether an entry is left out of scans: shelfie's own state and `.git` always,
+    /// other dotfiles unless `include_hidden` is set.
+    pub(crate) fn is_hidden_entry(name: &str, include_hidden: bool) -> bool {
+        is_reserved_name(name) || name == ".git" || (name.starts_with('.') && !include_hidden)
     }
 
-    async fn process_directory_static(path: &std::path::Path) -> Result<ProcessingItem> {
+    /// With `deep_preview`, up to that many of the sampled files are read to
+    /// characterize the directory by its content.
+    pub(crate) async fn process_directory_static(
+        path: &Path,
+        is_git_repository: bool,
+        include_hidden: bool,
+        deep_preview: Option<usize>,
+    ) -> Result<ProcessingItem> {
         const SAMPLE_SIZE: usize = 20;
 
         let name = path
@@ -216,6 +1376,17 @@ impl FileOrganizer {
             .unwrap_or("unknown")
             .to_string();
 
+        if is_git_repository {
+            return Ok(ProcessingItem::Directory(EnrichedDirectory {
+                path: path.to_path_buf(),
+                name,
+                sampled_items: Vec::new(),
+                is_git_repository,
+                readme_preview: Self::read_readme_preview(path).await,
+                content_preview: None,
+            }));
+        }
+
         let mut sampled_items = Vec::new();
         let mut count = 0;
 
@@ -227,7 +1398,7 @@ impl FileOrganizer {
 
                 let entry_path = entry.path();
                 if let Some(entry_name) = entry_path.file_name().and_then(|n| n.to_str()) {
-                    if entry_name.starts_with('.') {
+                    if Self::is_hidden_entry(entry_name, include_hidden) {
                         continue;
                     }
 
@@ -252,16 +1423,77 @@ impl FileOrganizer {
             }
         }
 
+        let content_preview = match deep_preview {
+            Some(max_files) => Self::sampled_file_previews(path, &sampled_items, max_files).await,
+            None => None,
+        };
+
         let enriched = EnrichedDirectory {
             path: path.to_path_buf(),
             name,
             sampled_items,
+            is_git_repository: false,
+            readme_preview: None,
+            content_preview,
         };
 
         Ok(ProcessingItem::Directory(enriched))
     }
 
-    fn create_organization_plan(&self) -> Result<OrganizationPlan> {
+    /// Previews of up to `max_files` of a directory's sampled files, one extension
+    /// each before any extension repeats, so they cover the kinds of files it holds.
+    /// The files are read at once, each with its own timeout, so a directory costs
+    /// about as long as its slowest file.
+    async fn sampled_file_previews(
+        path: &Path,
+        sampled_items: &[SampledItem],
+        max_files: usize,
+    ) -> Option<String> {
+        let max_files = max_files.min(MAX_DIR_PREVIEW_FILES);
+        let files = sampled_items.iter().filter(|item| item.is_file);
+        let mut picked: Vec<&SampledItem> = Vec::new();
+        for item in files.clone() {
+            if !picked.iter().any(|p| p.extension == item.extension) {
+                picked.push(item);
+            }
+        }
+        for item in files {
+            if !picked.iter().any(|p| p.name == item.name) {
+                picked.push(item);
+            }
+        }
+        picked.truncate(max_files);
+
+        let analyses = futures::future::join_all(picked.iter().map(|item| {
+            timeout(
+                DIR_PREVIEW_FILE_TIMEOUT,
+                AnalyzedFile::new(path.join(&item.name)),
+            )
+        }))
+        .await;
+
+        let previews = picked
+            .iter()
+            .zip(analyses)
+            .filter_map(|(item, analysis)| match analysis.ok()?.ok()?.content {
+                FileContent::Preview(preview) if !preview.trim().is_empty() => Some(format!(
+                    "{}: {}",
+                    item.name,
+                    preview
+                        .chars()
+                        .take(DIR_PREVIEW_CHARS_PER_FILE)
+                        .collect::<String>()
+                )),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        (!previews.is_empty()).then(|| previews.join("\n\n"))
+    }
+
+    fn create_organization_plan(
+        &self,
+        min_rename_confidence: Option<f64>,
+    ) -> Result<OrganizationPlan> {
         let database = &self.database;
         let cabinets = database.list_cabinets()?;
         let shelves = database.list_shelves(None)?;
@@ -281,13 +1513,20 @@ impl FileOrganizer {
                 let item_count = items.iter().filter(|i| i.shelf_id == shelf.id).count();
 
                 shelf_plans.push(ShelfPlan {
+                    id: shelf.id,
                     name: shelf.name.clone(),
                     description: shelf.description.clone(),
                     item_count,
                 });
             }
 
+            // Unsorted outlives reorganizing, but only gets a directory once it's used
+            if is_unsorted_name(&cabinet.name) && shelf_plans.iter().all(|s| s.item_count == 0) {
+                continue;
+            }
+
             cabinet_plans.push(CabinetPlan {
+                id: cabinet.id,
                 name: cabinet.name.clone(),
                 description: cabinet.description.clone(),
                 shelves: shelf_plans,
@@ -313,8 +1552,9 @@ impl FileOrganizer {
                 from: from.clone(),
                 to_cabinet: cabinet.name.clone(),
                 to_shelf: shelf.name.clone(),
-                new_name: item.suggested_name.clone(),
+                new_name: item.rename(min_rename_confidence),
                 reasoning: item.description.clone(),
+                opaque_reason: item.opaque_reason.clone(),
             });
         }
 
@@ -324,10 +1564,304 @@ impl FileOrganizer {
         })
     }
 
-    fn print_plan(&self, plan: &OrganizationPlan) -> Result<()> {
-        println!("\n{}", "Cabinet Structure:".cyan());
+    /// Applies user-selected post-processing to a plan before it is shown or executed.
+    fn transform_plan(
+        plan: OrganizationPlan,
+        options: &OrganizeOptions,
+    ) -> Result<OrganizationPlan> {
+        Ok(Self::lay_out_plan(
+            Self::limit_plan(plan, options)?,
+            options,
+        ))
+    }
 
+    /// Checks the plan in strict mode and folds it into the cabinet and shelf limits.
+    /// Names are still those in the database.
+    fn limit_plan(plan: OrganizationPlan, options: &OrganizeOptions) -> Result<OrganizationPlan> {
+        if options.batch.strict {
+            Self::check_strict(&plan, options)?;
+        }
+
+        Ok(Self::enforce_limits(
+            plan,
+            options.cabinet_limit(),
+            options.shelf_limit(),
+        ))
+    }
+
+    /// Applies the naming and layout options to a limited plan.
+    fn lay_out_plan(mut plan: OrganizationPlan, options: &OrganizeOptions) -> OrganizationPlan {
+        for movement in &mut plan.movements {
+            if options.no_rename {
+                movement.new_name = None;
+            }
+            if let Some(new_name) = &movement.new_name {
+                movement.new_name =
+                    Some(apply_naming_convention(new_name, options.naming_convention));
+            }
+            movement.to_cabinet = options.folder_name(&movement.to_cabinet);
+            movement.to_shelf = options.folder_name(&movement.to_shelf);
+        }
+
+        for cabinet in &mut plan.cabinets {
+            cabinet.name = options.folder_name(&cabinet.name);
+            for shelf in &mut cabinet.shelves {
+                shelf.name = options.folder_name(&shelf.name);
+            }
+        }
+
+        match options.flat {
+            Some(layout) => Self::flatten_plan(plan, layout),
+            None => plan,
+        }
+    }
+
+    /// In strict mode, the plan must be usable as the model produced it: nothing is
+    /// merged to stay within the limits and no folder is renamed to avoid shelfie's
+    /// own files.
+    fn check_strict(plan: &OrganizationPlan, options: &OrganizeOptions) -> Result<()> {
+        let max_cabinets = options.batch.cabinet_limit();
+        if plan.cabinets.len() > max_cabinets {
+            anyhow::bail!(
+                "The plan has {} cabinets, more than the limit of {}",
+                plan.cabinets.len(),
+                max_cabinets
+            );
+        }
+
+        let max_shelves = options.batch.shelf_limit();
         for cabinet in &plan.cabinets {
+            if cabinet.shelves.len() > max_shelves {
+                anyhow::bail!(
+                    "Cabinet {} has {} shelves, more than the limit of {}",
+                    cabinet.name,
+                    cabinet.shelves.len(),
+                    max_shelves
+                );
+            }
+            if is_reserved_name(&cabinet.name) {
+                anyhow::bail!(
+                    "Cabinet name {} is reserved for shelfie's own files",
+                    cabinet.name
+                );
+            }
+            if let Some(shelf) = cabinet.shelves.iter().find(|s| is_reserved_name(&s.name)) {
+                anyhow::bail!(
+                    "Shelf name {}/{} is reserved for shelfie's own files",
+                    cabinet.name,
+                    shelf.name
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The prompt asks the model to stay within the limits, but nothing stops it from
+    /// creating more. Beyond `max_cabinets`, the least populated cabinets are merged
+    /// into an Overflow cabinet (one shelf per merged shelf); beyond `max_shelves`, the
+    /// least populated shelves of a cabinet are merged into an Other shelf. The Unsorted
+    /// cabinet is never merged and stays last; when it takes the only slot, the other
+    /// cabinets are merged into it instead. An existing Overflow cabinet or Other shelf
+    /// is reused rather than merged into itself. One the plan adds isn't in the
+    /// database yet, so it gets a negative id.
+    fn enforce_limits(
+        mut plan: OrganizationPlan,
+        max_cabinets: usize,
+        max_shelves: usize,
+    ) -> OrganizationPlan {
+        let mut new_ids = (1..).map(|n: i64| -n);
+        let mut unsorted = plan
+            .cabinets
+            .iter()
+            .position(|cabinet| is_unsorted_name(&cabinet.name))
+            .map(|index| plan.cabinets.remove(index));
+        // Unsorted still counts towards the limit, it just can't be merged
+        let max_cabinets = max_cabinets.max(1) - usize::from(unsorted.is_some());
+        let mut limit_unsorted = false;
+
+        let mut overflow = plan
+            .cabinets
+            .iter()
+            .position(|cabinet| cabinet.name == OVERFLOW_CABINET)
+            .map(|index| plan.cabinets.remove(index));
+        let total = plan.cabinets.len() + usize::from(overflow.is_some());
+        if total > max_cabinets {
+            plan.cabinets.sort_by_key(|cabinet| {
+                std::cmp::Reverse(cabinet.shelves.iter().map(|s| s.item_count).sum::<usize>())
+            });
+            let (merged, target) = match (max_cabinets, unsorted.as_mut()) {
+                (0, Some(unsorted)) => {
+                    limit_unsorted = true;
+                    plan.cabinets.extend(overflow.take());
+                    (std::mem::take(&mut plan.cabinets), unsorted)
+                }
+                _ => {
+                    // One of the slots goes to Overflow itself
+                    let merged = plan.cabinets.split_off(max_cabinets - 1);
+                    let overflow = overflow.get_or_insert_with(|| CabinetPlan {
+                        id: new_ids.next().unwrap(),
+                        name: OVERFLOW_CABINET.to_string(),
+                        description: "Items from cabinets beyond the configured limit".to_string(),
+                        shelves: Vec::new(),
+                    });
+                    (merged, overflow)
+                }
+            };
+            let target_name = target.name.clone();
+            target.shelves.extend(merged.iter().flat_map(|cabinet| {
+                cabinet.shelves.iter().map(|shelf| ShelfPlan {
+                    name: format!("{} - {}", cabinet.name, shelf.name),
+                    ..shelf.clone()
+                })
+            }));
+            for movement in &mut plan.movements {
+                if merged.iter().any(|c| c.name == movement.to_cabinet) {
+                    movement.to_shelf = format!("{} - {}", movement.to_cabinet, movement.to_shelf);
+                    movement.to_cabinet = target_name.clone();
+                }
+            }
+
+            eprintln!(
+                "{}: the plan has {} cabinets, more than the limit of {}; merged the {} smallest into {}",
+                "Warning".yellow().bold(),
+                total + usize::from(unsorted.is_some()),
+                max_cabinets + usize::from(unsorted.is_some()),
+                merged.len(),
+                target_name
+            );
+        }
+        plan.cabinets.extend(overflow);
+        plan.cabinets.extend(unsorted);
+
+        for cabinet in &mut plan.cabinets {
+            // Unsorted's own shelves are left alone, unless other cabinets went into it
+            if cabinet.shelves.len() <= max_shelves
+                || (is_unsorted_name(&cabinet.name) && !limit_unsorted)
+            {
+                continue;
+            }
+
+            let total = cabinet.shelves.len();
+            let mut other = cabinet
+                .shelves
+                .iter()
+                .position(|shelf| shelf.name == OTHER_SHELF)
+                .map(|index| cabinet.shelves.remove(index));
+            cabinet
+                .shelves
+                .sort_by_key(|shelf| std::cmp::Reverse(shelf.item_count));
+            // One of the slots goes to Other itself
+            let merged = cabinet.shelves.split_off(max_shelves - 1);
+
+            let target = other.get_or_insert_with(|| ShelfPlan {
+                id: new_ids.next().unwrap(),
+                name: OTHER_SHELF.to_string(),
+                description: "Items from shelves beyond the configured limit".to_string(),
+                item_count: 0,
+            });
+            target.item_count += merged.iter().map(|s| s.item_count).sum::<usize>();
+            for movement in &mut plan.movements {
+                if movement.to_cabinet == cabinet.name
+                    && merged.iter().any(|s| s.name == movement.to_shelf)
+                {
+                    movement.to_shelf = OTHER_SHELF.to_string();
+                }
+            }
+            cabinet.shelves.extend(other);
+
+            eprintln!(
+                "{}: cabinet {} has {} shelves, more than the limit of {}; merged the {} smallest into {}",
+                "Warning".yellow().bold(),
+                cabinet.name,
+                total,
+                max_shelves,
+                merged.len(),
+                OTHER_SHELF
+            );
+        }
+
+        plan
+    }
+
+    /// Collapses cabinet and shelf into a single directory under the base path.
+    /// Each flattened directory becomes a cabinet without shelves, carrying the id of
+    /// the shelf it came from, and movements point at it with an empty shelf.
+    fn flatten_plan(plan: OrganizationPlan, layout: FlatLayout) -> OrganizationPlan {
+        let mut cabinets: Vec<CabinetPlan> = Vec::new();
+
+        for cabinet in plan.cabinets {
+            for shelf in cabinet.shelves {
+                let name = OrganizeOptions::flat_folder_name(layout, &cabinet.name, &shelf.name);
+                // With the shelf-only layout, same-named shelves from different cabinets merge
+                if cabinets.iter().any(|c| c.name == name) {
+                    continue;
+                }
+                cabinets.push(CabinetPlan {
+                    id: shelf.id,
+                    name,
+                    description: shelf.description,
+                    shelves: Vec::new(),
+                });
+            }
+        }
+
+        let movements = plan
+            .movements
+            .into_iter()
+            .map(|movement| FileMovement {
+                to_cabinet: OrganizeOptions::flat_folder_name(
+                    layout,
+                    &movement.to_cabinet,
+                    &movement.to_shelf,
+                ),
+                to_shelf: String::new(),
+                ..movement
+            })
+            .collect();
+
+        OrganizationPlan {
+            cabinets,
+            movements,
+        }
+    }
+
+    /// Asks to approve the directories the plan would create, without showing the
+    /// movements yet. Nothing to ask when every directory already exists.
+    fn confirm_structure(&self, plan: &OrganizationPlan) -> Result<bool> {
+        // Where case is ignored, a directory differing only in case exists as well
+        let new_dirs = plan
+            .cabinets
+            .iter()
+            .flat_map(|cabinet| self.shelf_paths(cabinet, false))
+            .flatten()
+            .filter(|path| !path.exists())
+            .collect::<Vec<_>>();
+        if new_dirs.is_empty() {
+            return Ok(true);
+        }
+
+        Self::print_structure(plan);
+        println!("\n{}", "New directories:".cyan());
+        for dir in &new_dirs {
+            println!("  {}", relative_display(dir, &self.base_path).green());
+        }
+
+        prompts::confirm(
+            &format!("Create these {} directories?", new_dirs.len()),
+            false,
+            true,
+        )
+    }
+
+    fn print_structure(plan: &OrganizationPlan) {
+        println!("\n{}", "Cabinet Structure:".cyan());
+
+        let (unsorted, sorted): (Vec<_>, Vec<_>) = plan
+            .cabinets
+            .iter()
+            .partition(|cabinet| is_unsorted_name(&cabinet.name));
+        for cabinet in sorted.into_iter().chain(unsorted) {
             println!(
                 "  🗄  {} - {}",
                 cabinet.name.blue().bold(),
@@ -343,6 +1877,10 @@ impl FileOrganizer {
                 );
             }
         }
+    }
+
+    fn print_plan(&self, plan: &OrganizationPlan) -> Result<()> {
+        Self::print_structure(plan);
 
         println!("\n{}", "File Movements:".cyan());
 
@@ -350,67 +1888,184 @@ impl FileOrganizer {
         let total = plan.movements.len();
 
         for (idx, movement) in plan.movements.iter().take(display_limit).enumerate() {
-            let from_name = movement
-                .from
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown");
-
-            let default_name = from_name.to_string();
-            let to_name = movement.new_name.as_ref().unwrap_or(&default_name);
-
             println!(
-                "  {} → {}/{}/{}",
-                from_name.yellow(),
-                movement.to_cabinet.blue(),
-                movement.to_shelf.green(),
-                if movement.new_name.is_some() {
-                    to_name.cyan().to_string()
-                } else {
-                    to_name.to_string()
-                }
+                "  {} → {}",
+                relative_display(&movement.from, &self.base_path).yellow(),
+                Self::format_destination(movement)
             );
 
             if idx < 5 || total <= display_limit {
                 println!("    {}", movement.reasoning.dimmed());
             }
+            if let Some(reason) = &movement.opaque_reason {
+                println!("    {}", format!("Moved whole: {}", reason).dimmed());
+            }
         }
 
         if total > display_limit {
             println!("  ... and {} more files", total - display_limit);
         }
 
+        Self::print_unsorted(plan, &self.base_path, display_limit);
+
         Ok(())
     }
 
-    async fn execute_plan(&self, plan: &OrganizationPlan) -> Result<()> {
+    /// Lists the items going to the Unsorted cabinet, which need sorting by hand.
+    fn print_unsorted(plan: &OrganizationPlan, base_path: &Path, display_limit: usize) {
+        let unsorted = plan
+            .movements
+            .iter()
+            .filter(|movement| is_unsorted_name(&movement.to_cabinet))
+            .collect::<Vec<_>>();
+        if unsorted.is_empty() {
+            return;
+        }
+
+        println!(
+            "\n{}",
+            format!(
+                "⚠️  Needs manual attention ({} items going to {}):",
+                unsorted.len(),
+                unsorted[0].to_cabinet
+            )
+            .yellow()
+        );
+        for movement in unsorted.iter().take(display_limit) {
+            println!("  {}", relative_display(&movement.from, base_path).yellow());
+        }
+        if unsorted.len() > display_limit {
+            println!("  ... and {} more", unsorted.len() - display_limit);
+        }
+    }
+
+    /// `cabinet/shelf/name` for a movement, with the name highlighted if it changes.
+    fn format_destination(movement: &FileMovement) -> String {
+        let from_name = movement
+            .from
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown");
+        let to_name = match &movement.new_name {
+            Some(new_name) => new_name.cyan().to_string(),
+            None => from_name.to_string(),
+        };
+
+        if movement.to_shelf.is_empty() {
+            format!("{}/{}", movement.to_cabinet.blue(), to_name)
+        } else {
+            format!(
+                "{}/{}/{}",
+                movement.to_cabinet.blue(),
+                movement.to_shelf.green(),
+                to_name
+            )
+        }
+    }
+
+    fn print_plan_diff(&self, diff: &PlanDiff) {
+        if diff.is_empty() {
+            println!(
+                "  No changes since the last executed plan ({} items stay where they are)",
+                diff.unchanged
+            );
+            return;
+        }
+
+        if !diff.new_cabinets.is_empty() || !diff.new_shelves.is_empty() {
+            println!("\n{}", "New Cabinets and Shelves:".cyan());
+            for cabinet in &diff.new_cabinets {
+                println!("  + 🗄  {}", cabinet.blue().bold());
+            }
+            for (cabinet, shelf) in &diff.new_shelves {
+                println!("  + 📁 {}/{}", cabinet.blue(), shelf.green());
+            }
+        }
+
+        let display_limit = 20;
+
+        if !diff.added.is_empty() {
+            println!("\n{}", "New Items:".cyan());
+            for movement in diff.added.iter().take(display_limit) {
+                println!(
+                    "  + {} → {}",
+                    relative_display(&movement.from, &self.base_path).yellow(),
+                    Self::format_destination(movement)
+                );
+            }
+            if diff.added.len() > display_limit {
+                println!("  ... and {} more", diff.added.len() - display_limit);
+            }
+        }
+
+        if !diff.moved.is_empty() {
+            println!("\n{}", "Moved Items:".cyan());
+            for moved in diff.moved.iter().take(display_limit) {
+                println!(
+                    "  ~ {}: {} → {}",
+                    relative_display(&moved.current.from, &self.base_path).yellow(),
+                    Self::format_destination(&moved.previous),
+                    Self::format_destination(&moved.current)
+                );
+            }
+            if diff.moved.len() > display_limit {
+                println!("  ... and {} more", diff.moved.len() - display_limit);
+            }
+        }
+
+        println!(
+            "\n  {} items unchanged (use --show-full-plan to see everything)",
+            diff.unchanged
+        );
+    }
+
+    async fn execute_plan(
+        &self,
+        plan: &OrganizationPlan,
+        options: &OrganizeOptions,
+    ) -> Result<ExecutionReport> {
         let total_operations = plan.cabinets.len() + plan.movements.len();
 
         if total_operations == 0 {
             println!("{}", "No operations to perform.".yellow());
-            return Ok(());
+            return Ok(ExecutionReport::default());
+        }
+        let hashes = self.duplicate_hashes(plan, options).await?;
+        if options.dry_run {
+            return Ok(self.preview_plan(plan, options, &hashes));
         }
 
-        let pb = ProgressBar::new(total_operations as u64);
-        pb.set_style(
-            ProgressStyle::default_bar()
-                .template(
-                    "{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}",
-                )
-                .unwrap()
-                .progress_chars("#>-"),
-        );
+        let pb = progress::bar(total_operations as u64, "operations");
+
+        // Destinations come from LLM-generated names, so every path is checked to stay
+        // inside the base path before anything is created or moved
+        let mut refused = Vec::new();
+        let mut missing = Vec::new();
+        let mut conflicts = Vec::new();
+        let mut duplicates = Vec::new();
+        let mut moved = 0;
+        let mut crossed_devices = 0;
+        let run_started = Utc::now();
+        let trash = options
+            .trash
+            .then(|| Trash::new(&self.base_path, run_started));
+        let duplicate_trash = (options.dedupe == Some(DuplicateAction::Trash) && trash.is_none())
+            .then(|| Trash::new(&self.base_path, run_started));
+        // Where the first moved file of each duplicated content ended up
+        let mut kept: HashMap<&str, PathBuf> = HashMap::new();
+        let mut described = 0;
+        let mut undescribed = Vec::new();
+        let case_insensitive = options.filesystem_case.is_insensitive(&self.base_path);
 
         // Create cabinet and shelf directories
         pb.set_message("Creating directory structure...");
 
         for cabinet in &plan.cabinets {
-            let cabinet_path = self.base_path.join(&cabinet.name);
-            tokio::fs::create_dir_all(&cabinet_path).await?;
-
-            for shelf in &cabinet.shelves {
-                let shelf_path = cabinet_path.join(&shelf.name);
-                tokio::fs::create_dir_all(&shelf_path).await?;
+            for shelf_path in self.shelf_paths(cabinet, case_insensitive) {
+                match shelf_path {
+                    Some(path) => tokio::fs::create_dir_all(&path).await?,
+                    None => refused.push(format!("directory {}", cabinet.name)),
+                }
             }
 
             pb.inc(1);
@@ -419,41 +2074,103 @@ impl FileOrganizer {
         // Move files
         pb.set_message("Moving files...");
 
+        let mut shelf_dirs = HashMap::new();
+        let mut claimed = HashSet::new();
         for movement in &plan.movements {
-            let to_dir = self
-                .base_path
-                .join(&movement.to_cabinet)
-                .join(&movement.to_shelf);
-
-            let file_name = if let Some(new_name) = &movement.new_name {
-                new_name.clone()
-            } else if let Some(name) = movement.from.file_name().and_then(|n| n.to_str()) {
-                name.to_string()
-            } else {
-                "unknown".to_string()
+            let final_name = Self::target_name(movement, options.extension_policy);
+
+            let to_dir = shelf_dirs
+                .entry((&movement.to_cabinet, &movement.to_shelf))
+                .or_insert_with(|| {
+                    self.contained_dir(
+                        &Self::destination_parts(&movement.to_cabinet, &movement.to_shelf),
+                        case_insensitive,
+                    )
+                })
+                .clone();
+            let Some(to_dir) = to_dir else {
+                refused.push(movement.from.display().to_string());
+                pb.inc(1);
+                continue;
             };
-
-            // Add extension if present
-            let final_name = if let Some(ext) = movement.from.extension() {
-                format!("{}.{}", file_name, ext.to_string_lossy())
-            } else {
-                file_name
+            let Some(to_file) = Self::join_contained(&to_dir, &final_name) else {
+                refused.push(movement.from.display().to_string());
+                pb.inc(1);
+                continue;
             };
 
-            let to_file = to_dir.join(final_name);
+            let hash = hashes.get(&movement.from).map(String::as_str);
+            if let Some(original) = hash.and_then(|hash| kept.get(hash))
+                && movement.from.exists()
+            {
+                let action = options.dedupe.unwrap_or(DuplicateAction::Report);
+                let handled = self.handle_duplicate(
+                    &movement.from,
+                    original,
+                    action,
+                    duplicate_trash.as_ref().or(trash.as_ref()),
+                );
+                duplicates.push(handled);
+                pb.inc(1);
+                continue;
+            }
 
             if movement.from.exists() {
                 tokio::fs::create_dir_all(&to_dir).await?;
 
-                // Try rename first, fall back to copy+delete
-                tokio::fs::rename(&movement.from, &to_file).await.or_else(
-                    |_| -> Result<(), std::io::Error> {
-                        std::fs::copy(&movement.from, &to_file)?;
-                        std::fs::remove_file(&movement.from)?;
-                        Ok(())
-                    },
-                )?;
+                // Catch escapes through symlinks that the name check can't see
+                if !to_dir.canonicalize()?.starts_with(&self.base_path) {
+                    refused.push(movement.from.display().to_string());
+                    pb.inc(1);
+                    continue;
+                }
+
+                let to_file = Self::avoid_claimed(to_file, &claimed, case_insensitive);
+                let Some(to_file) = Self::resolve_conflict(
+                    &movement.from,
+                    to_file,
+                    options.on_conflict,
+                    case_insensitive,
+                    trash.as_ref(),
+                    &pb,
+                )?
+                else {
+                    conflicts.push(relative_display(&movement.from, &self.base_path));
+                    pb.inc(1);
+                    continue;
+                };
+
+                let crosses_devices = Self::move_one(
+                    &movement.from,
+                    &to_file,
+                    &to_dir,
+                    trash.as_ref(),
+                    case_insensitive,
+                )
+                .await
+                .with_context(|| format!("Failed to move {}", movement.from.display()))?;
+                claimed.insert(Self::claim_key(&to_file, case_insensitive));
+                if let Some(hash) = hash {
+                    kept.insert(hash, to_file.clone());
+                }
+                if crosses_devices {
+                    crossed_devices += 1;
+                }
+                // The item has moved by now, so a description that can't be written is
+                // only reported
+                if options.write_descriptions {
+                    match write_description(&to_file, &movement.reasoning) {
+                        Ok(true) => described += 1,
+                        Ok(false) => {}
+                        Err(e) => undescribed.push(format!(
+                            "{}: {:#}",
+                            relative_display(&to_file, &self.base_path),
+                            e
+                        )),
+                    }
+                }
 
+                moved += 1;
                 pb.set_message(format!(
                     "Moved: {}",
                     movement
@@ -462,6 +2179,8 @@ impl FileOrganizer {
                         .and_then(|n| n.to_str())
                         .unwrap_or("unknown")
                 ));
+            } else {
+                missing.push(relative_display(&movement.from, &self.base_path));
             }
 
             pb.inc(1);
@@ -469,6 +2188,598 @@ impl FileOrganizer {
 
         pb.finish_with_message(format!("✓ Reorganized {} items", total_operations));
 
+        if options.write_manifests {
+            let written = self.write_manifests(plan, options.extension_policy)?;
+            println!("📝 Wrote {} shelf manifests", written);
+        }
+
+        if options.write_descriptions {
+            println!("📝 Wrote descriptions into {} files", described);
+        }
+        if !undescribed.is_empty() {
+            eprintln!(
+                "{}: couldn't write the description into {} files:",
+                "Warning".yellow().bold(),
+                undescribed.len()
+            );
+            for item in &undescribed {
+                eprintln!("  - {}", item);
+            }
+        }
+
+        let trashed = [&trash, &duplicate_trash]
+            .into_iter()
+            .flatten()
+            .map(Trash::kept)
+            .sum::<usize>();
+        Ok(ExecutionReport {
+            moved,
+            missing,
+            refused,
+            conflicts,
+            duplicates,
+            crossed_devices,
+            trashed,
+        })
+    }
+
+    /// Content hash of every planned item that shares it with another, with `--dedupe`.
+    /// Files analyzed without hashing, e.g. in a run before `--dedupe` was used, are
+    /// hashed now, and the hash is kept for later runs.
+    async fn duplicate_hashes(
+        &self,
+        plan: &OrganizationPlan,
+        options: &OrganizeOptions,
+    ) -> Result<HashMap<PathBuf, String>> {
+        if options.dedupe.is_none() {
+            return Ok(HashMap::new());
+        }
+
+        let planned = plan
+            .movements
+            .iter()
+            .map(|movement| movement.from.as_path())
+            .collect::<HashSet<_>>();
+        for item in self.database.list_all_items()? {
+            let path = PathBuf::from(&item.path);
+            if item.content_hash.is_some() || !planned.contains(path.as_path()) || !path.is_file() {
+                continue;
+            }
+            if let (Some(item_id), Some(hash)) = (item.id, AnalyzedFile::content_hash(&path).await)
+            {
+                self.database.set_content_hash(item_id, &hash)?;
+            }
+        }
+
+        let mut hashes = HashMap::new();
+        for group in self.database.find_duplicate_hashes()? {
+            for item in group {
+                if let Some(hash) = item.content_hash {
+                    hashes.insert(PathBuf::from(item.path), hash);
+                }
+            }
+        }
+        Ok(hashes)
+    }
+
+    /// Applies `action` to `duplicate`, whose content is the same as the moved
+    /// `original`, and describes what became of it. A duplicate that can't be
+    /// trashed or linked is left where it is.
+    fn handle_duplicate(
+        &self,
+        duplicate: &Path,
+        original: &Path,
+        action: DuplicateAction,
+        trash: Option<&Trash>,
+    ) -> String {
+        let description = format!(
+            "{} (same as {})",
+            relative_display(duplicate, &self.base_path),
+            relative_display(original, &self.base_path)
+        );
+        let result = match action {
+            DuplicateAction::Report => return description,
+            DuplicateAction::Trash => match trash {
+                Some(trash) => trash.keep(duplicate).map(|_| "trashed"),
+                None => return description,
+            },
+            DuplicateAction::Hardlink => Self::link_in_place(duplicate, original).map(|_| "linked"),
+        };
+        match result {
+            Ok(outcome) => format!("{}, {}", description, outcome),
+            Err(e) => format!("{}, left in place: {}", description, e),
+        }
+    }
+
+    /// Replaces `duplicate` with a hard link to `original`. The link is made next to it
+    /// first, so a failure leaves the duplicate untouched.
+    fn link_in_place(duplicate: &Path, original: &Path) -> std::io::Result<()> {
+        let mut link_name = std::ffi::OsString::from(".");
+        link_name.push(duplicate.file_name().unwrap_or_default());
+        link_name.push(".shelfie-link");
+        let link = duplicate.with_file_name(link_name);
+        std::fs::hard_link(original, &link)?;
+        std::fs::rename(&link, duplicate).inspect_err(|_| {
+            let _ = std::fs::remove_file(&link);
+        })
+    }
+
+    /// Prints what `execute_plan` would do: the directories it would create, and where
+    /// each item would go, with conflicts resolved as they would be. Nothing on disk is
+    /// touched, so destinations aren't checked for symlinks leaving the base path.
+    fn preview_plan(
+        &self,
+        plan: &OrganizationPlan,
+        options: &OrganizeOptions,
+        hashes: &HashMap<PathBuf, String>,
+    ) -> ExecutionReport {
+        // Probing would create a file, so `auto` goes by the platform's default
+        let case_insensitive = match options.filesystem_case {
+            FilesystemCase::Auto => cfg!(any(target_os = "macos", target_os = "windows")),
+            case => case.is_insensitive(&self.base_path),
+        };
+        let display = |path: &Path| relative_display(path, &self.base_path);
+        let mut report = ExecutionReport::default();
+        let mut directories = BTreeSet::new();
+
+        for cabinet in &plan.cabinets {
+            for shelf_path in self.shelf_paths(cabinet, case_insensitive) {
+                match shelf_path {
+                    Some(path) => self.collect_missing_dirs(&path, &mut directories),
+                    None => report.refused.push(format!("directory {}", cabinet.name)),
+                }
+            }
+        }
+
+        println!("\n{}", "Planned operations:".cyan().bold());
+        for dir in &directories {
+            println!("  {} {}/", "Create".dimmed(), display(dir));
+        }
+
+        // Nothing is moved, so destinations of earlier movements don't exist yet
+        let mut claimed = HashSet::new();
+        let mut kept: HashMap<&str, PathBuf> = HashMap::new();
+        for movement in &plan.movements {
+            let final_name = Self::target_name(movement, options.extension_policy);
+            let to_file = self
+                .contained_dir(
+                    &Self::destination_parts(&movement.to_cabinet, &movement.to_shelf),
+                    case_insensitive,
+                )
+                .and_then(|to_dir| Self::join_contained(&to_dir, &final_name));
+            let Some(to_file) = to_file else {
+                report.refused.push(movement.from.display().to_string());
+                continue;
+            };
+            let from = display(&movement.from);
+            if !movement.from.exists() {
+                println!("  {} {} (no longer exists)", "Skip".dimmed(), from);
+                report.missing.push(from);
+                continue;
+            }
+            let hash = hashes.get(&movement.from).map(String::as_str);
+            if let Some(original) = hash.and_then(|hash| kept.get(hash)) {
+                println!(
+                    "  {} {} (same as {})",
+                    "Duplicate".dimmed(),
+                    from,
+                    display(original)
+                );
+                report.duplicates.push(from);
+                continue;
+            }
+
+            let to_file = Self::avoid_claimed(to_file, &claimed, case_insensitive);
+            let is_itself = to_file == movement.from
+                || (case_insensitive && differ_only_in_case(&movement.from, &to_file));
+            let to_file = match options.on_conflict {
+                _ if is_itself || to_file.symlink_metadata().is_err() => to_file,
+                ConflictPolicy::Rename => Self::numbered_path(&to_file),
+                ConflictPolicy::Overwrite => to_file,
+                ConflictPolicy::Skip | ConflictPolicy::Ask => {
+                    println!(
+                        "  {} {} ({} already exists)",
+                        "Skip".dimmed(),
+                        from,
+                        display(&to_file)
+                    );
+                    report.conflicts.push(from);
+                    continue;
+                }
+            };
+
+            println!("  {} {} → {}", "Move".dimmed(), from, display(&to_file));
+            claimed.insert(Self::claim_key(&to_file, case_insensitive));
+            if let Some(hash) = hash {
+                kept.insert(hash, to_file.clone());
+            }
+            report.moved += 1;
+        }
+
+        if !report.refused.is_empty() {
+            eprintln!(
+                "{}: would refuse {} operations whose destination would leave {}",
+                "Warning".yellow().bold(),
+                report.refused.len(),
+                self.base_path.display()
+            );
+        }
+        println!(
+            "\n{}",
+            format!(
+                "DRY RUN: would move {} files, create {} directories",
+                report.moved,
+                directories.len()
+            )
+            .yellow()
+            .bold()
+        );
+        report
+    }
+
+    /// Adds `dir` and those of its parents inside the base path that don't exist yet.
+    fn collect_missing_dirs(&self, dir: &Path, missing: &mut BTreeSet<PathBuf>) {
+        for dir in dir.ancestors().take_while(|dir| *dir != self.base_path) {
+            if dir.exists() {
+                break;
+            }
+            missing.insert(dir.to_path_buf());
+        }
+    }
+
+    /// Writes a manifest into every shelf directory of the plan, listing all items the
+    /// database places on it, including those moved by earlier runs.
+    fn write_manifests(&self, plan: &OrganizationPlan, policy: ExtensionPolicy) -> Result<usize> {
+        let mut written = 0;
+        for cabinet in &plan.cabinets {
+            // Flattened cabinets have no shelves and are the directory themselves
+            let shelves = if cabinet.shelves.is_empty() {
+                vec![("", "")]
+            } else {
+                cabinet
+                    .shelves
+                    .iter()
+                    .map(|shelf| (shelf.name.as_str(), shelf.description.as_str()))
+                    .collect()
+            };
+
+            for (shelf, shelf_description) in shelves {
+                let Some(dir) = self.contained_path(&Self::destination_parts(&cabinet.name, shelf))
+                else {
+                    continue;
+                };
+                if !dir.is_dir() || !dir.canonicalize()?.starts_with(&self.base_path) {
+                    continue;
+                }
+
+                let mut items = Vec::new();
+                for movement in plan
+                    .movements
+                    .iter()
+                    .filter(|m| m.to_cabinet == cabinet.name && m.to_shelf == shelf)
+                {
+                    let item = self
+                        .database
+                        .get_item_by_path(&movement.from.to_string_lossy())?;
+                    let original_name = movement
+                        .from
+                        .file_name()
+                        .map(|name| name.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    items.push(ManifestItem {
+                        name: Self::target_name(movement, policy),
+                        original_name: item
+                            .as_ref()
+                            .map_or(original_name, |item| item.original_name.clone()),
+                        description: item
+                            .as_ref()
+                            .map_or(movement.reasoning.clone(), |item| item.description.clone()),
+                        file_type: item.map(|item| item.file_type),
+                        opaque_reason: movement.opaque_reason.clone(),
+                    });
+                }
+                collation().sort_by_name(&mut items, |item| &item.name);
+
+                ShelfManifest::new(
+                    (&cabinet.name, &cabinet.description),
+                    (shelf, shelf_description),
+                    items,
+                )
+                .write(&dir)?;
+                written += 1;
+            }
+        }
+        Ok(written)
+    }
+
+    /// The name an item gets at its destination. Bundles such as `Safari.app` always
+    /// keep their extension, as it is what makes them bundles.
+    pub(crate) fn target_name(movement: &FileMovement, policy: ExtensionPolicy) -> String {
+        let original = movement
+            .from
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown");
+        let Some(new_name) = &movement.new_name else {
+            return original.to_string();
+        };
+
+        let policy = if is_bundle_name(original) {
+            ExtensionPolicy::Preserve
+        } else {
+            policy
+        };
+        let source_extension = movement.from.extension().and_then(|ext| ext.to_str());
+        apply_extension_policy(new_name, source_extension, policy)
+    }
+
+    /// The single place where an existing destination is dealt with, according to
+    /// `policy`. Returns where to move the item, or `None` to leave it in place.
+    fn resolve_conflict(
+        from: &Path,
+        to: PathBuf,
+        policy: ConflictPolicy,
+        case_insensitive: bool,
+        trash: Option<&Trash>,
+        pb: &ProgressBar,
+    ) -> Result<Option<PathBuf>> {
+        // Dangling symlinks count as existing, as a move would replace them. A name
+        // differing only in case is the item itself on a case-insensitive filesystem.
+        if to.symlink_metadata().is_err()
+            || to == from
+            || (case_insensitive && differ_only_in_case(from, &to))
+        {
+            return Ok(Some(to));
+        }
+
+        match policy {
+            ConflictPolicy::Skip => Ok(None),
+            ConflictPolicy::Rename => Ok(Some(Self::numbered_path(&to))),
+            ConflictPolicy::Overwrite => {
+                match trash {
+                    Some(trash) => {
+                        trash.keep(&to)?;
+                    }
+                    None if to.is_dir() && !to.is_symlink() => std::fs::remove_dir_all(&to)?,
+                    None => std::fs::remove_file(&to)?,
+                }
+                Ok(Some(to))
+            }
+            ConflictPolicy::Ask if prompts::is_non_interactive() => Ok(None),
+            ConflictPolicy::Ask => {
+                let choices = [
+                    (ConflictPolicy::Skip, "Skip - leave it where it is"),
+                    (ConflictPolicy::Rename, "Keep both - number the moved item"),
+                    (ConflictPolicy::Overwrite, "Overwrite the existing item"),
+                ];
+                let labels = choices.iter().map(|(_, label)| *label).collect::<Vec<_>>();
+                let prompt = format!("{} already exists", to.display());
+                let choice = pb.suspend(|| prompts::select(&prompt, &labels))?;
+                Self::resolve_conflict(from, to, choices[choice].0, case_insensitive, trash, pb)
+            }
+        }
+    }
+
+    /// Where to move an item whose destination an item moved earlier in the same run
+    /// already took: the next free numbered name, whatever the conflict policy, as
+    /// replacing an item that was just organized is never intended.
+    fn avoid_claimed(
+        to_file: PathBuf,
+        claimed: &HashSet<PathBuf>,
+        case_insensitive: bool,
+    ) -> PathBuf {
+        if !claimed.contains(&Self::claim_key(&to_file, case_insensitive)) {
+            return to_file;
+        }
+        Self::first_free_numbered(&to_file, |candidate| {
+            candidate.symlink_metadata().is_ok()
+                || claimed.contains(&Self::claim_key(candidate, case_insensitive))
+        })
+    }
+
+    /// Identifies a destination in `avoid_claimed`, ignoring case where the
+    /// filesystem does.
+    fn claim_key(path: &Path, case_insensitive: bool) -> PathBuf {
+        if case_insensitive {
+            PathBuf::from(path.to_string_lossy().to_lowercase())
+        } else {
+            path.to_path_buf()
+        }
+    }
+
+    /// The first free `name (n).ext` next to `path`, counting from 2.
+    fn numbered_path(path: &Path) -> PathBuf {
+        Self::first_free_numbered(path, |candidate| candidate.symlink_metadata().is_ok())
+    }
+
+    /// The first `name (n).ext` next to `path`, counting from 2, that isn't `taken`.
+    fn first_free_numbered(path: &Path, taken: impl Fn(&Path) -> bool) -> PathBuf {
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let extension = path
+            .extension()
+            .map(|ext| format!(".{}", ext.to_string_lossy()))
+            .unwrap_or_default();
+        (2..)
+            .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, extension)))
+            .find(|candidate| !taken(candidate))
+            .expect("some numbered name is free")
+    }
+
+    /// Moves `from` to `to_file` in `to_dir`, returning whether it crossed devices. With
+    /// a trash, the original is copied and then kept in the trash instead.
+    ///
+    /// A file briefly locked by e.g. a virus scanner shouldn't abort the run, so each
+    /// step is retried on its own while it fails transiently. Retrying the whole move
+    /// would copy again onto a destination an earlier attempt already wrote.
+    async fn move_one(
+        from: &Path,
+        to_file: &Path,
+        to_dir: &Path,
+        trash: Option<&Trash>,
+        case_insensitive: bool,
+    ) -> Result<bool> {
+        // A copy would be the original itself, so there's nothing to keep in the trash
+        if case_insensitive && differ_only_in_case(from, to_file) {
+            // A failed case rename puts the item back, so it can be tried again
+            retry_transient(|| Self::rename_case(from, to_file)).await?;
+            return Ok(false);
+        }
+
+        if let Some(trash) = trash {
+            Self::copy_then(from, to_file, || {
+                trash.keep(from)?;
+                Ok(())
+            })
+            .await?;
+            return Ok(false);
+        }
+
+        // Renames can't cross devices, so those are copied and deleted instead. Other
+        // failed renames fall back to the same.
+        let crosses_devices = !same_device(from, to_dir)?;
+        if crosses_devices
+            || retry_transient(|| async { Ok(tokio::fs::rename(from, to_file).await?) })
+                .await
+                .is_err()
+        {
+            Self::move_across_devices(from, to_file).await?;
+        }
+        Ok(crosses_devices)
+    }
+
+    /// Copies `from` to `to` without blocking the runtime, then disposes of the
+    /// original with `finish`. Each step is retried on its own while it fails
+    /// transiently; `to` was free before the first attempt, so whatever a failed copy
+    /// left there is removed before the next one.
+    async fn copy_then(
+        from: &Path,
+        to: &Path,
+        mut finish: impl FnMut() -> Result<()>,
+    ) -> Result<()> {
+        let mut attempt = 0;
+        retry_transient(|| {
+            attempt += 1;
+            let retrying = attempt > 1;
+            let (from, to) = (from.to_path_buf(), to.to_path_buf());
+            async move {
+                // Walking a tree has no async counterpart, so it runs on the blocking pool
+                tokio::task::spawn_blocking(move || {
+                    if retrying {
+                        Self::remove_partial_copy(&to)?;
+                    }
+                    copy_recursive(&from, &to)
+                })
+                .await??;
+                Ok(())
+            }
+        })
+        .await?;
+
+        retry_transient(|| std::future::ready(finish())).await
+    }
+
+    /// Removes what a failed copy left at `to`, if anything.
+    fn remove_partial_copy(to: &Path) -> std::io::Result<()> {
+        match to.symlink_metadata() {
+            Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(to),
+            Ok(_) => std::fs::remove_file(to),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Renames `from` to a name differing only in case. Case-insensitive filesystems
+    /// may ignore such a rename or refuse it, so it goes through a temporary name.
+    async fn rename_case(from: &Path, to: &Path) -> Result<()> {
+        let name = from.file_name().unwrap_or_default().to_string_lossy();
+        let temporary = from.with_file_name(format!(".{}.shelfie-rename", name));
+        tokio::fs::rename(from, &temporary).await?;
+        if let Err(e) = tokio::fs::rename(&temporary, to).await {
+            // Put it back rather than leave it hidden under the temporary name
+            let _ = tokio::fs::rename(&temporary, from).await;
+            return Err(e.into());
+        }
         Ok(())
     }
+
+    /// Moves a file or a whole (opaque) directory where a rename isn't possible, without
+    /// blocking the runtime. File timestamps are kept.
+    async fn move_across_devices(from: &Path, to: &Path) -> Result<()> {
+        Self::copy_then(from, to, || {
+            let removed = if from.is_dir() {
+                std::fs::remove_dir_all(from)
+            } else {
+                std::fs::remove_file(from)
+            };
+            removed.with_context(|| format!("Failed to remove {} after copying it", from.display()))
+        })
+        .await
+    }
+
+    /// Directories to create for a cabinet: one per shelf, or the cabinet itself when it
+    /// has none (flat layout). `None` marks a destination outside the base path.
+    fn shelf_paths(&self, cabinet: &CabinetPlan, case_insensitive: bool) -> Vec<Option<PathBuf>> {
+        if cabinet.shelves.is_empty() {
+            return vec![self.contained_dir(&[&cabinet.name], case_insensitive)];
+        }
+        cabinet
+            .shelves
+            .iter()
+            .map(|shelf| self.contained_dir(&[&cabinet.name, &shelf.name], case_insensitive))
+            .collect()
+    }
+
+    /// Like `contained_path`, but on a case-insensitive filesystem an existing directory
+    /// whose name differs only in case is reused, so e.g. `Docs` goes into `docs`.
+    fn contained_dir(&self, parts: &[&str], case_insensitive: bool) -> Option<PathBuf> {
+        if !case_insensitive {
+            return self.contained_path(parts);
+        }
+        parts.iter().try_fold(self.base_path.clone(), |path, part| {
+            let joined = Self::join_contained(&path, part)?;
+            Some(Self::existing_case_variant(&path, part).unwrap_or(joined))
+        })
+    }
+
+    /// The directory in `dir` named `name` ignoring case, if there is one.
+    fn existing_case_variant(dir: &Path, name: &str) -> Option<PathBuf> {
+        let name = name.to_lowercase();
+        std::fs::read_dir(dir)
+            .ok()?
+            .flatten()
+            .find(|entry| {
+                entry.file_name().to_string_lossy().to_lowercase() == name && entry.path().is_dir()
+            })
+            .map(|entry| entry.path())
+    }
+
+    /// Joins `parts` onto the base path, or `None` if any of them could leave it.
+    fn contained_path(&self, parts: &[&str]) -> Option<PathBuf> {
+        parts.iter().try_fold(self.base_path.clone(), |path, part| {
+            Self::join_contained(&path, part)
+        })
+    }
+
+    /// Directory names under the base path for a cabinet's shelf: cabinet and shelf,
+    /// or just the cabinet when the shelf is empty (flat layout).
+    pub(crate) fn destination_parts<'a>(cabinet: &'a str, shelf: &'a str) -> Vec<&'a str> {
+        if shelf.is_empty() {
+            vec![cabinet]
+        } else {
+            vec![cabinet, shelf]
+        }
+    }
+
+    /// Joins a single name onto `dir`. Anything but one plain path component, such as
+    /// `..`, `.`, an empty name or `a/b`, is refused.
+    pub(crate) fn join_contained(dir: &Path, name: &str) -> Option<PathBuf> {
+        let components = Path::new(name).components().collect::<Vec<_>>();
+        matches!(components[..], [Component::Normal(_)]).then(|| dir.join(name))
+    }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,2122 @@
+use super::{
+    ConflictPolicy, DuplicateAction, ExecutionReport, FileOrganizer, FilesystemCase, FlatLayout,
+    OrganizeOptions,
+};
+use crate::batch_processor::BatchOptions;
+use crate::database::{Item, LEGACY_DB_NAME};
+use crate::exclude::{ExcludePattern, IGNORE_FILE_NAME};
+use crate::manifest::{MANIFEST_FILE_NAME, ShelfManifest};
+use crate::models::{
+    BatchAnalysisResponse, CabinetAssignment, CabinetPlan, FileMovement, ItemAnalysis,
+    OrganizationPlan, ProcessingItem, ShelfAssignment, ShelfPlan, SkeletonCabinet,
+    SkeletonResponse,
+};
+use crate::naming::{ExtensionPolicy, NamingConvention};
+use crate::providers::LLMProvider;
+use crate::trash::Trash;
+use clap::ValueEnum;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+
+#[test]
+fn test_cabinet_named_after_database_is_renamed() {
+    let plan = OrganizationPlan {
+        cabinets: vec![CabinetPlan {
+            id: 1,
+            name: LEGACY_DB_NAME.to_string(),
+            description: "Database files".to_string(),
+            shelves: vec![ShelfPlan {
+                id: 1,
+                name: "..".to_string(),
+                description: "Parent".to_string(),
+                item_count: 1,
+            }],
+        }],
+        movements: vec![FileMovement {
+            from: PathBuf::from("/tmp/notes.db"),
+            to_cabinet: LEGACY_DB_NAME.to_string(),
+            to_shelf: "..".to_string(),
+            new_name: None,
+            reasoning: String::new(),
+            opaque_reason: None,
+        }],
+    };
+
+    let plan = FileOrganizer::transform_plan(plan, &OrganizeOptions::default()).unwrap();
+
+    assert_eq!(plan.cabinets[0].name, "fs_organizer.db_folder");
+    assert_eq!(plan.cabinets[0].shelves[0].name, "folder");
+    assert_eq!(plan.movements[0].to_cabinet, "fs_organizer.db_folder");
+    assert_eq!(plan.movements[0].to_shelf, "folder");
+}
+
+fn two_shelf_plan() -> OrganizationPlan {
+    OrganizationPlan {
+        cabinets: vec![CabinetPlan {
+            id: 1,
+            name: "Finance".to_string(),
+            description: "Money matters".to_string(),
+            shelves: vec![
+                ShelfPlan {
+                    id: 1,
+                    name: "Taxes".to_string(),
+                    description: "Tax returns".to_string(),
+                    item_count: 1,
+                },
+                ShelfPlan {
+                    id: 2,
+                    name: "Banking".to_string(),
+                    description: "Statements".to_string(),
+                    item_count: 1,
+                },
+            ],
+        }],
+        movements: vec![FileMovement {
+            from: PathBuf::from("/tmp/return.pdf"),
+            to_cabinet: "Finance".to_string(),
+            to_shelf: "Taxes".to_string(),
+            new_name: None,
+            reasoning: String::new(),
+            opaque_reason: None,
+        }],
+    }
+}
+
+#[test]
+fn test_flat_plan_combines_cabinet_and_shelf() {
+    let options = OrganizeOptions {
+        flat: Some(FlatLayout::Combined),
+        ..Default::default()
+    };
+
+    let plan = FileOrganizer::transform_plan(two_shelf_plan(), &options).unwrap();
+
+    let names = plan
+        .cabinets
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Finance - Taxes", "Finance - Banking"]);
+    assert!(plan.cabinets.iter().all(|c| c.shelves.is_empty()));
+    assert_eq!(plan.movements[0].to_cabinet, "Finance - Taxes");
+    assert!(plan.movements[0].to_shelf.is_empty());
+}
+
+#[tokio::test]
+async fn test_flat_plan_moves_into_a_single_directory_level() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    let source = base_path.join("return.pdf");
+    fs::write(&source, "%PDF-1.4").unwrap();
+    let options = OrganizeOptions {
+        flat: Some(FlatLayout::Combined),
+        ..Default::default()
+    };
+    let mut plan = two_shelf_plan();
+    plan.movements[0].from = source.clone();
+    let plan = FileOrganizer::transform_plan(plan, &options).unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    organizer.execute_plan(&plan, &options).await.unwrap();
+
+    assert!(!source.exists());
+    assert!(base_path.join("Finance - Taxes/return.pdf").exists());
+}
+
+#[test]
+fn test_flat_plan_with_shelf_layout() {
+    let options = OrganizeOptions {
+        flat: Some(FlatLayout::Shelf),
+        ..Default::default()
+    };
+
+    let plan = FileOrganizer::transform_plan(two_shelf_plan(), &options).unwrap();
+
+    assert_eq!(plan.cabinets[0].name, "Taxes");
+    assert_eq!(plan.movements[0].to_cabinet, "Taxes");
+}
+
+#[test]
+fn test_no_rename_keeps_original_names() {
+    let mut plan = two_shelf_plan();
+    plan.movements[0].new_name = Some("tax-return-2023.pdf".to_string());
+    let options = OrganizeOptions {
+        no_rename: true,
+        naming_convention: NamingConvention::Kebab,
+        ..Default::default()
+    };
+
+    let plan = FileOrganizer::transform_plan(plan, &options).unwrap();
+
+    assert_eq!(plan.movements[0].new_name, None);
+    assert_eq!(
+        FileOrganizer::target_name(&plan.movements[0], ExtensionPolicy::Preserve),
+        "return.pdf"
+    );
+}
+
+#[tokio::test]
+async fn test_git_repository_is_characterized_by_readme() {
+    let temp_dir = TempDir::new().unwrap();
+    let repo = temp_dir.path().join("shelfie");
+    fs::create_dir_all(repo.join(".git")).unwrap();
+    fs::create_dir_all(repo.join("src")).unwrap();
+    fs::write(
+        repo.join("README.md"),
+        "# Shelfie\n\nAn   AI file organizer",
+    )
+    .unwrap();
+
+    assert!(FileOrganizer::is_git_repository(&repo));
+    assert!(!FileOrganizer::is_git_repository(temp_dir.path()));
+
+    match FileOrganizer::process_directory_static(&repo, true, false, None)
+        .await
+        .unwrap()
+    {
+        ProcessingItem::Directory(dir) => {
+            assert!(dir.is_git_repository);
+            assert!(dir.sampled_items.is_empty());
+            assert_eq!(
+                dir.readme_preview.as_deref(),
+                Some("# Shelfie An AI file organizer")
+            );
+        }
+        ProcessingItem::File(_) => panic!("Repository should be a directory item"),
+    }
+}
+
+#[tokio::test]
+async fn test_deep_dir_preview_reads_a_few_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let misc = temp_dir.path().join("misc");
+    fs::create_dir_all(&misc).unwrap();
+    fs::write(misc.join("a.txt"), "Quarterly invoice for consulting").unwrap();
+    fs::write(misc.join("b.txt"), "Another invoice").unwrap();
+    fs::write(misc.join("notes.md"), "# Meeting notes").unwrap();
+
+    let preview = |deep_preview| {
+        let misc = misc.clone();
+        async move {
+            match FileOrganizer::process_directory_static(&misc, false, false, deep_preview)
+                .await
+                .unwrap()
+            {
+                ProcessingItem::Directory(dir) => dir.content_preview,
+                ProcessingItem::File(_) => panic!("Expected a directory item"),
+            }
+        }
+    };
+
+    assert_eq!(preview(None).await, None);
+
+    // One file per extension comes first
+    let two = preview(Some(2)).await.unwrap();
+    assert_eq!(two.split("\n\n").count(), 2);
+    assert!(two.contains("notes.md: # Meeting notes"), "{}", two);
+    assert!(two.contains("invoice"), "{}", two);
+
+    let all = preview(Some(10)).await.unwrap();
+    assert_eq!(all.split("\n\n").count(), 3);
+}
+
+#[test]
+fn test_plan_carries_database_ids() {
+    let temp_dir = TempDir::new().unwrap();
+    let organizer = FileOrganizer::new(
+        LLMProvider::new_mock(Vec::new()),
+        temp_dir.path().to_path_buf(),
+        None,
+    )
+    .unwrap();
+    let cabinet_id = organizer
+        .database
+        .create_cabinet("Finance", "Money")
+        .unwrap();
+    let shelf_id = organizer
+        .database
+        .create_shelf(cabinet_id, "Taxes", "Returns")
+        .unwrap();
+
+    let plan = organizer.create_organization_plan(None).unwrap();
+
+    assert_eq!(plan.cabinets[0].id, cabinet_id);
+    assert_eq!(plan.cabinets[0].shelves[0].id, shelf_id);
+    let json = serde_json::to_value(&plan).unwrap();
+    assert_eq!(json["cabinets"][0]["shelves"][0]["id"], shelf_id);
+}
+
+#[test]
+fn test_low_confidence_renames_keep_original_name() {
+    let temp_dir = TempDir::new().unwrap();
+    let organizer = FileOrganizer::new(
+        LLMProvider::new_mock(Vec::new()),
+        temp_dir.path().to_path_buf(),
+        None,
+    )
+    .unwrap();
+    let cabinet_id = organizer
+        .database
+        .create_cabinet("Finance", "Money")
+        .unwrap();
+    let shelf_id = organizer
+        .database
+        .create_shelf(cabinet_id, "Taxes", "Returns")
+        .unwrap();
+    for (name, suggested, confidence) in [
+        ("scan001.pdf", "tax-return-2023.pdf", 0.95),
+        ("scan002.pdf", "receipt.pdf", 0.3),
+    ] {
+        organizer
+            .database
+            .insert_item(&Item {
+                id: None,
+                shelf_id,
+                path: temp_dir.path().join(name).to_string_lossy().to_string(),
+                original_name: name.to_string(),
+                suggested_name: Some(suggested.to_string()),
+                description: String::new(),
+                file_type: "pdf".to_string(),
+                is_opaque_dir: false,
+                opaque_reason: None,
+                rename_confidence: Some(confidence),
+                content_hash: None,
+                processed_at: chrono::Utc::now(),
+            })
+            .unwrap();
+    }
+    let new_name = |plan: &OrganizationPlan, name: &str| {
+        plan.movements
+            .iter()
+            .find(|movement| movement.from.ends_with(name))
+            .unwrap()
+            .new_name
+            .clone()
+    };
+
+    let plan = organizer.create_organization_plan(None).unwrap();
+    assert_eq!(
+        new_name(&plan, "scan002.pdf").as_deref(),
+        Some("receipt.pdf")
+    );
+
+    let plan = organizer.create_organization_plan(Some(0.7)).unwrap();
+    assert_eq!(
+        new_name(&plan, "scan001.pdf").as_deref(),
+        Some("tax-return-2023.pdf")
+    );
+    assert_eq!(new_name(&plan, "scan002.pdf"), None);
+}
+
+#[test]
+fn test_join_contained_takes_a_single_name() {
+    let dir = Path::new("/base/Finance");
+
+    assert_eq!(
+        FileOrganizer::join_contained(dir, "report.pdf"),
+        Some(dir.join("report.pdf"))
+    );
+    for name in ["..", ".", "", "a/b", "/etc", "./report.pdf"] {
+        assert_eq!(FileOrganizer::join_contained(dir, name), None, "{:?}", name);
+    }
+}
+
+#[tokio::test]
+async fn test_moves_outside_base_path_are_refused() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().join("base");
+    fs::create_dir_all(&base_path).unwrap();
+    let source = base_path.join("passwd");
+    fs::write(&source, "root:x:0:0").unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    let plan = OrganizationPlan {
+        cabinets: vec![CabinetPlan {
+            id: 1,
+            name: "../../etc".to_string(),
+            description: String::new(),
+            shelves: vec![ShelfPlan {
+                id: 1,
+                name: "System".to_string(),
+                description: String::new(),
+                item_count: 1,
+            }],
+        }],
+        movements: vec![FileMovement {
+            from: source.clone(),
+            to_cabinet: "../../etc".to_string(),
+            to_shelf: "System".to_string(),
+            new_name: None,
+            reasoning: String::new(),
+            opaque_reason: None,
+        }],
+    };
+
+    organizer
+        .execute_plan(&plan, &OrganizeOptions::default())
+        .await
+        .unwrap();
+
+    assert!(source.exists(), "The file should not have been moved");
+    assert!(!temp_dir.path().join("etc").exists());
+    assert!(
+        !temp_dir
+            .path()
+            .parent()
+            .unwrap()
+            .join("etc/System")
+            .exists()
+    );
+}
+
+#[tokio::test]
+async fn test_plan_does_not_move_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let source = temp_dir.path().join("invoice.txt");
+    fs::write(&source, "Invoice #42").unwrap();
+
+    let response = BatchAnalysisResponse {
+        items: vec![ItemAnalysis {
+            id: "0".to_string(),
+            description: "An invoice".to_string(),
+            suggested_name: String::new(),
+            rename_confidence: 0.0,
+            is_opaque_directory: false,
+            cabinet: CabinetAssignment {
+                assignment_type: "new".to_string(),
+                existing_id: 0,
+                new_name: "Finance".to_string(),
+                new_description: "Money matters".to_string(),
+            },
+            shelf: ShelfAssignment {
+                assignment_type: "new".to_string(),
+                existing_id: 0,
+                new_name: "Invoices".to_string(),
+                new_description: "Bills".to_string(),
+            },
+        }],
+    };
+    let organizer = FileOrganizer::new(
+        LLMProvider::new_mock(vec![serde_json::to_string(&response).unwrap()]),
+        temp_dir.path().to_path_buf(),
+        None,
+    )
+    .unwrap();
+
+    let plan = organizer.plan(&OrganizeOptions::default()).await.unwrap();
+
+    assert_eq!(plan.cabinets[0].name, "Finance");
+    assert_eq!(plan.movements.len(), 1);
+    assert_eq!(plan.movements[0].to_shelf, "Invoices");
+    assert!(source.exists(), "Planning should not move anything");
+    assert!(!temp_dir.path().join("Finance").exists());
+}
+
+#[tokio::test]
+async fn test_rescan_after_organizing_finds_nothing_new() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("invoice.txt"), "Invoice #42").unwrap();
+
+    let response = BatchAnalysisResponse {
+        items: vec![ItemAnalysis {
+            id: "0".to_string(),
+            description: "An invoice".to_string(),
+            suggested_name: "ACME Invoice".to_string(),
+            rename_confidence: 1.0,
+            is_opaque_directory: false,
+            cabinet: CabinetAssignment {
+                assignment_type: "new".to_string(),
+                existing_id: 0,
+                new_name: "Finance".to_string(),
+                new_description: "Money matters".to_string(),
+            },
+            shelf: ShelfAssignment {
+                assignment_type: "new".to_string(),
+                existing_id: 0,
+                new_name: "Invoices".to_string(),
+                new_description: "Bills".to_string(),
+            },
+        }],
+    };
+    let organizer = FileOrganizer::new(
+        LLMProvider::new_mock(vec![serde_json::to_string(&response).unwrap()]),
+        temp_dir.path().to_path_buf(),
+        None,
+    )
+    .unwrap();
+    // Deep enough to reach into the shelves
+    let options = OrganizeOptions {
+        max_depth: 3,
+        ..Default::default()
+    };
+    let plan = organizer.plan(&options).await.unwrap();
+    organizer.execute_plan(&plan, &options).await.unwrap();
+    assert!(
+        temp_dir
+            .path()
+            .join("Finance/Invoices/ACME Invoice.txt")
+            .exists()
+    );
+
+    let scan = organizer.collect_items(&options).await.unwrap();
+
+    assert!(scan.items.is_empty());
+    assert_eq!(scan.organized_dirs, 2);
+}
+
+#[tokio::test]
+async fn test_interrupted_scan_resumes_from_its_checkpoints() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    fs::write(base_path.join("invoice.txt"), "Invoice #42").unwrap();
+    fs::write(base_path.join("notes.txt"), "Groceries").unwrap();
+
+    let analysis = |id: &str| ItemAnalysis {
+        id: id.to_string(),
+        description: "A document".to_string(),
+        suggested_name: String::new(),
+        rename_confidence: 0.0,
+        is_opaque_directory: false,
+        cabinet: CabinetAssignment {
+            assignment_type: "new".to_string(),
+            existing_id: 0,
+            new_name: "Documents".to_string(),
+            new_description: "Paperwork".to_string(),
+        },
+        shelf: ShelfAssignment {
+            assignment_type: "new".to_string(),
+            existing_id: 0,
+            new_name: "Misc".to_string(),
+            new_description: "Everything else".to_string(),
+        },
+    };
+    let response = BatchAnalysisResponse {
+        items: vec![analysis("0"), analysis("1")],
+    };
+    let organizer = FileOrganizer::new(
+        LLMProvider::new_mock(vec![serde_json::to_string(&response).unwrap()]),
+        base_path.clone(),
+        None,
+    )
+    .unwrap();
+    let options = OrganizeOptions::default();
+
+    // A run stopped after scanning leaves its items checkpointed
+    let scan = organizer.collect_items(&options).await.unwrap();
+    assert_eq!((scan.items.len(), scan.resumed), (2, 0));
+    let scan = organizer.collect_items(&options).await.unwrap();
+    assert_eq!((scan.items.len(), scan.resumed), (2, 2));
+
+    // Changed since, so scanned again
+    fs::File::options()
+        .write(true)
+        .open(base_path.join("notes.txt"))
+        .unwrap()
+        .set_modified(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap();
+    let scan = organizer.collect_items(&options).await.unwrap();
+    assert_eq!((scan.items.len(), scan.resumed), (2, 1));
+
+    // Stored items no longer need their checkpoints
+    organizer.plan(&options).await.unwrap();
+    let settings = FileOrganizer::scan_settings(&options);
+    assert!(
+        organizer
+            .database
+            .load_pending_items(&settings)
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[tokio::test]
+async fn test_plan_skeleton_seeds_cabinets_and_prunes_unused_ones() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::write(temp_dir.path().join("invoice.txt"), "Invoice #42").unwrap();
+
+    let skeleton = SkeletonResponse {
+        cabinets: ["Finance", "Photos"]
+            .iter()
+            .map(|name| SkeletonCabinet {
+                name: name.to_string(),
+                description: String::new(),
+            })
+            .collect(),
+    };
+    // The skeleton is seeded into an empty database, so Finance gets id 1
+    let response = BatchAnalysisResponse {
+        items: vec![ItemAnalysis {
+            id: "0".to_string(),
+            description: "An invoice".to_string(),
+            suggested_name: String::new(),
+            rename_confidence: 0.0,
+            is_opaque_directory: false,
+            cabinet: CabinetAssignment {
+                assignment_type: "existing".to_string(),
+                existing_id: 1,
+                new_name: String::new(),
+                new_description: String::new(),
+            },
+            shelf: ShelfAssignment {
+                assignment_type: "new".to_string(),
+                existing_id: 0,
+                new_name: "Invoices".to_string(),
+                new_description: "Bills".to_string(),
+            },
+        }],
+    };
+    let organizer = FileOrganizer::new(
+        LLMProvider::new_mock(vec![
+            serde_json::to_string(&skeleton).unwrap(),
+            serde_json::to_string(&response).unwrap(),
+        ]),
+        temp_dir.path().to_path_buf(),
+        None,
+    )
+    .unwrap();
+    let options = OrganizeOptions {
+        plan_skeleton: Some(10),
+        ..Default::default()
+    };
+
+    let plan = organizer.plan(&options).await.unwrap();
+
+    let cabinets = plan
+        .cabinets
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(cabinets, vec!["Finance"]);
+    assert_eq!(plan.movements[0].to_shelf, "Invoices");
+}
+
+#[tokio::test]
+async fn test_cabinets_from_dirs_keeps_items_in_their_project() {
+    let temp_dir = TempDir::new().unwrap();
+    for project in ["alpha", "beta"] {
+        fs::create_dir(temp_dir.path().join(project)).unwrap();
+        fs::write(
+            temp_dir.path().join(project).join("notes.txt"),
+            "Meeting notes",
+        )
+        .unwrap();
+    }
+
+    // The model wants a new cabinet for both, but each stays in its project's cabinet
+    let analysis = |id: &str| ItemAnalysis {
+        id: id.to_string(),
+        description: "Notes".to_string(),
+        suggested_name: String::new(),
+        rename_confidence: 0.0,
+        is_opaque_directory: false,
+        cabinet: CabinetAssignment {
+            assignment_type: "new".to_string(),
+            existing_id: 0,
+            new_name: "Notes".to_string(),
+            new_description: "All notes".to_string(),
+        },
+        shelf: ShelfAssignment {
+            assignment_type: "new".to_string(),
+            existing_id: 0,
+            new_name: "Meetings".to_string(),
+            new_description: "Meeting notes".to_string(),
+        },
+    };
+    let response = BatchAnalysisResponse {
+        items: vec![analysis("0"), analysis("1")],
+    };
+    let organizer = FileOrganizer::new(
+        LLMProvider::new_mock(vec![serde_json::to_string(&response).unwrap()]),
+        temp_dir.path().to_path_buf(),
+        None,
+    )
+    .unwrap();
+    let options = OrganizeOptions {
+        cabinets_from_dirs: true,
+        ..Default::default()
+    };
+
+    let plan = organizer.plan(&options).await.unwrap();
+
+    let mut cabinets = plan
+        .cabinets
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>();
+    cabinets.sort();
+    assert_eq!(cabinets, vec!["alpha", "beta"]);
+    assert_eq!(plan.movements.len(), 2);
+    for movement in &plan.movements {
+        let project = movement.from.parent().unwrap().file_name().unwrap();
+        assert_eq!(movement.to_cabinet, project.to_string_lossy());
+        assert_eq!(movement.to_shelf, "Meetings");
+    }
+}
+
+/// Executes a plan moving `report.txt` onto a shelf that already holds one.
+async fn execute_with_conflict(policy: ConflictPolicy) -> (TempDir, PathBuf, PathBuf) {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    let source = base_path.join("report.txt");
+    fs::write(&source, "new report").unwrap();
+    let shelf_dir = base_path.join("Finance/Taxes");
+    fs::create_dir_all(&shelf_dir).unwrap();
+    fs::write(shelf_dir.join("report.txt"), "old report").unwrap();
+
+    let organizer = FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path, None).unwrap();
+    let mut plan = two_shelf_plan();
+    plan.movements[0].from = source.clone();
+    let options = OrganizeOptions {
+        on_conflict: policy,
+        ..Default::default()
+    };
+
+    organizer.execute_plan(&plan, &options).await.unwrap();
+    (temp_dir, source, shelf_dir)
+}
+
+#[tokio::test]
+async fn test_conflict_skip_leaves_source_in_place() {
+    let (_dir, source, shelf_dir) = execute_with_conflict(ConflictPolicy::Skip).await;
+
+    assert_eq!(fs::read_to_string(&source).unwrap(), "new report");
+    assert_eq!(
+        fs::read_to_string(shelf_dir.join("report.txt")).unwrap(),
+        "old report"
+    );
+}
+
+#[tokio::test]
+async fn test_conflict_rename_keeps_both() {
+    let (_dir, source, shelf_dir) = execute_with_conflict(ConflictPolicy::Rename).await;
+
+    assert!(!source.exists());
+    assert_eq!(
+        fs::read_to_string(shelf_dir.join("report.txt")).unwrap(),
+        "old report"
+    );
+    assert_eq!(
+        fs::read_to_string(shelf_dir.join("report (2).txt")).unwrap(),
+        "new report"
+    );
+}
+
+#[tokio::test]
+async fn test_conflict_overwrite_replaces_destination() {
+    let (_dir, source, shelf_dir) = execute_with_conflict(ConflictPolicy::Overwrite).await;
+
+    assert!(!source.exists());
+    assert_eq!(
+        fs::read_to_string(shelf_dir.join("report.txt")).unwrap(),
+        "new report"
+    );
+    assert_eq!(fs::read_dir(&shelf_dir).unwrap().count(), 1);
+}
+
+#[tokio::test]
+async fn test_conflict_ask_skips_without_prompts() {
+    crate::prompts::set_non_interactive(true);
+
+    let (_dir, source, shelf_dir) = execute_with_conflict(ConflictPolicy::Ask).await;
+
+    assert_eq!(fs::read_to_string(&source).unwrap(), "new report");
+    assert_eq!(
+        fs::read_to_string(shelf_dir.join("report.txt")).unwrap(),
+        "old report"
+    );
+}
+
+#[tokio::test]
+async fn test_items_moved_to_the_same_name_both_survive() {
+    crate::prompts::set_non_interactive(true);
+
+    for policy in ConflictPolicy::value_variants() {
+        let temp_dir = TempDir::new().unwrap();
+        let base_path = temp_dir.path().to_path_buf();
+        for dir in ["2023", "2024"] {
+            fs::create_dir(base_path.join(dir)).unwrap();
+            fs::write(base_path.join(dir).join("report.txt"), dir).unwrap();
+        }
+
+        let organizer =
+            FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+        let mut plan = two_shelf_plan();
+        plan.movements = ["2023", "2024"]
+            .iter()
+            .map(|dir| FileMovement {
+                from: base_path.join(dir).join("report.txt"),
+                to_cabinet: "Finance".to_string(),
+                to_shelf: "Taxes".to_string(),
+                new_name: None,
+                reasoning: String::new(),
+                opaque_reason: None,
+            })
+            .collect();
+        let options = OrganizeOptions {
+            on_conflict: *policy,
+            ..Default::default()
+        };
+
+        let report = organizer.execute_plan(&plan, &options).await.unwrap();
+
+        let shelf_dir = base_path.join("Finance/Taxes");
+        assert_eq!(report.moved, 2, "{:?}", policy);
+        assert_eq!(
+            fs::read_to_string(shelf_dir.join("report.txt")).unwrap(),
+            "2023"
+        );
+        assert_eq!(
+            fs::read_to_string(shelf_dir.join("report (2).txt")).unwrap(),
+            "2024"
+        );
+    }
+}
+
+/// Executes a plan renaming `Photo.JPG` on its shelf to `photo.JPG`.
+async fn execute_case_only_rename(filesystem_case: FilesystemCase, trash: bool) -> TempDir {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    let shelf_dir = base_path.join("Finance/Taxes");
+    fs::create_dir_all(&shelf_dir).unwrap();
+    fs::write(shelf_dir.join("Photo.JPG"), "photo").unwrap();
+
+    let organizer = FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path, None).unwrap();
+    let mut plan = two_shelf_plan();
+    plan.movements[0].from = organizer.base_path.join("Finance/Taxes/Photo.JPG");
+    plan.movements[0].new_name = Some("photo".to_string());
+    let options = OrganizeOptions {
+        filesystem_case,
+        trash,
+        ..Default::default()
+    };
+
+    let report = organizer.execute_plan(&plan, &options).await.unwrap();
+    assert_eq!(report.moved, 1);
+    assert!(report.conflicts.is_empty());
+    assert_eq!(report.trashed, 0);
+    temp_dir
+}
+
+fn shelf_entries(base_path: &std::path::Path) -> Vec<String> {
+    fs::read_dir(base_path.join("Finance/Taxes"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_case_only_rename_goes_through_a_temporary_name() {
+    // Forced, the two-step rename works on a case-sensitive filesystem too
+    let temp_dir = execute_case_only_rename(FilesystemCase::Insensitive, true).await;
+
+    // Nothing went to the trash, since the copy would have been the original itself
+    assert_eq!(shelf_entries(temp_dir.path()), vec!["photo.JPG"]);
+    assert!(!Trash::dir(temp_dir.path()).exists());
+}
+
+#[cfg(any(target_os = "macos", target_os = "windows"))]
+#[tokio::test]
+async fn test_case_only_rename_on_a_case_insensitive_filesystem() {
+    let temp_dir = execute_case_only_rename(FilesystemCase::Auto, false).await;
+
+    assert_eq!(shelf_entries(temp_dir.path()), vec!["photo.JPG"]);
+}
+
+#[tokio::test]
+async fn test_directories_differing_in_case_are_reused_where_case_is_ignored() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    fs::create_dir_all(base_path.join("finance/taxes")).unwrap();
+    let source = base_path.join("return.pdf");
+    fs::write(&source, "return").unwrap();
+
+    let organizer = FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path, None).unwrap();
+    let mut plan = two_shelf_plan();
+    plan.movements[0].from = source.clone();
+    let options = OrganizeOptions {
+        filesystem_case: FilesystemCase::Insensitive,
+        ..Default::default()
+    };
+    organizer.execute_plan(&plan, &options).await.unwrap();
+
+    let base_path = temp_dir.path();
+    assert!(base_path.join("finance/taxes/return.pdf").exists());
+    assert!(base_path.join("finance/Banking").is_dir());
+    let mut top_level = fs::read_dir(base_path)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|name| !name.starts_with('.'))
+        .collect::<Vec<_>>();
+    top_level.sort();
+    assert_eq!(top_level, vec!["finance"]);
+}
+
+/// Executes a plan moving two byte-identical invoices, scanned with `--dedupe`, onto
+/// the same shelf.
+/// Moves two byte-identical invoices with `--dedupe`. Their items carry
+/// `stored_hash`, or no hash, as if analyzed without `--dedupe`.
+async fn execute_with_duplicates(
+    action: DuplicateAction,
+    stored_hash: Option<&str>,
+) -> (TempDir, ExecutionReport) {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    let database = crate::database::Database::open_or_create(&base_path).unwrap();
+    let cabinet_id = database.create_cabinet("Finance", "Money").unwrap();
+    let shelf_id = database.create_shelf(cabinet_id, "Taxes", "Bills").unwrap();
+    let mut plan = two_shelf_plan();
+    plan.movements.clear();
+    for name in ["invoice.pdf", "invoice (1).pdf"] {
+        let source = base_path.join(name);
+        fs::write(&source, "%PDF-1.4 invoice").unwrap();
+        database
+            .insert_item(&Item {
+                id: None,
+                shelf_id,
+                path: source.to_string_lossy().to_string(),
+                original_name: name.to_string(),
+                suggested_name: None,
+                description: "An invoice".to_string(),
+                file_type: "application/pdf".to_string(),
+                is_opaque_dir: false,
+                opaque_reason: None,
+                rename_confidence: None,
+                content_hash: stored_hash.map(str::to_string),
+                processed_at: chrono::Utc::now(),
+            })
+            .unwrap();
+        plan.movements.push(FileMovement {
+            from: source,
+            to_cabinet: "Finance".to_string(),
+            to_shelf: "Taxes".to_string(),
+            new_name: None,
+            reasoning: String::new(),
+            opaque_reason: None,
+        });
+    }
+    drop(database);
+
+    let organizer = FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path, None).unwrap();
+    let options = OrganizeOptions {
+        dedupe: Some(action),
+        ..Default::default()
+    };
+    let report = organizer.execute_plan(&plan, &options).await.unwrap();
+    (temp_dir, report)
+}
+
+#[tokio::test]
+async fn test_dedupe_moves_only_the_first_of_identical_files() {
+    let (temp_dir, report) = execute_with_duplicates(DuplicateAction::Report, Some("same")).await;
+    let base_path = temp_dir.path();
+
+    assert_eq!(report.moved, 1);
+    assert_eq!(
+        report.duplicates,
+        vec!["invoice (1).pdf (same as Finance/Taxes/invoice.pdf)".to_string()]
+    );
+    assert!(base_path.join("Finance/Taxes/invoice.pdf").exists());
+    assert!(!base_path.join("invoice.pdf").exists());
+    assert!(base_path.join("invoice (1).pdf").exists());
+}
+
+#[tokio::test]
+async fn test_dedupe_hashes_items_analyzed_without_it() {
+    let (temp_dir, report) = execute_with_duplicates(DuplicateAction::Report, None).await;
+
+    assert_eq!(report.moved, 1);
+    assert_eq!(
+        report.duplicates,
+        vec!["invoice (1).pdf (same as Finance/Taxes/invoice.pdf)".to_string()]
+    );
+    let database = crate::database::Database::open_or_create(temp_dir.path()).unwrap();
+    let hashes = database
+        .find_duplicate_hashes()
+        .unwrap()
+        .into_iter()
+        .map(|group| group.len())
+        .collect::<Vec<_>>();
+    assert_eq!(hashes, vec![2]);
+}
+
+#[tokio::test]
+async fn test_dedupe_trashes_or_links_duplicates() {
+    let (temp_dir, report) = execute_with_duplicates(DuplicateAction::Trash, Some("same")).await;
+    assert_eq!(report.moved, 1);
+    assert_eq!(report.trashed, 1);
+    assert!(!temp_dir.path().join("invoice (1).pdf").exists());
+    assert!(
+        walkdir::WalkDir::new(Trash::dir(temp_dir.path()))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .any(|e| e.file_name() == "invoice (1).pdf")
+    );
+
+    let (temp_dir, report) = execute_with_duplicates(DuplicateAction::Hardlink, Some("same")).await;
+    assert_eq!(report.moved, 1);
+    let duplicate = temp_dir.path().join("invoice (1).pdf");
+    assert_eq!(fs::read_to_string(&duplicate).unwrap(), "%PDF-1.4 invoice");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        assert_eq!(fs::metadata(&duplicate).unwrap().nlink(), 2);
+    }
+}
+
+#[tokio::test]
+async fn test_trash_keeps_originals() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    let source = base_path.join("invoice.txt");
+    fs::write(&source, "Invoice #42").unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    let mut plan = two_shelf_plan();
+    plan.movements = vec![FileMovement {
+        from: source.clone(),
+        to_cabinet: plan.cabinets[0].name.clone(),
+        to_shelf: plan.cabinets[0].shelves[0].name.clone(),
+        new_name: Some("invoice".to_string()),
+        reasoning: String::new(),
+        opaque_reason: None,
+    }];
+    let options = OrganizeOptions {
+        trash: true,
+        ..Default::default()
+    };
+
+    let report = organizer.execute_plan(&plan, &options).await.unwrap();
+    assert_eq!(report.trashed, 1);
+
+    let moved = base_path
+        .join(&plan.cabinets[0].name)
+        .join(&plan.cabinets[0].shelves[0].name)
+        .join("invoice.txt");
+    assert_eq!(fs::read_to_string(moved).unwrap(), "Invoice #42");
+    assert!(!source.exists());
+    let trashed = walkdir::WalkDir::new(Trash::dir(&base_path))
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_name() == "invoice.txt")
+        .count();
+    assert_eq!(trashed, 1);
+}
+
+#[tokio::test]
+async fn test_dry_run_leaves_the_filesystem_untouched() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    fs::write(base_path.join("report.txt"), "new report").unwrap();
+    fs::create_dir(base_path.join("Finance")).unwrap();
+    fs::write(base_path.join("statement.txt"), "statement").unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    let mut plan = two_shelf_plan();
+    plan.movements = ["report.txt", "statement.txt", "gone.txt"]
+        .iter()
+        .map(|name| FileMovement {
+            from: base_path.join(name),
+            to_cabinet: "Finance".to_string(),
+            to_shelf: "Taxes".to_string(),
+            new_name: Some("report".to_string()),
+            reasoning: String::new(),
+            opaque_reason: None,
+        })
+        .collect();
+    let options = OrganizeOptions {
+        dry_run: true,
+        ..Default::default()
+    };
+    let entries = || {
+        walkdir::WalkDir::new(&base_path)
+            .into_iter()
+            .map(|entry| entry.unwrap().into_path())
+            .collect::<Vec<_>>()
+    };
+    let before = entries();
+
+    let report = organizer.execute_plan(&plan, &options).await.unwrap();
+
+    assert_eq!(entries(), before);
+    // Both land on the same name, so the second would be numbered
+    assert_eq!(report.moved, 2);
+    assert_eq!(report.missing, ["gone.txt"]);
+    assert!(report.conflicts.is_empty());
+}
+
+fn plan_with_cabinets(cabinets: &[(&str, &[(&str, usize)])]) -> OrganizationPlan {
+    let mut id = 0;
+    let mut plan = OrganizationPlan {
+        cabinets: Vec::new(),
+        movements: Vec::new(),
+    };
+    for (cabinet, shelves) in cabinets {
+        id += 1;
+        let mut cabinet_plan = CabinetPlan {
+            id,
+            name: cabinet.to_string(),
+            description: String::new(),
+            shelves: Vec::new(),
+        };
+        for (shelf, item_count) in *shelves {
+            id += 1;
+            cabinet_plan.shelves.push(ShelfPlan {
+                id,
+                name: shelf.to_string(),
+                description: String::new(),
+                item_count: *item_count,
+            });
+            for i in 0..*item_count {
+                plan.movements.push(FileMovement {
+                    from: PathBuf::from(format!("/tmp/{}-{}-{}", cabinet, shelf, i)),
+                    to_cabinet: cabinet.to_string(),
+                    to_shelf: shelf.to_string(),
+                    new_name: None,
+                    reasoning: String::new(),
+                    opaque_reason: None,
+                });
+            }
+        }
+        plan.cabinets.push(cabinet_plan);
+    }
+    plan
+}
+
+#[test]
+fn test_excess_cabinets_are_merged_into_overflow() {
+    let plan = plan_with_cabinets(&[
+        ("Small", &[("Misc", 1)]),
+        ("Finance", &[("Taxes", 5)]),
+        ("Photos", &[("Trips", 3)]),
+    ]);
+
+    let plan = FileOrganizer::enforce_limits(plan, 2, 10);
+
+    let names = plan
+        .cabinets
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Finance", "Overflow"]);
+    assert_eq!(plan.cabinets[1].shelves.len(), 2);
+    let moved = plan
+        .movements
+        .iter()
+        .find(|m| m.from == std::path::Path::new("/tmp/Small-Misc-0"))
+        .unwrap();
+    assert_eq!(moved.to_cabinet, "Overflow");
+    assert_eq!(moved.to_shelf, "Small - Misc");
+}
+
+#[test]
+fn test_excess_shelves_are_merged_into_other() {
+    let plan = plan_with_cabinets(&[("Finance", &[("Taxes", 5), ("Receipts", 1), ("Banking", 3)])]);
+
+    let plan = FileOrganizer::enforce_limits(plan, 10, 2);
+
+    let shelves = &plan.cabinets[0].shelves;
+    assert_eq!(shelves.len(), 2);
+    assert_eq!(shelves[0].name, "Taxes");
+    assert_eq!(shelves[1].name, "Other");
+    assert_eq!(shelves[1].item_count, 4);
+    assert!(
+        plan.movements
+            .iter()
+            .all(|m| m.to_shelf == "Taxes" || m.to_shelf == "Other")
+    );
+}
+
+#[test]
+fn test_new_overflow_and_other_get_fresh_ids() {
+    let plan = plan_with_cabinets(&[
+        ("Small", &[("Misc", 1)]),
+        ("Finance", &[("Taxes", 5), ("Receipts", 1), ("Banking", 3)]),
+    ]);
+
+    let plan = FileOrganizer::enforce_limits(plan, 1, 2);
+
+    let ids = plan
+        .cabinets
+        .iter()
+        .flat_map(|c| std::iter::once(c.id).chain(c.shelves.iter().map(|s| s.id)))
+        .collect::<Vec<_>>();
+    let overflow = &plan.cabinets[0];
+    assert_eq!(overflow.name, "Overflow");
+    assert!(overflow.id < 0);
+    let other = overflow.shelves.iter().find(|s| s.name == "Other").unwrap();
+    assert!(other.id < 0);
+    assert_ne!(other.id, overflow.id);
+    let mut unique = ids.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), ids.len());
+}
+
+#[test]
+fn test_existing_overflow_and_other_are_reused() {
+    let plan = plan_with_cabinets(&[
+        ("Overflow", &[("Old", 1)]),
+        ("Finance", &[("Taxes", 5), ("Other", 1), ("Banking", 3)]),
+        ("Photos", &[("Trips", 3)]),
+        ("Small", &[("Misc", 2)]),
+    ]);
+    let overflow_id = plan.cabinets[0].id;
+    let other_id = plan.cabinets[1].shelves[1].id;
+
+    let plan = FileOrganizer::enforce_limits(plan, 3, 2);
+
+    let names = plan
+        .cabinets
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Finance", "Photos", "Overflow"]);
+    let overflow = &plan.cabinets[2];
+    assert_eq!(overflow.id, overflow_id);
+    let shelves = overflow
+        .shelves
+        .iter()
+        .map(|s| s.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(shelves, vec!["Old", "Small - Misc"]);
+
+    let finance = &plan.cabinets[0];
+    assert_eq!(finance.shelves.len(), 2);
+    assert_eq!(finance.shelves[1].name, "Other");
+    assert_eq!(finance.shelves[1].id, other_id);
+    assert_eq!(finance.shelves[1].item_count, 4);
+    assert!(plan.movements.iter().all(|m| m.to_shelf != "Banking"));
+}
+
+#[test]
+fn test_plan_within_limits_is_unchanged() {
+    let plan = plan_with_cabinets(&[("Finance", &[("Taxes", 2)]), ("Photos", &[("Trips", 1)])]);
+
+    let limited = FileOrganizer::enforce_limits(plan.clone(), 2, 1);
+
+    assert_eq!(
+        serde_json::to_value(&limited).unwrap(),
+        serde_json::to_value(&plan).unwrap()
+    );
+}
+
+#[test]
+fn test_strict_plan_is_rejected_instead_of_merged() {
+    let options = OrganizeOptions {
+        batch: BatchOptions {
+            max_shelves: Some(2),
+            strict: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let plan = plan_with_cabinets(&[("Finance", &[("Taxes", 5), ("Receipts", 1), ("Banking", 3)])]);
+
+    let error = FileOrganizer::transform_plan(plan, &options).unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("Cabinet Finance has 3 shelves, more than the limit of 2"),
+        "{}",
+        error
+    );
+}
+
+#[test]
+fn test_limit_options_tighten_the_batch_limits() {
+    let options = OrganizeOptions {
+        limit_cabinets: Some(2),
+        limit_shelves: Some(2),
+        ..Default::default()
+    };
+    let plan = plan_with_cabinets(&[
+        ("Archive", &[("Misc", 3), ("Taxes", 2), ("Receipts", 1)]),
+        ("Music", &[("Misc", 1)]),
+        ("Games", &[("Misc", 2)]),
+    ]);
+
+    let plan = FileOrganizer::transform_plan(plan, &options).unwrap();
+
+    let names = plan
+        .cabinets
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Archive", "Overflow"]);
+    assert!(plan.cabinets.iter().all(|c| c.shelves.len() <= 2));
+    assert_eq!(plan.movements.len(), 9);
+}
+
+#[test]
+fn test_unsorted_is_never_merged() {
+    let plan = plan_with_cabinets(&[
+        ("Unsorted", &[("Unsorted", 1), ("Later", 1)]),
+        ("Archive", &[("Misc", 3)]),
+        ("Music", &[("Misc", 2)]),
+        ("Games", &[("Misc", 1)]),
+    ]);
+
+    let plan = FileOrganizer::enforce_limits(plan, 3, 1);
+
+    let names = plan
+        .cabinets
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>();
+    // Unsorted counts towards the limit without being merged or losing shelves
+    assert_eq!(names, vec!["Archive", "Overflow", "Unsorted"]);
+    assert_eq!(plan.cabinets[2].shelves.len(), 2);
+}
+
+#[test]
+fn test_limit_of_one_with_unsorted_merges_into_unsorted() {
+    let plan = plan_with_cabinets(&[
+        ("Finance", &[("Taxes", 5)]),
+        ("Unsorted", &[("Unsorted", 1)]),
+        ("Photos", &[("Trips", 3)]),
+    ]);
+
+    let plan = FileOrganizer::enforce_limits(plan, 1, 10);
+
+    assert_eq!(plan.cabinets.len(), 1);
+    let unsorted = &plan.cabinets[0];
+    assert_eq!(unsorted.name, "Unsorted");
+    let shelves = unsorted
+        .shelves
+        .iter()
+        .map(|s| s.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        shelves,
+        vec!["Unsorted", "Finance - Taxes", "Photos - Trips"]
+    );
+    assert!(plan.movements.iter().all(|m| m.to_cabinet == "Unsorted"));
+}
+
+/// Files in `base_path` recorded as analyzed into the given cabinets and shelves.
+fn seed_analyzed(organizer: &FileOrganizer, cabinets: &[(&str, &[(&str, usize)])]) {
+    for (cabinet, shelves) in cabinets {
+        let cabinet_id = organizer.database.create_cabinet(cabinet, "").unwrap();
+        for (shelf, item_count) in *shelves {
+            let shelf_id = organizer
+                .database
+                .create_shelf(cabinet_id, shelf, "")
+                .unwrap();
+            for i in 0..*item_count {
+                let name = format!("{}-{}-{}.txt", cabinet, shelf, i);
+                let path = organizer.base_path.join(&name);
+                fs::write(&path, &name).unwrap();
+                organizer
+                    .database
+                    .insert_item(&Item {
+                        id: None,
+                        shelf_id,
+                        path: path.to_string_lossy().to_string(),
+                        original_name: name,
+                        suggested_name: None,
+                        description: String::new(),
+                        file_type: "text/plain".to_string(),
+                        is_opaque_dir: false,
+                        opaque_reason: None,
+                        rename_confidence: None,
+                        content_hash: None,
+                        processed_at: chrono::Utc::now(),
+                    })
+                    .unwrap();
+            }
+        }
+    }
+}
+
+/// Cabinets, shelves and where every item is filed, by name.
+fn structure(organizer: &FileOrganizer) -> Vec<(String, String, String)> {
+    let cabinets = organizer.database.list_cabinets().unwrap();
+    let shelves = organizer.database.list_shelves(None).unwrap();
+    let mut filed = shelves
+        .iter()
+        .map(|shelf| {
+            let cabinet = cabinets.iter().find(|c| c.id == shelf.cabinet_id).unwrap();
+            (cabinet.name.clone(), shelf.name.clone(), String::new())
+        })
+        .chain(
+            organizer
+                .database
+                .list_all_items()
+                .unwrap()
+                .into_iter()
+                .map(|item| {
+                    let shelf = shelves.iter().find(|s| s.id == item.shelf_id).unwrap();
+                    let cabinet = cabinets.iter().find(|c| c.id == shelf.cabinet_id).unwrap();
+                    (cabinet.name.clone(), shelf.name.clone(), item.original_name)
+                }),
+        )
+        .collect::<Vec<_>>();
+    filed.sort();
+    filed
+}
+
+#[tokio::test]
+async fn test_plan_and_dry_run_leave_the_structure_unchanged() {
+    let temp_dir = TempDir::new().unwrap();
+    let organizer = FileOrganizer::new(
+        LLMProvider::new_mock(Vec::new()),
+        temp_dir.path().to_path_buf(),
+        None,
+    )
+    .unwrap();
+    seed_analyzed(
+        &organizer,
+        &[
+            ("Archive", &[("Misc", 3), ("Taxes", 2), ("Receipts", 1)]),
+            ("Music", &[("Misc", 1)]),
+        ],
+    );
+    let before = structure(&organizer);
+    let options = OrganizeOptions {
+        resume: true,
+        auto_confirm: true,
+        limit_cabinets: Some(1),
+        limit_shelves: Some(1),
+        ..Default::default()
+    };
+
+    let plan = organizer.plan(&options).await.unwrap();
+    assert_eq!(plan.cabinets.len(), 1);
+    assert_eq!(structure(&organizer), before);
+
+    organizer
+        .analyze_and_organize(&OrganizeOptions {
+            dry_run: true,
+            ..options
+        })
+        .await
+        .unwrap();
+    assert_eq!(structure(&organizer), before);
+}
+
+#[tokio::test]
+async fn test_executed_limits_are_written_to_the_database() {
+    let temp_dir = TempDir::new().unwrap();
+    let organizer = FileOrganizer::new(
+        LLMProvider::new_mock(Vec::new()),
+        temp_dir.path().to_path_buf(),
+        None,
+    )
+    .unwrap();
+    seed_analyzed(
+        &organizer,
+        &[
+            ("Archive", &[("Misc", 3), ("Taxes", 2), ("Receipts", 1)]),
+            ("Music", &[("Misc", 1)]),
+            ("Games", &[("Misc", 2)]),
+        ],
+    );
+    let options = OrganizeOptions {
+        resume: true,
+        auto_confirm: true,
+        limit_cabinets: Some(2),
+        limit_shelves: Some(2),
+        ..Default::default()
+    };
+
+    organizer.analyze_and_organize(&options).await.unwrap();
+
+    let cabinets = organizer
+        .database
+        .list_cabinets()
+        .unwrap()
+        .into_iter()
+        .map(|c| c.name)
+        .collect::<Vec<_>>();
+    assert_eq!(cabinets.len(), 2);
+    assert!(cabinets.contains(&"Archive".to_string()));
+    assert!(cabinets.contains(&"Overflow".to_string()));
+    let filed = structure(&organizer);
+    assert!(filed.contains(&(
+        "Overflow".to_string(),
+        "Music - Misc".to_string(),
+        "Music-Misc-0.txt".to_string()
+    )));
+    assert!(filed.contains(&(
+        "Archive".to_string(),
+        "Other".to_string(),
+        "Archive-Receipts-0.txt".to_string()
+    )));
+    assert_eq!(organizer.database.list_all_items().unwrap().len(), 9);
+    assert!(
+        temp_dir
+            .path()
+            .join("Overflow/Music - Misc/Music-Misc-0.txt")
+            .exists()
+    );
+
+    // The database now matches the plan, so the next run has nothing to merge
+    let plan = organizer.create_organization_plan(None).unwrap();
+    let limited = FileOrganizer::enforce_limits(plan.clone(), 2, 2);
+    assert_eq!(
+        serde_json::to_value(&limited).unwrap(),
+        serde_json::to_value(&plan).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn test_relative_path_skips_items_processed_via_absolute_path() {
+    // Relative to the working directory cargo runs tests in
+    let temp_dir = tempfile::Builder::new()
+        .prefix("shelfie-relative")
+        .tempdir_in("target")
+        .unwrap();
+    let relative = PathBuf::from("target").join(temp_dir.path().file_name().unwrap());
+    fs::write(relative.join("notes.txt"), "notes").unwrap();
+
+    // First run, given an absolute path
+    let absolute = temp_dir.path().canonicalize().unwrap();
+    let first =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), absolute.clone(), None).unwrap();
+    let cabinet_id = first.database.create_cabinet("Notes", "Notes").unwrap();
+    let shelf_id = first
+        .database
+        .create_shelf(cabinet_id, "Misc", "Misc")
+        .unwrap();
+    first
+        .database
+        .insert_item(&Item {
+            id: None,
+            shelf_id,
+            path: absolute.join("notes.txt").to_string_lossy().to_string(),
+            original_name: "notes.txt".to_string(),
+            suggested_name: None,
+            description: "Notes".to_string(),
+            file_type: "text/plain".to_string(),
+            is_opaque_dir: false,
+            opaque_reason: None,
+            rename_confidence: None,
+            content_hash: None,
+            processed_at: chrono::Utc::now(),
+        })
+        .unwrap();
+
+    // Second run, given a relative path
+    let second = FileOrganizer::new(
+        LLMProvider::new_mock(Vec::new()),
+        crate::utils::resolve_directory(&relative).unwrap(),
+        None,
+    )
+    .unwrap();
+    let scan = second
+        .collect_items(&OrganizeOptions::default())
+        .await
+        .unwrap();
+
+    assert!(scan.items.is_empty());
+    assert_eq!(scan.already_processed, 1);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_symlinked_base_path_is_canonicalized() {
+    let temp_dir = TempDir::new().unwrap();
+    let real = temp_dir.path().join("real");
+    fs::create_dir(&real).unwrap();
+    fs::write(real.join("notes.txt"), "notes").unwrap();
+    let link = temp_dir.path().join("link");
+    std::os::unix::fs::symlink(&real, &link).unwrap();
+
+    // Through the symlink, with a detour through `..` for good measure
+    let organizer = FileOrganizer::new(
+        LLMProvider::new_mock(Vec::new()),
+        link.join("..").join("link"),
+        None,
+    )
+    .unwrap();
+    let canonical = real.canonicalize().unwrap();
+    assert_eq!(organizer.base_path, canonical);
+
+    let scan = organizer
+        .collect_items(&OrganizeOptions::default())
+        .await
+        .unwrap();
+    let paths = scan
+        .items
+        .iter()
+        .map(|item| item.path().to_path_buf())
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec![canonical.join("notes.txt")]);
+    // The database lives in the real directory, not next to the symlink
+    assert!(organizer.db_path.starts_with(&canonical));
+
+    // Items recorded through the symlink are recognized when given the real path
+    let cabinet_id = organizer.database.create_cabinet("Notes", "Notes").unwrap();
+    let shelf_id = organizer
+        .database
+        .create_shelf(cabinet_id, "Misc", "Misc")
+        .unwrap();
+    organizer
+        .database
+        .insert_item(&Item {
+            id: None,
+            shelf_id,
+            path: paths[0].to_string_lossy().to_string(),
+            original_name: "notes.txt".to_string(),
+            suggested_name: None,
+            description: "Notes".to_string(),
+            file_type: "text/plain".to_string(),
+            is_opaque_dir: false,
+            opaque_reason: None,
+            rename_confidence: None,
+            content_hash: None,
+            processed_at: chrono::Utc::now(),
+        })
+        .unwrap();
+    let direct = FileOrganizer::new(LLMProvider::new_mock(Vec::new()), real, None).unwrap();
+    let scan = direct
+        .collect_items(&OrganizeOptions::default())
+        .await
+        .unwrap();
+    assert!(scan.items.is_empty());
+    assert_eq!(scan.already_processed, 1);
+}
+
+#[tokio::test]
+async fn test_scan_with_a_single_task_finds_everything() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    for i in 0..5 {
+        fs::write(base_path.join(format!("note-{}.txt", i)), "hello").unwrap();
+    }
+
+    let organizer = FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path, None).unwrap();
+    let options = OrganizeOptions {
+        scan_concurrency: 1,
+        ..Default::default()
+    };
+    let scan = organizer.collect_items(&options).await.unwrap();
+
+    assert_eq!(scan.items.len(), 5);
+}
+
+#[tokio::test]
+async fn test_scan_does_not_descend_into_bundles() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    fs::create_dir_all(base_path.join("Editor.app/Contents/MacOS")).unwrap();
+    fs::write(base_path.join("Editor.app/Contents/Info.plist"), "<plist/>").unwrap();
+    fs::create_dir_all(base_path.join("docs")).unwrap();
+    fs::write(base_path.join("docs/readme.txt"), "hello").unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    let options = OrganizeOptions {
+        max_depth: 4,
+        ..Default::default()
+    };
+    let scan = organizer.collect_items(&options).await.unwrap();
+
+    let mut paths = scan
+        .items
+        .iter()
+        .map(|item| match item {
+            ProcessingItem::File(file) => file.path.clone(),
+            ProcessingItem::Directory(dir) => dir.path.clone(),
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![
+            base_path.join("Editor.app"),
+            base_path.join("docs"),
+            base_path.join("docs/readme.txt"),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_scan_skips_excluded_entries() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    fs::write(base_path.join("Cargo.lock"), "# lock").unwrap();
+    fs::write(base_path.join("notes.txt"), "hello").unwrap();
+    fs::create_dir_all(base_path.join("target/debug")).unwrap();
+    fs::write(base_path.join("target/debug/app"), "binary").unwrap();
+    fs::create_dir_all(base_path.join("docs")).unwrap();
+    fs::write(base_path.join("docs/yarn.lock"), "# lock").unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    let options = OrganizeOptions {
+        max_depth: 4,
+        exclude: ["*.lock", "target/"]
+            .iter()
+            .map(|glob| ExcludePattern::parse(glob).unwrap())
+            .collect(),
+        ..Default::default()
+    };
+    let scan = organizer.collect_items(&options).await.unwrap();
+
+    let mut paths = scan
+        .items
+        .iter()
+        .map(|item| match item {
+            ProcessingItem::File(file) => file.path.clone(),
+            ProcessingItem::Directory(dir) => dir.path.clone(),
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    assert_eq!(
+        paths,
+        vec![base_path.join("docs"), base_path.join("notes.txt")]
+    );
+    assert_eq!(scan.excluded, 3);
+}
+
+#[tokio::test]
+async fn test_scan_follows_the_ignore_file() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    fs::write(
+        base_path.join(IGNORE_FILE_NAME),
+        "# scratch files\n*.tmp\n!keep.tmp\nbuild/\n",
+    )
+    .unwrap();
+    fs::write(base_path.join("draft.tmp"), "draft").unwrap();
+    fs::write(base_path.join("keep.tmp"), "keep").unwrap();
+    fs::create_dir_all(base_path.join("build")).unwrap();
+    fs::write(base_path.join("build/out.bin"), "binary").unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    let options = OrganizeOptions {
+        max_depth: 4,
+        // Hidden files are organized, but never the ignore file itself
+        include_hidden: true,
+        ..Default::default()
+    };
+    let scan = organizer.collect_items(&options).await.unwrap();
+
+    let paths = scan
+        .items
+        .iter()
+        .map(|item| match item {
+            ProcessingItem::File(file) => file.path.clone(),
+            ProcessingItem::Directory(dir) => dir.path.clone(),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(paths, vec![base_path.join("keep.tmp")]);
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_scan_skips_fifos_but_keeps_empty_files() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    fs::write(base_path.join("empty.txt"), "").unwrap();
+    let status = std::process::Command::new("mkfifo")
+        .arg(base_path.join("pipe"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    let scan = organizer
+        .collect_items(&OrganizeOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(scan.special, 1);
+    assert_eq!(scan.items.len(), 1);
+    assert!(matches!(
+        &scan.items[0],
+        ProcessingItem::File(file) if file.path == base_path.join("empty.txt")
+    ));
+}
+
+#[tokio::test]
+async fn test_dotfiles_are_scanned_only_with_include_hidden() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    fs::write(base_path.join(".bashrc"), "alias ll='ls -l'").unwrap();
+    fs::create_dir_all(base_path.join(".git/objects")).unwrap();
+    fs::create_dir_all(base_path.join(".config/nvim")).unwrap();
+    fs::write(base_path.join(".config/nvim/init.lua"), "-- config").unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    let scanned = |include_hidden| {
+        let options = OrganizeOptions {
+            max_depth: 3,
+            include_hidden,
+            ..Default::default()
+        };
+        let organizer = &organizer;
+        async move {
+            let scan = organizer.collect_items(&options).await.unwrap();
+            let mut paths = scan
+                .items
+                .iter()
+                .map(|item| match item {
+                    ProcessingItem::File(file) => file.path.clone(),
+                    ProcessingItem::Directory(dir) => dir.path.clone(),
+                })
+                .collect::<Vec<_>>();
+            paths.sort();
+            paths
+        }
+    };
+
+    // Without the flag, nothing inside hidden directories is picked up either
+    assert!(scanned(false).await.is_empty());
+    assert_eq!(
+        scanned(true).await,
+        vec![
+            base_path.join(".bashrc"),
+            base_path.join(".config"),
+            base_path.join(".config/nvim"),
+            base_path.join(".config/nvim/init.lua"),
+        ]
+    );
+}
+
+#[test]
+fn test_target_name_applies_extension_policy() {
+    let movement = |from: &str, new_name: Option<&str>| FileMovement {
+        from: PathBuf::from(from),
+        to_cabinet: "Apps".to_string(),
+        to_shelf: "Editors".to_string(),
+        new_name: new_name.map(str::to_string),
+        reasoning: String::new(),
+        opaque_reason: None,
+    };
+    let target =
+        |from, new_name, policy| FileOrganizer::target_name(&movement(from, new_name), policy);
+
+    // Items that aren't renamed keep their name under every policy
+    for policy in [
+        ExtensionPolicy::Preserve,
+        ExtensionPolicy::AllowChange,
+        ExtensionPolicy::Strip,
+    ] {
+        assert_eq!(target("/in/notes.txt", None, policy), "notes.txt");
+    }
+
+    assert_eq!(
+        target(
+            "/in/data.txt",
+            Some("config.json"),
+            ExtensionPolicy::Preserve
+        ),
+        "config.txt"
+    );
+    assert_eq!(
+        target(
+            "/in/data.txt",
+            Some("config.json"),
+            ExtensionPolicy::AllowChange
+        ),
+        "config.json"
+    );
+    assert_eq!(
+        target("/in/data.txt", Some("config"), ExtensionPolicy::AllowChange),
+        "config.txt"
+    );
+    assert_eq!(
+        target("/in/data.txt", Some("config.json"), ExtensionPolicy::Strip),
+        "config"
+    );
+    assert_eq!(
+        target(
+            "/in/scan.pdf",
+            Some("tax_return.pdf"),
+            ExtensionPolicy::Preserve
+        ),
+        "tax_return.pdf"
+    );
+
+    // Bundles always keep their extension
+    assert_eq!(
+        target(
+            "/in/Editor.app",
+            Some("Text Editor"),
+            ExtensionPolicy::Strip
+        ),
+        "Text Editor.app"
+    );
+}
+
+#[tokio::test]
+async fn test_missing_sources_are_reported() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    let present = base_path.join("present.txt");
+    fs::write(&present, "here").unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    let mut plan = two_shelf_plan();
+    plan.movements = ["present", "gone"]
+        .iter()
+        .map(|name| FileMovement {
+            from: base_path.join(format!("{}.txt", name)),
+            to_cabinet: "Finance".to_string(),
+            to_shelf: "Taxes".to_string(),
+            new_name: Some(name.to_string()),
+            reasoning: String::new(),
+            opaque_reason: None,
+        })
+        .collect();
+
+    let report = organizer
+        .execute_plan(&plan, &OrganizeOptions::default())
+        .await
+        .unwrap();
+
+    assert_eq!(report.moved, 1);
+    assert_eq!(report.missing, vec!["gone.txt".to_string()]);
+    assert!(report.refused.is_empty());
+    assert!(base_path.join("Finance/Taxes/present.txt").exists());
+}
+
+#[tokio::test]
+async fn test_execute_from_database_without_provider() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    assert!(FileOrganizer::open_existing(base_path.clone(), None).is_err());
+
+    let source = base_path.join("invoice.txt");
+    fs::write(&source, "Invoice #42").unwrap();
+    let database = crate::database::Database::open_or_create(&base_path).unwrap();
+    let cabinet_id = database.create_cabinet("Finance", "Money").unwrap();
+    let shelf_id = database
+        .create_shelf(cabinet_id, "Invoices", "Bills")
+        .unwrap();
+    database
+        .insert_item(&Item {
+            id: None,
+            shelf_id,
+            path: source.to_string_lossy().to_string(),
+            original_name: "invoice.txt".to_string(),
+            suggested_name: Some("invoice_42".to_string()),
+            description: "An invoice".to_string(),
+            file_type: "text/plain".to_string(),
+            is_opaque_dir: false,
+            opaque_reason: None,
+            rename_confidence: None,
+            content_hash: None,
+            processed_at: chrono::Utc::now(),
+        })
+        .unwrap();
+    drop(database);
+
+    let organizer = FileOrganizer::open_existing(base_path.clone(), None).unwrap();
+    let options = OrganizeOptions {
+        auto_confirm: true,
+        ..Default::default()
+    };
+    organizer.execute_from_database(&options).await.unwrap();
+
+    assert!(!source.exists());
+    assert!(base_path.join("Finance/Invoices/invoice_42.txt").exists());
+}
+
+#[tokio::test]
+async fn test_opaque_directory_moves_by_copy() {
+    // A rename across devices fails, so exercise the copy fallback directly
+    let temp_dir = TempDir::new().unwrap();
+    let from = temp_dir.path().join("node_modules");
+    fs::create_dir_all(from.join("left-pad/lib")).unwrap();
+    fs::write(from.join("left-pad/package.json"), "{}").unwrap();
+    fs::write(from.join("left-pad/lib/index.js"), "module.exports = 1").unwrap();
+    let to = temp_dir.path().join("Code/Dependencies/node_modules");
+    fs::create_dir_all(to.parent().unwrap()).unwrap();
+
+    FileOrganizer::move_across_devices(&from, &to)
+        .await
+        .unwrap();
+
+    assert!(!from.exists());
+    assert_eq!(
+        fs::read_to_string(to.join("left-pad/lib/index.js")).unwrap(),
+        "module.exports = 1"
+    );
+    assert!(to.join("left-pad/package.json").exists());
+}
+
+#[tokio::test]
+async fn test_transient_failure_after_copy_retries_only_the_removal() {
+    let temp_dir = TempDir::new().unwrap();
+    let from = temp_dir.path().join("report.txt");
+    fs::write(&from, "quarterly numbers").unwrap();
+    let to = temp_dir.path().join("Work/report.txt");
+    fs::create_dir_all(to.parent().unwrap()).unwrap();
+
+    // The first removal changes the source and fails like a briefly locked file, so a
+    // second copy would show in the destination
+    let mut removals = 0;
+    FileOrganizer::copy_then(&from, &to, || {
+        removals += 1;
+        if removals == 1 {
+            fs::write(&from, "changed after the copy")?;
+            return Err(std::io::Error::from(std::io::ErrorKind::Interrupted).into());
+        }
+        fs::remove_file(&from)?;
+        Ok(())
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(removals, 2);
+    assert!(!from.exists());
+    assert_eq!(fs::read_to_string(&to).unwrap(), "quarterly numbers");
+}
+
+#[tokio::test]
+async fn test_move_across_devices_keeps_timestamps() {
+    // Tests can't mount a second filesystem, so the cross-device path is called directly
+    let temp_dir = TempDir::new().unwrap();
+    let from = temp_dir.path().join("report.txt");
+    fs::write(&from, "quarterly numbers").unwrap();
+    let modified =
+        std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+    fs::File::options()
+        .write(true)
+        .open(&from)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    let to = temp_dir.path().join("Work/report.txt");
+    fs::create_dir_all(to.parent().unwrap()).unwrap();
+
+    FileOrganizer::move_across_devices(&from, &to)
+        .await
+        .unwrap();
+
+    assert!(!from.exists());
+    assert_eq!(fs::read_to_string(&to).unwrap(), "quarterly numbers");
+    assert_eq!(fs::metadata(&to).unwrap().modified().unwrap(), modified);
+}
+
+#[tokio::test]
+async fn test_manifests_describe_shelf_items() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    let source = base_path.join("scan001.pdf");
+    fs::write(&source, "Tax return 2023").unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    let mut plan = two_shelf_plan();
+    let taxes = plan.cabinets[0].shelves[0].clone();
+    let cabinet_id = organizer
+        .database
+        .create_cabinet(&plan.cabinets[0].name, "Money")
+        .unwrap();
+    let shelf_id = organizer
+        .database
+        .create_shelf(cabinet_id, &taxes.name, &taxes.description)
+        .unwrap();
+    organizer
+        .database
+        .insert_item(&Item {
+            id: None,
+            shelf_id,
+            path: source.to_string_lossy().to_string(),
+            original_name: "scan001.pdf".to_string(),
+            suggested_name: Some("tax-return-2023".to_string()),
+            description: "Tax return for 2023".to_string(),
+            file_type: "application/pdf".to_string(),
+            is_opaque_dir: false,
+            opaque_reason: None,
+            rename_confidence: None,
+            content_hash: None,
+            processed_at: chrono::Utc::now(),
+        })
+        .unwrap();
+    plan.movements = vec![FileMovement {
+        from: source.clone(),
+        to_cabinet: plan.cabinets[0].name.clone(),
+        to_shelf: taxes.name.clone(),
+        new_name: Some("tax-return-2023".to_string()),
+        reasoning: String::new(),
+        opaque_reason: None,
+    }];
+    let options = OrganizeOptions {
+        write_manifests: true,
+        ..Default::default()
+    };
+
+    organizer.execute_plan(&plan, &options).await.unwrap();
+
+    let shelf_dir = base_path.join(&plan.cabinets[0].name).join(&taxes.name);
+    let manifest: ShelfManifest =
+        serde_json::from_str(&fs::read_to_string(shelf_dir.join(MANIFEST_FILE_NAME)).unwrap())
+            .unwrap();
+    assert_eq!(manifest.shelf, taxes.name);
+    assert_eq!(manifest.items.len(), 1);
+    assert_eq!(manifest.items[0].name, "tax-return-2023.pdf");
+    assert_eq!(manifest.items[0].original_name, "scan001.pdf");
+    assert_eq!(manifest.items[0].description, "Tax return for 2023");
+    assert_eq!(
+        manifest.items[0].file_type.as_deref(),
+        Some("application/pdf")
+    );
+    assert!(
+        base_path
+            .join(&plan.cabinets[0].name)
+            .join(&plan.cabinets[0].shelves[1].name)
+            .join(MANIFEST_FILE_NAME)
+            .exists()
+    );
+
+    // Manifests are never picked up as items, even with hidden files included
+    let scan = organizer
+        .collect_items(&OrganizeOptions {
+            max_depth: 3,
+            include_hidden: true,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    assert!(scan.items.iter().all(|item| match item {
+        ProcessingItem::File(file) => !file.path.ends_with(MANIFEST_FILE_NAME),
+        ProcessingItem::Directory(_) => true,
+    }));
+}
+
+#[tokio::test]
+async fn test_manifests_are_written_into_flat_directories() {
+    let temp_dir = TempDir::new().unwrap();
+    let base_path = temp_dir.path().to_path_buf();
+    let source = base_path.join("return.pdf");
+    fs::write(&source, "Tax return 2023").unwrap();
+    let options = OrganizeOptions {
+        flat: Some(FlatLayout::Combined),
+        write_manifests: true,
+        ..Default::default()
+    };
+    let mut plan = two_shelf_plan();
+    plan.movements[0].from = source.clone();
+    let plan = FileOrganizer::transform_plan(plan, &options).unwrap();
+
+    let organizer =
+        FileOrganizer::new(LLMProvider::new_mock(Vec::new()), base_path.clone(), None).unwrap();
+    organizer.execute_plan(&plan, &options).await.unwrap();
+
+    let dir = base_path.join("Finance - Taxes");
+    let manifest: ShelfManifest =
+        serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST_FILE_NAME)).unwrap()).unwrap();
+    assert_eq!(manifest.items.len(), 1);
+    assert_eq!(manifest.items[0].name, "return.pdf");
+    assert!(
+        base_path
+            .join("Finance - Banking")
+            .join(MANIFEST_FILE_NAME)
+            .exists()
+    );
+}
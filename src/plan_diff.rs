@@ -0,0 +1,87 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::models::{FileMovement, OrganizationPlan};
+
+/// What changed between the last executed plan and a new one.
+#[derive(Debug, Clone, Default)]
+pub struct PlanDiff {
+    pub new_cabinets: Vec<String>,
+    /// (cabinet, shelf) pairs that didn't exist before
+    pub new_shelves: Vec<(String, String)>,
+    /// Items that weren't part of the previous plan
+    pub added: Vec<FileMovement>,
+    pub moved: Vec<MovedItem>,
+    pub unchanged: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct MovedItem {
+    pub previous: FileMovement,
+    pub current: FileMovement,
+}
+
+impl PlanDiff {
+    pub fn is_empty(&self) -> bool {
+        self.new_cabinets.is_empty()
+            && self.new_shelves.is_empty()
+            && self.added.is_empty()
+            && self.moved.is_empty()
+    }
+}
+
+/// Compares two plans by name and by each item's source path.
+pub fn diff_plans(old: &OrganizationPlan, new: &OrganizationPlan) -> PlanDiff {
+    let old_cabinets = old
+        .cabinets
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<HashSet<_>>();
+    let old_shelves = old
+        .cabinets
+        .iter()
+        .flat_map(|c| {
+            c.shelves
+                .iter()
+                .map(move |s| (c.name.as_str(), s.name.as_str()))
+        })
+        .collect::<HashSet<_>>();
+    let old_movements = old
+        .movements
+        .iter()
+        .map(|m| (&m.from, m))
+        .collect::<HashMap<_, _>>();
+
+    let mut diff = PlanDiff::default();
+
+    for cabinet in &new.cabinets {
+        if !old_cabinets.contains(cabinet.name.as_str()) {
+            diff.new_cabinets.push(cabinet.name.clone());
+        }
+        for shelf in &cabinet.shelves {
+            if !old_shelves.contains(&(cabinet.name.as_str(), shelf.name.as_str())) {
+                diff.new_shelves
+                    .push((cabinet.name.clone(), shelf.name.clone()));
+            }
+        }
+    }
+
+    for movement in &new.movements {
+        match old_movements.get(&movement.from) {
+            None => diff.added.push(movement.clone()),
+            Some(previous) if same_destination(previous, movement) => diff.unchanged += 1,
+            Some(previous) => diff.moved.push(MovedItem {
+                previous: (*previous).clone(),
+                current: movement.clone(),
+            }),
+        }
+    }
+
+    diff
+}
+
+fn same_destination(a: &FileMovement, b: &FileMovement) -> bool {
+    a.to_cabinet == b.to_cabinet && a.to_shelf == b.to_shelf && a.new_name == b.new_name
+}
+
+#[cfg(test)]
+mod tests;
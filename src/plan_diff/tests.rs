@@ -0,0 +1,88 @@
+use super::diff_plans;
+use crate::models::{CabinetPlan, FileMovement, OrganizationPlan, ShelfPlan};
+use std::path::PathBuf;
+
+fn movement(from: &str, cabinet: &str, shelf: &str) -> FileMovement {
+    FileMovement {
+        from: PathBuf::from(from),
+        to_cabinet: cabinet.to_string(),
+        to_shelf: shelf.to_string(),
+        new_name: None,
+        reasoning: String::new(),
+        opaque_reason: None,
+    }
+}
+
+fn plan(structure: &[(&str, &[&str])], movements: Vec<FileMovement>) -> OrganizationPlan {
+    OrganizationPlan {
+        cabinets: structure
+            .iter()
+            .enumerate()
+            .map(|(id, (cabinet, shelves))| CabinetPlan {
+                id: id as i64 + 1,
+                name: cabinet.to_string(),
+                description: String::new(),
+                shelves: shelves
+                    .iter()
+                    .enumerate()
+                    .map(|(shelf_id, shelf)| ShelfPlan {
+                        id: shelf_id as i64 + 1,
+                        name: shelf.to_string(),
+                        description: String::new(),
+                        item_count: 0,
+                    })
+                    .collect(),
+            })
+            .collect(),
+        movements,
+    }
+}
+
+#[test]
+fn test_diff_plans() {
+    let old = plan(
+        &[("Finance", &["Taxes"])],
+        vec![
+            movement("/docs/return.pdf", "Finance", "Taxes"),
+            movement("/docs/statement.pdf", "Finance", "Taxes"),
+        ],
+    );
+    let new = plan(
+        &[("Finance", &["Taxes", "Banking"]), ("Photos", &["Travel"])],
+        vec![
+            movement("/docs/return.pdf", "Finance", "Taxes"),
+            movement("/docs/statement.pdf", "Finance", "Banking"),
+            movement("/docs/beach.jpg", "Photos", "Travel"),
+        ],
+    );
+
+    let diff = diff_plans(&old, &new);
+
+    assert_eq!(diff.new_cabinets, vec!["Photos"]);
+    assert_eq!(
+        diff.new_shelves,
+        vec![
+            ("Finance".to_string(), "Banking".to_string()),
+            ("Photos".to_string(), "Travel".to_string())
+        ]
+    );
+    assert_eq!(diff.added.len(), 1);
+    assert_eq!(diff.added[0].from, PathBuf::from("/docs/beach.jpg"));
+    assert_eq!(diff.moved.len(), 1);
+    assert_eq!(diff.moved[0].previous.to_shelf, "Taxes");
+    assert_eq!(diff.moved[0].current.to_shelf, "Banking");
+    assert_eq!(diff.unchanged, 1);
+}
+
+#[test]
+fn test_identical_plans_have_empty_diff() {
+    let old = plan(
+        &[("Finance", &["Taxes"])],
+        vec![movement("/docs/return.pdf", "Finance", "Taxes")],
+    );
+
+    let diff = diff_plans(&old, &old.clone());
+
+    assert!(diff.is_empty());
+    assert_eq!(diff.unchanged, 1);
+}
@@ -1,11 +1,11 @@
 use anyhow::{Context, Result};
 use colored::*;
-use dialoguer::{Input, theme::ColorfulTheme};
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::ProgressBar;
 use rig::{
     agent::Agent,
     client::completion::CompletionModelHandle,
     completion::{Prompt, request::ToolDefinition},
+    message::{AssistantContent, Message},
     tool::Tool,
 };
 use serde::{Deserialize, Serialize};
@@ -13,7 +13,17 @@ use serde_json;
 use std::{path::PathBuf, sync::Arc, time::Duration};
 use tokio::sync::Mutex;
 
-use crate::{database::Database, models::OrganizationPlan, providers::LLMProvider};
+use crate::{
+    database::Database, models::OrganizationPlan, plan_diff::diff_plans, progress, prompts,
+    providers::LLMProvider, utils::relative_display,
+};
+
+/// Output token limit for the refinement agent when none is configured.
+const DEFAULT_AGENT_MAX_TOKENS: u64 = 8192;
+/// Turns the refinement agent may take per piece of feedback, by default.
+pub const DEFAULT_REFINE_MAX_TURNS: usize = 20;
+/// Seconds the refinement agent may work on a piece of feedback, by default.
+pub const DEFAULT_REFINE_TIMEOUT_SECS: u64 = 300;
 
 #[derive(Debug, thiserror::Error)]
 pub enum PlanToolError {
@@ -85,6 +95,38 @@ pub struct PlanRefiner {
     provider: LLMProvider,
     database: Arc<Database>,
     base_path: PathBuf,
+    min_rename_confidence: Option<f64>,
+    /// Remember the feedback and the moves it caused for future runs (`--use-history`).
+    record_history: bool,
+    max_turns: usize,
+    timeout: Duration,
+}
+
+/// How much of its budget the refinement agent used on a piece of feedback.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct AgentUsage {
+    turns: usize,
+    tool_calls: usize,
+}
+
+impl AgentUsage {
+    /// Counts the model's responses and the tools they called in the agent's history.
+    fn from_history(history: &[Message]) -> Self {
+        history
+            .iter()
+            .filter_map(|message| match message {
+                Message::Assistant { content, .. } => Some(content),
+                Message::User { .. } => None,
+            })
+            .fold(Self::default(), |usage, content| Self {
+                turns: usage.turns + 1,
+                tool_calls: usage.tool_calls
+                    + content
+                        .iter()
+                        .filter(|content| matches!(content, AssistantContent::ToolCall(_)))
+                        .count(),
+            })
+    }
 }
 
 // Tool definitions
@@ -518,13 +560,36 @@ impl PlanRefiner {
             provider,
             database,
             base_path,
+            min_rename_confidence: None,
+            record_history: false,
+            max_turns: DEFAULT_REFINE_MAX_TURNS,
+            timeout: Duration::from_secs(DEFAULT_REFINE_TIMEOUT_SECS),
         }
     }
 
+    /// Bounds the agent's work on each piece of feedback to `max_turns` responses and
+    /// `timeout`.
+    pub fn with_limits(mut self, max_turns: usize, timeout: Duration) -> Self {
+        self.max_turns = max_turns;
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_history(mut self, record_history: bool) -> Self {
+        self.record_history = record_history;
+        self
+    }
+
+    pub fn with_min_rename_confidence(mut self, min_rename_confidence: Option<f64>) -> Self {
+        self.min_rename_confidence = min_rename_confidence;
+        self
+    }
+
     pub async fn refine_plan_with_feedback(
         &self,
         current_plan: &OrganizationPlan,
     ) -> Result<Option<OrganizationPlan>> {
+        let mut feedback_given = Vec::new();
         loop {
             // Get user feedback
             let user_feedback = self.get_user_feedback()?;
@@ -539,15 +604,10 @@ impl PlanRefiner {
                 "Analyzing feedback and refining plan...".cyan().bold()
             );
 
+            feedback_given.push(user_feedback.trim().to_string());
+
             // Create progress spinner
-            let progress_bar = ProgressBar::new_spinner();
-            progress_bar.set_style(
-                ProgressStyle::default_spinner()
-                    .template("{spinner:.green} {msg}")
-                    .unwrap(),
-            );
-            progress_bar.set_message("Starting plan refinement...");
-            progress_bar.enable_steady_tick(Duration::from_millis(200));
+            let progress_bar = progress::spinner("Starting plan refinement...");
 
             let progress_arc = Arc::new(Mutex::new(progress_bar));
 
@@ -565,6 +625,9 @@ impl PlanRefiner {
 
                     // Ask if user accepts the revised plan
                     if self.get_plan_approval()? {
+                        if self.record_history {
+                            self.record_correction(&feedback_given, current_plan, &new_plan);
+                        }
                         return Ok(Some(new_plan));
                     } else {
                         println!("\n{}", "Let's continue refining the plan.".yellow());
@@ -583,6 +646,39 @@ impl PlanRefiner {
         }
     }
 
+    /// Stores the accepted refinement so later runs can follow the same preferences.
+    /// Failing to do so never blocks the plan itself.
+    fn record_correction(
+        &self,
+        feedback: &[String],
+        original: &OrganizationPlan,
+        refined: &OrganizationPlan,
+    ) {
+        let moves: Vec<String> = diff_plans(original, refined)
+            .moved
+            .iter()
+            .map(|moved| {
+                format!(
+                    "{} → {}/{}",
+                    relative_display(&moved.current.from, &self.base_path),
+                    moved.current.to_cabinet,
+                    moved.current.to_shelf
+                )
+            })
+            .collect();
+
+        if let Err(e) = self
+            .database
+            .record_correction(&feedback.join("; "), &moves)
+        {
+            eprintln!(
+                "{}: Failed to remember this correction: {}",
+                "Warning".yellow().bold(),
+                e
+            );
+        }
+    }
+
     async fn refine_with_agent(
         &self,
         user_feedback: &str,
@@ -590,11 +686,39 @@ impl PlanRefiner {
         progress_bar: Arc<Mutex<ProgressBar>>,
     ) -> Result<()> {
         let agent = self.build_agent(user_feedback, progress_bar.clone())?;
-        let response = agent
+        // The tools change the database as they go, so an unfinished refinement is undone
+        let snapshot = self.database.export()?;
+        let mut history = Vec::new();
+        let request = agent
             .prompt("Please examine the current organization and implement the requested changes.")
-            .multi_turn(20)
-            .extended_details()
-            .await?;
+            .with_history(&mut history)
+            .multi_turn(self.max_turns)
+            .extended_details();
+        let result = tokio::select! {
+            result = tokio::time::timeout(self.timeout, request) => match result {
+                Ok(response) => response.map_err(anyhow::Error::from),
+                Err(_) => Err(anyhow::anyhow!(
+                    "the agent didn't finish within {}s",
+                    self.timeout.as_secs()
+                )),
+            },
+            _ = tokio::signal::ctrl_c() => Err(anyhow::anyhow!("interrupted")),
+        };
+        let usage = AgentUsage::from_history(&history);
+
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                self.database
+                    .import(&snapshot)
+                    .context("Failed to undo the unfinished refinement")?;
+                println!(
+                    "↩️  Undid the refinement's changes after {} turns and {} tool calls",
+                    usage.turns, usage.tool_calls
+                );
+                return Err(e);
+            }
+        };
 
         {
             let pb = progress_bar.lock().await;
@@ -603,6 +727,14 @@ impl PlanRefiner {
 
         println!("\n{}", "Agent Response:".green().bold());
         println!("{}", response.output);
+        println!(
+            "{}",
+            format!(
+                "Used {} of {} turns and {} tool calls",
+                usage.turns, self.max_turns, usage.tool_calls
+            )
+            .dimmed()
+        );
 
         Ok(())
     }
@@ -666,11 +798,17 @@ Your task:
 Please start by examining the current organization structure."#,
             user_feedback
         );
-        let agent = self
-            .provider
-            .get_agent()?
+        let mut builder = self.provider.get_agent()?;
+        if let Some(params) = self.provider.sampling_params() {
+            builder = builder.additional_params(params);
+        }
+        let agent = builder
             .preamble(&initial_prompt)
-            .max_tokens(8192)
+            .max_tokens(
+                self.provider
+                    .max_tokens()
+                    .unwrap_or(DEFAULT_AGENT_MAX_TOKENS),
+            )
             .tool(list_cabinets_tool)
             .tool(list_items_tool)
             .tool(move_item_tool)
@@ -693,20 +831,11 @@ Please start by examining the current organization structure."#,
         println!("  - \"Group all video files together regardless of format\"");
         println!("Type 'exit' to cancel.\n");
 
-        let feedback: String = Input::with_theme(&ColorfulTheme::default())
-            .with_prompt("What would you like to change?")
-            .interact_text()?;
-
-        Ok(feedback)
+        prompts::input("What would you like to change?", false)
     }
 
     fn get_plan_approval(&self) -> Result<bool> {
-        use dialoguer::Confirm;
-
-        Confirm::with_theme(&ColorfulTheme::default())
-            .with_prompt("Do you approve this revised plan?")
-            .default(false)
-            .interact()
+        prompts::confirm("Do you approve this revised plan?", false, true)
             .context("Failed to get user confirmation")
     }
 
@@ -730,6 +859,7 @@ Please start by examining the current organization structure."#,
                 let item_count = items.iter().filter(|i| i.shelf_id == shelf.id).count();
 
                 shelf_plans.push(crate::models::ShelfPlan {
+                    id: shelf.id,
                     name: shelf.name.clone(),
                     description: shelf.description.clone(),
                     item_count,
@@ -737,6 +867,7 @@ Please start by examining the current organization structure."#,
             }
 
             cabinet_plans.push(crate::models::CabinetPlan {
+                id: cabinet.id,
                 name: cabinet.name.clone(),
                 description: cabinet.description.clone(),
                 shelves: shelf_plans,
@@ -762,8 +893,9 @@ Please start by examining the current organization structure."#,
                 from: from.clone(),
                 to_cabinet: cabinet.name.clone(),
                 to_shelf: shelf.name.clone(),
-                new_name: item.suggested_name.clone(),
+                new_name: item.rename(self.min_rename_confidence),
                 reasoning: item.description.clone(),
+                opaque_reason: item.opaque_reason.clone(),
             });
         }
 
@@ -810,7 +942,7 @@ Please start by examining the current organization structure."#,
 
             println!(
                 "  {} → {}/{}/{}",
-                from_name.yellow(),
+                relative_display(&movement.from, &self.base_path).yellow(),
                 movement.to_cabinet.blue(),
                 movement.to_shelf.green(),
                 if movement.new_name.is_some() {
@@ -832,3 +964,6 @@ Please start by examining the current organization structure."#,
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests;
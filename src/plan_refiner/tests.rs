@@ -0,0 +1,35 @@
+use super::*;
+use rig::OneOrMany;
+
+#[test]
+fn test_agent_usage_counts_responses_and_tool_calls() {
+    let history = vec![
+        Message::user("Please examine the current organization"),
+        Message::Assistant {
+            id: None,
+            content: OneOrMany::many(vec![
+                AssistantContent::tool_call("1", "list_cabinets", serde_json::json!({})),
+                AssistantContent::tool_call("2", "list_items", serde_json::json!({})),
+            ])
+            .unwrap(),
+        },
+        Message::user("(tool results)"),
+        Message::Assistant {
+            id: None,
+            content: OneOrMany::one(AssistantContent::tool_call(
+                "3",
+                "move_item",
+                serde_json::json!({"item_id": 1}),
+            )),
+        },
+        Message::assistant("Moved the invoice to Finance."),
+    ];
+
+    assert_eq!(
+        AgentUsage::from_history(&history),
+        AgentUsage {
+            turns: 3,
+            tool_calls: 3
+        }
+    );
+}
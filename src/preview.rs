@@ -0,0 +1,413 @@
+use anyhow::{Result, anyhow, bail};
+use colored::*;
+use extractous::Extractor;
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use crate::{file_analyzer::PREVIEW_MAX_CHARS, file_metadata::read_pdf_title};
+
+/// Bytes from the start of a document handed to extractous.
+const DOCUMENT_HEAD_BYTES: u64 = 1024;
+/// Bytes read from a text file: enough for a full preview of four-byte characters.
+const TEXT_HEAD_BYTES: u64 = PREVIEW_MAX_CHARS as u64 * 4;
+/// Bytes searched for an image's dimensions. JPEGs put theirs after the EXIF
+/// block, which can take up to 64 KiB.
+const IMAGE_HEAD_BYTES: u64 = 128 * 1024;
+/// Entries listed in the preview of an archive.
+const ARCHIVE_MAX_ENTRIES: usize = 50;
+/// Largest zip central directory read for a listing.
+const ZIP_DIRECTORY_MAX_BYTES: u64 = 1024 * 1024;
+/// Document metadata keys holding a title, in order of preference.
+const TITLE_METADATA_KEYS: &[&str] = &["dc:title", "title", "pdf:docinfo:title"];
+/// Documents whose metadata is worth extracting from the whole file when the head
+/// has no title. Office documents keep theirs in a zip member or a trailing stream.
+const FULL_METADATA_MIME_PREFIXES: &[&str] = &[
+    "application/vnd.openxmlformats-officedocument.",
+    "application/vnd.oasis.opendocument.",
+    "application/vnd.ms-",
+    "application/msword",
+    "application/epub+zip",
+];
+
+/// `None` when the extractous native libraries failed to load.
+static EXTRACTOR: OnceLock<Option<Extractor>> = OnceLock::new();
+static REGISTRY: OnceLock<RwLock<PreviewRegistry>> = OnceLock::new();
+
+/// A file's preview before it is cleaned up and truncated.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawPreview {
+    pub text: String,
+    /// The document's title, from its metadata
+    pub title: Option<String>,
+}
+
+impl RawPreview {
+    pub fn new(text: String) -> Self {
+        Self { text, title: None }
+    }
+}
+
+/// Produces the content previews of one kind of file. Strategies run on the
+/// blocking pool, so they may read the file synchronously.
+pub trait PreviewStrategy: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// `None` when the file turns out not to be one this strategy understands, in
+    /// which case the document strategy is used instead.
+    fn preview(&self, path: &Path) -> Result<Option<RawPreview>>;
+}
+
+/// Preview strategies keyed by mime type prefix, e.g. `image/` or `application/zip`.
+/// Files no strategy is registered for are previewed as documents by extractous.
+pub struct PreviewRegistry {
+    strategies: Vec<(String, Arc<dyn PreviewStrategy>)>,
+    fallback: Arc<dyn PreviewStrategy>,
+}
+
+impl PreviewRegistry {
+    /// A registry that previews everything as a document.
+    pub fn new() -> Self {
+        Self {
+            strategies: Vec::new(),
+            fallback: Arc::new(DocumentPreview),
+        }
+    }
+
+    pub fn with_builtins() -> Self {
+        let mut registry = Self::new();
+        registry.register("text/", Arc::new(TextPreview));
+        registry.register("image/", Arc::new(ImagePreview));
+        registry.register("application/zip", Arc::new(ArchivePreview));
+        registry.register("application/x-tar", Arc::new(ArchivePreview));
+        registry
+    }
+
+    /// Registers `strategy` for mime types starting with `mime_prefix`, replacing
+    /// the one registered for the same prefix before.
+    pub fn register(&mut self, mime_prefix: &str, strategy: Arc<dyn PreviewStrategy>) {
+        self.strategies.retain(|(prefix, _)| prefix != mime_prefix);
+        self.strategies.push((mime_prefix.to_string(), strategy));
+    }
+
+    /// The strategy with the longest prefix of `mime_type`, or the document one.
+    pub fn strategy_for(&self, mime_type: &str) -> Arc<dyn PreviewStrategy> {
+        self.strategies
+            .iter()
+            .filter(|(prefix, _)| mime_type.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or_else(
+                || Arc::clone(&self.fallback),
+                |(_, strategy)| Arc::clone(strategy),
+            )
+    }
+
+    pub fn fallback(&self) -> Arc<dyn PreviewStrategy> {
+        Arc::clone(&self.fallback)
+    }
+}
+
+impl Default for PreviewRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The registry `AnalyzedFile` previews files with, starting out with the built-in
+/// strategies. Strategies registered on it apply for the rest of the process.
+pub fn registry() -> &'static RwLock<PreviewRegistry> {
+    REGISTRY.get_or_init(|| RwLock::new(PreviewRegistry::with_builtins()))
+}
+
+/// The strategy to preview files of `mime_type` with, and the one to fall back to
+/// when it declines.
+pub fn strategies_for(mime_type: &str) -> (Arc<dyn PreviewStrategy>, Arc<dyn PreviewStrategy>) {
+    let registry = registry().read().unwrap_or_else(|e| e.into_inner());
+    (registry.strategy_for(mime_type), registry.fallback())
+}
+
+/// Initializes the extractor on first use. extractous loads a native library that can
+/// panic on unsupported platforms, in which case we warn once and carry on without
+/// document previews.
+fn shared_extractor() -> Option<&'static Extractor> {
+    EXTRACTOR
+        .get_or_init(|| {
+            match std::panic::catch_unwind(|| {
+                Extractor::new().set_extract_string_max_length(PREVIEW_MAX_CHARS as i32)
+            }) {
+                Ok(extractor) => Some(extractor),
+                Err(_) => {
+                    eprintln!(
+                        "{} Content extraction is unavailable on this system; \
+                        files will be classified by name, type and size only. \
+                        Pass --no-content to skip it explicitly.",
+                        "Warning:".yellow().bold()
+                    );
+                    None
+                }
+            }
+        })
+        .as_ref()
+}
+
+fn read_head(path: &Path, max_bytes: u64) -> Result<Vec<u8>> {
+    let mut head = Vec::new();
+    File::open(path)?.take(max_bytes).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// Text extracted by extractous from the start of the file, which handles PDFs,
+/// office documents and plain text alike.
+pub struct DocumentPreview;
+
+impl PreviewStrategy for DocumentPreview {
+    fn name(&self) -> &'static str {
+        "document"
+    }
+
+    fn preview(&self, path: &Path) -> Result<Option<RawPreview>> {
+        let extractor =
+            shared_extractor().ok_or_else(|| anyhow!("Content extraction unavailable"))?;
+        let (text, metadata) =
+            extractor.extract_bytes_to_string(&read_head(path, DOCUMENT_HEAD_BYTES)?)?;
+        let title = metadata_title(&metadata).or_else(|| full_title(extractor, path));
+        Ok(Some(RawPreview { text, title }))
+    }
+}
+
+fn metadata_title(metadata: &extractous::Metadata) -> Option<String> {
+    TITLE_METADATA_KEYS
+        .iter()
+        .filter_map(|key| metadata.get(*key))
+        .flatten()
+        .map(|title| title.trim())
+        .find(|title| !title.is_empty())
+        .map(str::to_string)
+}
+
+/// The title of a document that keeps it beyond the head handed to extractous,
+/// e.g. in a PDF's document information near its end. Other files aren't read in
+/// full, and a title that can't be read is left out.
+fn full_title(extractor: &Extractor, path: &Path) -> Option<String> {
+    let mime_type = infer::get_from_path(path).ok()??.mime_type();
+    if mime_type == "application/pdf" {
+        read_pdf_title(path).ok().flatten()
+    } else if FULL_METADATA_MIME_PREFIXES
+        .iter()
+        .any(|prefix| mime_type.starts_with(prefix))
+    {
+        let (_, metadata) = extractor
+            .extract_file_to_string(&path.to_string_lossy())
+            .ok()?;
+        metadata_title(&metadata)
+    } else {
+        None
+    }
+}
+
+/// The start of a text file as is, e.g. scripts detected by their shebang.
+pub struct TextPreview;
+
+impl PreviewStrategy for TextPreview {
+    fn name(&self) -> &'static str {
+        "text"
+    }
+
+    fn preview(&self, path: &Path) -> Result<Option<RawPreview>> {
+        let head = read_head(path, TEXT_HEAD_BYTES)?;
+        // The head may end in the middle of a character
+        let text = String::from_utf8_lossy(&head);
+        Ok(Some(RawPreview::new(
+            text.trim_end_matches(char::REPLACEMENT_CHARACTER)
+                .to_string(),
+        )))
+    }
+}
+
+/// An image's dimensions, read from its header. Formats whose header isn't
+/// understood are left to the document strategy.
+pub struct ImagePreview;
+
+impl PreviewStrategy for ImagePreview {
+    fn name(&self) -> &'static str {
+        "image"
+    }
+
+    fn preview(&self, path: &Path) -> Result<Option<RawPreview>> {
+        Ok(image_dimensions(&read_head(path, IMAGE_HEAD_BYTES)?)
+            .map(|(width, height)| RawPreview::new(format!("Image, {}×{} pixels", width, height))))
+    }
+}
+
+/// Width and height of a PNG, GIF, BMP or JPEG image.
+pub(crate) fn image_dimensions(bytes: &[u8]) -> Option<(u32, u32)> {
+    let be16 = |at: usize| Some(u16::from_be_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let le16 = |at: usize| Some(u16::from_le_bytes(bytes.get(at..at + 2)?.try_into().ok()?));
+    let be32 = |at: usize| Some(u32::from_be_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+    let le32 = |at: usize| Some(i32::from_le_bytes(bytes.get(at..at + 4)?.try_into().ok()?));
+
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some((be32(16)?, be32(20)?));
+    }
+    if bytes.starts_with(b"GIF8") {
+        return Some((u32::from(le16(6)?), u32::from(le16(8)?)));
+    }
+    if bytes.starts_with(b"BM") {
+        // Bottom-up bitmaps have a negative height
+        return Some((le32(18)?.unsigned_abs(), le32(22)?.unsigned_abs()));
+    }
+    if bytes.starts_with(&[0xFF, 0xD8]) {
+        let mut at = 2;
+        while *bytes.get(at)? == 0xFF {
+            let marker = *bytes.get(at + 1)?;
+            if marker == 0xFF {
+                // Fill byte
+                at += 1;
+                continue;
+            }
+            // Start-of-frame markers, except DHT, JPG and DAC which share the range
+            if (0xC0..=0xCF).contains(&marker) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+                return Some((u32::from(be16(at + 7)?), u32::from(be16(at + 5)?)));
+            }
+            at += 2 + usize::from(be16(at + 2)?);
+        }
+    }
+    None
+}
+
+/// Lists the entries of zip and tar archives instead of their compressed bytes.
+pub struct ArchivePreview;
+
+impl PreviewStrategy for ArchivePreview {
+    fn name(&self) -> &'static str {
+        "archive"
+    }
+
+    fn preview(&self, path: &Path) -> Result<Option<RawPreview>> {
+        let mut file = File::open(path)?;
+        let mut magic = [0u8; 4];
+        let is_zip = file.read(&mut magic)? == 4 && magic == *b"PK\x03\x04";
+        file.rewind()?;
+        let (entries, total) = if is_zip {
+            zip_entries(&mut file)?
+        } else {
+            tar_entries(&mut file)?
+        };
+        Ok(Some(RawPreview::new(archive_listing(&entries, total))))
+    }
+}
+
+/// `total` is `None` when the archive has more entries than listed but it isn't
+/// known how many.
+fn archive_listing(entries: &[String], total: Option<usize>) -> String {
+    let mut listing = match total {
+        Some(total) => format!("Archive with {} entries:", total),
+        None => "Archive starting with:".to_string(),
+    };
+    for entry in entries {
+        listing.push('\n');
+        listing.push_str(entry);
+    }
+    match total {
+        Some(total) if total > entries.len() => {
+            listing.push_str(&format!("\n… and {} more", total - entries.len()))
+        }
+        None => listing.push_str("\n…"),
+        _ => {}
+    }
+    listing
+}
+
+/// Names from the central directory at the end of a zip file, and how many
+/// entries it has.
+fn zip_entries(file: &mut File) -> Result<(Vec<String>, Option<usize>)> {
+    // The end-of-directory record is 22 bytes plus a comment of up to 64 KiB
+    let tail_len = file.metadata()?.len().min(22 + u64::from(u16::MAX));
+    file.seek(SeekFrom::End(-(tail_len as i64)))?;
+    let mut tail = vec![0; tail_len as usize];
+    file.read_exact(&mut tail)?;
+    let end = tail
+        .windows(4)
+        .rposition(|window| window == b"PK\x05\x06")
+        .map(|at| &tail[at..])
+        .filter(|record| record.len() >= 22)
+        .ok_or_else(|| anyhow!("No zip directory found"))?;
+    let le16 =
+        |bytes: &[u8], at: usize| usize::from(u16::from_le_bytes([bytes[at], bytes[at + 1]]));
+    let le32 = |bytes: &[u8], at: usize| {
+        u64::from(u32::from_le_bytes([
+            bytes[at],
+            bytes[at + 1],
+            bytes[at + 2],
+            bytes[at + 3],
+        ]))
+    };
+    let total = le16(end, 10);
+
+    file.seek(SeekFrom::Start(le32(end, 16)))?;
+    let mut directory = Vec::new();
+    file.take(le32(end, 12).min(ZIP_DIRECTORY_MAX_BYTES))
+        .read_to_end(&mut directory)?;
+
+    let mut entries = Vec::new();
+    let mut at = 0;
+    while entries.len() < ARCHIVE_MAX_ENTRIES
+        && directory.get(at..at + 4) == Some(b"PK\x01\x02".as_slice())
+        && at + 46 <= directory.len()
+    {
+        let name_len = le16(&directory, at + 28);
+        let Some(name) = directory.get(at + 46..at + 46 + name_len) else {
+            break;
+        };
+        entries.push(String::from_utf8_lossy(name).into_owned());
+        at += 46 + name_len + le16(&directory, at + 30) + le16(&directory, at + 32);
+    }
+    // Zip64 archives keep the real count elsewhere
+    Ok((entries, (total != usize::from(u16::MAX)).then_some(total)))
+}
+
+/// Names from the headers of a tar file. Only the first entries are read, so the
+/// total is known only for small archives.
+fn tar_entries(file: &mut File) -> Result<(Vec<String>, Option<usize>)> {
+    let mut entries = Vec::new();
+    let mut header = [0u8; 512];
+    loop {
+        let complete = file.read_exact(&mut header).is_ok();
+        if !complete && entries.is_empty() {
+            bail!("Not a tar archive");
+        }
+        if !complete || header.iter().all(|b| *b == 0) {
+            let total = entries.len();
+            return Ok((entries, Some(total)));
+        }
+        if !header[257..].starts_with(b"ustar") && entries.is_empty() {
+            bail!("Not a tar archive");
+        }
+        let field = |range: std::ops::Range<usize>| {
+            let bytes = &header[range];
+            let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            String::from_utf8_lossy(&bytes[..end]).into_owned()
+        };
+        let size = u64::from_str_radix(field(124..136).trim(), 8).unwrap_or(0);
+        // Extended headers describe the entry that follows rather than being one
+        if !matches!(header[156], b'x' | b'g' | b'L' | b'K') {
+            if entries.len() == ARCHIVE_MAX_ENTRIES {
+                return Ok((entries, None));
+            }
+            let prefix = field(345..500);
+            let name = field(0..100);
+            entries.push(if prefix.is_empty() {
+                name
+            } else {
+                format!("{}/{}", prefix, name)
+            });
+        }
+        file.seek(SeekFrom::Current(size.div_ceil(512) as i64 * 512))?;
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,198 @@
+use super::{
+    ArchivePreview, DocumentPreview, PreviewRegistry, PreviewStrategy, RawPreview,
+    image_dimensions, registry, strategies_for,
+};
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tempfile::TempDir;
+
+struct Fixed(&'static str);
+
+impl PreviewStrategy for Fixed {
+    fn name(&self) -> &'static str {
+        self.0
+    }
+
+    fn preview(&self, _path: &Path) -> Result<Option<RawPreview>> {
+        Ok(Some(RawPreview::new(self.0.to_string())))
+    }
+}
+
+/// A zip holding empty, stored files with the given names.
+fn zip_with(names: &[&str]) -> Vec<u8> {
+    let mut local = Vec::new();
+    let mut directory = Vec::new();
+    for name in names {
+        let offset = local.len() as u32;
+        local.extend_from_slice(b"PK\x03\x04");
+        local.extend_from_slice(&[0; 22]);
+        local.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        local.extend_from_slice(&[0; 2]);
+        local.extend_from_slice(name.as_bytes());
+
+        directory.extend_from_slice(b"PK\x01\x02");
+        directory.extend_from_slice(&[0; 24]);
+        directory.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        directory.extend_from_slice(&[0; 12]);
+        directory.extend_from_slice(&offset.to_le_bytes());
+        directory.extend_from_slice(name.as_bytes());
+    }
+
+    let mut zip = local;
+    let directory_offset = zip.len() as u32;
+    zip.extend_from_slice(&directory);
+    zip.extend_from_slice(b"PK\x05\x06");
+    zip.extend_from_slice(&[0; 4]);
+    zip.extend_from_slice(&(names.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(names.len() as u16).to_le_bytes());
+    zip.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    zip.extend_from_slice(&directory_offset.to_le_bytes());
+    zip.extend_from_slice(&[0; 2]);
+    zip
+}
+
+/// A ustar archive with one file per `(name, content)`.
+fn tar_with(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut tar = Vec::new();
+    for (name, content) in files {
+        let mut header = [0u8; 512];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", content.len()).as_bytes());
+        header[156] = b'0';
+        header[257..262].copy_from_slice(b"ustar");
+        tar.extend_from_slice(&header);
+        tar.extend_from_slice(content);
+        tar.resize(tar.len().div_ceil(512) * 512, 0);
+    }
+    tar.extend_from_slice(&[0; 1024]);
+    tar
+}
+
+#[test]
+fn test_longest_prefix_wins() {
+    let mut registry = PreviewRegistry::new();
+    registry.register("image/", Arc::new(Fixed("image")));
+    registry.register("image/svg", Arc::new(Fixed("svg")));
+
+    assert_eq!(registry.strategy_for("image/png").name(), "image");
+    assert_eq!(registry.strategy_for("image/svg+xml").name(), "svg");
+    assert_eq!(registry.strategy_for("application/pdf").name(), "document");
+
+    registry.register("image/", Arc::new(Fixed("replaced")));
+    assert_eq!(registry.strategy_for("image/png").name(), "replaced");
+}
+
+#[test]
+fn test_builtin_strategies() {
+    let registry = PreviewRegistry::with_builtins();
+    assert_eq!(registry.strategy_for("text/x-python").name(), "text");
+    assert_eq!(registry.strategy_for("image/jpeg").name(), "image");
+    assert_eq!(registry.strategy_for("application/zip").name(), "archive");
+    assert_eq!(registry.strategy_for("application/x-tar").name(), "archive");
+    assert_eq!(registry.strategy_for("Unknown").name(), "document");
+}
+
+#[test]
+fn test_registered_strategy_is_used_process_wide() {
+    registry()
+        .write()
+        .unwrap()
+        .register("application/x-test-preview", Arc::new(Fixed("custom")));
+    let (strategy, fallback) = strategies_for("application/x-test-preview");
+    assert_eq!(strategy.name(), "custom");
+    assert_eq!(fallback.name(), "document");
+}
+
+#[test]
+fn test_image_dimensions() {
+    let png = fs::read("test_dir/12.png").unwrap();
+    assert_eq!(image_dimensions(&png), Some((500, 500)));
+
+    let gif = b"GIF89a\x40\x01\xf0\x00";
+    assert_eq!(image_dimensions(gif), Some((320, 240)));
+
+    // SOI, an APP0 segment to skip, then a baseline frame of 640x480
+    let jpeg = [
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x01, 0xE0,
+        0x02, 0x80,
+    ];
+    assert_eq!(image_dimensions(&jpeg), Some((640, 480)));
+
+    assert_eq!(image_dimensions(b"II*\x00"), None);
+    assert_eq!(image_dimensions(&[0xFF, 0xD8, 0xFF]), None);
+}
+
+#[test]
+fn test_zip_archive_is_listed() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("photos.zip");
+    fs::write(&path, zip_with(&["trip/", "trip/beach.jpg"])).unwrap();
+
+    let preview = ArchivePreview.preview(&path).unwrap().unwrap();
+    assert_eq!(
+        preview.text,
+        "Archive with 2 entries:\ntrip/\ntrip/beach.jpg"
+    );
+}
+
+#[test]
+fn test_tar_archive_is_listed() {
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("backup.tar");
+    fs::write(
+        &path,
+        tar_with(&[("notes.txt", &b"x".repeat(700)), ("todo.md", b"- a")]),
+    )
+    .unwrap();
+
+    let preview = ArchivePreview.preview(&path).unwrap().unwrap();
+    assert_eq!(preview.text, "Archive with 2 entries:\nnotes.txt\ntodo.md");
+
+    fs::write(&path, b"not an archive at all").unwrap();
+    assert!(ArchivePreview.preview(&path).is_err());
+}
+
+#[test]
+fn test_pdf_title_beyond_the_head_is_found() {
+    use lopdf::{Document, Object, Stream, dictionary, text_string};
+
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("report.pdf");
+    let mut document = Document::with_version("1.5");
+    let pages_id = document.new_object_id();
+    let content_id = document.add_object(Stream::new(
+        dictionary! {},
+        b"BT /F1 12 Tf (Quarterly numbers) Tj ET\n".repeat(100),
+    ));
+    let page_id = document.add_object(dictionary! {
+        "Type" => "Page",
+        "Parent" => pages_id,
+        "Contents" => content_id,
+    });
+    document.objects.insert(
+        pages_id,
+        Object::Dictionary(dictionary! {
+            "Type" => "Pages",
+            "Kids" => vec![page_id.into()],
+            "Count" => 1,
+        }),
+    );
+    let catalog_id = document.add_object(dictionary! {
+        "Type" => "Catalog",
+        "Pages" => pages_id,
+    });
+    let info_id = document.add_object(dictionary! {
+        "Title" => text_string("Quarterly Report"),
+    });
+    document.trailer.set("Root", catalog_id);
+    document.trailer.set("Info", info_id);
+    document.save(&path).unwrap();
+    let contents = fs::read(&path).unwrap();
+    let title_at = contents.windows(6).position(|w| w == b"/Title").unwrap();
+    assert!(title_at > 1024, "the title should be past the head");
+
+    let preview = DocumentPreview.preview(&path).unwrap().unwrap();
+    assert_eq!(preview.title.as_deref(), Some("Quarterly Report"));
+}
@@ -0,0 +1,76 @@
+use crate::providers::{Provider, TokenUsage};
+
+/// What a model charges, in US dollars per million tokens.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPrice {
+    const FREE: Self = Self {
+        input: 0.0,
+        output: 0.0,
+    };
+
+    const fn new(input: f64, output: f64) -> Self {
+        Self { input, output }
+    }
+
+    pub fn is_free(&self) -> bool {
+        *self == Self::FREE
+    }
+
+    /// Dollars `usage` costs at this price.
+    pub fn cost(&self, usage: &TokenUsage) -> f64 {
+        (usage.prompt_tokens as f64 * self.input + usage.completion_tokens as f64 * self.output)
+            / 1_000_000.0
+    }
+}
+
+/// List prices of the models offered by `shelfie config`, for a rough estimate only.
+/// A model gets the price of the first fragment its name contains, so more specific
+/// names come before the families they belong to.
+const PRICES: &[(&str, ModelPrice)] = &[
+    ("gpt-5-nano", ModelPrice::new(0.05, 0.40)),
+    ("gpt-5-mini", ModelPrice::new(0.25, 2.00)),
+    ("gpt-5", ModelPrice::new(1.25, 10.00)),
+    ("gpt-4.1-nano", ModelPrice::new(0.10, 0.40)),
+    ("gpt-4.1-mini", ModelPrice::new(0.40, 1.60)),
+    ("gpt-4.1", ModelPrice::new(2.00, 8.00)),
+    ("gpt-4o-mini", ModelPrice::new(0.15, 0.60)),
+    ("gpt-4o", ModelPrice::new(2.50, 10.00)),
+    ("gpt-4-turbo", ModelPrice::new(10.00, 30.00)),
+    ("gpt-4", ModelPrice::new(30.00, 60.00)),
+    ("gpt-3.5", ModelPrice::new(0.50, 1.50)),
+    ("claude-3-haiku", ModelPrice::new(0.25, 1.25)),
+    ("haiku", ModelPrice::new(1.00, 5.00)),
+    ("sonnet", ModelPrice::new(3.00, 15.00)),
+    ("opus", ModelPrice::new(15.00, 75.00)),
+    ("llama-3.1-8b", ModelPrice::new(0.05, 0.08)),
+    ("llama-3.3-70b", ModelPrice::new(0.59, 0.79)),
+    ("mistral-small", ModelPrice::new(0.10, 0.30)),
+    ("mistral-large", ModelPrice::new(2.00, 6.00)),
+];
+
+/// Assumed for models missing from the table: a mid-range hosted model.
+pub const FALLBACK_PRICE: ModelPrice = ModelPrice::new(2.50, 10.00);
+
+/// The price of `model` on `provider`. Local models are free; `None` for models
+/// that aren't in the table.
+pub fn model_price(provider: &Provider, model: &str) -> Option<ModelPrice> {
+    match provider {
+        Provider::Ollama => return Some(ModelPrice::FREE),
+        #[cfg(test)]
+        Provider::Mock(_) => return Some(ModelPrice::FREE),
+        _ => {}
+    }
+    let model = model.to_lowercase();
+    PRICES
+        .iter()
+        .find(|(fragment, _)| model.contains(fragment))
+        .map(|(_, price)| *price)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,35 @@
+use super::*;
+
+#[test]
+fn test_specific_models_are_priced_before_their_family() {
+    assert_eq!(
+        model_price(&Provider::OpenAI, "gpt-4o-mini-2024-07-18"),
+        Some(ModelPrice::new(0.15, 0.60))
+    );
+    assert_eq!(
+        model_price(&Provider::OpenAI, "GPT-4o"),
+        Some(ModelPrice::new(2.50, 10.00))
+    );
+    assert_eq!(
+        model_price(&Provider::Anthropic, "claude-4-sonnet-latest"),
+        Some(ModelPrice::new(3.00, 15.00))
+    );
+}
+
+#[test]
+fn test_local_models_are_free_and_unknown_ones_unpriced() {
+    assert!(model_price(&Provider::Ollama, "llama3").unwrap().is_free());
+    assert_eq!(model_price(&Provider::Groq, "some-new-model"), None);
+}
+
+#[test]
+fn test_cost_adds_both_directions() {
+    let usage = TokenUsage {
+        requests: 2,
+        prompt_tokens: 2_000_000,
+        completion_tokens: 500_000,
+    };
+
+    let cost = ModelPrice::new(2.50, 10.00).cost(&usage);
+    assert!((cost - 10.0).abs() < 1e-9, "{}", cost);
+}
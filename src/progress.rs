@@ -0,0 +1,185 @@
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle, TermLike};
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Turns off progress output for the rest of the process (`--quiet-progress`).
+pub fn set_quiet(enabled: bool) {
+    QUIET.store(enabled, Ordering::Relaxed);
+}
+
+/// How progress is shown on stderr.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProgressMode {
+    /// Animated spinners and bars
+    Fancy,
+    /// One plain line per update, for dumb terminals, CI logs and redirected output
+    Plain,
+    Hidden,
+}
+
+impl ProgressMode {
+    pub fn detect() -> Self {
+        Self::for_terminal(
+            QUIET.load(Ordering::Relaxed),
+            io::stderr().is_terminal(),
+            std::env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    pub fn for_terminal(quiet: bool, is_terminal: bool, term: Option<&str>) -> Self {
+        if quiet {
+            ProgressMode::Hidden
+        } else if !is_terminal || term == Some("dumb") {
+            ProgressMode::Plain
+        } else {
+            ProgressMode::Fancy
+        }
+    }
+}
+
+/// A spinner showing `message`, ticking on its own in fancy mode.
+pub fn spinner(message: &str) -> ProgressBar {
+    let mode = ProgressMode::detect();
+    let progress_bar = ProgressBar::with_draw_target(None, draw_target(mode));
+    progress_bar.set_style(match mode {
+        ProgressMode::Fancy => ProgressStyle::default_spinner()
+            .template("{spinner:.green} {msg}")
+            .unwrap(),
+        _ => ProgressStyle::default_spinner().template("{msg}").unwrap(),
+    });
+    progress_bar.set_message(message.to_string());
+    if mode == ProgressMode::Fancy {
+        progress_bar.enable_steady_tick(Duration::from_millis(200));
+    }
+    progress_bar
+}
+
+/// A bar counting up to `len` `unit`s (e.g. "batches"), followed by its message.
+/// Fancy bars tick on their own so the elapsed time keeps moving.
+pub fn bar(len: u64, unit: &str) -> ProgressBar {
+    let mode = ProgressMode::detect();
+    let progress_bar = ProgressBar::with_draw_target(Some(len), draw_target(mode));
+    set_bar_style(&progress_bar, mode, unit);
+    if mode == ProgressMode::Fancy {
+        progress_bar.enable_steady_tick(Duration::from_millis(200));
+    }
+    progress_bar
+}
+
+/// Turns a spinner from [`spinner`] into a bar counting up to `len` `unit`s.
+pub fn switch_to_bar(progress_bar: &ProgressBar, len: u64, unit: &str) {
+    set_bar_style(progress_bar, ProgressMode::detect(), unit);
+    progress_bar.set_length(len);
+}
+
+fn set_bar_style(progress_bar: &ProgressBar, mode: ProgressMode, unit: &str) {
+    let template = match mode {
+        ProgressMode::Fancy => format!(
+            "{{spinner:.green}} [{{elapsed_precise}}] [{{bar:40.cyan/blue}}] {{pos}}/{{len}} {} {{msg}}",
+            unit
+        ),
+        _ => format!("Processed {{pos}}/{{len}} {} {{msg}}", unit),
+    };
+    progress_bar.set_style(ProgressStyle::default_bar().template(&template).unwrap());
+}
+
+fn draw_target(mode: ProgressMode) -> ProgressDrawTarget {
+    match mode {
+        ProgressMode::Fancy => ProgressDrawTarget::stderr(),
+        // At most one line a second, so a long run doesn't flood the log
+        ProgressMode::Plain => {
+            ProgressDrawTarget::term_like_with_hz(Box::new(PlainLines::new(io::stderr())), 1)
+        }
+        ProgressMode::Hidden => ProgressDrawTarget::hidden(),
+    }
+}
+
+/// Writes each redraw as a plain line, skipping cursor movement and redraws that
+/// didn't change anything.
+pub struct PlainLines<W> {
+    output: Mutex<W>,
+    state: Mutex<PlainLinesState>,
+}
+
+#[derive(Default)]
+struct PlainLinesState {
+    current: String,
+    last: String,
+}
+
+impl<W: Write> PlainLines<W> {
+    pub fn new(output: W) -> Self {
+        Self {
+            output: Mutex::new(output),
+            state: Mutex::default(),
+        }
+    }
+
+    fn emit(&self) -> io::Result<()> {
+        let mut state = self.state.lock().unwrap();
+        let line = std::mem::take(&mut state.current).trim().to_string();
+        if line.is_empty() || line == state.last {
+            return Ok(());
+        }
+        writeln!(self.output.lock().unwrap(), "{}", line)?;
+        state.last = line;
+        Ok(())
+    }
+}
+
+impl<W> fmt::Debug for PlainLines<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PlainLines").finish_non_exhaustive()
+    }
+}
+
+impl<W: Write + Send> TermLike for PlainLines<W> {
+    fn width(&self) -> u16 {
+        // Wide enough that indicatif doesn't wrap a line; the padding it adds is trimmed
+        500
+    }
+
+    fn move_cursor_up(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_down(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_right(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn move_cursor_left(&self, _n: usize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn write_line(&self, s: &str) -> io::Result<()> {
+        self.write_str(s)?;
+        self.emit()
+    }
+
+    fn write_str(&self, s: &str) -> io::Result<()> {
+        self.state.lock().unwrap().current.push_str(s);
+        Ok(())
+    }
+
+    fn clear_line(&self) -> io::Result<()> {
+        self.state.lock().unwrap().current.clear();
+        Ok(())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.emit()?;
+        self.output.lock().unwrap().flush()
+    }
+}
+
+#[cfg(test)]
+mod tests;
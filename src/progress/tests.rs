@@ -0,0 +1,65 @@
+use super::{PlainLines, ProgressMode};
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_progress_mode_for_terminal() {
+    assert_eq!(
+        ProgressMode::for_terminal(false, true, Some("xterm-256color")),
+        ProgressMode::Fancy
+    );
+    assert_eq!(
+        ProgressMode::for_terminal(false, true, Some("dumb")),
+        ProgressMode::Plain
+    );
+    assert_eq!(
+        ProgressMode::for_terminal(false, false, Some("xterm")),
+        ProgressMode::Plain
+    );
+    assert_eq!(
+        ProgressMode::for_terminal(true, true, None),
+        ProgressMode::Hidden
+    );
+}
+
+#[test]
+fn test_plain_lines_prints_each_change_once() {
+    let buffer = SharedBuffer::default();
+    let progress_bar = ProgressBar::with_draw_target(
+        Some(3),
+        ProgressDrawTarget::term_like(Box::new(PlainLines::new(buffer.clone()))),
+    );
+    progress_bar.set_style(
+        ProgressStyle::default_bar()
+            .template("Processed {pos}/{len} batches")
+            .unwrap(),
+    );
+
+    progress_bar.inc(1);
+    progress_bar.tick();
+    progress_bar.inc(1);
+    progress_bar.finish();
+
+    let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines.last(), Some(&"Processed 3/3 batches"));
+    assert!(lines.contains(&"Processed 2/3 batches"));
+    let mut deduped = lines.clone();
+    deduped.dedup();
+    assert_eq!(deduped, lines);
+    assert!(!output.contains('\u{1b}'));
+}
@@ -0,0 +1,126 @@
+use anyhow::{Context, Result, bail};
+use std::{fs, path::Path};
+
+/// Placeholders a template must contain, since the model can't assign items
+/// without seeing them and the existing structure.
+pub const REQUIRED_PLACEHOLDERS: &[&str] = &["cabinets", "shelves", "items"];
+
+/// The built-in batch analysis prompt.
+pub const DEFAULT_PROMPT_TEMPLATE: &str = "Analyze these files and directories for organization. \
+You have up to {max_cabinets} cabinets (top-level containers) and up to {max_shelves} shelves per cabinet.
+
+Existing Cabinets:
+{cabinets}
+
+Existing Shelves:
+{shelves}
+
+Items to analyze:
+{items}
+
+For each item, provide:
+1. A brief description (one sentence)
+2. A suggested_name (better name if needed, or empty string if current name is fine) and your rename_confidence in it, from 0 to 1
+3. For directories, determine if they're opaque (homogeneous content, generated files, etc.)
+4. Assign to an existing or new cabinet and shelf
+
+For cabinet and shelf assignments:
+- To use existing: set assignment_type='existing', existing_id to the ID, new_name='' and new_description=''
+- To create new: set assignment_type='new', existing_id=0, new_name and new_description to actual values
+
+Guidelines:
+- Group related items together
+- Use existing cabinets/shelves when appropriate
+- Create new ones only when necessary
+- Keep names short and descriptive
+- When an item has a title, prefer it as the basis of its suggested_name
+- Do not treat non-English items any differently
+";
+
+/// Values substituted into a prompt template.
+pub struct PromptValues<'a> {
+    pub max_cabinets: usize,
+    pub max_shelves: usize,
+    pub cabinets: &'a str,
+    pub shelves: &'a str,
+    pub items: &'a str,
+}
+
+/// A batch analysis prompt with `{placeholder}`s, so the instructions can be
+/// customized without recompiling.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PromptTemplate {
+    text: String,
+}
+
+impl Default for PromptTemplate {
+    fn default() -> Self {
+        Self {
+            text: DEFAULT_PROMPT_TEMPLATE.to_string(),
+        }
+    }
+}
+
+impl PromptTemplate {
+    pub fn parse(text: &str) -> Result<Self> {
+        let missing = REQUIRED_PLACEHOLDERS
+            .iter()
+            .filter(|name| !text.contains(&format!("{{{}}}", name)))
+            .map(|name| format!("{{{}}}", name))
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            bail!("missing required placeholders: {}", missing.join(", "));
+        }
+
+        Ok(Self {
+            text: text.to_string(),
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read prompt template {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid prompt template {}", path.display()))
+    }
+
+    /// Substitutes the placeholders in a single pass, so braces in file names or
+    /// descriptions are never mistaken for placeholders. Unknown placeholders are
+    /// left as they are.
+    pub fn render(&self, values: &PromptValues) -> String {
+        let mut rendered = String::with_capacity(self.text.len() + values.items.len());
+        let mut rest = self.text.as_str();
+
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            let after = &rest[start + 1..];
+            let value = after.find('}').and_then(|end| {
+                let value = match &after[..end] {
+                    "max_cabinets" => values.max_cabinets.to_string(),
+                    "max_shelves" => values.max_shelves.to_string(),
+                    "cabinets" => values.cabinets.to_string(),
+                    "shelves" => values.shelves.to_string(),
+                    "items" => values.items.to_string(),
+                    _ => return None,
+                };
+                Some((value, end))
+            });
+
+            match value {
+                Some((value, end)) => {
+                    rendered.push_str(&value);
+                    rest = &after[end + 1..];
+                }
+                None => {
+                    rendered.push('{');
+                    rest = after;
+                }
+            }
+        }
+        rendered.push_str(rest);
+
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests;
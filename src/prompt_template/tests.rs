@@ -0,0 +1,49 @@
+use super::{PromptTemplate, PromptValues};
+use std::fs;
+use tempfile::TempDir;
+
+fn values() -> PromptValues<'static> {
+    PromptValues {
+        max_cabinets: 5,
+        max_shelves: 7,
+        cabinets: "ID: 1, Name: Finance",
+        shelves: "ID: 2, Cabinet ID: 1, Name: Taxes",
+        items: "ID: 0, Name: {items}.txt",
+    }
+}
+
+#[test]
+fn test_default_template_substitutes_all_placeholders() {
+    let prompt = PromptTemplate::default().render(&values());
+
+    assert!(prompt.contains("up to 5 cabinets"));
+    assert!(prompt.contains("up to 7 shelves per cabinet"));
+    assert!(prompt.contains("Existing Cabinets:\nID: 1, Name: Finance\n"));
+    assert!(prompt.contains("Existing Shelves:\nID: 2, Cabinet ID: 1, Name: Taxes\n"));
+    // Substituted values are not scanned for placeholders again
+    assert!(prompt.contains("Items to analyze:\nID: 0, Name: {items}.txt\n"));
+}
+
+#[test]
+fn test_custom_template_keeps_unknown_braces() {
+    let template = PromptTemplate::parse(
+        "Legal docs {cabinets} | {shelves} | {items} {\"json\": true} {other}",
+    )
+    .unwrap();
+
+    assert_eq!(
+        template.render(&values()),
+        "Legal docs ID: 1, Name: Finance | ID: 2, Cabinet ID: 1, Name: Taxes | \
+        ID: 0, Name: {items}.txt {\"json\": true} {other}"
+    );
+}
+
+#[test]
+fn test_load_rejects_missing_placeholders() {
+    let dir = TempDir::new().unwrap();
+    let path = dir.path().join("prompt.txt");
+    fs::write(&path, "Sort these: {items}").unwrap();
+
+    let error = format!("{:#}", PromptTemplate::load(&path).unwrap_err());
+    assert!(error.contains("{cabinets}, {shelves}"), "{}", error);
+}
@@ -0,0 +1,74 @@
+use anyhow::{Result, bail};
+use colored::*;
+use dialoguer::{Confirm, Input, Select, theme::ColorfulTheme};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Setting this to `1`, `true` or `yes` runs shelfie without any prompts, e.g. in CI.
+pub const AUTO_CONFIRM_ENV: &str = "SHELFIE_AUTO_CONFIRM";
+
+static NON_INTERACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Turns off prompting for the rest of the process (`--yes`).
+pub fn set_non_interactive(enabled: bool) {
+    NON_INTERACTIVE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_non_interactive() -> bool {
+    NON_INTERACTIVE.load(Ordering::Relaxed)
+        || std::env::var(AUTO_CONFIRM_ENV).is_ok_and(|value| is_truthy(&value))
+}
+
+fn is_truthy(value: &str) -> bool {
+    matches!(
+        value.trim().to_lowercase().as_str(),
+        "1" | "true" | "yes" | "on"
+    )
+}
+
+/// Asks a yes/no question. In non-interactive mode, answers `non_interactive_answer`
+/// without waiting on stdin; callers should pass `false` for destructive actions.
+pub fn confirm(prompt: &str, default: bool, non_interactive_answer: bool) -> Result<bool> {
+    if is_non_interactive() {
+        eprintln!(
+            "{} {}",
+            prompt,
+            if non_interactive_answer { "yes" } else { "no" }.dimmed()
+        );
+        return Ok(non_interactive_answer);
+    }
+
+    Ok(Confirm::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .default(default)
+        .interact()?)
+}
+
+/// Asks for free text. Fails in non-interactive mode, as there is no sensible default.
+pub fn input(prompt: &str, allow_empty: bool) -> Result<String> {
+    if is_non_interactive() {
+        bail!("'{}' needs an answer, but prompts are disabled", prompt);
+    }
+
+    Ok(Input::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .allow_empty(allow_empty)
+        .interact_text()?)
+}
+
+/// Asks the user to pick one of `items`. Fails in non-interactive mode.
+pub fn select<T: ToString>(prompt: &str, items: &[T]) -> Result<usize> {
+    if is_non_interactive() {
+        bail!(
+            "'{}' needs an answer, but prompts are disabled; run 'shelfie config edit' interactively first",
+            prompt
+        );
+    }
+
+    Ok(Select::with_theme(&ColorfulTheme::default())
+        .with_prompt(prompt)
+        .items(items)
+        .interact()?)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,11 @@
+use super::is_truthy;
+
+#[test]
+fn test_env_values() {
+    assert!(is_truthy("1"));
+    assert!(is_truthy("TRUE"));
+    assert!(is_truthy(" yes "));
+    assert!(!is_truthy("0"));
+    assert!(!is_truthy("false"));
+    assert!(!is_truthy(""));
+}
@@ -1,28 +1,145 @@
 use anyhow::{Result, anyhow};
-use dialoguer::{Input, Select, theme::ColorfulTheme};
+use colored::*;
 use rig::client::builder::{BoxAgentBuilder, DynClientBuilder};
+use rig::client::completion::CompletionClientDyn;
 use rig::client::{CompletionClient, ProviderClient};
-use rig::providers::{anthropic, ollama, openai};
+use rig::completion::CompletionModel;
+use rig::extractor::ExtractorBuilder;
+use rig::providers::{anthropic, groq, mistral, ollama, openai};
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::sync::{Arc, Mutex};
 
-use crate::config::Config;
+use crate::{batch_processor::BatchProcessor, config::Config, prompts};
+
+/// Low temperature keeps classification consistent between runs.
+const DEFAULT_TEMPERATURE: f64 = 0.2;
+/// The tool rig's extractor receives the response through; its parameters are the
+/// response type's `schemars` JSON schema.
+const EXTRACTOR_TOOL_NAME: &str = "submit";
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Provider {
     OpenAI,
     Anthropic,
     Ollama,
+    Groq,
+    Mistral,
     #[cfg(test)]
     Mock(Vec<String>),
 }
 
+impl Provider {
+    /// Parses a provider name as used in `provider:model` specs, e.g. `ollama`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "openai" => Some(Provider::OpenAI),
+            "anthropic" => Some(Provider::Anthropic),
+            "ollama" => Some(Provider::Ollama),
+            "groq" => Some(Provider::Groq),
+            "mistral" => Some(Provider::Mistral),
+            _ => None,
+        }
+    }
+}
+
+impl Provider {
+    /// The environment variable that overrides the provider's API base URL.
+    fn base_url_var(&self) -> Option<&'static str> {
+        match self {
+            Provider::OpenAI => Some("OPENAI_BASE_URL"),
+            Provider::Anthropic => Some("ANTHROPIC_BASE_URL"),
+            Provider::Ollama => Some("OLLAMA_API_BASE_URL"),
+            _ => None,
+        }
+    }
+
+    /// Where the provider's API is served by default; `None` for providers whose
+    /// base URL can't be changed.
+    fn default_base_url(&self) -> Option<&'static str> {
+        match self {
+            Provider::OpenAI => Some("https://api.openai.com/v1"),
+            Provider::Anthropic => Some("https://api.anthropic.com"),
+            Provider::Ollama => Some("http://localhost:11434"),
+            _ => None,
+        }
+    }
+
+    /// Whether requests can be sent to another server, e.g. a self-hosted gateway.
+    pub fn supports_base_url(&self) -> bool {
+        self.default_base_url().is_some()
+    }
+}
+
+/// The base URL requests to `provider` go to: the configured one, else the one in the
+/// provider's environment variable, e.g. `OPENAI_BASE_URL`, else its default. `var`
+/// looks up an environment variable.
+pub fn resolve_base_url(
+    provider: &Provider,
+    configured: Option<&str>,
+    var: impl Fn(&str) -> Option<String>,
+) -> Option<String> {
+    configured
+        .map(str::to_string)
+        .or_else(|| var(provider.base_url_var()?).filter(|url| !url.trim().is_empty()))
+        .or_else(|| provider.default_base_url().map(str::to_string))
+        .map(|url| url.trim_end_matches('/').to_string())
+}
+
+pub const PROVIDER_ENV_VAR: &str = "SHELFIE_PROVIDER";
+pub const MODEL_ENV_VAR: &str = "SHELFIE_MODEL";
+
+/// The provider and model named by `SHELFIE_PROVIDER` and `SHELFIE_MODEL`, if the
+/// provider is set. `var` looks up an environment variable.
+pub fn provider_from_env(
+    var: impl Fn(&str) -> Option<String>,
+) -> Result<Option<(Provider, String)>> {
+    let Some(name) = var(PROVIDER_ENV_VAR).filter(|name| !name.trim().is_empty()) else {
+        return Ok(None);
+    };
+    let provider = Provider::parse(name.trim()).ok_or_else(|| {
+        anyhow!(
+            "{} must be one of openai, anthropic, ollama, groq or mistral, found {:?}",
+            PROVIDER_ENV_VAR,
+            name
+        )
+    })?;
+    let model_name = var(MODEL_ENV_VAR)
+        .map(|model| model.trim().to_string())
+        .filter(|model| !model.is_empty())
+        .ok_or_else(|| anyhow!("{} is set but {} is not", PROVIDER_ENV_VAR, MODEL_ENV_VAR))?;
+    Ok(Some((provider, model_name)))
+}
+
+/// Whether `model_name` is among the provider's `available` models, allowing for
+/// Ollama's implicit `:latest` tag and Anthropic's undated aliases.
+pub fn model_is_listed(provider: &Provider, model_name: &str, available: &[String]) -> bool {
+    if available.iter().any(|model| model == model_name) {
+        return true;
+    }
+    match provider {
+        Provider::Ollama => {
+            !model_name.contains(':')
+                && available
+                    .iter()
+                    .any(|model| *model == format!("{}:latest", model_name))
+        }
+        Provider::Anthropic => {
+            let stem = model_name.strip_suffix("-latest").unwrap_or(model_name);
+            available.iter().any(|model| model.starts_with(stem))
+        }
+        _ => false,
+    }
+}
+
 impl std::fmt::Display for Provider {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Provider::OpenAI => write!(f, "OpenAI"),
             Provider::Anthropic => write!(f, "Anthropic"),
             Provider::Ollama => write!(f, "Ollama (Local)"),
+            Provider::Groq => write!(f, "Groq"),
+            Provider::Mistral => write!(f, "Mistral"),
             #[cfg(test)]
             Provider::Mock(_) => write!(f, "Mock Provider"),
         }
@@ -60,11 +177,32 @@ struct OllamaModelsResponse {
     models: Vec<OllamaModel>,
 }
 
+/// Estimated tokens spent on successful extractions. The providers' actual usage
+/// isn't reported through extraction, so both sides are estimated from their text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub requests: usize,
+    pub prompt_tokens: usize,
+    pub completion_tokens: usize,
+}
+
+impl TokenUsage {
+    pub fn total(&self) -> usize {
+        self.prompt_tokens + self.completion_tokens
+    }
+}
+
 #[derive(Clone)]
 pub struct LLMProvider {
     provider: Provider,
     model_name: String,
+    max_tokens: Option<u64>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    /// Configured API base URL, overriding the environment's
+    base_url: Option<String>,
     mock_call_count: std::sync::Arc<std::sync::Mutex<usize>>,
+    usage: Arc<Mutex<TokenUsage>>,
 }
 
 impl LLMProvider {
@@ -73,35 +211,80 @@ impl LLMProvider {
         Self {
             provider: Provider::Mock(responses),
             model_name: "mock-model".to_string(),
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            base_url: None,
             mock_call_count: std::sync::Arc::new(std::sync::Mutex::new(0)),
+            usage: Arc::default(),
         }
     }
 
     pub async fn new() -> Result<Self> {
+        let config = Config::load()?;
+
+        // SHELFIE_PROVIDER/SHELFIE_MODEL (e.g. from a .env) take precedence over the saved choice
+        if let Some((provider, model_name)) = provider_from_env(|key| env::var(key).ok())? {
+            eprintln!(
+                "Using {:?} with model {} from the environment",
+                provider, model_name
+            );
+
+            // The saved base URL is for the saved provider
+            let base_url = config
+                .as_ref()
+                .filter(|config| config.provider.to_string() == provider.to_string())
+                .and_then(|config| config.base_url.clone());
+            Self::validate_ai_provider_config(&provider, &model_name, base_url.as_deref()).await?;
+
+            return Ok(Self {
+                provider,
+                model_name,
+                max_tokens: config.as_ref().and_then(|config| config.max_tokens),
+                temperature: config.as_ref().and_then(|config| config.temperature),
+                top_p: config.as_ref().and_then(|config| config.top_p),
+                base_url,
+                mock_call_count: std::sync::Arc::new(std::sync::Mutex::new(0)),
+                usage: Arc::default(),
+            });
+        }
+
         // Try to load existing config first
-        if let Some(config) = Config::load()? {
-            println!(
-                "Using saved configuration: {} with model {}",
-                format!("{:?}", config.provider),
-                config.model_name
+        if let Some(config) = config {
+            eprintln!(
+                "Using saved configuration: {:?} with model {}",
+                config.provider, config.model_name
             );
 
-            Self::validate_ai_provider_config(&config.provider).await?;
+            Self::validate_ai_provider_config(
+                &config.provider,
+                &config.model_name,
+                config.base_url.as_deref(),
+            )
+            .await?;
 
             return Ok(Self {
                 provider: config.provider,
                 model_name: config.model_name,
+                max_tokens: config.max_tokens,
+                temperature: config.temperature,
+                top_p: config.top_p,
+                base_url: config.base_url,
                 mock_call_count: std::sync::Arc::new(std::sync::Mutex::new(0)),
+                usage: Arc::default(),
             });
         }
 
         // If no config exists, prompt user and save the selection
-        let providers = vec![Provider::OpenAI, Provider::Anthropic, Provider::Ollama];
+        let providers = vec![
+            Provider::OpenAI,
+            Provider::Anthropic,
+            Provider::Ollama,
+            Provider::Groq,
+            Provider::Mistral,
+        ];
 
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select LLM Provider")
-            .items(&providers)
-            .interact()?;
+        let selection = prompts::select("Select LLM Provider", &providers)?;
 
         let provider = providers[selection].clone();
         let model_name = Self::select_model(&provider).await?;
@@ -110,17 +293,64 @@ impl LLMProvider {
         let config = Config {
             provider: provider.clone(),
             model_name: model_name.clone(),
+            max_tokens: None,
+            db_path: None,
+            temperature: None,
+            top_p: None,
+            max_cabinets: None,
+            max_shelves_per_cabinet: None,
+            prompt_template: None,
+            unsorted_cabinet: None,
+            scan_concurrency: None,
+            extraction_parallelism: None,
+            base_url: None,
         };
         config.save()?;
 
         Ok(Self {
             provider,
             model_name,
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            base_url: None,
             mock_call_count: std::sync::Arc::new(std::sync::Mutex::new(0)),
+            usage: Arc::default(),
         })
     }
 
-    async fn validate_ai_provider_config(provider: &Provider) -> Result<()> {
+    /// The same settings with another provider and model, counting its usage
+    /// separately. Fails when the provider's credentials aren't set or it lacks the model.
+    pub async fn with_model(&self, provider: Provider, model_name: &str) -> Result<Self> {
+        // The configured base URL only applies to the configured provider
+        let base_url = self
+            .base_url
+            .clone()
+            .filter(|_| self.provider.to_string() == provider.to_string());
+        Self::validate_ai_provider_config(&provider, model_name, base_url.as_deref()).await?;
+        Ok(Self {
+            provider,
+            model_name: model_name.to_string(),
+            base_url,
+            usage: Arc::default(),
+            ..self.clone()
+        })
+    }
+
+    /// The base URL requests go to, for providers where it can be changed.
+    pub fn base_url(&self) -> Option<String> {
+        resolve_base_url(&self.provider, self.base_url.as_deref(), |key| {
+            env::var(key).ok()
+        })
+    }
+
+    /// Checks that the provider's credentials are set and that it actually serves
+    /// `model_name`, so a missing model fails here rather than in the first batch.
+    async fn validate_ai_provider_config(
+        provider: &Provider,
+        model_name: &str,
+        base_url: Option<&str>,
+    ) -> Result<()> {
         match provider {
             Provider::OpenAI => {
                 env::var("OPENAI_API_KEY").map_err(|err| {
@@ -132,30 +362,112 @@ impl LLMProvider {
                     anyhow!("ANTHROPIC_API_KEY environment variable is not set: {}", err)
                 })?;
             }
-            Provider::Ollama => {
+            Provider::Ollama if base_url.is_none() => {
                 env::var("OLLAMA_API_BASE_URL").map_err(|err| {
                     anyhow!(
-                        "OLLAMA_API_BASE_URL environment variable is not set: {}",
+                        "OLLAMA_API_BASE_URL environment variable is not set and no base_url \
+                        is configured: {}",
                         err
                     )
                 })?;
             }
+            Provider::Ollama => {}
+            Provider::Groq => {
+                env::var("GROQ_API_KEY").map_err(|err| {
+                    anyhow!("GROQ_API_KEY environment variable is not set: {}", err)
+                })?;
+            }
+            Provider::Mistral => {
+                env::var("MISTRAL_API_KEY").map_err(|err| {
+                    anyhow!("MISTRAL_API_KEY environment variable is not set: {}", err)
+                })?;
+            }
             #[cfg(test)]
             Provider::Mock(_) => {
                 // Mock provider doesn't need validation
             }
         }
-        Ok(())
+        let base_url = resolve_base_url(provider, base_url, |key| env::var(key).ok());
+        Self::verify_model_available(
+            provider,
+            model_name,
+            base_url.as_deref().unwrap_or_default(),
+        )
+        .await
+    }
+
+    async fn verify_model_available(
+        provider: &Provider,
+        model_name: &str,
+        base_url: &str,
+    ) -> Result<()> {
+        let available = match provider {
+            // Ollama has to be running anyway, so not reaching it is an error too
+            Provider::Ollama => Self::list_ollama_models(base_url).await?,
+            Provider::OpenAI => {
+                let request = reqwest::Client::new()
+                    .get(format!("{}/models", base_url))
+                    .bearer_auth(env::var("OPENAI_API_KEY")?);
+                match Self::fetch_model_ids(request).await {
+                    Some(models) => models,
+                    None => return Ok(()),
+                }
+            }
+            Provider::Anthropic => {
+                let request = reqwest::Client::new()
+                    .get(format!("{}/v1/models?limit=1000", base_url))
+                    .header("x-api-key", env::var("ANTHROPIC_API_KEY")?)
+                    .header("anthropic-version", "2023-06-01");
+                match Self::fetch_model_ids(request).await {
+                    Some(models) => models,
+                    None => return Ok(()),
+                }
+            }
+            // Their model lists fall back to guesses, so there's nothing reliable to check
+            Provider::Groq | Provider::Mistral => return Ok(()),
+            #[cfg(test)]
+            Provider::Mock(_) => return Ok(()),
+        };
+
+        if model_is_listed(provider, model_name, &available) {
+            return Ok(());
+        }
+        Err(match provider {
+            Provider::Ollama => anyhow!(
+                "model '{}' not found; run `ollama pull {}`",
+                model_name,
+                model_name
+            ),
+            _ => anyhow!(
+                "model '{}' is not available from {}; run `shelfie config reset` to pick another",
+                model_name,
+                provider
+            ),
+        })
+    }
+
+    /// The model ids from a `/v1/models` listing, which OpenAI and Anthropic both return
+    /// as `data[].id`. `None` when the list can't be fetched, so the model goes unchecked.
+    async fn fetch_model_ids(request: reqwest::RequestBuilder) -> Option<Vec<String>> {
+        let response = request.send().await.ok()?;
+        if !response.status().is_success() {
+            return None;
+        }
+        let models: OpenAIModelsResponse = response.json().await.ok()?;
+        Some(models.data.into_iter().map(|m| m.id).collect())
     }
 
     pub async fn new_interactive() -> Result<Self> {
         // Force new provider selection (ignore existing config)
-        let providers = vec![Provider::OpenAI, Provider::Anthropic, Provider::Ollama];
+        let providers = vec![
+            Provider::OpenAI,
+            Provider::Anthropic,
+            Provider::Ollama,
+            Provider::Groq,
+            Provider::Mistral,
+        ];
 
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select LLM Provider")
-            .items(&providers)
-            .interact()?;
+        let selection = prompts::select("Select LLM Provider", &providers)?;
 
         let provider = providers[selection].clone();
         let model_name = Self::select_model(&provider).await?;
@@ -163,7 +475,12 @@ impl LLMProvider {
         Ok(Self {
             provider,
             model_name,
+            max_tokens: None,
+            temperature: None,
+            top_p: None,
+            base_url: None,
             mock_call_count: std::sync::Arc::new(std::sync::Mutex::new(0)),
+            usage: Arc::default(),
         })
     }
 
@@ -176,10 +493,10 @@ impl LLMProvider {
 
         // Ask for filter if there are many models
         if models.len() > 10 {
-            let filter: String = Input::with_theme(&ColorfulTheme::default())
-                .with_prompt("Filter models (e.g., 'gpt-5', 'claude-4', or press Enter for all)")
-                .allow_empty(true)
-                .interact_text()?;
+            let filter = prompts::input(
+                "Filter models (e.g., 'gpt-5', 'claude-4', or press Enter for all)",
+                true,
+            )?;
 
             if !filter.is_empty() {
                 models.retain(|model| model.to_lowercase().contains(&filter.to_lowercase()));
@@ -190,30 +507,31 @@ impl LLMProvider {
             }
         }
 
-        let selection = Select::with_theme(&ColorfulTheme::default())
-            .with_prompt("Select Model")
-            .items(&models)
-            .interact()?;
+        let selection = prompts::select("Select Model", &models)?;
 
         Ok(models[selection].clone())
     }
 
     async fn list_models(provider: &Provider) -> Result<Vec<String>> {
+        let base_url = resolve_base_url(provider, None, |key| env::var(key).ok());
+        let base_url = base_url.as_deref().unwrap_or_default();
         match provider {
-            Provider::OpenAI => Self::list_openai_models().await,
-            Provider::Anthropic => Self::list_anthropic_models().await,
-            Provider::Ollama => Self::list_ollama_models().await,
+            Provider::OpenAI => Self::list_openai_models(base_url).await,
+            Provider::Anthropic => Self::list_anthropic_models(base_url).await,
+            Provider::Ollama => Self::list_ollama_models(base_url).await,
+            Provider::Groq => Self::list_groq_models().await,
+            Provider::Mistral => Self::list_mistral_models().await,
             #[cfg(test)]
             Provider::Mock(_) => Ok(vec!["mock-model".to_string()]),
         }
     }
 
-    async fn list_openai_models() -> Result<Vec<String>> {
+    async fn list_openai_models(base_url: &str) -> Result<Vec<String>> {
         let api_key = env::var("OPENAI_API_KEY").map_err(|_| anyhow!("OPENAI_API_KEY not set"))?;
 
         let client = reqwest::Client::new();
         let response = client
-            .get("https://api.openai.com/v1/models")
+            .get(format!("{}/models", base_url))
             .header("Authorization", format!("Bearer {}", api_key))
             .send()
             .await?;
@@ -237,13 +555,65 @@ impl LLMProvider {
         Ok(model_names)
     }
 
-    async fn list_anthropic_models() -> Result<Vec<String>> {
+    async fn list_groq_models() -> Result<Vec<String>> {
+        Self::list_openai_compatible_models(
+            "https://api.groq.com/openai/v1/models",
+            "GROQ_API_KEY",
+            &["llama-3.3-70b-versatile", "llama-3.1-8b-instant"],
+        )
+        .await
+    }
+
+    async fn list_mistral_models() -> Result<Vec<String>> {
+        Self::list_openai_compatible_models(
+            "https://api.mistral.ai/v1/models",
+            "MISTRAL_API_KEY",
+            &["mistral-large-latest", "mistral-small-latest"],
+        )
+        .await
+    }
+
+    /// Lists models from an endpoint that answers in OpenAI's `/models` format, falling
+    /// back to a few well-known models if the request fails.
+    async fn list_openai_compatible_models(
+        url: &str,
+        api_key_var: &str,
+        fallback: &[&str],
+    ) -> Result<Vec<String>> {
+        let api_key = env::var(api_key_var).map_err(|_| anyhow!("{} not set", api_key_var))?;
+
+        let client = reqwest::Client::new();
+        let response = client
+            .get(url)
+            .header("Authorization", format!("Bearer {}", api_key))
+            .send()
+            .await;
+
+        let mut model_names = match response {
+            Ok(resp) if resp.status().is_success() => {
+                let models: OpenAIModelsResponse = resp.json().await?;
+                models.data.into_iter().map(|m| m.id).collect::<Vec<_>>()
+            }
+            _ => Vec::new(),
+        };
+
+        model_names.sort();
+        model_names.dedup();
+
+        if model_names.is_empty() {
+            model_names = fallback.iter().map(|m| m.to_string()).collect();
+        }
+
+        Ok(model_names)
+    }
+
+    async fn list_anthropic_models(base_url: &str) -> Result<Vec<String>> {
         let api_key =
             env::var("ANTHROPIC_API_KEY").map_err(|_| anyhow!("ANTHROPIC_API_KEY not set"))?;
 
         let client = reqwest::Client::new();
         let response = client
-            .get("https://api.anthropic.com/v1/models")
+            .get(format!("{}/v1/models", base_url))
             .header("x-api-key", api_key)
             .header("anthropic-version", "2023-06-01")
             .send()
@@ -270,9 +640,7 @@ impl LLMProvider {
         }
     }
 
-    async fn list_ollama_models() -> Result<Vec<String>> {
-        let base_url = env::var("OLLAMA_API_BASE_URL")
-            .unwrap_or_else(|_| "http://localhost:11434".to_string());
+    async fn list_ollama_models(base_url: &str) -> Result<Vec<String>> {
         let client = reqwest::Client::new();
         let response = client.get(format!("{}/api/tags", base_url)).send().await;
 
@@ -301,11 +669,17 @@ impl LLMProvider {
 
     pub fn get_agent(&self) -> Result<BoxAgentBuilder> {
         Ok(match self.get_provider() {
-            Provider::OpenAI => DynClientBuilder::new().agent("openai", self.get_model_name())?,
+            Provider::OpenAI => {
+                CompletionClientDyn::agent(&self.openai_client()?, self.get_model_name())
+            }
             Provider::Anthropic => {
-                DynClientBuilder::new().agent("anthropic", self.get_model_name())?
+                CompletionClientDyn::agent(&self.anthropic_client()?, self.get_model_name())
             }
-            Provider::Ollama => DynClientBuilder::new().agent("ollama", self.get_model_name())?,
+            Provider::Ollama => {
+                CompletionClientDyn::agent(&self.ollama_client()?, self.get_model_name())
+            }
+            Provider::Groq => DynClientBuilder::new().agent("groq", self.get_model_name())?,
+            Provider::Mistral => DynClientBuilder::new().agent("mistral", self.get_model_name())?,
             #[cfg(test)]
             Provider::Mock(_) => {
                 return Err(anyhow!("Mock provider doesn't support agent creation"));
@@ -313,7 +687,55 @@ impl LLMProvider {
         })
     }
 
-    pub async fn extract<T>(&self, _prompt: &str) -> Result<T>
+    fn openai_client(&self) -> Result<openai::Client> {
+        let api_key = env::var("OPENAI_API_KEY").map_err(|_| anyhow!("OPENAI_API_KEY not set"))?;
+        Ok(openai::Client::builder(&api_key)
+            .base_url(&self.api_base_url()?)
+            .build()?)
+    }
+
+    fn anthropic_client(&self) -> Result<anthropic::Client> {
+        let api_key =
+            env::var("ANTHROPIC_API_KEY").map_err(|_| anyhow!("ANTHROPIC_API_KEY not set"))?;
+        Ok(anthropic::Client::builder(&api_key)
+            .base_url(&self.api_base_url()?)
+            .build()?)
+    }
+
+    fn ollama_client(&self) -> Result<ollama::Client> {
+        let base_url = self.api_base_url()?;
+        ollama::Client::builder()
+            .base_url(&base_url)
+            .build()
+            .map_err(|_| anyhow!("Invalid Ollama base URL: {}", base_url))
+    }
+
+    fn api_base_url(&self) -> Result<String> {
+        self.base_url()
+            .ok_or_else(|| anyhow!("{} doesn't support a custom base URL", self.provider))
+    }
+
+    pub async fn extract<T>(&self, prompt: &str) -> Result<T>
+    where
+        T: schemars::JsonSchema
+            + for<'a> serde::Deserialize<'a>
+            + serde::Serialize
+            + Send
+            + Sync
+            + 'static,
+    {
+        let extracted = self.extract_uncounted::<T>(prompt).await?;
+
+        let completion = serde_json::to_string(&extracted).unwrap_or_default();
+        let mut usage = self.usage.lock().unwrap();
+        usage.requests += 1;
+        usage.prompt_tokens += BatchProcessor::estimate_tokens(prompt);
+        usage.completion_tokens += BatchProcessor::estimate_tokens(&completion);
+
+        Ok(extracted)
+    }
+
+    async fn extract_uncounted<T>(&self, _prompt: &str) -> Result<T>
     where
         T: schemars::JsonSchema
             + for<'a> serde::Deserialize<'a>
@@ -324,28 +746,29 @@ impl LLMProvider {
     {
         match self.get_provider() {
             Provider::OpenAI => {
-                let client = openai::Client::from_env();
-                let extractor = client.extractor::<T>(self.get_model_name()).build();
-                extractor
-                    .extract(_prompt)
+                let client = self.openai_client()?;
+                self.run_extractor(client.extractor::<T>(self.get_model_name()), _prompt)
                     .await
-                    .map_err(|e| anyhow!("Extraction failed: {}", e))
             }
             Provider::Anthropic => {
-                let client = anthropic::Client::from_env();
-                let extractor = client.extractor::<T>(self.get_model_name()).build();
-                extractor
-                    .extract(_prompt)
+                let client = self.anthropic_client()?;
+                self.run_extractor(client.extractor::<T>(self.get_model_name()), _prompt)
                     .await
-                    .map_err(|e| anyhow!("Extraction failed: {}", e))
             }
             Provider::Ollama => {
-                let client = ollama::Client::from_env();
-                let extractor = client.extractor::<T>(self.get_model_name()).build();
-                extractor
-                    .extract(_prompt)
+                let client = self.ollama_client()?;
+                self.run_extractor(client.extractor::<T>(self.get_model_name()), _prompt)
+                    .await
+            }
+            Provider::Groq => {
+                let client = groq::Client::from_env();
+                self.run_extractor(client.extractor::<T>(self.get_model_name()), _prompt)
+                    .await
+            }
+            Provider::Mistral => {
+                let client = mistral::Client::from_env();
+                self.run_extractor(client.extractor::<T>(self.get_model_name()), _prompt)
                     .await
-                    .map_err(|e| anyhow!("Extraction failed: {}", e))
             }
             #[cfg(test)]
             Provider::Mock(responses) => {
@@ -358,12 +781,191 @@ impl LLMProvider {
                 }
 
                 let json_response = &responses[response_index];
+                // Lets tests simulate provider errors, e.g. a context overflow
+                if let Some(message) = json_response.strip_prefix("error: ") {
+                    return Err(anyhow!("{}", message));
+                }
                 serde_json::from_str(json_response)
                     .map_err(|e| anyhow!("Failed to parse mock response: {}", e))
             }
         }
     }
 
+    /// Applies the run's token limit and sampling parameters, then extracts.
+    async fn run_extractor<T, M>(&self, builder: ExtractorBuilder<T, M>, prompt: &str) -> Result<T>
+    where
+        T: schemars::JsonSchema
+            + for<'a> serde::Deserialize<'a>
+            + serde::Serialize
+            + Send
+            + Sync
+            + 'static,
+        M: CompletionModel + Sync,
+    {
+        let mut builder = builder;
+        if let Some(max_tokens) = self.max_tokens {
+            builder = builder.max_tokens(max_tokens);
+        }
+        if let Some(params) = self.extraction_params() {
+            builder = builder.additional_params(params);
+        }
+        builder
+            .build()
+            .extract(prompt)
+            .await
+            .map_err(|e| anyhow!("Extraction failed: {}", e))
+    }
+
+    /// Overrides the configured output token limit, warning when it exceeds what the
+    /// model is known to support.
+    pub fn with_max_tokens(mut self, max_tokens: Option<u64>) -> Self {
+        if max_tokens.is_some() {
+            self.max_tokens = max_tokens;
+        }
+
+        if let (Some(requested), Some(ceiling)) = (
+            self.max_tokens,
+            Self::known_max_output_tokens(&self.model_name),
+        ) && requested > ceiling
+        {
+            eprintln!(
+                "{}: max_tokens {} exceeds the {} output token limit of {} ({}); requests may be rejected",
+                "Warning".yellow().bold(),
+                requested,
+                self.model_name,
+                ceiling,
+                self.provider
+            );
+        }
+
+        self
+    }
+
+    /// Output token ceilings for the model families we know about.
+    fn known_max_output_tokens(model_name: &str) -> Option<u64> {
+        let model = model_name.to_lowercase();
+        if model.contains("opus") {
+            Some(32_000)
+        } else if model.contains("claude") {
+            Some(64_000)
+        } else if model.contains("gpt-5") {
+            Some(128_000)
+        } else if model.contains("gpt-4.1") {
+            Some(32_768)
+        } else if model.contains("gpt-4o") {
+            Some(16_384)
+        } else if model.contains("gpt-4") {
+            Some(8_192)
+        } else if model.contains("gpt-3.5") {
+            Some(4_096)
+        } else {
+            None
+        }
+    }
+
+    /// Overrides the configured sampling parameters.
+    pub fn with_sampling(mut self, temperature: Option<f64>, top_p: Option<f64>) -> Self {
+        if temperature.is_some() {
+            self.temperature = temperature;
+        }
+        if top_p.is_some() {
+            self.top_p = top_p;
+        }
+        self
+    }
+
+    /// Provider request parameters for temperature and top_p, if any apply.
+    ///
+    /// OpenAI and Anthropic take them at the top level of the request, while rig merges
+    /// Ollama's additional params into its `options` object, so the same keys work for
+    /// all three. OpenAI reasoning models only accept their default sampling, and
+    /// Anthropic recommends against setting both, so those cases are narrowed down.
+    pub fn sampling_params(&self) -> Option<serde_json::Value> {
+        let model = self.model_name.to_lowercase();
+        let is_reasoning_model = matches!(self.provider, Provider::OpenAI)
+            && ["gpt-5", "o1", "o3", "o4"]
+                .iter()
+                .any(|prefix| model.starts_with(prefix));
+        if is_reasoning_model {
+            return None;
+        }
+
+        let temperature = self.temperature.unwrap_or(DEFAULT_TEMPERATURE);
+        let mut params = serde_json::json!({ "temperature": temperature });
+        if let Some(top_p) = self.top_p {
+            match self.provider {
+                // Only one of the two should be set; an explicit top_p wins over the default
+                Provider::Anthropic if self.temperature.is_none() => {
+                    params = serde_json::json!({ "top_p": top_p });
+                }
+                Provider::Anthropic => {}
+                _ => params["top_p"] = serde_json::json!(top_p),
+            }
+        }
+
+        Some(params)
+    }
+
+    /// Sampling and structured output parameters for an extraction request.
+    pub fn extraction_params(&self) -> Option<serde_json::Value> {
+        let mut params = serde_json::Map::new();
+        for extra in [self.sampling_params(), self.structured_output_params()]
+            .into_iter()
+            .flatten()
+        {
+            if let serde_json::Value::Object(extra) = extra {
+                params.extend(extra);
+            }
+        }
+        (!params.is_empty()).then_some(serde_json::Value::Object(params))
+    }
+
+    /// Request parameters that make the model answer through the extractor's tool,
+    /// so the response is constrained to its JSON schema while it is generated
+    /// rather than only asked for in the prompt. `None` for providers that can't
+    /// force a tool call: rig passes Ollama's additional params as model options, so
+    /// its models keep relying on the prompt.
+    pub fn structured_output_params(&self) -> Option<serde_json::Value> {
+        match self.provider {
+            Provider::OpenAI | Provider::Groq => Some(serde_json::json!({
+                "tool_choice": {
+                    "type": "function",
+                    "function": { "name": EXTRACTOR_TOOL_NAME }
+                },
+                "parallel_tool_calls": false
+            })),
+            Provider::Anthropic => Some(serde_json::json!({
+                "tool_choice": { "type": "tool", "name": EXTRACTOR_TOOL_NAME }
+            })),
+            // Mistral can't name the tool, but with a single tool "any" amounts to it
+            Provider::Mistral => Some(serde_json::json!({ "tool_choice": "any" })),
+            Provider::Ollama => None,
+            #[cfg(test)]
+            Provider::Mock(_) => None,
+        }
+    }
+
+    /// Whether the provider rejected a request for not fitting the model's context
+    /// window. Providers only say so in the error message.
+    pub fn is_context_overflow(error: &anyhow::Error) -> bool {
+        const MARKERS: &[&str] = &[
+            "context_length_exceeded",
+            "context length",
+            "context window",
+            "prompt is too long",
+            "input is too long",
+            "too many tokens",
+        ];
+        error.chain().any(|cause| {
+            let message = cause.to_string().to_lowercase();
+            MARKERS.iter().any(|marker| message.contains(marker))
+        })
+    }
+
+    pub fn max_tokens(&self) -> Option<u64> {
+        self.max_tokens
+    }
+
     pub fn get_model_name(&self) -> &str {
         &self.model_name
     }
@@ -371,4 +973,11 @@ impl LLMProvider {
     pub fn get_provider(&self) -> &Provider {
         &self.provider
     }
+
+    pub fn token_usage(&self) -> TokenUsage {
+        *self.usage.lock().unwrap()
+    }
 }
+
+#[cfg(test)]
+mod tests;
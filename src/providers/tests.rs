@@ -0,0 +1,195 @@
+use super::{LLMProvider, Provider, model_is_listed, provider_from_env, resolve_base_url};
+use serde_json::json;
+
+fn provider(provider: Provider, model_name: &str) -> LLMProvider {
+    LLMProvider {
+        provider,
+        model_name: model_name.to_string(),
+        max_tokens: None,
+        temperature: None,
+        top_p: None,
+        base_url: None,
+        mock_call_count: Default::default(),
+        usage: Default::default(),
+    }
+}
+
+#[test]
+fn test_default_temperature_is_low() {
+    let params = provider(Provider::Ollama, "llama3").sampling_params();
+    assert_eq!(params, Some(json!({ "temperature": 0.2 })));
+}
+
+#[test]
+fn test_openai_sends_temperature_and_top_p() {
+    let params = provider(Provider::OpenAI, "gpt-4o")
+        .with_sampling(Some(0.5), Some(0.9))
+        .sampling_params();
+    assert_eq!(params, Some(json!({ "temperature": 0.5, "top_p": 0.9 })));
+}
+
+#[test]
+fn test_openai_reasoning_models_keep_default_sampling() {
+    let params = provider(Provider::OpenAI, "gpt-5-mini")
+        .with_sampling(Some(0.5), None)
+        .sampling_params();
+    assert_eq!(params, None);
+}
+
+#[test]
+fn test_anthropic_sends_only_one_sampling_parameter() {
+    let top_p_only = provider(Provider::Anthropic, "claude-4-sonnet-latest")
+        .with_sampling(None, Some(0.9))
+        .sampling_params();
+    assert_eq!(top_p_only, Some(json!({ "top_p": 0.9 })));
+
+    let both = provider(Provider::Anthropic, "claude-4-sonnet-latest")
+        .with_sampling(Some(0.3), Some(0.9))
+        .sampling_params();
+    assert_eq!(both, Some(json!({ "temperature": 0.3 })));
+}
+
+#[tokio::test]
+async fn test_successful_extractions_count_estimated_tokens() {
+    let provider = LLMProvider::new_mock(vec![json!({ "value": "abcd" }).to_string()]);
+
+    provider
+        .extract::<serde_json::Value>("12345678")
+        .await
+        .unwrap();
+    provider.extract::<serde_json::Value>("1234").await.unwrap();
+
+    let usage = provider.token_usage();
+    assert_eq!(usage.requests, 2);
+    assert_eq!(usage.prompt_tokens, 3);
+    assert_eq!(usage.completion_tokens, 8);
+    assert_eq!(usage.total(), 11);
+}
+
+#[test]
+fn test_provider_from_env() {
+    let env = |vars: &'static [(&'static str, &'static str)]| {
+        move |key: &str| {
+            vars.iter()
+                .find(|(name, _)| *name == key)
+                .map(|(_, value)| value.to_string())
+        }
+    };
+
+    assert!(provider_from_env(env(&[])).unwrap().is_none());
+    let (provider, model_name) = provider_from_env(env(&[
+        ("SHELFIE_PROVIDER", "Ollama"),
+        ("SHELFIE_MODEL", " llama3 "),
+    ]))
+    .unwrap()
+    .unwrap();
+    assert!(matches!(provider, Provider::Ollama));
+    assert_eq!(model_name, "llama3");
+    assert!(provider_from_env(env(&[("SHELFIE_PROVIDER", "openai")])).is_err());
+    assert!(
+        provider_from_env(env(&[("SHELFIE_PROVIDER", "bard"), ("SHELFIE_MODEL", "x")])).is_err()
+    );
+}
+
+#[test]
+fn test_model_is_listed() {
+    let ollama = vec!["llama3:latest".to_string(), "qwen2.5:7b".to_string()];
+    assert!(model_is_listed(&Provider::Ollama, "llama3", &ollama));
+    assert!(model_is_listed(&Provider::Ollama, "qwen2.5:7b", &ollama));
+    assert!(!model_is_listed(&Provider::Ollama, "qwen2.5", &ollama));
+    assert!(!model_is_listed(&Provider::Ollama, "mistral", &ollama));
+
+    let anthropic = vec!["claude-sonnet-4-20250514".to_string()];
+    assert!(model_is_listed(
+        &Provider::Anthropic,
+        "claude-sonnet-4-20250514",
+        &anthropic
+    ));
+    assert!(model_is_listed(
+        &Provider::Anthropic,
+        "claude-sonnet-4-latest",
+        &anthropic
+    ));
+    assert!(!model_is_listed(
+        &Provider::Anthropic,
+        "claude-opus-4-latest",
+        &anthropic
+    ));
+
+    let openai = vec!["gpt-5".to_string()];
+    assert!(model_is_listed(&Provider::OpenAI, "gpt-5", &openai));
+    assert!(!model_is_listed(&Provider::OpenAI, "gpt-5-mini", &openai));
+}
+
+#[test]
+fn test_structured_output_forces_the_extractor_tool() {
+    let openai = provider(Provider::OpenAI, "gpt-4o").structured_output_params();
+    assert_eq!(
+        openai.unwrap()["tool_choice"],
+        json!({ "type": "function", "function": { "name": "submit" } })
+    );
+
+    let anthropic =
+        provider(Provider::Anthropic, "claude-4-sonnet-latest").structured_output_params();
+    assert_eq!(
+        anthropic,
+        Some(json!({ "tool_choice": { "type": "tool", "name": "submit" } }))
+    );
+
+    assert_eq!(
+        provider(Provider::Mistral, "mistral-large-latest").structured_output_params(),
+        Some(json!({ "tool_choice": "any" }))
+    );
+    assert_eq!(
+        provider(Provider::Ollama, "llama3").structured_output_params(),
+        None
+    );
+}
+
+#[test]
+fn test_extraction_params_combine_sampling_and_structured_output() {
+    let params = provider(Provider::Anthropic, "claude-4-sonnet-latest")
+        .with_sampling(Some(0.3), None)
+        .extraction_params();
+    assert_eq!(
+        params,
+        Some(json!({
+            "temperature": 0.3,
+            "tool_choice": { "type": "tool", "name": "submit" }
+        }))
+    );
+
+    let reasoning = provider(Provider::OpenAI, "gpt-5-mini").extraction_params();
+    assert_eq!(reasoning.unwrap()["parallel_tool_calls"], json!(false));
+}
+
+#[test]
+fn test_context_overflow_errors_are_recognized() {
+    let openai = anyhow::anyhow!("context_length_exceeded: maximum context length is 128000");
+    let anthropic = anyhow::anyhow!("prompt is too long: 210000 tokens > 200000 maximum")
+        .context("Extraction failed");
+    assert!(LLMProvider::is_context_overflow(&openai));
+    assert!(LLMProvider::is_context_overflow(&anthropic));
+    assert!(!LLMProvider::is_context_overflow(&anyhow::anyhow!(
+        "rate limit exceeded"
+    )));
+}
+
+#[test]
+fn test_base_url_prefers_config_then_env_then_default() {
+    let env = |key: &str| (key == "OPENAI_BASE_URL").then(|| "http://gateway:4000/v1".to_string());
+
+    assert_eq!(
+        resolve_base_url(&Provider::OpenAI, Some("http://litellm:8000/v1/"), env).as_deref(),
+        Some("http://litellm:8000/v1")
+    );
+    assert_eq!(
+        resolve_base_url(&Provider::OpenAI, None, env).as_deref(),
+        Some("http://gateway:4000/v1")
+    );
+    assert_eq!(
+        resolve_base_url(&Provider::Anthropic, None, env).as_deref(),
+        Some("https://api.anthropic.com")
+    );
+    assert_eq!(resolve_base_url(&Provider::Groq, None, env), None);
+}
@@ -0,0 +1,170 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use std::path::Path;
+
+use crate::models::{FileMovement, OrganizationPlan};
+
+/// What `--report` writes the plan as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// A readable document with the structure and a table of movements
+    Markdown,
+    /// One row per movement, for reviewing in a spreadsheet
+    Csv,
+    /// A standalone page with the structure and a table of movements
+    Html,
+    /// The plan as `shelfie plan` prints it
+    Json,
+}
+
+impl ReportFormat {
+    /// Guesses the format from the report's extension, falling back to Markdown.
+    pub fn for_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        match extension.as_deref() {
+            Some("csv") => Self::Csv,
+            Some("html" | "htm") => Self::Html,
+            Some("json") => Self::Json,
+            _ => Self::Markdown,
+        }
+    }
+}
+
+/// Header row of the CSV report.
+const CSV_COLUMNS: [&str; 5] = ["from", "to_cabinet", "to_shelf", "new_name", "reasoning"];
+
+/// Renders the plan in the given format.
+pub fn render_plan(plan: &OrganizationPlan, format: ReportFormat) -> String {
+    match format {
+        ReportFormat::Markdown => render_markdown(plan),
+        ReportFormat::Csv => render_csv(plan),
+        ReportFormat::Html => render_html(plan),
+        ReportFormat::Json => {
+            serde_json::to_string_pretty(plan).expect("plans always serialize to JSON")
+        }
+    }
+}
+
+/// Writes the rendered plan to `path`.
+pub fn write_report(plan: &OrganizationPlan, format: ReportFormat, path: &Path) -> Result<()> {
+    std::fs::write(path, render_plan(plan, format))
+        .with_context(|| format!("Failed to write the report {}", path.display()))
+}
+
+fn movement_fields(movement: &FileMovement) -> [String; 5] {
+    [
+        movement.from.display().to_string(),
+        movement.to_cabinet.clone(),
+        movement.to_shelf.clone(),
+        movement.new_name.clone().unwrap_or_default(),
+        movement.reasoning.clone(),
+    ]
+}
+
+fn render_markdown(plan: &OrganizationPlan) -> String {
+    let mut out = String::from("# Organization Plan\n\n## Structure\n\n");
+    for cabinet in &plan.cabinets {
+        out.push_str(&format!(
+            "- **{}** — {}\n",
+            markdown_cell(&cabinet.name),
+            markdown_cell(&cabinet.description)
+        ));
+        for shelf in &cabinet.shelves {
+            out.push_str(&format!(
+                "  - {} ({} items) — {}\n",
+                markdown_cell(&shelf.name),
+                shelf.item_count,
+                markdown_cell(&shelf.description)
+            ));
+        }
+    }
+
+    out.push_str(&format!(
+        "\n## Movements\n\n{} items move.\n\n",
+        plan.movements.len()
+    ));
+    out.push_str("| From | Cabinet | Shelf | New name | Reasoning |\n");
+    out.push_str("| --- | --- | --- | --- | --- |\n");
+    for movement in &plan.movements {
+        let cells = movement_fields(movement).map(|field| markdown_cell(&field));
+        out.push_str(&format!("| {} |\n", cells.join(" | ")));
+    }
+    out
+}
+
+/// Keeps a value on one table row: pipes are escaped and line breaks become spaces.
+fn markdown_cell(value: &str) -> String {
+    value
+        .replace('|', "\\|")
+        .split(['\n', '\r'])
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn render_csv(plan: &OrganizationPlan) -> String {
+    let mut out = format!("{}\n", CSV_COLUMNS.join(","));
+    for movement in &plan.movements {
+        let row = movement_fields(movement).map(|field| csv_field(&field));
+        out.push_str(&format!("{}\n", row.join(",")));
+    }
+    out
+}
+
+/// Quotes a field when it holds a separator, quote or line break (RFC 4180).
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn render_html(plan: &OrganizationPlan) -> String {
+    let mut out = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>Organization Plan</title>\n</head>\n<body>\n\
+        <h1>Organization Plan</h1>\n<h2>Structure</h2>\n<ul>\n",
+    );
+    for cabinet in &plan.cabinets {
+        out.push_str(&format!(
+            "<li><strong>{}</strong> — {}\n<ul>\n",
+            html_escape(&cabinet.name),
+            html_escape(&cabinet.description)
+        ));
+        for shelf in &cabinet.shelves {
+            out.push_str(&format!(
+                "<li>{} ({} items) — {}</li>\n",
+                html_escape(&shelf.name),
+                shelf.item_count,
+                html_escape(&shelf.description)
+            ));
+        }
+        out.push_str("</ul>\n</li>\n");
+    }
+
+    out.push_str(&format!(
+        "</ul>\n<h2>Movements</h2>\n<p>{} items move.</p>\n<table>\n\
+        <tr><th>From</th><th>Cabinet</th><th>Shelf</th><th>New name</th><th>Reasoning</th></tr>\n",
+        plan.movements.len()
+    ));
+    for movement in &plan.movements {
+        let cells = movement_fields(movement).map(|field| html_escape(&field));
+        out.push_str(&format!("<tr><td>{}</td></tr>\n", cells.join("</td><td>")));
+    }
+    out.push_str("</table>\n</body>\n</html>\n");
+    out
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests;
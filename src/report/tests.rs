@@ -0,0 +1,109 @@
+use super::*;
+use crate::models::{CabinetPlan, ShelfPlan};
+use std::path::PathBuf;
+
+fn sample_plan() -> OrganizationPlan {
+    OrganizationPlan {
+        cabinets: vec![CabinetPlan {
+            id: 1,
+            name: "Finance".to_string(),
+            description: "Money matters".to_string(),
+            shelves: vec![ShelfPlan {
+                id: 2,
+                name: "Invoices".to_string(),
+                description: "Bills | receipts".to_string(),
+                item_count: 2,
+            }],
+        }],
+        movements: vec![
+            FileMovement {
+                from: PathBuf::from("/home/user/inv_2024.pdf"),
+                to_cabinet: "Finance".to_string(),
+                to_shelf: "Invoices".to_string(),
+                new_name: Some("Invoice 2024.pdf".to_string()),
+                reasoning: "An invoice, from \"ACME\", dated 2024".to_string(),
+                opaque_reason: None,
+            },
+            FileMovement {
+                from: PathBuf::from("/home/user/receipt.png"),
+                to_cabinet: "Finance".to_string(),
+                to_shelf: "Invoices".to_string(),
+                new_name: None,
+                reasoning: "A receipt\nfor <lunch>".to_string(),
+                opaque_reason: None,
+            },
+        ],
+    }
+}
+
+#[test]
+fn test_csv_has_one_row_per_movement() {
+    let csv = render_plan(&sample_plan(), ReportFormat::Csv);
+    let lines = csv.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines[0], "from,to_cabinet,to_shelf,new_name,reasoning");
+    assert_eq!(
+        lines[1],
+        "/home/user/inv_2024.pdf,Finance,Invoices,Invoice 2024.pdf,\
+        \"An invoice, from \"\"ACME\"\", dated 2024\""
+    );
+    // A quoted line break keeps the second movement a single record
+    assert_eq!(
+        &lines[2..],
+        [
+            "/home/user/receipt.png,Finance,Invoices,,\"A receipt",
+            "for <lunch>\""
+        ]
+    );
+}
+
+#[test]
+fn test_markdown_has_structure_tree_and_movements_table() {
+    let markdown = render_plan(&sample_plan(), ReportFormat::Markdown);
+
+    assert!(markdown.contains("- **Finance** — Money matters\n"));
+    assert!(markdown.contains("  - Invoices (2 items) — Bills \\| receipts\n"));
+    assert!(markdown.contains("| From | Cabinet | Shelf | New name | Reasoning |\n"));
+    assert!(
+        markdown.contains(
+            "| /home/user/receipt.png | Finance | Invoices |  | A receipt for <lunch> |\n"
+        )
+    );
+}
+
+#[test]
+fn test_html_escapes_values() {
+    let html = render_plan(&sample_plan(), ReportFormat::Html);
+
+    assert!(html.contains("<td>A receipt\nfor &lt;lunch&gt;</td>"));
+    assert!(!html.contains("<lunch>"));
+}
+
+#[test]
+fn test_json_round_trips() {
+    let json = render_plan(&sample_plan(), ReportFormat::Json);
+    let plan: OrganizationPlan = serde_json::from_str(&json).unwrap();
+
+    assert_eq!(plan.movements.len(), 2);
+    assert_eq!(plan.cabinets[0].shelves[0].name, "Invoices");
+}
+
+#[test]
+fn test_format_follows_the_report_extension() {
+    assert_eq!(
+        ReportFormat::for_path(Path::new("plan.CSV")),
+        ReportFormat::Csv
+    );
+    assert_eq!(
+        ReportFormat::for_path(Path::new("plan.htm")),
+        ReportFormat::Html
+    );
+    assert_eq!(
+        ReportFormat::for_path(Path::new("plan.json")),
+        ReportFormat::Json
+    );
+    assert_eq!(
+        ReportFormat::for_path(Path::new("plan.txt")),
+        ReportFormat::Markdown
+    );
+}
@@ -0,0 +1,85 @@
+use anyhow::{Result, anyhow};
+
+use crate::{
+    batch_processor::BatchProcessor,
+    models::{ItemMetadata, ProcessingItem, SkeletonResponse},
+    providers::LLMProvider,
+    template::{OrganizationTemplate, TemplateCabinet},
+};
+
+/// Items shown to the model when proposing a skeleton, unless configured otherwise.
+pub const DEFAULT_SKELETON_SAMPLE_SIZE: usize = 40;
+
+/// Picks up to `size` items spread evenly over `items`, so the sample covers the
+/// whole scan rather than just its first directory.
+pub fn sample_items(items: &[ProcessingItem], size: usize) -> Vec<&ProcessingItem> {
+    let size = size.max(1);
+    if items.len() <= size {
+        return items.iter().collect();
+    }
+    (0..size).map(|i| &items[i * items.len() / size]).collect()
+}
+
+/// Asks the model for the top-level cabinets of a directory from a sample of its
+/// items. The cabinets come back as a template without shelves, ready to be seeded
+/// before the full analysis so that every batch sorts into the same structure.
+pub async fn propose_skeleton(
+    provider: &LLMProvider,
+    sample: &[&ProcessingItem],
+    total_items: usize,
+    max_cabinets: usize,
+) -> Result<OrganizationTemplate> {
+    let metadata = sample
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| BatchProcessor::item_metadata(idx, item))
+        .collect::<Vec<_>>();
+    let prompt = prompt(&metadata, total_items, max_cabinets);
+
+    let mut last_error = None;
+    for _ in 0..BatchProcessor::MAX_EXTRACTION_RETRIES {
+        match provider.extract::<SkeletonResponse>(&prompt).await {
+            Ok(response) => {
+                let mut cabinets: Vec<TemplateCabinet> = Vec::new();
+                for cabinet in response.cabinets {
+                    let name = cabinet.name.trim();
+                    if name.is_empty() || cabinets.iter().any(|c| c.name == name) {
+                        continue;
+                    }
+                    cabinets.push(TemplateCabinet {
+                        name: name.to_string(),
+                        description: cabinet.description,
+                        shelves: Vec::new(),
+                    });
+                }
+                cabinets.truncate(max_cabinets);
+                return Ok(OrganizationTemplate { cabinets });
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(last_error.unwrap_or_else(|| anyhow!("Failed to propose a cabinet skeleton")))
+}
+
+fn prompt(metadata: &[ItemMetadata], total_items: usize, max_cabinets: usize) -> String {
+    format!(
+        "These are {} items sampled from a directory of {} items that is about to be \
+        organized into cabinets (top-level containers).\n\n\
+        Sample items:\n{}\n\n\
+        Propose up to {} cabinets that would fit all kinds of items in the directory, \
+        judging from the sample.\n\n\
+        Guidelines:\n\
+        - Prefer a few broad cabinets over many narrow ones\n\
+        - Cabinets must not overlap in what they hold\n\
+        - Keep names short and descriptive\n\
+        - Do not treat non-English items any differently\n",
+        metadata.len(),
+        total_items,
+        BatchProcessor::format_items(metadata),
+        max_cabinets
+    )
+}
+
+#[cfg(test)]
+mod tests;
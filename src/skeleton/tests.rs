@@ -0,0 +1,76 @@
+use super::{propose_skeleton, sample_items};
+use crate::database::Database;
+use crate::models::{EnrichedFile, ProcessingItem, SkeletonCabinet, SkeletonResponse};
+use crate::providers::LLMProvider;
+use std::path::PathBuf;
+use tempfile::TempDir;
+
+fn file(name: &str) -> ProcessingItem {
+    ProcessingItem::File(EnrichedFile {
+        path: PathBuf::from(format!("/tmp/{}", name)),
+        name: name.to_string(),
+        extension: None,
+        file_type: "Unknown".to_string(),
+        size: 1,
+        content_preview: None,
+        detected_title: None,
+        owner: None,
+        perceptual_hash: None,
+        content_hash: None,
+    })
+}
+
+fn name(item: &ProcessingItem) -> &str {
+    match item {
+        ProcessingItem::File(file) => &file.name,
+        ProcessingItem::Directory(dir) => &dir.name,
+    }
+}
+
+#[test]
+fn test_sample_is_spread_over_all_items() {
+    let items = (0..10).map(|i| file(&i.to_string())).collect::<Vec<_>>();
+
+    let sample = sample_items(&items, 4);
+    let names = sample.iter().map(|item| name(item)).collect::<Vec<_>>();
+    assert_eq!(names, vec!["0", "2", "5", "7"]);
+
+    assert_eq!(sample_items(&items, 50).len(), 10);
+}
+
+#[tokio::test]
+async fn test_skeleton_is_deduplicated_capped_and_seeded() {
+    let cabinet = |name: &str| SkeletonCabinet {
+        name: name.to_string(),
+        description: format!("All things {}", name),
+    };
+    let response = SkeletonResponse {
+        cabinets: vec![
+            cabinet("Finance"),
+            cabinet(" Finance "),
+            cabinet(""),
+            cabinet("Photos"),
+            cabinet("Code"),
+        ],
+    };
+    let provider = LLMProvider::new_mock(vec![serde_json::to_string(&response).unwrap()]);
+    let items = vec![file("invoice"), file("beach")];
+    let sample = sample_items(&items, 10);
+
+    let skeleton = propose_skeleton(&provider, &sample, items.len(), 2)
+        .await
+        .unwrap();
+
+    let names = skeleton
+        .cabinets
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["Finance", "Photos"]);
+    assert!(skeleton.cabinets.iter().all(|c| c.shelves.is_empty()));
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = Database::open_or_create(temp_dir.path()).unwrap();
+    assert_eq!(skeleton.seed(&db).unwrap(), (2, 0));
+    assert_eq!(db.list_cabinets().unwrap().len(), 2);
+}
@@ -0,0 +1,92 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::Path};
+
+use crate::database::Database;
+
+/// A reusable cabinet/shelf structure, without any items.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrganizationTemplate {
+    pub cabinets: Vec<TemplateCabinet>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateCabinet {
+    pub name: String,
+    pub description: String,
+    pub shelves: Vec<TemplateShelf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TemplateShelf {
+    pub name: String,
+    pub description: String,
+}
+
+impl OrganizationTemplate {
+    pub fn from_database(database: &Database) -> Result<Self> {
+        let shelves = database.list_shelves(None)?;
+        let cabinets = database
+            .list_cabinets()?
+            .into_iter()
+            .map(|cabinet| TemplateCabinet {
+                shelves: shelves
+                    .iter()
+                    .filter(|s| s.cabinet_id == cabinet.id)
+                    .map(|s| TemplateShelf {
+                        name: s.name.clone(),
+                        description: s.description.clone(),
+                    })
+                    .collect(),
+                name: cabinet.name,
+                description: cabinet.description,
+            })
+            .collect();
+
+        Ok(Self { cabinets })
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read template {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Invalid template file {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        fs::write(path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write template {}", path.display()))
+    }
+
+    /// Creates the template's cabinets and shelves in the database, keeping any
+    /// that already exist. Returns the number of cabinets and shelves created.
+    pub fn seed(&self, database: &Database) -> Result<(usize, usize)> {
+        let mut created_cabinets = 0;
+        let mut created_shelves = 0;
+
+        for cabinet in &self.cabinets {
+            let cabinet_id = match database.get_cabinet_by_name(&cabinet.name)? {
+                Some(existing) => existing.id,
+                None => {
+                    created_cabinets += 1;
+                    database.create_cabinet(&cabinet.name, &cabinet.description)?
+                }
+            };
+
+            for shelf in &cabinet.shelves {
+                if database
+                    .get_shelf_by_name(cabinet_id, &shelf.name)?
+                    .is_none()
+                {
+                    database.create_shelf(cabinet_id, &shelf.name, &shelf.description)?;
+                    created_shelves += 1;
+                }
+            }
+        }
+
+        Ok((created_cabinets, created_shelves))
+    }
+}
+
+#[cfg(test)]
+mod tests;
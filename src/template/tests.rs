@@ -0,0 +1,65 @@
+use super::{OrganizationTemplate, TemplateCabinet, TemplateShelf};
+use crate::database::Database;
+use tempfile::TempDir;
+
+fn sample_template() -> OrganizationTemplate {
+    OrganizationTemplate {
+        cabinets: vec![TemplateCabinet {
+            name: "Finance".to_string(),
+            description: "Money matters".to_string(),
+            shelves: vec![
+                TemplateShelf {
+                    name: "Taxes".to_string(),
+                    description: "Tax returns and receipts".to_string(),
+                },
+                TemplateShelf {
+                    name: "Banking".to_string(),
+                    description: "Bank statements".to_string(),
+                },
+            ],
+        }],
+    }
+}
+
+#[test]
+fn test_template_round_trip_through_database() {
+    let source_dir = TempDir::new().unwrap();
+    let source_db = Database::open_or_create(source_dir.path()).unwrap();
+    sample_template().seed(&source_db).unwrap();
+
+    let exported = OrganizationTemplate::from_database(&source_db).unwrap();
+    let template_path = source_dir.path().join("template.json");
+    exported.save(&template_path).unwrap();
+
+    let loaded = OrganizationTemplate::load(&template_path).unwrap();
+    assert_eq!(loaded, exported);
+    assert_eq!(loaded.cabinets.len(), 1);
+    assert_eq!(loaded.cabinets[0].shelves.len(), 2);
+
+    let target_dir = TempDir::new().unwrap();
+    let target_db = Database::open_or_create(target_dir.path()).unwrap();
+    let (cabinets, shelves) = loaded.seed(&target_db).unwrap();
+
+    assert_eq!((cabinets, shelves), (1, 2));
+    let finance = target_db.get_cabinet_by_name("Finance").unwrap().unwrap();
+    assert!(
+        target_db
+            .get_shelf_by_name(finance.id, "Taxes")
+            .unwrap()
+            .is_some()
+    );
+}
+
+#[test]
+fn test_seed_keeps_existing_structure() {
+    let dir = TempDir::new().unwrap();
+    let db = Database::open_or_create(dir.path()).unwrap();
+    let cabinet_id = db.create_cabinet("Finance", "Existing").unwrap();
+    db.create_shelf(cabinet_id, "Taxes", "Existing").unwrap();
+
+    let (cabinets, shelves) = sample_template().seed(&db).unwrap();
+
+    assert_eq!((cabinets, shelves), (0, 1));
+    let finance = db.get_cabinet_by_name("Finance").unwrap().unwrap();
+    assert_eq!(finance.description, "Existing");
+}
@@ -0,0 +1,139 @@
+use clap::ValueEnum;
+use colored::*;
+use serde::{Serialize, Serializer};
+use std::time::{Duration, Instant};
+
+/// How `--timings` reports the phase timings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum TimingsFormat {
+    Table,
+    Json,
+}
+
+/// How long each phase of a run took, for telling whether a run is bound by content
+/// extraction, the model or the disk.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunTimings {
+    pub phases: Vec<PhaseTiming>,
+    pub batches: Vec<BatchTiming>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTiming {
+    pub phase: String,
+    #[serde(rename = "seconds", serialize_with = "as_seconds")]
+    pub duration: Duration,
+    /// Items the phase went through, e.g. files scanned or moved
+    pub items: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchTiming {
+    pub items: usize,
+    #[serde(rename = "seconds", serialize_with = "as_seconds")]
+    pub duration: Duration,
+    pub succeeded: bool,
+}
+
+fn as_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+impl PhaseTiming {
+    pub fn items_per_second(&self) -> Option<f64> {
+        let seconds = self.duration.as_secs_f64();
+        (self.items > 0 && seconds > 0.0).then(|| self.items as f64 / seconds)
+    }
+}
+
+impl RunTimings {
+    pub fn record_phase(&mut self, phase: &str, started: Instant, items: usize) {
+        self.phases.push(PhaseTiming {
+            phase: phase.to_string(),
+            duration: started.elapsed(),
+            items,
+        });
+    }
+
+    pub fn total(&self) -> Duration {
+        self.phases.iter().map(|phase| phase.duration).sum()
+    }
+
+    pub fn slowest_batch(&self) -> Option<(usize, &BatchTiming)> {
+        self.batches
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, batch)| batch.duration)
+    }
+
+    /// Prints the timings to stderr, leaving stdout to the plan or the run's output.
+    pub fn print(&self, format: TimingsFormat) {
+        match format {
+            TimingsFormat::Table => self.print_table(),
+            TimingsFormat::Json => match serde_json::to_string_pretty(&self.to_json()) {
+                Ok(json) => eprintln!("{}", json),
+                Err(e) => eprintln!(
+                    "{}: could not serialize timings: {}",
+                    "Warning".yellow().bold(),
+                    e
+                ),
+            },
+        }
+    }
+
+    fn print_table(&self) {
+        eprintln!("\n{}", "⏱️  Phase Timings".cyan().bold());
+        for phase in &self.phases {
+            let throughput = phase
+                .items_per_second()
+                .map(|rate| format!("{} items, {:.1} items/s", phase.items, rate))
+                .unwrap_or_default();
+            eprintln!(
+                "  {:<10} {:>9.2}s  {}",
+                phase.phase,
+                phase.duration.as_secs_f64(),
+                throughput.dimmed()
+            );
+        }
+        eprintln!(
+            "  {:<10} {:>9.2}s",
+            "total".bold(),
+            self.total().as_secs_f64()
+        );
+
+        if let Some((index, slowest)) = self.slowest_batch() {
+            let total: Duration = self.batches.iter().map(|batch| batch.duration).sum();
+            eprintln!(
+                "  {} batches averaging {:.2}s; slowest was batch {} ({} items, {:.2}s)",
+                self.batches.len(),
+                total.as_secs_f64() / self.batches.len() as f64,
+                index + 1,
+                slowest.items,
+                slowest.duration.as_secs_f64()
+            );
+        }
+    }
+
+    /// The timings with throughput and the slowest batch worked out, as printed by
+    /// `--timings json`.
+    pub fn to_json(&self) -> serde_json::Value {
+        let phases = self
+            .phases
+            .iter()
+            .map(|phase| {
+                let mut value = serde_json::to_value(phase).unwrap_or_default();
+                value["items_per_second"] = phase.items_per_second().into();
+                value
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({
+            "phases": phases,
+            "total_seconds": self.total().as_secs_f64(),
+            "batches": self.batches,
+            "slowest_batch": self.slowest_batch().map(|(index, _)| index + 1),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests;
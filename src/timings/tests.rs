@@ -0,0 +1,44 @@
+use super::{BatchTiming, PhaseTiming, RunTimings};
+use std::time::Duration;
+
+fn phase(name: &str, millis: u64, items: usize) -> PhaseTiming {
+    PhaseTiming {
+        phase: name.to_string(),
+        duration: Duration::from_millis(millis),
+        items,
+    }
+}
+
+fn batch(millis: u64, items: usize) -> BatchTiming {
+    BatchTiming {
+        items,
+        duration: Duration::from_millis(millis),
+        succeeded: true,
+    }
+}
+
+#[test]
+fn test_timings_summary() {
+    let timings = RunTimings {
+        phases: vec![
+            phase("scan", 500, 100),
+            phase("analysis", 4000, 100),
+            phase("planning", 0, 0),
+        ],
+        batches: vec![batch(1000, 40), batch(2500, 40), batch(500, 20)],
+    };
+
+    assert_eq!(timings.total(), Duration::from_millis(4500));
+    assert_eq!(timings.phases[0].items_per_second(), Some(200.0));
+    assert_eq!(timings.phases[2].items_per_second(), None);
+    let (index, slowest) = timings.slowest_batch().unwrap();
+    assert_eq!((index, slowest.items), (1, 40));
+
+    let json = timings.to_json();
+    assert_eq!(json["total_seconds"], 4.5);
+    assert_eq!(json["phases"][1]["seconds"], 4.0);
+    assert_eq!(json["phases"][1]["items_per_second"], 25.0);
+    assert!(json["phases"][2]["items_per_second"].is_null());
+    assert_eq!(json["slowest_batch"], 2);
+    assert_eq!(json["batches"][2]["items"], 20);
+}
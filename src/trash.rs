@@ -0,0 +1,93 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{
+    change_set::{Change, ChangeSet},
+    database::Database,
+};
+
+/// Directory inside `.shelfie/` that `--trash` moves originals into.
+pub const TRASH_DIR_NAME: &str = "trash";
+/// Where the trash lived before the state directory; moved on first use.
+pub const LEGACY_TRASH_DIR_NAME: &str = ".shelfie-trash";
+
+/// Keeps the originals replaced during one run, under a timestamped directory so
+/// that repeated runs don't overwrite each other.
+pub struct Trash {
+    base_path: PathBuf,
+    run_dir: PathBuf,
+    /// Originals kept so far
+    kept: AtomicUsize,
+}
+
+impl Trash {
+    pub fn new(base_path: &Path, run_started: DateTime<Utc>) -> Self {
+        Self {
+            base_path: base_path.to_path_buf(),
+            run_dir: Self::dir(base_path).join(run_started.format("%Y%m%d-%H%M%S").to_string()),
+            kept: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn dir(base_path: &Path) -> PathBuf {
+        Database::state_dir(base_path).join(TRASH_DIR_NAME)
+    }
+
+    /// Moves `original` into the trash, keeping its path relative to the base path.
+    pub fn keep(&self, original: &Path) -> std::io::Result<PathBuf> {
+        let relative = original
+            .strip_prefix(&self.base_path)
+            .unwrap_or_else(|_| Path::new(original.file_name().unwrap_or_default()));
+        let destination = self.run_dir.join(relative);
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(original, &destination)?;
+        self.kept.fetch_add(1, Ordering::Relaxed);
+        Ok(destination)
+    }
+
+    /// How many originals `keep` has moved into the trash.
+    pub fn kept(&self) -> usize {
+        self.kept.load(Ordering::Relaxed)
+    }
+
+    /// One deletion per run kept in the trash of `base_path`, oldest first.
+    pub fn pending_deletion(base_path: &Path) -> Result<ChangeSet> {
+        let dir = Self::dir(base_path);
+        let mut changes = ChangeSet::new();
+        if !dir.exists() {
+            return Ok(changes);
+        }
+
+        let mut entries = std::fs::read_dir(&dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort();
+        for path in entries {
+            let files = walkdir::WalkDir::new(&path)
+                .into_iter()
+                .filter_map(|e| e.ok())
+                .filter(|e| e.file_type().is_file())
+                .count();
+            changes.push(Change::Delete { path, files });
+        }
+        Ok(changes)
+    }
+
+    /// Applies `changes` from `pending_deletion` and removes the then empty trash,
+    /// returning how many files were deleted.
+    pub fn empty(base_path: &Path, changes: &ChangeSet) -> Result<usize> {
+        changes.apply(None)?;
+        let dir = Self::dir(base_path);
+        if dir.exists() && std::fs::read_dir(&dir)?.next().is_none() {
+            std::fs::remove_dir(&dir)?;
+        }
+        Ok(changes.deleted_files())
+    }
+}
+
+#[cfg(test)]
+mod tests;
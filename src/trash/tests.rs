@@ -0,0 +1,46 @@
+use super::Trash;
+use chrono::{TimeZone, Utc};
+use std::fs;
+use tempfile::TempDir;
+
+#[test]
+fn test_keep_preserves_relative_path() {
+    let temp_dir = TempDir::new().unwrap();
+    fs::create_dir_all(temp_dir.path().join("docs")).unwrap();
+    let original = temp_dir.path().join("docs/report.txt");
+    fs::write(&original, "draft").unwrap();
+
+    let run_started = Utc.with_ymd_and_hms(2024, 5, 1, 12, 30, 0).unwrap();
+    let kept = Trash::new(temp_dir.path(), run_started)
+        .keep(&original)
+        .unwrap();
+
+    assert!(!original.exists());
+    assert_eq!(
+        kept,
+        temp_dir
+            .path()
+            .join(".shelfie/trash/20240501-123000/docs/report.txt")
+    );
+    assert_eq!(fs::read_to_string(kept).unwrap(), "draft");
+}
+
+#[test]
+fn test_empty_removes_trash() {
+    let temp_dir = TempDir::new().unwrap();
+    let nothing = Trash::pending_deletion(temp_dir.path()).unwrap();
+    assert!(nothing.is_empty());
+    assert_eq!(Trash::empty(temp_dir.path(), &nothing).unwrap(), 0);
+
+    let original = temp_dir.path().join("a.txt");
+    fs::write(&original, "a").unwrap();
+    Trash::new(temp_dir.path(), Utc::now())
+        .keep(&original)
+        .unwrap();
+
+    let changes = Trash::pending_deletion(temp_dir.path()).unwrap();
+    assert_eq!(changes.deleted_files(), 1);
+    assert!(Trash::dir(temp_dir.path()).exists());
+    assert_eq!(Trash::empty(temp_dir.path(), &changes).unwrap(), 1);
+    assert!(!Trash::dir(temp_dir.path()).exists());
+}
@@ -1,7 +1,9 @@
+use crate::collation::collation;
 use crate::file_analyzer::AnalyzedFile;
-use anyhow::Result;
+use anyhow::{Context, Result};
 use colored::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 pub fn print_tree(path: &Path, prefix: &str, is_last: bool) {
     let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
@@ -17,13 +19,335 @@ pub fn print_tree(path: &Path, prefix: &str, is_last: bool) {
         };
 
         if let Ok(entries) = std::fs::read_dir(path) {
-            let mut entries: Vec<_> = entries.filter_map(|e| e.ok()).collect();
-            entries.sort_by_key(|e| e.path());
+            let mut entries: Vec<_> = entries
+                .filter_map(|e| e.ok())
+                .map(|e| (e.file_name().to_string_lossy().into_owned(), e.path()))
+                .collect();
+            collation().sort_by_name(&mut entries, |(name, _)| name);
 
             let count = entries.len();
-            for (i, entry) in entries.iter().enumerate() {
-                print_tree(&entry.path(), &new_prefix, i == count - 1);
+            for (i, (_, path)) in entries.iter().enumerate() {
+                print_tree(path, &new_prefix, i == count - 1);
             }
         }
     }
 }
+
+/// Shows `path` relative to `base` (e.g. `src/config.json`), falling back to the full
+/// path for anything outside it.
+pub fn relative_display(path: &Path, base: &Path) -> String {
+    match path.strip_prefix(base) {
+        Ok(relative) if !relative.as_os_str().is_empty() => relative.display().to_string(),
+        _ => path.display().to_string(),
+    }
+}
+
+/// Parses a size in bytes, given as a plain number or with a unit, e.g. `50MB`,
+/// `1.5GiB` or `512k`. `k`, `M`, `G` and `T` are powers of 1000, `KiB` to `TiB` of 1024.
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let number: f64 = number.parse().map_err(|_| {
+        format!(
+            "expected a size like 500000, 50MB or 2GiB, found {:?}",
+            value
+        )
+    })?;
+    let multiplier: u64 = match unit.trim().to_lowercase().as_str() {
+        "" | "b" => 1,
+        "k" | "kb" => 1_000,
+        "m" | "mb" => 1_000_000,
+        "g" | "gb" => 1_000_000_000,
+        "t" | "tb" => 1_000_000_000_000,
+        "kib" => 1 << 10,
+        "mib" => 1 << 20,
+        "gib" => 1 << 30,
+        "tib" => 1 << 40,
+        unit => return Err(format!("unknown size unit {:?}", unit)),
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+/// Copies a file, or a directory with everything in it, to `to`. Symlinks are
+/// recreated rather than followed.
+pub fn copy_recursive(from: &Path, to: &Path) -> std::io::Result<()> {
+    if !from.is_dir() {
+        std::fs::copy(from, to)?;
+        return preserve_times(&std::fs::metadata(from)?, to);
+    }
+
+    for entry in walkdir::WalkDir::new(from) {
+        let entry = entry?;
+        let target = to.join(entry.path().strip_prefix(from).unwrap_or(entry.path()));
+        if entry.file_type().is_dir() {
+            std::fs::create_dir_all(&target)?;
+        } else if entry.file_type().is_symlink() {
+            copy_symlink(entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+            preserve_times(&entry.metadata()?, &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Gives the file at `to` the modification (and, where known, access) time from `metadata`.
+pub fn preserve_times(metadata: &std::fs::Metadata, to: &Path) -> std::io::Result<()> {
+    let mut times = std::fs::FileTimes::new().set_modified(metadata.modified()?);
+    if let Ok(accessed) = metadata.accessed() {
+        times = times.set_accessed(accessed);
+    }
+    // Windows needs write access to change a file's times. A copy of a read-only file
+    // can't be opened for writing, but Unix only needs ownership.
+    let file = match std::fs::OpenOptions::new().write(true).open(to) {
+        Ok(file) => file,
+        Err(e) if cfg!(unix) && e.kind() == std::io::ErrorKind::PermissionDenied => {
+            std::fs::File::open(to)?
+        }
+        Err(e) => return Err(e),
+    };
+    file.set_times(times)
+}
+
+/// Whether `path` and the directory `dir` are on the same device, i.e. whether a
+/// rename between them can work.
+#[cfg(unix)]
+pub fn same_device(path: &Path, dir: &Path) -> std::io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+    Ok(std::fs::symlink_metadata(path)?.dev() == std::fs::metadata(dir)?.dev())
+}
+
+/// Device ids aren't available here; a failed rename still falls back to a copy.
+#[cfg(not(unix))]
+pub fn same_device(_path: &Path, _dir: &Path) -> std::io::Result<bool> {
+    Ok(true)
+}
+
+/// Whether the filesystem holding `dir` treats names differing only in case as the
+/// same, as macOS and Windows do by default. Found out by creating a probe file; if
+/// that isn't possible, the filesystem is taken to be case-sensitive.
+pub fn is_case_insensitive(dir: &Path) -> bool {
+    let probe = format!(".shelfie-case-probe-{}", std::process::id());
+    let probe_path = dir.join(&probe);
+    if std::fs::File::create_new(&probe_path).is_err() {
+        return false;
+    }
+    let insensitive = dir.join(probe.to_uppercase()).exists();
+    let _ = std::fs::remove_file(&probe_path);
+    insensitive
+}
+
+/// Whether two paths name the same entry on a case-insensitive filesystem without
+/// being identical, e.g. `Photo.JPG` and `photo.jpg`.
+pub fn differ_only_in_case(a: &Path, b: &Path) -> bool {
+    a != b && a.to_string_lossy().to_lowercase() == b.to_string_lossy().to_lowercase()
+}
+
+/// Who owns a file, as `alice (group staff)`. Ids without a user or group name are
+/// shown as numbers.
+#[cfg(unix)]
+pub fn file_owner(metadata: &std::fs::Metadata) -> Option<String> {
+    use std::os::unix::fs::MetadataExt;
+    let user = user_name(metadata.uid()).unwrap_or_else(|| format!("uid {}", metadata.uid()));
+    let group = group_name(metadata.gid()).unwrap_or_else(|| format!("gid {}", metadata.gid()));
+    Some(format!("{} (group {})", user, group))
+}
+
+/// Owners aren't looked up here (Windows has SIDs rather than uids).
+#[cfg(not(unix))]
+pub fn file_owner(_metadata: &std::fs::Metadata) -> Option<String> {
+    None
+}
+
+/// Resolved user and group names by `(is_group, id)`.
+#[cfg(unix)]
+type OwnerNames = std::collections::BTreeMap<(bool, u32), Option<String>>;
+
+/// Resolved names are cached, since every file of a user asks for the same one and
+/// lookups can go over the network (NIS, LDAP).
+#[cfg(unix)]
+static OWNER_NAMES: std::sync::Mutex<OwnerNames> = std::sync::Mutex::new(OwnerNames::new());
+
+#[cfg(unix)]
+fn cached_name(is_group: bool, id: u32, lookup: fn(u32) -> Option<String>) -> Option<String> {
+    OWNER_NAMES
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .entry((is_group, id))
+        .or_insert_with(|| lookup(id))
+        .clone()
+}
+
+#[cfg(unix)]
+fn user_name(uid: u32) -> Option<String> {
+    cached_name(false, uid, |uid| {
+        let mut buffer = vec![0 as libc::c_char; 4096];
+        let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        // SAFETY: the buffers outlive the call and their sizes are passed along
+        let status = unsafe {
+            libc::getpwuid_r(
+                uid,
+                &mut passwd,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        if status != 0 || result.is_null() {
+            return None;
+        }
+        // SAFETY: on success, pw_name points to a NUL-terminated string in `buffer`
+        let name = unsafe { std::ffi::CStr::from_ptr(passwd.pw_name) };
+        Some(name.to_string_lossy().into_owned())
+    })
+}
+
+#[cfg(unix)]
+fn group_name(gid: u32) -> Option<String> {
+    cached_name(true, gid, |gid| {
+        let mut buffer = vec![0 as libc::c_char; 4096];
+        let mut group: libc::group = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        // SAFETY: the buffers outlive the call and their sizes are passed along
+        let status = unsafe {
+            libc::getgrgid_r(
+                gid,
+                &mut group,
+                buffer.as_mut_ptr(),
+                buffer.len(),
+                &mut result,
+            )
+        };
+        if status != 0 || result.is_null() {
+            return None;
+        }
+        // SAFETY: on success, gr_name points to a NUL-terminated string in `buffer`
+        let name = unsafe { std::ffi::CStr::from_ptr(group.gr_name) };
+        Some(name.to_string_lossy().into_owned())
+    })
+}
+
+/// How often a filesystem operation is tried while it keeps failing transiently.
+pub const TRANSIENT_ATTEMPTS: u32 = 3;
+const TRANSIENT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Whether an I/O error is likely to go away on its own, e.g. a file briefly locked
+/// by a virus scanner, as opposed to permanent ones like a missing file.
+pub fn is_transient_io_error(error: &std::io::Error) -> bool {
+    // ERROR_SHARING_VIOLATION and ERROR_LOCK_VIOLATION
+    #[cfg(windows)]
+    if matches!(error.raw_os_error(), Some(32 | 33)) {
+        return true;
+    }
+    matches!(
+        error.kind(),
+        std::io::ErrorKind::Interrupted | std::io::ErrorKind::WouldBlock
+    )
+}
+
+/// Runs `operation` until it succeeds, fails with a permanent error, or has failed
+/// transiently [`TRANSIENT_ATTEMPTS`] times, waiting a little longer before each retry.
+pub async fn retry_transient<T, F, Fut>(mut operation: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if attempt < TRANSIENT_ATTEMPTS && is_transient(&e) => {
+                tokio::time::sleep(TRANSIENT_RETRY_DELAY * attempt).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<std::io::Error>())
+        .any(is_transient_io_error)
+}
+
+#[cfg(unix)]
+fn copy_symlink(link: &Path, target: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(link)?, target)
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(link: &Path, target: &Path) -> std::io::Result<()> {
+    std::fs::copy(link, target).map(|_| ())
+}
+
+/// Expands a leading `~` and resolves `path` to an absolute, canonical path, so the
+/// database, cabinet paths and skip checks all see the same form of the directory
+/// however it was given on the command line.
+pub fn resolve_directory(path: &Path) -> Result<PathBuf> {
+    let expanded = expand_home(path, dirs::home_dir().as_deref());
+    expanded
+        .canonicalize()
+        .with_context(|| format!("Directory does not exist: {}", path.display()))
+}
+
+/// Directories with more top-level entries than this are treated like a home
+/// directory: almost certainly not something to reorganize wholesale.
+pub const MAX_SAFE_TOP_LEVEL_ENTRIES: usize = 5000;
+
+/// Why organizing a directory is almost certainly a mistake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DangerousTarget {
+    FilesystemRoot,
+    HomeDirectory,
+    ManyEntries(usize),
+}
+
+impl std::fmt::Display for DangerousTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DangerousTarget::FilesystemRoot => write!(f, "it is the root of a filesystem"),
+            DangerousTarget::HomeDirectory => write!(f, "it is your home directory"),
+            DangerousTarget::ManyEntries(limit) => {
+                write!(f, "it has more than {} top-level entries", limit)
+            }
+        }
+    }
+}
+
+/// Checks a resolved directory against the filesystem root (`/`, or a drive root
+/// on Windows), the home directory and a top-level entry count above `max_entries`.
+pub fn dangerous_target(
+    directory: &Path,
+    home: Option<&Path>,
+    max_entries: usize,
+) -> Option<DangerousTarget> {
+    if directory.parent().is_none() {
+        return Some(DangerousTarget::FilesystemRoot);
+    }
+    if home
+        .and_then(|home| home.canonicalize().ok())
+        .is_some_and(|home| home == directory)
+    {
+        return Some(DangerousTarget::HomeDirectory);
+    }
+
+    let entries = std::fs::read_dir(directory)
+        .map(|entries| entries.take(max_entries + 1).count())
+        .unwrap_or(0);
+    (entries > max_entries).then_some(DangerousTarget::ManyEntries(max_entries))
+}
+
+fn expand_home(path: &Path, home: Option<&Path>) -> PathBuf {
+    match (path.strip_prefix("~"), home) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,220 @@
+use super::{
+    DangerousTarget, TRANSIENT_ATTEMPTS, dangerous_target, differ_only_in_case, expand_home,
+    file_owner, is_case_insensitive, parse_size, preserve_times, relative_display,
+    resolve_directory, retry_transient, same_device,
+};
+use anyhow::Context;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn test_relative_display() {
+    let base = Path::new("/home/user/Downloads");
+
+    assert_eq!(
+        relative_display(Path::new("/home/user/Downloads/src/config.json"), base),
+        "src/config.json"
+    );
+    assert_eq!(
+        relative_display(Path::new("./src/config.json"), Path::new(".")),
+        "src/config.json"
+    );
+    assert_eq!(
+        relative_display(Path::new("/etc/hosts"), base),
+        "/etc/hosts"
+    );
+}
+
+#[test]
+fn test_expand_home() {
+    let home = Path::new("/home/user");
+
+    assert_eq!(
+        expand_home(Path::new("~/Downloads"), Some(home)),
+        PathBuf::from("/home/user/Downloads")
+    );
+    assert_eq!(expand_home(Path::new("~"), Some(home)), home);
+    assert_eq!(
+        expand_home(Path::new("~other/Downloads"), Some(home)),
+        PathBuf::from("~other/Downloads")
+    );
+    assert_eq!(
+        expand_home(Path::new("stuff"), Some(home)),
+        PathBuf::from("stuff")
+    );
+}
+
+#[test]
+fn test_resolve_directory_is_absolute() {
+    let resolved = resolve_directory(Path::new("./src/../src")).unwrap();
+
+    assert!(resolved.is_absolute());
+    assert_eq!(resolved, Path::new(env!("CARGO_MANIFEST_DIR")).join("src"));
+    assert!(resolve_directory(Path::new("does/not/exist")).is_err());
+}
+
+#[test]
+fn test_dangerous_targets() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let home = temp_dir.path().canonicalize().unwrap();
+    let downloads = home.join("Downloads");
+    std::fs::create_dir(&downloads).unwrap();
+    for i in 0..3 {
+        std::fs::write(downloads.join(format!("file{}.txt", i)), "").unwrap();
+    }
+
+    let root = home.ancestors().last().unwrap();
+    assert_eq!(
+        dangerous_target(root, Some(&home), 10_000_000),
+        Some(DangerousTarget::FilesystemRoot)
+    );
+    assert_eq!(
+        dangerous_target(&home, Some(&home), 10),
+        Some(DangerousTarget::HomeDirectory)
+    );
+    assert_eq!(
+        dangerous_target(&downloads, Some(&home), 2),
+        Some(DangerousTarget::ManyEntries(2))
+    );
+    assert_eq!(dangerous_target(&downloads, Some(&home), 3), None);
+    assert_eq!(dangerous_target(&downloads, None, 3), None);
+}
+
+#[test]
+fn test_same_device() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let file = temp_dir.path().join("notes.txt");
+    std::fs::write(&file, "notes").unwrap();
+
+    assert!(same_device(&file, temp_dir.path()).unwrap());
+    // procfs is always its own filesystem
+    #[cfg(target_os = "linux")]
+    assert!(!same_device(&file, Path::new("/proc")).unwrap());
+}
+
+#[test]
+fn test_case_insensitivity_is_probed() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+
+    let insensitive = is_case_insensitive(temp_dir.path());
+    // The probe is cleaned up
+    assert_eq!(std::fs::read_dir(temp_dir.path()).unwrap().count(), 0);
+    #[cfg(target_os = "linux")]
+    assert!(!insensitive);
+    #[cfg(any(target_os = "macos", target_os = "windows"))]
+    assert!(insensitive);
+    // Without write access there's nothing to probe with
+    assert!(!is_case_insensitive(&temp_dir.path().join("missing")));
+}
+
+#[test]
+fn test_differ_only_in_case() {
+    assert!(differ_only_in_case(
+        Path::new("/docs/Photo.JPG"),
+        Path::new("/docs/photo.jpg")
+    ));
+    assert!(!differ_only_in_case(
+        Path::new("/docs/photo.jpg"),
+        Path::new("/docs/photo.jpg")
+    ));
+    assert!(!differ_only_in_case(
+        Path::new("/docs/photo.jpg"),
+        Path::new("/docs/photo.png")
+    ));
+}
+
+#[tokio::test]
+async fn test_retry_transient_recovers_from_a_transient_failure() {
+    let mut attempts = 0;
+    let result = retry_transient(|| {
+        attempts += 1;
+        let attempt = attempts;
+        async move {
+            if attempt == 1 {
+                // Wrapped in context like the real moves, so the cause has to be found
+                Err(Error::from(ErrorKind::Interrupted)).context("Failed to move report.pdf")
+            } else {
+                Ok(attempt)
+            }
+        }
+    })
+    .await;
+
+    assert_eq!(result.unwrap(), 2);
+    assert_eq!(attempts, 2);
+}
+
+#[tokio::test]
+async fn test_retry_transient_fails_fast_on_permanent_errors() {
+    let mut attempts = 0;
+    let result: anyhow::Result<()> = retry_transient(|| {
+        attempts += 1;
+        async { Err(Error::from(ErrorKind::PermissionDenied).into()) }
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(attempts, 1);
+
+    let mut attempts = 0;
+    let result: anyhow::Result<()> = retry_transient(|| {
+        attempts += 1;
+        async { Err(Error::from(ErrorKind::WouldBlock).into()) }
+    })
+    .await;
+    assert!(result.is_err());
+    assert_eq!(attempts, TRANSIENT_ATTEMPTS);
+}
+
+#[test]
+fn test_preserve_times_on_a_read_only_copy() {
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let from = temp_dir.path().join("from.txt");
+    let to = temp_dir.path().join("to.txt");
+    std::fs::write(&from, "report").unwrap();
+    let modified = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_000_000);
+    std::fs::File::options()
+        .write(true)
+        .open(&from)
+        .unwrap()
+        .set_modified(modified)
+        .unwrap();
+    let mut permissions = std::fs::metadata(&from).unwrap().permissions();
+    permissions.set_readonly(true);
+    std::fs::set_permissions(&from, permissions).unwrap();
+    std::fs::copy(&from, &to).unwrap();
+
+    preserve_times(&std::fs::metadata(&from).unwrap(), &to).unwrap();
+
+    assert_eq!(
+        std::fs::metadata(&to).unwrap().modified().unwrap(),
+        modified
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_file_owner_names_user_and_group() {
+    use std::os::unix::fs::MetadataExt;
+
+    let dir = tempfile::TempDir::new().unwrap();
+    let metadata = std::fs::metadata(dir.path()).unwrap();
+    let owner = file_owner(&metadata).unwrap();
+
+    assert!(owner.contains(" (group "), "{}", owner);
+    // Root has a name everywhere; other ids may not
+    if metadata.uid() == 0 {
+        assert!(owner.starts_with("root "), "{}", owner);
+    }
+}
+
+#[test]
+fn test_parse_size_accepts_units() {
+    assert_eq!(parse_size("4096"), Ok(4096));
+    assert_eq!(parse_size("50MB"), Ok(50_000_000));
+    assert_eq!(parse_size("1.5 GiB"), Ok(1_610_612_736));
+    assert_eq!(parse_size("512k"), Ok(512_000));
+
+    assert!(parse_size("MB").is_err());
+    assert!(parse_size("10 parsecs").is_err());
+    assert!(parse_size("-5MB").is_err());
+}
@@ -0,0 +1,166 @@
+use anyhow::{Context, Result};
+use colored::*;
+use notify::{EventKind, RecursiveMode, Watcher};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::organizer::{FileOrganizer, OrganizeOptions};
+
+/// Seconds a new file must stay unchanged before it's organized, by default.
+pub const DEFAULT_SETTLE_SECS: u64 = 5;
+/// How often pending files are checked for changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// New files the watcher reported, waiting until they stop changing, e.g. while a
+/// download is still being written.
+#[derive(Debug, Default)]
+pub(crate) struct PendingFiles {
+    files: HashMap<PathBuf, PendingFile>,
+}
+
+#[derive(Debug)]
+struct PendingFile {
+    size: u64,
+    modified: Option<SystemTime>,
+    unchanged_since: Instant,
+}
+
+impl PendingFiles {
+    /// Records a change to `path`. Directories, hidden files and files that are gone,
+    /// e.g. because they were just organized, are ignored.
+    pub fn touch(&mut self, path: &Path, now: Instant) {
+        let hidden = path
+            .file_name()
+            .is_none_or(|name| FileOrganizer::is_hidden_entry(&name.to_string_lossy(), false));
+        match std::fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() && !hidden => {
+                self.files.insert(
+                    path.to_path_buf(),
+                    PendingFile {
+                        size: metadata.len(),
+                        modified: metadata.modified().ok(),
+                        unchanged_since: now,
+                    },
+                );
+            }
+            _ => {
+                self.files.remove(path);
+            }
+        }
+    }
+
+    /// Checks the pending files again, restarting the wait of those that changed
+    /// without the watcher noticing and forgetting those that are gone.
+    pub fn refresh(&mut self, now: Instant) {
+        self.files.retain(|path, file| {
+            let Ok(metadata) = std::fs::metadata(path) else {
+                return false;
+            };
+            let modified = metadata.modified().ok();
+            if metadata.len() != file.size || modified != file.modified {
+                file.size = metadata.len();
+                file.modified = modified;
+                file.unchanged_since = now;
+            }
+            true
+        });
+    }
+
+    /// Whether there are pending files and none of them changed for `settle`.
+    pub fn settled(&self, now: Instant, settle: Duration) -> bool {
+        !self.files.is_empty()
+            && self
+                .files
+                .values()
+                .all(|file| now.duration_since(file.unchanged_since) >= settle)
+    }
+
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.files.clear();
+    }
+}
+
+/// Organizes new files in `base_path` once they stop changing, until Ctrl-C. Each run
+/// is the usual incremental one, so only items not in the database are analyzed. A
+/// run in progress is finished before stopping.
+pub async fn watch(
+    organizer: &FileOrganizer,
+    base_path: &Path,
+    options: &OrganizeOptions,
+    settle: Duration,
+) -> Result<()> {
+    if options.batch.existing_only && !organizer.has_structure()? {
+        anyhow::bail!(
+            "There are no cabinets to sort new files into yet: organize {} once first, \
+            or pass --allow-new-cabinets",
+            base_path.display()
+        );
+    }
+
+    let (sender, mut events) = tokio::sync::mpsc::unbounded_channel();
+    let mut watcher = notify::recommended_watcher(move |event| {
+        // The receiver only goes away when watching stops
+        let _ = sender.send(event);
+    })
+    .context("Failed to start the file watcher")?;
+    // Organized items move into cabinet directories, which aren't watched
+    watcher
+        .watch(base_path, RecursiveMode::NonRecursive)
+        .with_context(|| format!("Failed to watch {}", base_path.display()))?;
+
+    println!(
+        "👀 Watching {} for new files - press Ctrl-C to stop",
+        base_path.display().to_string().yellow()
+    );
+
+    let mut pending = PendingFiles::default();
+    let mut ticker = tokio::time::interval(POLL_INTERVAL);
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+    loop {
+        tokio::select! {
+            _ = &mut ctrl_c => break,
+            Some(event) = events.recv() => match event {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    for path in &event.paths {
+                        pending.touch(path, Instant::now());
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("{}: file watcher error: {}", "Warning".yellow().bold(), e),
+            },
+            _ = ticker.tick() => {
+                let now = Instant::now();
+                pending.refresh(now);
+                if !pending.settled(now, settle) {
+                    continue;
+                }
+
+                println!("\n📥 {} new files settled - organizing", pending.len());
+                pending.clear();
+                // A failed run shouldn't end the watch; its items are retried next time
+                match organizer.analyze_and_organize(options).await {
+                    Ok(()) => println!("👀 Waiting for more files..."),
+                    Err(e) => eprintln!(
+                        "{}: organizing failed, waiting for more files: {}",
+                        "Warning".yellow().bold(),
+                        e
+                    ),
+                }
+            }
+        }
+    }
+
+    println!("\n{}", "Stopped watching.".yellow());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,47 @@
+use super::*;
+use std::fs;
+use tempfile::TempDir;
+
+const SETTLE: Duration = Duration::from_secs(5);
+
+#[test]
+fn test_file_settles_once_it_stops_changing() {
+    let temp_dir = TempDir::new().unwrap();
+    let download = temp_dir.path().join("report.pdf");
+    fs::write(&download, "part one").unwrap();
+    let start = Instant::now();
+
+    let mut pending = PendingFiles::default();
+    pending.touch(&download, start);
+    assert!(!pending.settled(start + Duration::from_secs(2), SETTLE));
+
+    // Still being written: the wait starts over
+    fs::write(&download, "part one, part two").unwrap();
+    pending.refresh(start + Duration::from_secs(3));
+    assert!(!pending.settled(start + Duration::from_secs(6), SETTLE));
+    assert!(pending.settled(start + Duration::from_secs(8), SETTLE));
+}
+
+#[test]
+fn test_directories_hidden_and_vanished_files_are_not_pending() {
+    let temp_dir = TempDir::new().unwrap();
+    let now = Instant::now();
+    let mut pending = PendingFiles::default();
+
+    fs::create_dir(temp_dir.path().join("Finance")).unwrap();
+    pending.touch(&temp_dir.path().join("Finance"), now);
+    fs::write(temp_dir.path().join(".DS_Store"), "").unwrap();
+    pending.touch(&temp_dir.path().join(".DS_Store"), now);
+    pending.touch(&temp_dir.path().join("missing.txt"), now);
+    assert_eq!(pending.len(), 0);
+    assert!(!pending.settled(now + SETTLE, SETTLE));
+
+    // Organized away before it settled
+    let moved = temp_dir.path().join("notes.txt");
+    fs::write(&moved, "notes").unwrap();
+    pending.touch(&moved, now);
+    assert_eq!(pending.len(), 1);
+    fs::remove_file(&moved).unwrap();
+    pending.refresh(now + Duration::from_secs(1));
+    assert_eq!(pending.len(), 0);
+}
@@ -0,0 +1,194 @@
+use anyhow::{Result, anyhow};
+use colored::*;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    collation::collation,
+    models::{FileMovement, OrganizationPlan},
+    organizer::{FileOrganizer, OrganizeOptions},
+    plan_diff::{PlanDiff, diff_plans},
+    providers::{LLMProvider, Provider, TokenUsage},
+};
+
+/// One model's plan for a directory, and what it cost to make.
+pub struct ModelRun {
+    pub label: String,
+    pub plan: OrganizationPlan,
+    pub usage: TokenUsage,
+}
+
+/// Parses a `--models` entry: `provider:model`, or a model of the configured
+/// provider. Ollama tags such as `llama3:8b` stay intact, since only a known
+/// provider name counts as a prefix.
+pub fn parse_model_spec(spec: &str) -> Option<(Option<Provider>, &str)> {
+    let spec = spec.trim();
+    let (provider, model) = match spec.split_once(':') {
+        Some((prefix, model)) => match Provider::parse(prefix) {
+            Some(provider) => (Some(provider), model.trim()),
+            None => (None, spec),
+        },
+        None => (None, spec),
+    };
+    (!model.is_empty()).then_some((provider, model))
+}
+
+/// Runs the analysis for `directory` with `provider` against a throwaway
+/// database, so the directory's own database is left untouched.
+pub async fn plan_with(
+    label: &str,
+    provider: LLMProvider,
+    directory: &Path,
+    options: &OrganizeOptions,
+) -> Result<ModelRun> {
+    let db_dir = scratch_dir()?;
+
+    let result = async {
+        let organizer =
+            FileOrganizer::new(provider.clone(), directory.to_path_buf(), Some(&db_dir))?;
+        organizer.plan(options).await
+    }
+    .await;
+    let _ = std::fs::remove_dir_all(&db_dir);
+
+    Ok(ModelRun {
+        label: label.to_string(),
+        plan: result?,
+        usage: provider.token_usage(),
+    })
+}
+
+fn scratch_dir() -> Result<PathBuf> {
+    for attempt in 0..100 {
+        let dir =
+            std::env::temp_dir().join(format!("shelfie-whatif-{}-{}", std::process::id(), attempt));
+        if std::fs::create_dir(&dir).is_ok() {
+            return Ok(dir);
+        }
+    }
+    Err(anyhow!(
+        "Failed to create a scratch directory for the analysis"
+    ))
+}
+
+/// Item counts per cabinet and shelf for each plan, keyed by `cabinet/shelf`.
+pub fn structure_counts(plans: [&OrganizationPlan; 2]) -> BTreeMap<String, [usize; 2]> {
+    let mut counts: BTreeMap<String, [usize; 2]> = BTreeMap::new();
+    for (i, plan) in plans.iter().enumerate() {
+        for movement in &plan.movements {
+            counts.entry(destination(movement)).or_default()[i] += 1;
+        }
+    }
+    counts
+}
+
+pub fn print_comparison(first: &ModelRun, second: &ModelRun) {
+    let mut counts = structure_counts([&first.plan, &second.plan])
+        .into_iter()
+        .collect::<Vec<_>>();
+    collation().sort_by_name(&mut counts, |(key, _)| key);
+    let width = counts
+        .iter()
+        .map(|(key, _)| key.chars().count())
+        .chain([9])
+        .max()
+        .unwrap_or(0);
+    let column = first.label.chars().count().max(6);
+
+    println!("\n{}", "🔀 Model Comparison".cyan().bold());
+    println!(
+        "  {}  {}  {}",
+        format!("{:width$}", "Structure").bold(),
+        format!("{:>column$}", first.label).yellow(),
+        second.label.yellow(),
+    );
+    for (key, [a, b]) in &counts {
+        let cell = |count: usize| {
+            if count == 0 {
+                "-".to_string()
+            } else {
+                count.to_string()
+            }
+        };
+        let line = format!("  {:width$}  {:>column$}  {}", key, cell(*a), cell(*b));
+        if a == b {
+            println!("{}", line);
+        } else {
+            println!("{}", line.yellow());
+        }
+    }
+
+    let diff = diff_plans(&first.plan, &second.plan);
+    print_differences(&diff, first, second);
+
+    println!("\n{}", "Estimated token usage:".bold());
+    for run in [first, second] {
+        println!(
+            "  {}: ~{} tokens ({} prompt, {} completion) in {} requests",
+            run.label.yellow(),
+            run.usage.total(),
+            run.usage.prompt_tokens,
+            run.usage.completion_tokens,
+            run.usage.requests
+        );
+    }
+}
+
+fn print_differences(diff: &PlanDiff, first: &ModelRun, second: &ModelRun) {
+    let total = first.plan.movements.len();
+    if diff.moved.is_empty() {
+        println!(
+            "\n{}",
+            format!("Both models place all {} items the same way", total).green()
+        );
+    } else {
+        println!(
+            "\n{}",
+            format!(
+                "Placed differently ({} of {} items):",
+                diff.moved.len(),
+                total
+            )
+            .bold()
+        );
+        for moved in &diff.moved {
+            let name = moved
+                .current
+                .from
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_else(|| moved.current.from.display().to_string());
+            println!(
+                "  {}: {} vs {}",
+                name,
+                destination(&moved.previous).yellow(),
+                destination(&moved.current).green()
+            );
+        }
+    }
+
+    // Items only one model got to, e.g. when a batch failed for the other
+    let only_first = total - diff.unchanged - diff.moved.len();
+    let only_second = diff.added.len();
+    if only_first + only_second > 0 {
+        println!(
+            "{}",
+            format!(
+                "{} items only planned by {}, {} only by {}",
+                only_first, first.label, only_second, second.label
+            )
+            .yellow()
+        );
+    }
+}
+
+fn destination(movement: &FileMovement) -> String {
+    if movement.to_shelf.is_empty() {
+        movement.to_cabinet.clone()
+    } else {
+        format!("{}/{}", movement.to_cabinet, movement.to_shelf)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,61 @@
+use super::{parse_model_spec, structure_counts};
+use crate::models::{FileMovement, OrganizationPlan};
+use crate::providers::Provider;
+use std::path::PathBuf;
+
+fn movement(from: &str, cabinet: &str, shelf: &str) -> FileMovement {
+    FileMovement {
+        from: PathBuf::from(from),
+        to_cabinet: cabinet.to_string(),
+        to_shelf: shelf.to_string(),
+        new_name: None,
+        reasoning: String::new(),
+        opaque_reason: None,
+    }
+}
+
+#[test]
+fn test_model_spec_with_and_without_provider() {
+    assert!(matches!(parse_model_spec("gpt-4o"), Some((None, "gpt-4o"))));
+    assert!(matches!(
+        parse_model_spec("ollama:llama3"),
+        Some((Some(Provider::Ollama), "llama3"))
+    ));
+    assert!(matches!(
+        parse_model_spec("Ollama:llama3:8b"),
+        Some((Some(Provider::Ollama), "llama3:8b"))
+    ));
+    assert!(matches!(
+        parse_model_spec("llama3:8b"),
+        Some((None, "llama3:8b"))
+    ));
+    assert!(parse_model_spec("groq:").is_none());
+    assert!(parse_model_spec(" ").is_none());
+}
+
+#[test]
+fn test_structure_counts_side_by_side() {
+    let first = OrganizationPlan {
+        cabinets: Vec::new(),
+        movements: vec![
+            movement("/a.pdf", "Finance", "Taxes"),
+            movement("/b.pdf", "Finance", "Taxes"),
+            movement("/c.jpg", "Photos", ""),
+        ],
+    };
+    let second = OrganizationPlan {
+        cabinets: Vec::new(),
+        movements: vec![
+            movement("/a.pdf", "Finance", "Taxes"),
+            movement("/b.pdf", "Finance", "Receipts"),
+            movement("/c.jpg", "Photos", ""),
+        ],
+    };
+
+    let counts = structure_counts([&first, &second]);
+
+    assert_eq!(counts["Finance/Taxes"], [2, 1]);
+    assert_eq!(counts["Finance/Receipts"], [0, 1]);
+    assert_eq!(counts["Photos"], [1, 1]);
+    assert_eq!(counts.len(), 3);
+}
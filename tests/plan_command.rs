@@ -0,0 +1,118 @@
+use httpmock::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use tempfile::TempDir;
+
+/// An Ollama server that has `llama3` installed.
+fn ollama() -> MockServer {
+    let server = MockServer::start();
+    server.mock(|when, then| {
+        when.method(GET).path("/api/tags");
+        then.status(200)
+            .json_body(serde_json::json!({ "models": [{ "name": "llama3:latest" }] }));
+    });
+    server
+}
+
+/// Runs `shelfie plan` on `target` with `home` as the home directory and none of the
+/// caller's provider settings.
+fn plan(home: &Path, target: &Path, env: &[(&str, &str)]) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_shelfie"));
+    command
+        .arg("plan")
+        .arg(target)
+        .current_dir(home)
+        .env("HOME", home)
+        .env_remove("SHELFIE_PROVIDER")
+        .env_remove("SHELFIE_MODEL")
+        .env_remove("OLLAMA_API_BASE_URL");
+    for (key, value) in env {
+        command.env(key, value);
+    }
+    command.output().unwrap()
+}
+
+/// The plan printed to stdout, failing if anything else was printed there.
+fn plan_json(output: &Output) -> serde_json::Value {
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    serde_json::from_slice(&output.stdout).unwrap_or_else(|e| {
+        panic!(
+            "stdout isn't just the plan ({}):\n{}",
+            e,
+            String::from_utf8_lossy(&output.stdout)
+        )
+    })
+}
+
+#[test]
+fn test_plan_with_saved_configuration_prints_only_json() {
+    let server = ollama();
+    let home = TempDir::new().unwrap();
+    let config_dir = home.path().join(".fs-organiser");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(
+        config_dir.join("config.json"),
+        serde_json::json!({
+            "provider": "Ollama",
+            "model_name": "llama3",
+            "base_url": server.base_url(),
+        })
+        .to_string(),
+    )
+    .unwrap();
+    let target = TempDir::new().unwrap();
+
+    let output = plan(home.path(), target.path(), &[]);
+
+    let plan = plan_json(&output);
+    assert_eq!(plan["movements"], serde_json::json!([]));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Using saved configuration"));
+}
+
+#[test]
+fn test_plan_with_provider_from_environment_prints_only_json() {
+    let server = ollama();
+    let home = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+
+    let output = plan(
+        home.path(),
+        target.path(),
+        &[
+            ("SHELFIE_PROVIDER", "ollama"),
+            ("SHELFIE_MODEL", "llama3"),
+            ("OLLAMA_API_BASE_URL", &server.base_url()),
+        ],
+    );
+
+    let plan = plan_json(&output);
+    assert_eq!(plan["movements"], serde_json::json!([]));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("from the environment"));
+}
+
+#[test]
+fn test_plan_after_legacy_state_migration_prints_only_json() {
+    let server = ollama();
+    let home = TempDir::new().unwrap();
+    let target = TempDir::new().unwrap();
+    fs::write(target.path().join(".fs_organizer.db"), "").unwrap();
+
+    let output = plan(
+        home.path(),
+        target.path(),
+        &[
+            ("SHELFIE_PROVIDER", "ollama"),
+            ("SHELFIE_MODEL", "llama3"),
+            ("OLLAMA_API_BASE_URL", &server.base_url()),
+        ],
+    );
+
+    plan_json(&output);
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Moved shelfie's state"));
+    assert!(!target.path().join(".fs_organizer.db").exists());
+}